@@ -17,18 +17,22 @@ use kv::{
 };
 use kv::{
 	COL_COUNT, COL_BLOCK_HASHES, COL_BLOCK_HEADERS, COL_BLOCK_TRANSACTIONS, COL_TRANSACTIONS,
-	COL_TRANSACTIONS_META, COL_BLOCK_NUMBERS
+	COL_TRANSACTIONS_META, COL_BLOCK_NUMBERS, COL_ADDRESS_OUT_POINTS, COL_ADDRESS_TRANSACTIONS
 };
 use best_block::BestBlock;
 use {
 	BlockRef, Error, BlockHeaderProvider, BlockProvider, BlockOrigin, TransactionMeta, IndexedBlockProvider,
 	TransactionMetaProvider, TransactionProvider, TransactionOutputProvider, BlockChain, Store,
-	SideChainOrigin, ForkChain, Forkable, CanonStore, ConfigStore, TransactionUtxoProvider
+	SideChainOrigin, ForkChain, Forkable, CanonStore, ConfigStore, TransactionUtxoProvider,
+	TransactionHistoryProvider
 };
-use script::Script;
+use script::{Script, ScriptType};
 
 const KEY_BEST_BLOCK_NUMBER: &'static str = "best_block_number";
 const KEY_BEST_BLOCK_HASH: &'static str = "best_block_hash";
+/// Present once address index covers whole canon chain
+const KEY_ADDRESS_INDEX_VERSION: &'static str = "address_index_version";
+const ADDRESS_INDEX_VERSION: u32 = 1;
 
 const MAX_FORK_ROUTE_PRESET: usize = 2048;
 
@@ -63,6 +67,8 @@ impl BlockChainDatabase<CacheDatabase<AutoFlushingOverlayDatabase<DiskDatabase>>
 		cfg.set_cache(Some(COL_BLOCK_HASHES), total_cache / 12);
 		cfg.set_cache(Some(COL_BLOCK_TRANSACTIONS), total_cache / 12);
 		cfg.set_cache(Some(COL_BLOCK_NUMBERS), total_cache / 12);
+		cfg.set_cache(Some(COL_ADDRESS_OUT_POINTS), total_cache / 12);
+		cfg.set_cache(Some(COL_ADDRESS_TRANSACTIONS), total_cache / 12);
 
 		cfg.bloom_filters.insert(Some(COL_TRANSACTIONS_META), 32);
 
@@ -309,6 +315,16 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			update.insert(KeyValue::TransactionMeta(hash, meta));
 		}
 
+		let mut modified_addresses: HashMap<H160, Vec<OutPoint>> = HashMap::new();
+		let mut modified_history: HashMap<H160, Vec<H256>> = HashMap::new();
+		self.index_addresses(&block, &mut modified_addresses, &mut modified_history)?;
+		Self::write_address_out_points(&mut update, modified_addresses);
+		Self::write_address_transactions(&mut update, modified_history);
+		// index of database created with genesis block covers whole chain
+		if new_best_block.number == 0 {
+			update.insert(KeyValue::Meta(KEY_ADDRESS_INDEX_VERSION, serialize(&ADDRESS_INDEX_VERSION)));
+		}
+
 		self.db.write(update).map_err(Error::DatabaseError)?;
 		*best_block = new_best_block;
 		Ok(())
@@ -365,6 +381,38 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 			update.insert(KeyValue::TransactionMeta(hash, meta));
 		}
 
+		// undo address index changes in reverse order, so outputs spent within this block are restored last
+		let mut modified_addresses: HashMap<H160, Vec<OutPoint>> = HashMap::new();
+		let mut modified_history: HashMap<H160, Vec<H256>> = HashMap::new();
+		for (tx_index, tx) in block.transactions.iter().enumerate().rev() {
+			let mut tx_addresses: Vec<H160> = Vec::new();
+			for index in 0..tx.raw.outputs.len() {
+				let out_point = OutPoint { hash: tx.hash.clone(), index: index as u32 };
+				for address in output_address_hashes(&tx.raw.outputs[index].script_pubkey) {
+					self.modified_address_out_points(&mut modified_addresses, address.clone())
+						.retain(|existing| *existing != out_point);
+					tx_addresses.push(address);
+				}
+			}
+
+			if tx_index != 0 {
+				for input in &tx.raw.inputs {
+					for address in self.previous_output_addresses(&input.previous_output)? {
+						self.modified_address_out_points(&mut modified_addresses, address.clone())
+							.push(input.previous_output.clone());
+						tx_addresses.push(address);
+					}
+				}
+			}
+
+			for address in tx_addresses {
+				self.modified_address_transactions(&mut modified_history, address)
+					.retain(|hash| *hash != tx.hash);
+			}
+		}
+		Self::write_address_out_points(&mut update, modified_addresses);
+		Self::write_address_transactions(&mut update, modified_history);
+
 		for tx in block.transactions {
 			update.delete(Key::TransactionMeta(tx.hash));
 		}
@@ -374,10 +422,149 @@ impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
 		Ok(block_hash)
 	}
 
+	/// Checks if address index covers canon chain. Databases created before the index existed don't have it
+	pub fn has_address_index(&self) -> bool {
+		self.get(Key::Meta(KEY_ADDRESS_INDEX_VERSION)).is_some()
+	}
+
+	/// Builds address index of canon chain from scratch, replacing entries which were indexed so far
+	pub fn reindex_addresses(&self) -> Result<(), Error> {
+		let best_block = self.best_block.read();
+		let mut modified_addresses: HashMap<H160, Vec<OutPoint>> = HashMap::new();
+		let mut modified_history: HashMap<H160, Vec<H256>> = HashMap::new();
+		// empty database has no blocks to index
+		let indexed_blocks = if best_block.hash.is_zero() { 0 } else { best_block.number + 1 };
+		for number in 0..indexed_blocks {
+			let block = self.indexed_block(BlockRef::Number(number)).ok_or(Error::CannotCanonize)?;
+			// entries start empty instead of being loaded, so blocks which are already indexed aren't indexed twice.
+			// Outputs are spent after they are created, so every spent output is covered too
+			for tx in &block.transactions {
+				for output in &tx.raw.outputs {
+					for address in output_address_hashes(&output.script_pubkey) {
+						modified_addresses.entry(address.clone()).or_insert_with(Vec::new);
+						modified_history.entry(address).or_insert_with(Vec::new);
+					}
+				}
+			}
+			self.index_addresses(&block, &mut modified_addresses, &mut modified_history)?;
+		}
+
+		let mut update = DBTransaction::new();
+		Self::write_address_out_points(&mut update, modified_addresses);
+		Self::write_address_transactions(&mut update, modified_history);
+		update.insert(KeyValue::Meta(KEY_ADDRESS_INDEX_VERSION, serialize(&ADDRESS_INDEX_VERSION)));
+		self.db.write(update).map_err(Error::DatabaseError)
+	}
+
+	/// Adds outputs of canonized block to address index and removes outputs it spends
+	fn index_addresses(
+		&self,
+		block: &IndexedBlock,
+		modified_addresses: &mut HashMap<H160, Vec<OutPoint>>,
+		modified_history: &mut HashMap<H160, Vec<H256>>,
+	) -> Result<(), Error> {
+		for (tx_index, tx) in block.transactions.iter().enumerate() {
+			let mut tx_addresses: Vec<H160> = Vec::new();
+			if tx_index != 0 {
+				for input in &tx.raw.inputs {
+					for address in self.previous_output_addresses(&input.previous_output)? {
+						self.modified_address_out_points(modified_addresses, address.clone())
+							.retain(|out_point| *out_point != input.previous_output);
+						tx_addresses.push(address);
+					}
+				}
+			}
+
+			for (index, output) in tx.raw.outputs.iter().enumerate() {
+				for address in output_address_hashes(&output.script_pubkey) {
+					self.modified_address_out_points(modified_addresses, address.clone())
+						.push(OutPoint { hash: tx.hash.clone(), index: index as u32 });
+					tx_addresses.push(address);
+				}
+			}
+
+			for address in tx_addresses {
+				let transactions = self.modified_address_transactions(modified_history, address);
+				// transaction may pay to the address it spends from
+				if transactions.last() != Some(&tx.hash) {
+					transactions.push(tx.hash.clone());
+				}
+			}
+		}
+		Ok(())
+	}
+
 	fn get(&self, key: Key) -> Option<Value> {
 		self.db.get(&key).expect("db value to be fine").into_option()
 	}
 
+	fn address_out_points(&self, address: &H160) -> Vec<OutPoint> {
+		self.get(Key::AddressOutPoints(address.clone()))
+			.and_then(Value::as_address_out_points)
+			.map(List::into)
+			.unwrap_or_default()
+	}
+
+	/// Returns address index entry modified during current canonize or decanonize, loading it from db first time
+	fn modified_address_out_points<'a>(&self, modified: &'a mut HashMap<H160, Vec<OutPoint>>, address: H160) -> &'a mut Vec<OutPoint> {
+		use std::collections::hash_map::Entry;
+
+		match modified.entry(address) {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => {
+				let out_points = self.address_out_points(entry.key());
+				entry.insert(out_points)
+			}
+		}
+	}
+
+	fn address_transactions(&self, address: &H160) -> Vec<H256> {
+		self.get(Key::AddressTransactions(address.clone()))
+			.and_then(Value::as_address_transactions)
+			.map(List::into)
+			.unwrap_or_default()
+	}
+
+	/// Same as `modified_address_out_points`, but for address transaction history
+	fn modified_address_transactions<'a>(&self, modified: &'a mut HashMap<H160, Vec<H256>>, address: H160) -> &'a mut Vec<H256> {
+		use std::collections::hash_map::Entry;
+
+		match modified.entry(address) {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => {
+				let transactions = self.address_transactions(entry.key());
+				entry.insert(transactions)
+			}
+		}
+	}
+
+	fn write_address_transactions(update: &mut DBTransaction, modified: HashMap<H160, Vec<H256>>) {
+		for (address, transactions) in modified {
+			if transactions.is_empty() {
+				update.delete(Key::AddressTransactions(address));
+			} else {
+				update.insert(KeyValue::AddressTransactions(address, List::from(transactions)));
+			}
+		}
+	}
+
+	fn previous_output_addresses(&self, previous_output: &OutPoint) -> Result<Vec<H160>, Error> {
+		self.transaction(&previous_output.hash)
+			.and_then(|tx| tx.outputs.into_iter().nth(previous_output.index as usize))
+			.map(|output| output_address_hashes(&output.script_pubkey))
+			.ok_or(Error::CannotCanonize)
+	}
+
+	fn write_address_out_points(update: &mut DBTransaction, modified: HashMap<H160, Vec<OutPoint>>) {
+		for (address, out_points) in modified {
+			if out_points.is_empty() {
+				update.delete(Key::AddressOutPoints(address));
+			} else {
+				update.insert(KeyValue::AddressOutPoints(address, List::from(out_points)));
+			}
+		}
+	}
+
 	fn resolve_hash(&self, block_ref: BlockRef) -> Option<H256> {
 		match block_ref {
 			BlockRef::Number(n) => self.block_hash(n),
@@ -483,53 +670,32 @@ impl<T> TransactionMetaProvider for BlockChainDatabase<T> where T: KeyValueDatab
 	}
 }
 
-impl<T> BlockChainDatabase<T> where T: KeyValueDatabase {
-	fn transaction_utxo_for_block(&self, block_height: u32, address: &H160) -> Vec<OutPoint> {
-		let block = BlockRef::Number(block_height);
-		let transaction_hashes = self.block_transaction_hashes(block);
-		let address_bytes = address.clone().take();
-		let mut outputs: Vec<OutPoint> = vec![];
-		for hash in transaction_hashes.iter()	//TODO maybe rewrite it in iterator style
-		{
-			let meta = self.transaction_meta(&hash).unwrap();
-			if !meta.is_fully_spent()
-			{
-				let transaction = self.transaction(&hash).unwrap();
-				for (index, is_spent) in meta.bits.iter().skip(1).enumerate() //skip coinbase state bit
-				{
-					if !is_spent
-					{
-						//TODO somehow transaction meta contains redundant bits after being loaded from disk
-						if transaction.outputs.len() > index
-						{
-							let output = &transaction.outputs[index];
-							//TODO maybe using script here is redundant and its enough to compare plain bytes?
-							let ref script_bytes = output.script_pubkey;
-							let script: Script = script_bytes.clone().into();
-							let script_addresses = script.extract_destinations().unwrap_or(vec![]);
-							if script_addresses.iter().any(|address| address.hash[..] == address_bytes)
-							{
-								outputs.push( OutPoint { hash: hash.clone(), index: index as u32 } );
-							}
-						}
-					}
-				}
-			}
+/// Hashes of addresses output pays to. Pay to witness script hash outputs are not indexed,
+/// because their 32 bytes script hash is not an address hash
+fn output_address_hashes(script_pubkey: &Bytes) -> Vec<H160> {
+	let script: Script = script_pubkey.clone().into();
+	if script.script_type() == ScriptType::WitnessScript {
+		return vec![];
+	}
+
+	let mut hashes: Vec<H160> = vec![];
+	for address in script.extract_destinations().unwrap_or_default() {
+		if !hashes.contains(&address.hash) {
+			hashes.push(address.hash);
 		}
-		outputs
 	}
+	hashes
 }
 
-impl<T> TransactionUtxoProvider for BlockChainDatabase<T> where T: KeyValueDatabase {	
+impl<T> TransactionUtxoProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn transaction_with_output_address(&self, address: &H160) -> Vec<OutPoint> {
-		let best_block = self.best_block();
-		let mut out_points: Vec<OutPoint> = vec![];
-		for block_number in (0..best_block.number + 1).rev() //add +1 instead of inclusive range, because they are considered unstable
-		{
-			let block_outpoints = self.transaction_utxo_for_block(block_number, address);
-			out_points.extend(block_outpoints);
-		}
-		out_points
+		self.address_out_points(address)
+	}
+}
+
+impl<T> TransactionHistoryProvider for BlockChainDatabase<T> where T: KeyValueDatabase {
+	fn transactions_with_address(&self, address: &H160) -> Vec<H256> {
+		self.address_transactions(address)
 	}
 }
 
@@ -601,6 +767,14 @@ impl<T> CanonStore for BlockChainDatabase<T> where T: KeyValueDatabase {
 	fn as_store(&self) -> &Store {
 		&*self
 	}
+
+	fn has_address_index(&self) -> bool {
+		BlockChainDatabase::has_address_index(self)
+	}
+
+	fn reindex_addresses(&self) -> Result<(), Error> {
+		BlockChainDatabase::reindex_addresses(self)
+	}
 }
 
 impl<T> Store for BlockChainDatabase<T> where T: KeyValueDatabase {
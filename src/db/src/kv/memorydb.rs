@@ -2,10 +2,10 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::mem::replace;
 use parking_lot::RwLock;
-use hash::H256;
+use hash::{H160, H256};
 use bytes::Bytes;
 use ser::List;
-use chain::{Transaction as ChainTransaction, BlockHeader};
+use chain::{Transaction as ChainTransaction, BlockHeader, OutPoint};
 use kv::{Transaction, Key, KeyState, Operation, Value, KeyValueDatabase, KeyValue};
 use {TransactionMeta};
 
@@ -19,6 +19,8 @@ struct InnerDatabase {
 	transaction_meta: HashMap<H256, KeyState<TransactionMeta>>,
 	block_number: HashMap<H256, KeyState<u32>>,
 	configuration: HashMap<&'static str, KeyState<Bytes>>,
+	address_out_points: HashMap<H160, KeyState<List<OutPoint>>>,
+	address_transactions: HashMap<H160, KeyState<List<H256>>>,
 }
 
 #[derive(Default, Debug)]
@@ -53,6 +55,12 @@ impl MemoryDatabase {
 		let configuration = replace(&mut db.configuration, HashMap::default()).into_iter()
 			.flat_map(|(key, state)| state.into_operation(key, KeyValue::Configuration, Key::Configuration));
 
+		let address_out_points = replace(&mut db.address_out_points, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::AddressOutPoints, Key::AddressOutPoints));
+
+		let address_transactions = replace(&mut db.address_transactions, HashMap::default()).into_iter()
+			.flat_map(|(key, state)| state.into_operation(key, KeyValue::AddressTransactions, Key::AddressTransactions));
+
 		Transaction {
 			operations: meta
 				.chain(block_hash)
@@ -62,6 +70,8 @@ impl MemoryDatabase {
 				.chain(transaction_meta)
 				.chain(block_number)
 				.chain(configuration)
+				.chain(address_out_points)
+				.chain(address_transactions)
 				.collect()
 		}
 	}
@@ -81,6 +91,8 @@ impl KeyValueDatabase for MemoryDatabase {
 					KeyValue::TransactionMeta(key, value) => { db.transaction_meta.insert(key, KeyState::Insert(value)); },
 					KeyValue::BlockNumber(key, value) => { db.block_number.insert(key, KeyState::Insert(value)); },
 					KeyValue::Configuration(key, value) => { db.configuration.insert(key, KeyState::Insert(value)); },
+					KeyValue::AddressOutPoints(key, value) => { db.address_out_points.insert(key, KeyState::Insert(value)); },
+					KeyValue::AddressTransactions(key, value) => { db.address_transactions.insert(key, KeyState::Insert(value)); },
 				},
 				Operation::Delete(delete) => match delete {
 					Key::Meta(key) => { db.meta.insert(key, KeyState::Delete); }
@@ -91,6 +103,8 @@ impl KeyValueDatabase for MemoryDatabase {
 					Key::TransactionMeta(key) => { db.transaction_meta.insert(key, KeyState::Delete); }
 					Key::BlockNumber(key) => { db.block_number.insert(key, KeyState::Delete); }
 					Key::Configuration(key) => { db.configuration.insert(key, KeyState::Delete); }
+					Key::AddressOutPoints(key) => { db.address_out_points.insert(key, KeyState::Delete); }
+					Key::AddressTransactions(key) => { db.address_transactions.insert(key, KeyState::Delete); }
 				}
 			}
 		}
@@ -108,6 +122,8 @@ impl KeyValueDatabase for MemoryDatabase {
 			Key::TransactionMeta(ref key) => db.transaction_meta.get(key).cloned().unwrap_or_default().map(Value::TransactionMeta),
 			Key::BlockNumber(ref key) => db.block_number.get(key).cloned().unwrap_or_default().map(Value::BlockNumber),
 			Key::Configuration(ref key) => db.configuration.get(key).cloned().unwrap_or_default().map(Value::Configuration),
+			Key::AddressOutPoints(ref key) => db.address_out_points.get(key).cloned().unwrap_or_default().map(Value::AddressOutPoints),
+			Key::AddressTransactions(ref key) => db.address_transactions.get(key).cloned().unwrap_or_default().map(Value::AddressTransactions),
 		};
 
 		Ok(result)
@@ -1,7 +1,7 @@
 use bytes::Bytes;
-use hash::H256;
+use hash::{H160, H256};
 use ser::{serialize, List, deserialize};
-use chain::{Transaction as ChainTransaction, BlockHeader};
+use chain::{Transaction as ChainTransaction, BlockHeader, OutPoint};
 use {TransactionMeta};
 
 pub const COL_COUNT: u32 = 10;
@@ -13,6 +13,8 @@ pub const COL_TRANSACTIONS: u32 = 4;
 pub const COL_TRANSACTIONS_META: u32 = 5;
 pub const COL_BLOCK_NUMBERS: u32 = 6;
 pub const COL_CONFIGURATION: u32 = 7;
+pub const COL_ADDRESS_OUT_POINTS: u32 = 8;
+pub const COL_ADDRESS_TRANSACTIONS: u32 = 9;
 
 #[derive(Debug)]
 pub enum Operation {
@@ -30,6 +32,8 @@ pub enum KeyValue {
 	TransactionMeta(H256, TransactionMeta),
 	BlockNumber(H256, u32),
 	Configuration(&'static str, Bytes),
+	AddressOutPoints(H160, List<OutPoint>),
+	AddressTransactions(H160, List<H256>),
 }
 
 #[derive(Debug)]
@@ -42,6 +46,8 @@ pub enum Key {
 	TransactionMeta(H256),
 	BlockNumber(H256),
 	Configuration(&'static str),
+	AddressOutPoints(H160),
+	AddressTransactions(H160),
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +60,8 @@ pub enum Value {
 	TransactionMeta(TransactionMeta),
 	BlockNumber(u32),
 	Configuration(Bytes),
+	AddressOutPoints(List<OutPoint>),
+	AddressTransactions(List<H256>),
 }
 
 impl Value {
@@ -67,6 +75,8 @@ impl Value {
 			Key::TransactionMeta(_) => deserialize(bytes).map(Value::TransactionMeta),
 			Key::BlockNumber(_) => deserialize(bytes).map(Value::BlockNumber),
 			Key::Configuration(_) => deserialize(bytes).map(Value::Configuration),
+			Key::AddressOutPoints(_) => deserialize(bytes).map(Value::AddressOutPoints),
+			Key::AddressTransactions(_) => deserialize(bytes).map(Value::AddressTransactions),
 		}.map_err(|e| format!("{:?}", e))
 	}
 
@@ -125,6 +135,20 @@ impl Value {
 			_ => None,
 		}
 	}
+
+	pub fn as_address_out_points(self) -> Option<List<OutPoint>> {
+		match self {
+			Value::AddressOutPoints(list) => Some(list),
+			_ => None,
+		}
+	}
+
+	pub fn as_address_transactions(self) -> Option<List<H256>> {
+		match self {
+			Value::AddressTransactions(list) => Some(list),
+			_ => None,
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -228,6 +252,8 @@ impl<'a> From<&'a KeyValue> for RawKeyValue {
 			KeyValue::TransactionMeta(ref key, ref value) => (COL_TRANSACTIONS_META, serialize(key), serialize(value)),
 			KeyValue::BlockNumber(ref key, ref value) => (COL_BLOCK_NUMBERS, serialize(key), serialize(value)),
 			KeyValue::Configuration(ref key, ref value) => (COL_CONFIGURATION, serialize(key), serialize(value)),
+			KeyValue::AddressOutPoints(ref key, ref value) => (COL_ADDRESS_OUT_POINTS, serialize(key), serialize(value)),
+			KeyValue::AddressTransactions(ref key, ref value) => (COL_ADDRESS_TRANSACTIONS, serialize(key), serialize(value)),
 		};
 
 		RawKeyValue {
@@ -263,6 +289,8 @@ impl<'a> From<&'a Key> for RawKey {
 			Key::TransactionMeta(ref key) => (COL_TRANSACTIONS_META, serialize(key)),
 			Key::BlockNumber(ref key) => (COL_BLOCK_NUMBERS, serialize(key)),
 			Key::Configuration(ref key) => (COL_CONFIGURATION, serialize(key)),
+			Key::AddressOutPoints(ref key) => (COL_ADDRESS_OUT_POINTS, serialize(key)),
+			Key::AddressTransactions(ref key) => (COL_ADDRESS_TRANSACTIONS, serialize(key)),
 		};
 
 		RawKey {
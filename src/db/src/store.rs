@@ -2,11 +2,18 @@ use std::sync::Arc;
 use chain::BlockHeader;
 use {
 	BestBlock, BlockProvider, BlockHeaderProvider, TransactionProvider, TransactionMetaProvider,
-	TransactionOutputProvider, BlockChain, IndexedBlockProvider, Forkable, Error, TransactionUtxoProvider
+	TransactionOutputProvider, BlockChain, IndexedBlockProvider, Forkable, Error, TransactionUtxoProvider,
+	TransactionHistoryProvider
 };
 
 pub trait CanonStore: Store + Forkable + ConfigStore {
 	fn as_store(&self) -> &Store;
+
+	/// Checks if address index covers canon chain
+	fn has_address_index(&self) -> bool;
+
+	/// Builds address index of canon chain from scratch
+	fn reindex_addresses(&self) -> Result<(), Error>;
 }
 
 /// Configuration storage interface
@@ -33,7 +40,8 @@ pub trait Store: AsSubstore {
 /// Allows casting Arc<Store> to reference to any substore type
 pub trait AsSubstore: BlockChain + IndexedBlockProvider +
 					  TransactionProvider + TransactionMetaProvider +
-					  TransactionOutputProvider + TransactionUtxoProvider {
+					  TransactionOutputProvider + TransactionUtxoProvider +
+					  TransactionHistoryProvider {
 	fn as_block_provider(&self) -> &BlockProvider;
 
 	fn as_block_header_provider(&self) -> &BlockHeaderProvider;
@@ -45,11 +53,14 @@ pub trait AsSubstore: BlockChain + IndexedBlockProvider +
 	fn as_transaction_meta_provider(&self) -> &TransactionMetaProvider;
 
 	fn as_transaction_utxo_provider(&self) -> &TransactionUtxoProvider;
+
+	fn as_transaction_history_provider(&self) -> &TransactionHistoryProvider;
 }
 
 impl<T> AsSubstore for T where T: BlockChain + IndexedBlockProvider +
 								  TransactionProvider + TransactionMetaProvider +
-								  TransactionOutputProvider + TransactionUtxoProvider {
+								  TransactionOutputProvider + TransactionUtxoProvider +
+								  TransactionHistoryProvider {
 	fn as_block_provider(&self) -> &BlockProvider {
 		&*self
 	}
@@ -73,6 +84,10 @@ impl<T> AsSubstore for T where T: BlockChain + IndexedBlockProvider +
 	fn as_transaction_utxo_provider(&self) -> &TransactionUtxoProvider {
 		&*self
 	}
+
+	fn as_transaction_history_provider(&self) -> &TransactionHistoryProvider {
+		&*self
+	}
 }
 pub trait UtxoAndOutputProvider: TransactionOutputProvider + TransactionOutputProvider {}
 
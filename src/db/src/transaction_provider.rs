@@ -35,4 +35,9 @@ pub trait TransactionMetaProvider: Send + Sync {
 
 pub trait TransactionUtxoProvider: Send + Sync {
 	fn transaction_with_output_address(&self, address: &H160) -> Vec<OutPoint>;
+}
+
+/// Canon chain transactions paying to or spending from address, oldest first
+pub trait TransactionHistoryProvider: Send + Sync {
+	fn transactions_with_address(&self, address: &H160) -> Vec<H256>;
 }
\ No newline at end of file
@@ -2,9 +2,10 @@ extern crate chain;
 extern crate db;
 extern crate chain_builder;
 
-use chain::IndexedBlock;
-use db::kv::{MemoryDatabase, SharedMemoryDatabase};
-use db::{BlockChainDatabase, BlockProvider, SideChainOrigin, ForkChain};
+use chain::{IndexedBlock, OutPoint};
+use db::kv::{Key, KeyValueDatabase, MemoryDatabase, SharedMemoryDatabase, Transaction as DBTransaction};
+use db::hash::H160;
+use db::{BlockChainDatabase, BlockProvider, SideChainOrigin, ForkChain, TransactionUtxoProvider, TransactionHistoryProvider};
 
 #[test]
 fn insert_block() {
@@ -126,3 +127,152 @@ fn switch_to_simple_fork() {
 	assert_eq!(store.best_block().hash, store.block_hash(2).unwrap());
 
 }
+
+#[test]
+fn address_index() {
+	// p2pkh scripts for two different addresses
+	let script1 = "76a914111111111111111111111111111111111111111188ac";
+	let script2 = "76a914222222222222222222222222222222222222222288ac";
+	let address1: H160 = "1111111111111111111111111111111111111111".into();
+	let address2: H160 = "2222222222222222222222222222222222222222".into();
+
+	let b0 = chain_builder::block_builder()
+		.header().build()
+		.transaction().coinbase()
+			.output().value(10).script_pubkey(script1).build()
+			.output().value(20).script_pubkey(script1).build()
+			.build()
+		.build();
+	let coinbase_hash = b0.transactions[0].hash();
+	let b1 = chain_builder::block_builder()
+		.header().parent(b0.hash()).build()
+		.transaction().coinbase().build()
+		.transaction()
+			.input().hash(coinbase_hash.clone()).index(0).build()
+			.output().value(10).script_pubkey(script2).build()
+			.build()
+		.build();
+	let spending_hash = b1.transactions[1].hash();
+	let b0: IndexedBlock = b0.into();
+	let b1: IndexedBlock = b1.into();
+
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+
+	store.canonize(b0.hash()).unwrap();
+	assert_eq!(store.transaction_with_output_address(&address1), vec![
+		OutPoint { hash: coinbase_hash.clone(), index: 0 },
+		OutPoint { hash: coinbase_hash.clone(), index: 1 },
+	]);
+	assert!(store.transaction_with_output_address(&address2).is_empty());
+
+	store.canonize(b1.hash()).unwrap();
+	assert_eq!(store.transaction_with_output_address(&address1), vec![OutPoint { hash: coinbase_hash.clone(), index: 1 }]);
+	assert_eq!(store.transaction_with_output_address(&address2), vec![OutPoint { hash: spending_hash, index: 0 }]);
+
+	store.decanonize().unwrap();
+	assert_eq!(store.transaction_with_output_address(&address1).len(), 2);
+	assert!(store.transaction_with_output_address(&address1).contains(&OutPoint { hash: coinbase_hash, index: 0 }));
+	assert!(store.transaction_with_output_address(&address2).is_empty());
+}
+
+#[test]
+fn address_history() {
+	let script1 = "76a914111111111111111111111111111111111111111188ac";
+	let script2 = "76a914222222222222222222222222222222222222222288ac";
+	let address1: H160 = "1111111111111111111111111111111111111111".into();
+	let address2: H160 = "2222222222222222222222222222222222222222".into();
+
+	let b0 = chain_builder::block_builder()
+		.header().build()
+		.transaction().coinbase()
+			.output().value(10).script_pubkey(script1).build()
+			.output().value(20).script_pubkey(script1).build()
+			.build()
+		.build();
+	let coinbase_hash = b0.transactions[0].hash();
+	let b1 = chain_builder::block_builder()
+		.header().parent(b0.hash()).build()
+		.transaction().coinbase().build()
+		.transaction()
+			.input().hash(coinbase_hash.clone()).index(0).build()
+			.output().value(5).script_pubkey(script2).build()
+			.output().value(5).script_pubkey(script1).build()
+			.build()
+		.build();
+	let spending_hash = b1.transactions[1].hash();
+	let b0: IndexedBlock = b0.into();
+	let b1: IndexedBlock = b1.into();
+
+	let store = BlockChainDatabase::open(MemoryDatabase::default());
+	store.insert(b0.clone()).unwrap();
+	store.insert(b1.clone()).unwrap();
+
+	store.canonize(b0.hash()).unwrap();
+	store.canonize(b1.hash()).unwrap();
+	// spending transaction is listed once, though it both spends from and pays to address1
+	assert_eq!(store.transactions_with_address(&address1), vec![coinbase_hash.clone(), spending_hash.clone()]);
+	assert_eq!(store.transactions_with_address(&address2), vec![spending_hash]);
+
+	store.decanonize().unwrap();
+	assert_eq!(store.transactions_with_address(&address1), vec![coinbase_hash]);
+	assert!(store.transactions_with_address(&address2).is_empty());
+}
+
+#[test]
+fn address_index_is_rebuilt_for_database_without_it() {
+	let script1 = "76a914111111111111111111111111111111111111111188ac";
+	let script2 = "76a914222222222222222222222222222222222222222288ac";
+	let address1: H160 = "1111111111111111111111111111111111111111".into();
+	let address2: H160 = "2222222222222222222222222222222222222222".into();
+
+	let b0 = chain_builder::block_builder()
+		.header().build()
+		.transaction().coinbase()
+			.output().value(10).script_pubkey(script1).build()
+			.output().value(20).script_pubkey(script1).build()
+			.build()
+		.build();
+	let coinbase_hash = b0.transactions[0].hash();
+	let b1 = chain_builder::block_builder()
+		.header().parent(b0.hash()).build()
+		.transaction().coinbase().build()
+		.transaction()
+			.input().hash(coinbase_hash.clone()).index(0).build()
+			.output().value(10).script_pubkey(script2).build()
+			.build()
+		.build();
+	let spending_hash = b1.transactions[1].hash();
+	let b0: IndexedBlock = b0.into();
+	let b1: IndexedBlock = b1.into();
+
+	let shared_database = SharedMemoryDatabase::default();
+	{
+		let store = BlockChainDatabase::open(shared_database.clone());
+		store.insert(b0.clone()).unwrap();
+		store.insert(b1.clone()).unwrap();
+		store.canonize(b0.hash()).unwrap();
+		store.canonize(b1.hash()).unwrap();
+		assert!(store.has_address_index());
+	}
+
+	// database written before the index existed lacks its marker and entries of older blocks,
+	// while entries of blocks canonized after upgrade are there
+	let mut update = DBTransaction::new();
+	update.delete(Key::Meta("address_index_version"));
+	update.delete(Key::AddressOutPoints(address1.clone()));
+	update.delete(Key::AddressTransactions(address1.clone()));
+	shared_database.write(update).unwrap();
+
+	let store = BlockChainDatabase::open(shared_database);
+	assert!(!store.has_address_index());
+	assert!(store.transaction_with_output_address(&address1).is_empty());
+
+	store.reindex_addresses().unwrap();
+	assert!(store.has_address_index());
+	assert_eq!(store.transaction_with_output_address(&address1), vec![OutPoint { hash: coinbase_hash.clone(), index: 1 }]);
+	assert_eq!(store.transaction_with_output_address(&address2), vec![OutPoint { hash: spending_hash.clone(), index: 0 }]);
+	assert_eq!(store.transactions_with_address(&address1), vec![coinbase_hash, spending_hash.clone()]);
+	assert_eq!(store.transactions_with_address(&address2), vec![spending_hash]);
+}
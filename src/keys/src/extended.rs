@@ -0,0 +1,481 @@
+//! BIP32 hierarchical deterministic keys
+
+use std::fmt;
+use std::str::FromStr;
+use secp256k1::key;
+use base58::{ToBase58, FromBase58};
+use crypto::{checksum, dhash160, hmac_sha512};
+use hash::{H32, H256, H264};
+use network::Network;
+use {DisplayLayout, Error, KeyPair, Private, Public, Secret, SECP256K1};
+
+/// Child indexes starting from this one use hardened derivation
+pub const HARDENED_INDEX: u32 = 0x80000000;
+
+const EXTENDED_KEY_SIZE: usize = 78;
+
+/// Path of child indexes from master key, e.g. m/44'/0'/0'/0/1
+#[derive(Debug, PartialEq, Clone)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+	/// BIP44 path m/44'/coin_type'/account'/change/index
+	pub fn bip44(network: Network, account: u32, change: bool, index: u32) -> Self {
+		let coin_type = match network {
+			Network::Mainnet => 0,
+			Network::Testnet => 1,
+		};
+		DerivationPath(vec![
+			44 | HARDENED_INDEX,
+			coin_type | HARDENED_INDEX,
+			account | HARDENED_INDEX,
+			change as u32,
+			index,
+		])
+	}
+
+	pub fn indexes(&self) -> &[u32] {
+		&self.0
+	}
+}
+
+impl fmt::Display for DerivationPath {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		try!(write!(f, "m"));
+		for index in &self.0 {
+			if index & HARDENED_INDEX != 0 {
+				try!(write!(f, "/{}'", index & !HARDENED_INDEX));
+			} else {
+				try!(write!(f, "/{}", index));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for DerivationPath {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> where Self: Sized {
+		let mut parts = s.split('/');
+		if parts.next() != Some("m") {
+			return Err(Error::InvalidDerivationPath);
+		}
+
+		let indexes = try!(parts
+			.map(|part| {
+				let (number, hardened) = match part.ends_with('\'') || part.ends_with('h') {
+					true => (&part[..part.len() - 1], true),
+					false => (part, false),
+				};
+				match number.parse::<u32>() {
+					Ok(index) if index < HARDENED_INDEX => Ok(if hardened { index | HARDENED_INDEX } else { index }),
+					_ => Err(Error::InvalidDerivationPath),
+				}
+			})
+			.collect::<Result<Vec<_>, _>>());
+
+		Ok(DerivationPath(indexes))
+	}
+}
+
+/// Private key with chain code allowing to derive child keys
+#[derive(PartialEq, Clone)]
+pub struct ExtendedPrivate {
+	pub network: Network,
+	pub depth: u8,
+	/// First 4 bytes of parent public key hash
+	pub parent_fingerprint: H32,
+	pub child_number: u32,
+	pub chain_code: H256,
+	pub secret: Secret,
+}
+
+impl ExtendedPrivate {
+	/// Creates master key from seed, e.g. from `Mnemonic::to_seed`
+	pub fn from_seed(seed: &[u8], network: Network) -> Result<Self, Error> {
+		let i = hmac_sha512(b"Bitcoin seed", seed);
+		let mut secret = Secret::default();
+		secret.copy_from_slice(&i[..32]);
+		try!(key::SecretKey::from_slice(&SECP256K1, &*secret));
+
+		let mut chain_code = H256::default();
+		chain_code.copy_from_slice(&i[32..]);
+
+		Ok(ExtendedPrivate {
+			network,
+			depth: 0,
+			parent_fingerprint: H32::default(),
+			child_number: 0,
+			chain_code,
+			secret,
+		})
+	}
+
+	pub fn derive_child(&self, index: u32) -> Result<Self, Error> {
+		let context = &SECP256K1;
+		let public = try!(self.public());
+
+		let mut data = Vec::with_capacity(37);
+		if index & HARDENED_INDEX != 0 {
+			data.push(0);
+			data.extend_from_slice(&*self.secret);
+		} else {
+			data.extend_from_slice(&*public);
+		}
+		data.extend_from_slice(&be_bytes(index));
+
+		let i = hmac_sha512(&*self.chain_code, &data);
+		let mut child = try!(key::SecretKey::from_slice(context, &i[..32]));
+		let parent = try!(key::SecretKey::from_slice(context, &*self.secret));
+		try!(child.add_assign(context, &parent));
+
+		let mut secret = Secret::default();
+		secret.copy_from_slice(&child[0..32]);
+		let mut chain_code = H256::default();
+		chain_code.copy_from_slice(&i[32..]);
+
+		Ok(ExtendedPrivate {
+			network: self.network,
+			depth: self.depth.wrapping_add(1),
+			parent_fingerprint: fingerprint(&public),
+			child_number: index,
+			chain_code,
+			secret,
+		})
+	}
+
+	pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Error> {
+		path.indexes().iter().fold(Ok(self.clone()), |key, index| key.and_then(|key| key.derive_child(*index)))
+	}
+
+	/// Compressed public key of this extended key
+	pub fn public(&self) -> Result<H264, Error> {
+		let context = &SECP256K1;
+		let secret = try!(key::SecretKey::from_slice(context, &*self.secret));
+		let public = try!(key::PublicKey::from_secret_key(context, &secret));
+		let mut result = H264::default();
+		result.copy_from_slice(&public.serialize_vec(context, true)[0..33]);
+		Ok(result)
+	}
+
+	pub fn extended_public(&self) -> Result<ExtendedPublic, Error> {
+		Ok(ExtendedPublic {
+			network: self.network,
+			depth: self.depth,
+			parent_fingerprint: self.parent_fingerprint.clone(),
+			child_number: self.child_number,
+			chain_code: self.chain_code.clone(),
+			public: try!(self.public()),
+		})
+	}
+
+	pub fn private(&self) -> Private {
+		Private {
+			network: self.network,
+			secret: self.secret.clone(),
+			compressed: true,
+		}
+	}
+
+	pub fn key_pair(&self) -> Result<KeyPair, Error> {
+		KeyPair::from_private(self.private())
+	}
+}
+
+/// Public key with chain code allowing to derive non hardened child public keys
+#[derive(PartialEq, Clone)]
+pub struct ExtendedPublic {
+	pub network: Network,
+	pub depth: u8,
+	/// First 4 bytes of parent public key hash
+	pub parent_fingerprint: H32,
+	pub child_number: u32,
+	pub chain_code: H256,
+	/// Compressed public key
+	pub public: H264,
+}
+
+impl ExtendedPublic {
+	pub fn derive_child(&self, index: u32) -> Result<Self, Error> {
+		if index & HARDENED_INDEX != 0 {
+			return Err(Error::InvalidDerivationPath);
+		}
+
+		let context = &SECP256K1;
+		let mut data = Vec::with_capacity(37);
+		data.extend_from_slice(&*self.public);
+		data.extend_from_slice(&be_bytes(index));
+
+		let i = hmac_sha512(&*self.chain_code, &data);
+		let tweak = try!(key::SecretKey::from_slice(context, &i[..32]));
+		let mut child = try!(key::PublicKey::from_slice(context, &*self.public));
+		try!(child.add_exp_assign(context, &tweak));
+
+		let mut public = H264::default();
+		public.copy_from_slice(&child.serialize_vec(context, true)[0..33]);
+		let mut chain_code = H256::default();
+		chain_code.copy_from_slice(&i[32..]);
+
+		Ok(ExtendedPublic {
+			network: self.network,
+			depth: self.depth.wrapping_add(1),
+			parent_fingerprint: fingerprint(&self.public),
+			child_number: index,
+			chain_code,
+			public,
+		})
+	}
+
+	pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Error> {
+		path.indexes().iter().fold(Ok(self.clone()), |key, index| key.and_then(|key| key.derive_child(*index)))
+	}
+
+	pub fn public(&self) -> Public {
+		Public::Compressed(self.public.clone())
+	}
+}
+
+fn be_bytes(value: u32) -> [u8; 4] {
+	[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn read_be_u32(data: &[u8]) -> u32 {
+	(data[0] as u32) << 24 | (data[1] as u32) << 16 | (data[2] as u32) << 8 | data[3] as u32
+}
+
+fn fingerprint(public: &H264) -> H32 {
+	let mut result = H32::default();
+	result.copy_from_slice(&dhash160(&**public)[0..4]);
+	result
+}
+
+/// Serializes common part of extended keys and appends checksum
+fn extended_layout(version: u32, depth: u8, parent_fingerprint: &H32, child_number: u32, chain_code: &H256, key: &[u8]) -> Vec<u8> {
+	let mut result = Vec::with_capacity(EXTENDED_KEY_SIZE + 4);
+	result.extend_from_slice(&be_bytes(version));
+	result.push(depth);
+	result.extend_from_slice(&**parent_fingerprint);
+	result.extend_from_slice(&be_bytes(child_number));
+	result.extend_from_slice(&**chain_code);
+	result.extend_from_slice(key);
+	let cs = checksum(&result);
+	result.extend_from_slice(&*cs);
+	result
+}
+
+/// Checks length and checksum and returns version
+fn check_extended_layout(data: &[u8]) -> Result<u32, Error> {
+	if data.len() != EXTENDED_KEY_SIZE + 4 {
+		return Err(Error::InvalidExtendedKey);
+	}
+
+	let cs = checksum(&data[0..EXTENDED_KEY_SIZE]);
+	if &data[EXTENDED_KEY_SIZE..] != &*cs {
+		return Err(Error::InvalidChecksum);
+	}
+
+	Ok(read_be_u32(&data[0..4]))
+}
+
+impl DisplayLayout for ExtendedPrivate {
+	type Target = Vec<u8>;
+
+	fn layout(&self) -> Self::Target {
+		let version = match self.network {
+			Network::Mainnet => 0x0488ADE4,
+			Network::Testnet => 0x04358394,
+		};
+		let mut key = vec![0u8];
+		key.extend_from_slice(&*self.secret);
+		extended_layout(version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code, &key)
+	}
+
+	fn from_layout(data: &[u8]) -> Result<Self, Error> where Self: Sized {
+		let network = match try!(check_extended_layout(data)) {
+			0x0488ADE4 => Network::Mainnet,
+			0x04358394 => Network::Testnet,
+			_ => return Err(Error::InvalidExtendedKey),
+		};
+
+		if data[45] != 0 {
+			return Err(Error::InvalidExtendedKey);
+		}
+
+		let mut secret = Secret::default();
+		secret.copy_from_slice(&data[46..78]);
+		try!(key::SecretKey::from_slice(&SECP256K1, &*secret));
+
+		let mut parent_fingerprint = H32::default();
+		parent_fingerprint.copy_from_slice(&data[5..9]);
+		let mut chain_code = H256::default();
+		chain_code.copy_from_slice(&data[13..45]);
+
+		Ok(ExtendedPrivate {
+			network,
+			depth: data[4],
+			parent_fingerprint,
+			child_number: read_be_u32(&data[9..13]),
+			chain_code,
+			secret,
+		})
+	}
+}
+
+impl DisplayLayout for ExtendedPublic {
+	type Target = Vec<u8>;
+
+	fn layout(&self) -> Self::Target {
+		let version = match self.network {
+			Network::Mainnet => 0x0488B21E,
+			Network::Testnet => 0x043587CF,
+		};
+		extended_layout(version, self.depth, &self.parent_fingerprint, self.child_number, &self.chain_code, &*self.public)
+	}
+
+	fn from_layout(data: &[u8]) -> Result<Self, Error> where Self: Sized {
+		let network = match try!(check_extended_layout(data)) {
+			0x0488B21E => Network::Mainnet,
+			0x043587CF => Network::Testnet,
+			_ => return Err(Error::InvalidExtendedKey),
+		};
+
+		let mut public = H264::default();
+		public.copy_from_slice(&data[45..78]);
+		try!(key::PublicKey::from_slice(&SECP256K1, &*public));
+
+		let mut parent_fingerprint = H32::default();
+		parent_fingerprint.copy_from_slice(&data[5..9]);
+		let mut chain_code = H256::default();
+		chain_code.copy_from_slice(&data[13..45]);
+
+		Ok(ExtendedPublic {
+			network,
+			depth: data[4],
+			parent_fingerprint,
+			child_number: read_be_u32(&data[9..13]),
+			chain_code,
+			public,
+		})
+	}
+}
+
+impl fmt::Debug for ExtendedPrivate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		try!(writeln!(f, "network: {:?}", self.network));
+		try!(writeln!(f, "depth: {}", self.depth));
+		try!(writeln!(f, "child number: {}", self.child_number));
+		writeln!(f, "chain code: {}", self.chain_code)
+	}
+}
+
+impl fmt::Display for ExtendedPrivate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.layout().to_base58())
+	}
+}
+
+impl FromStr for ExtendedPrivate {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> where Self: Sized {
+		let data = try!(s.from_base58().map_err(|_| Error::InvalidExtendedKey));
+		ExtendedPrivate::from_layout(&data)
+	}
+}
+
+impl fmt::Debug for ExtendedPublic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self)
+	}
+}
+
+impl fmt::Display for ExtendedPublic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.layout().to_base58())
+	}
+}
+
+impl FromStr for ExtendedPublic {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> where Self: Sized {
+		let data = try!(s.from_base58().map_err(|_| Error::InvalidExtendedKey));
+		ExtendedPublic::from_layout(&data)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use primitives::bytes::Bytes;
+	use network::Network;
+	use Error;
+	use super::{ExtendedPrivate, ExtendedPublic, DerivationPath, HARDENED_INDEX};
+
+	/// Test vector 1 from:
+	/// https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki
+	const SEED: &'static str = "000102030405060708090a0b0c0d0e0f";
+	const PATH: &'static str = "m/0'/1/2'/2/1000000000";
+	const PRIVATES: [&'static str; 6] = [
+		"xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+		"xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+		"xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+		"xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+		"xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334",
+		"xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+	];
+	const PUBLICS: [&'static str; 6] = [
+		"xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+		"xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+		"xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+		"xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+		"xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+		"xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+	];
+
+	#[test]
+	fn test_derivation_path_from_str() {
+		let path: DerivationPath = PATH.parse().unwrap();
+		assert_eq!(path.indexes(), &[HARDENED_INDEX, 1, 2 | HARDENED_INDEX, 2, 1000000000]);
+		assert_eq!(path.to_string(), PATH);
+		assert_eq!("m/44'/0'/0'/1/5".parse::<DerivationPath>().unwrap(), DerivationPath::bip44(Network::Mainnet, 0, true, 5));
+		assert_eq!("44'/0'".parse::<DerivationPath>(), Err(Error::InvalidDerivationPath));
+		assert_eq!("m/2147483648".parse::<DerivationPath>(), Err(Error::InvalidDerivationPath));
+	}
+
+	#[test]
+	fn test_extended_private_derivation() {
+		let seed: Bytes = SEED.into();
+		let mut key = ExtendedPrivate::from_seed(&seed, Network::Mainnet).unwrap();
+		let path: DerivationPath = PATH.parse().unwrap();
+
+		assert_eq!(key.to_string(), PRIVATES[0]);
+		assert_eq!(key.extended_public().unwrap().to_string(), PUBLICS[0]);
+		for (i, index) in path.indexes().iter().enumerate() {
+			key = key.derive_child(*index).unwrap();
+			assert_eq!(key.to_string(), PRIVATES[i + 1]);
+			assert_eq!(key.extended_public().unwrap().to_string(), PUBLICS[i + 1]);
+		}
+
+		let master = ExtendedPrivate::from_seed(&seed, Network::Mainnet).unwrap();
+		assert_eq!(master.derive_path(&path).unwrap(), key);
+	}
+
+	#[test]
+	fn test_extended_public_derivation() {
+		let key: ExtendedPublic = PUBLICS[4].parse().unwrap();
+		assert_eq!(key.derive_child(1000000000).unwrap().to_string(), PUBLICS[5]);
+		assert_eq!(key.derive_child(HARDENED_INDEX), Err(Error::InvalidDerivationPath));
+	}
+
+	#[test]
+	fn test_extended_keys_from_str() {
+		for key in PRIVATES.iter() {
+			assert_eq!(key.parse::<ExtendedPrivate>().unwrap().to_string(), *key);
+		}
+		for key in PUBLICS.iter() {
+			assert_eq!(key.parse::<ExtendedPublic>().unwrap().to_string(), *key);
+		}
+		assert_eq!(PUBLICS[0].parse::<ExtendedPrivate>(), Err(Error::InvalidExtendedKey));
+	}
+}
@@ -12,7 +12,9 @@ extern crate primitives;
 pub mod generator;
 mod address;
 mod display;
+mod extended;
 mod keypair;
+mod mnemonic;
 mod error;
 pub mod network;
 mod private;
@@ -24,7 +26,9 @@ pub use primitives::{hash, bytes};
 
 pub use address::{Type, Address};
 pub use display::DisplayLayout;
+pub use extended::{ExtendedPrivate, ExtendedPublic, DerivationPath, HARDENED_INDEX};
 pub use keypair::KeyPair;
+pub use mnemonic::Mnemonic;
 pub use error::Error;
 pub use private::Private;
 pub use public::Public;
@@ -0,0 +1,165 @@
+//! BIP39 mnemonic code for generating deterministic keys
+
+use std::fmt;
+use std::str::FromStr;
+use crypto::{sha256, pbkdf2_sha512};
+use hash::H512;
+use generator::Random;
+use Error;
+
+const WORDLIST: &'static str = include_str!("english.txt");
+const SEED_ITERATIONS: u32 = 2048;
+
+lazy_static! {
+	static ref WORDS: Vec<&'static str> = WORDLIST.split_whitespace().collect();
+}
+
+/// Sequence of english words encoding entropy and its checksum
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mnemonic {
+	words: Vec<&'static str>,
+}
+
+impl Mnemonic {
+	/// Generates new random mnemonic. Word count must be one of 12, 15, 18, 21 or 24
+	pub fn generate(word_count: usize) -> Result<Self, Error> {
+		if word_count < 12 || word_count > 24 || word_count % 3 != 0 {
+			return Err(Error::InvalidMnemonic);
+		}
+		let mut entropy = vec![0u8; word_count / 3 * 4];
+		try!(Random::generate_bytes(&mut entropy));
+		Mnemonic::from_entropy(&entropy)
+	}
+
+	pub fn from_entropy(entropy: &[u8]) -> Result<Self, Error> {
+		if entropy.len() < 16 || entropy.len() > 32 || entropy.len() % 4 != 0 {
+			return Err(Error::InvalidMnemonic);
+		}
+
+		let mut data = entropy.to_vec();
+		data.push(sha256(entropy)[0]);
+		let bits_count = entropy.len() * 8 + entropy.len() / 4;
+
+		let words = (0..bits_count / 11)
+			.map(|word| {
+				let index = (0..11).fold(0usize, |index, bit| (index << 1) | bit_at(&data, word * 11 + bit) as usize);
+				WORDS[index]
+			})
+			.collect();
+
+		Ok(Mnemonic { words })
+	}
+
+	/// Returns entropy encoded by this mnemonic
+	pub fn entropy(&self) -> Vec<u8> {
+		let entropy_len = self.words.len() / 3 * 4;
+		let mut data = vec![0u8; entropy_len + 1];
+		for (word_index, word) in self.words.iter().enumerate() {
+			let index = WORDS.binary_search(word).expect("words are always taken from wordlist");
+			for bit in 0..11 {
+				if index & (1 << (10 - bit)) != 0 {
+					let position = word_index * 11 + bit;
+					data[position / 8] |= 1 << (7 - position % 8);
+				}
+			}
+		}
+		data.truncate(entropy_len);
+		data
+	}
+
+	/// Derives 512 bit seed from mnemonic and optional passphrase. Passphrase is not normalized, so ascii is advised
+	pub fn to_seed(&self, passphrase: &str) -> H512 {
+		let mut seed = H512::default();
+		let salt = "mnemonic".to_owned() + passphrase;
+		pbkdf2_sha512(self.to_string().as_bytes(), salt.as_bytes(), SEED_ITERATIONS, &mut *seed);
+		seed
+	}
+
+	pub fn words(&self) -> &[&'static str] {
+		&self.words
+	}
+}
+
+fn bit_at(data: &[u8], position: usize) -> bool {
+	data[position / 8] & (1 << (7 - position % 8)) != 0
+}
+
+impl fmt::Display for Mnemonic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.words.join(" "))
+	}
+}
+
+impl FromStr for Mnemonic {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> where Self: Sized {
+		let words = try!(s.split_whitespace()
+			.map(|word| WORDS.binary_search_by(|probe| probe.cmp(&word)).map(|index| WORDS[index]).map_err(|_| Error::InvalidMnemonic))
+			.collect::<Result<Vec<_>, _>>());
+
+		if words.len() < 12 || words.len() > 24 || words.len() % 3 != 0 {
+			return Err(Error::InvalidMnemonic);
+		}
+
+		let mnemonic = Mnemonic { words };
+		// encoding entropy back again recalculates checksum words
+		match Mnemonic::from_entropy(&mnemonic.entropy()) {
+			Ok(ref checked) if *checked == mnemonic => Ok(mnemonic),
+			_ => Err(Error::InvalidChecksum),
+		}
+	}
+}
+
+impl From<&'static str> for Mnemonic {
+	fn from(s: &'static str) -> Self {
+		s.parse().unwrap()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use hash::H512;
+	use Error;
+	use super::Mnemonic;
+
+	/// Tests from:
+	/// https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+	#[test]
+	fn test_mnemonic_from_entropy() {
+		let mnemonic = Mnemonic::from_entropy(&[0u8; 16]).unwrap();
+		assert_eq!(mnemonic.to_string(), "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about");
+
+		let mnemonic = Mnemonic::from_entropy(&[0x7f; 16]).unwrap();
+		assert_eq!(mnemonic.to_string(), "legal winner thank year wave sausage worth useful legal winner thank yellow");
+	}
+
+	#[test]
+	fn test_mnemonic_entropy() {
+		let mnemonic: Mnemonic = "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic".into();
+		let expected: Vec<u8> = vec![0x9e, 0x88, 0x5d, 0x95, 0x2a, 0xd3, 0x62, 0xca, 0xeb, 0x4e, 0xfe, 0x34, 0xa8, 0xe9, 0x1b, 0xd2];
+		assert_eq!(mnemonic.entropy(), expected);
+	}
+
+	#[test]
+	fn test_mnemonic_to_seed() {
+		let mnemonic: Mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".into();
+		let expected: H512 = "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04".into();
+		assert_eq!(mnemonic.to_seed("TREZOR"), expected);
+	}
+
+	#[test]
+	fn test_mnemonic_invalid() {
+		assert_eq!("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon".parse::<Mnemonic>(), Err(Error::InvalidChecksum));
+		assert_eq!("abandon abandon abandon".parse::<Mnemonic>(), Err(Error::InvalidMnemonic));
+		assert_eq!("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon rustheus".parse::<Mnemonic>(), Err(Error::InvalidMnemonic));
+	}
+
+	#[test]
+	fn test_mnemonic_generate() {
+		let mnemonic = Mnemonic::generate(24).unwrap();
+		assert_eq!(mnemonic.words().len(), 24);
+		assert_eq!(mnemonic.to_string().parse::<Mnemonic>().unwrap(), mnemonic);
+		assert_eq!(Mnemonic::generate(13), Err(Error::InvalidMnemonic));
+	}
+}
@@ -208,14 +208,14 @@ impl<'a, T> Iterator for FittingTransactionsIterator<'a, T> where T: Iterator<It
 			match size_step.and(sigops_step) {
 				NextStep::Append => {
 					self.block_size.apply(transaction_size);
-					self.sigops.apply(transaction_size);
+					self.sigops.apply(sigops_count);
 					self.previous_entries.push(entry);
 					return Some(entry);
 				},
 				NextStep::FinishAndAppend => {
 					self.finished = true;
 					self.block_size.apply(transaction_size);
-					self.sigops.apply(transaction_size);
+					self.sigops.apply(sigops_count);
 					self.previous_entries.push(entry);
 					return Some(entry);
 				},
@@ -270,7 +270,50 @@ impl BlockAssembler {
 
 #[cfg(test)]
 mod tests {
-	use super::{SizePolicy, NextStep};
+	use std::sync::Arc;
+	use chain::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+	use db::{BlockChainDatabase, SharedStore};
+	use params::{ConsensusFork, ConsensusParams, NetworkParams};
+	use primitives::bytes::Bytes;
+	use primitives::hash::H256;
+	use verification::block_reward_satoshi;
+	use fee::NonZeroFeeCalculator;
+	use memory_pool::MemoryPool;
+	use super::{BlockAssembler, BlockTemplate, SizePolicy, NextStep};
+
+	/// Transaction spending unknown output. Its fee equals to output value in tests
+	fn transaction(spent: u8, value: u64, script_sig: Bytes, script_pubkey: Bytes) -> Transaction {
+		Transaction {
+			version: 1,
+			inputs: vec![TransactionInput {
+				previous_output: OutPoint {
+					hash: H256::from(spent),
+					index: 0,
+				},
+				script_sig: script_sig,
+				sequence: 0xffffffff,
+				script_witness: vec![],
+			}],
+			outputs: vec![TransactionOutput {
+				value: value,
+				script_pubkey: script_pubkey,
+			}],
+			lock_time: 0,
+		}
+	}
+
+	fn assemble(transactions: Vec<Transaction>, max_block_size: u32, max_block_sigops: u32) -> BlockTemplate {
+		let store: SharedStore = Arc::new(BlockChainDatabase::init_test_chain(vec![NetworkParams::Regtest.genesis_block().into()]));
+		let mut pool = MemoryPool::new();
+		for transaction in transactions {
+			pool.insert_verified(transaction.into(), &NonZeroFeeCalculator).unwrap();
+		}
+		let assembler = BlockAssembler {
+			max_block_size: max_block_size,
+			max_block_sigops: max_block_sigops,
+		};
+		assembler.create_new_block(&store, &pool, 1234568, &ConsensusParams::dev(NetworkParams::Regtest, ConsensusFork::NoFork))
+	}
 
 	#[test]
 	fn test_size_policy() {
@@ -303,6 +346,33 @@ mod tests {
 		assert_eq!(NextStep::FinishAndAppend.and(NextStep::Append), NextStep::FinishAndAppend);
 	}
 
+	#[test]
+	fn test_block_assembler_coinbase_value_includes_fees() {
+		let transactions = vec![
+			transaction(1, 1_000, "51".into(), "51".into()),
+			transaction(2, 2_000, "51".into(), "51".into()),
+		];
+		let template = assemble(transactions, 1_000_000, 80_000);
+		assert_eq!(template.height, 1);
+		assert_eq!(template.transactions.len(), 2);
+		assert_eq!(template.coinbase_value, block_reward_satoshi(1) + 3_000);
+	}
+
+	#[test]
+	fn test_block_assembler_sigop_limit_counts_sigops_not_bytes() {
+		// transactions without sigops are far bigger than sigop limit
+		let big_script_sig: Bytes = vec![0x51u8; 500].into();
+		let transactions = (0..10).map(|spent| transaction(spent, 1_000, big_script_sig.clone(), "51".into())).collect();
+		let template = assemble(transactions, 1_000_000, 100);
+		assert_eq!(template.transactions.len(), 10);
+
+		// each output has 10 OP_CHECKSIG, so only 2 transactions fit 25 sigops
+		let checksigs: Bytes = vec![0xacu8; 10].into();
+		let transactions = (0..4).map(|spent| transaction(spent, 1_000, "51".into(), checksigs.clone())).collect();
+		let template = assemble(transactions, 1_000_000, 25);
+		assert_eq!(template.transactions.len(), 2);
+	}
+
 	#[test]
 	fn test_fitting_transactions_iterator_max_block_size_reached() {
 	}
@@ -1,6 +1,30 @@
 use chain::Transaction;
 use ser::Serializable;
-use db::TransactionProvider;
+use db::{TransactionOutputProvider, TransactionProvider};
+use verification::TransactionError;
+use memory_pool::MemoryPool;
+
+/// Calculates fee of transaction, which is about to enter memory pool
+pub trait MemoryPoolFeeCalculator {
+	/// Fails when some of transaction inputs can't be valued or transaction spends more than its inputs
+	fn calculate(&self, memory_pool: &MemoryPool, transaction: &Transaction) -> Result<u64, TransactionError>;
+}
+
+/// Values inputs using outputs of in-pool parents and then outputs from given provider
+pub struct FeeCalculator<'a>(pub &'a TransactionOutputProvider);
+
+impl<'a> MemoryPoolFeeCalculator for FeeCalculator<'a> {
+	fn calculate(&self, memory_pool: &MemoryPool, transaction: &Transaction) -> Result<u64, TransactionError> {
+		let mut inputs_sum = 0u64;
+		for (index, input) in transaction.inputs.iter().enumerate() {
+			let output = memory_pool.transaction_output(&input.previous_output, usize::max_value())
+				.or_else(|| self.0.transaction_output(&input.previous_output, usize::max_value()))
+				.ok_or(TransactionError::Input(index))?;
+			inputs_sum = inputs_sum.checked_add(output.value).ok_or(TransactionError::Overspend)?;
+		}
+		inputs_sum.checked_sub(transaction.total_spends()).ok_or(TransactionError::Overspend)
+	}
+}
 
 pub fn transaction_fee(store: &TransactionProvider, transaction: &Transaction) -> u64 {
 	let inputs_sum = transaction.inputs.iter().map(|input| {
@@ -16,12 +40,24 @@ pub fn transaction_fee_rate(store: &TransactionProvider, transaction: &Transacti
 	transaction_fee(store, transaction) / transaction.serialized_size() as u64
 }
 
+/// Values transaction by its outputs, so tests can order transactions without knowing their inputs
+#[cfg(test)]
+pub struct NonZeroFeeCalculator;
+
+#[cfg(test)]
+impl MemoryPoolFeeCalculator for NonZeroFeeCalculator {
+	fn calculate(&self, _memory_pool: &MemoryPool, transaction: &Transaction) -> Result<u64, TransactionError> {
+		Ok(transaction.total_spends())
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	extern crate chain_builder;
 
 	use std::sync::Arc;
 	use db::{BlockChainDatabase, AsSubstore};
+	use memory_pool::OrderingStrategy;
 	use super::*;
 
 	#[test]
@@ -51,4 +87,36 @@ mod tests {
 		assert_eq!(transaction_fee_rate(db.as_transaction_provider(), &tx0), 0);
 		assert_eq!(transaction_fee_rate(db.as_transaction_provider(), &tx2), 4_901);
 	}
+	#[test]
+	fn test_fee_calculator() {
+		let b0 = chain_builder::block_builder().header().nonce(1).build()
+			.transaction()
+				.output().value(1_000_000).build()
+				.build()
+			.build();
+		let tx0 = b0.transactions[0].clone();
+		let db = Arc::new(BlockChainDatabase::init_test_chain(vec![b0.into()]));
+
+		// spends stored output
+		let tx1: Transaction = chain_builder::TransactionBuilder::with_output(900_000).add_input(&tx0, 0).into();
+		// spends output of in-pool parent
+		let tx2: Transaction = chain_builder::TransactionBuilder::with_output(850_000).add_input(&tx1, 0).into();
+		// spends output, which is neither stored nor in pool
+		let tx3: Transaction = chain_builder::TransactionBuilder::with_output(10).add_input(&tx2, 0).into();
+		// spends more than its input is worth
+		let tx4: Transaction = chain_builder::TransactionBuilder::with_output(1_000_001).add_input(&tx0, 0).into();
+
+		let mut pool = MemoryPool::new();
+		let calculator = FeeCalculator(db.as_transaction_output_provider());
+		assert_eq!(calculator.calculate(&pool, &tx1), Ok(100_000));
+		assert_eq!(calculator.calculate(&pool, &tx2), Err(TransactionError::Input(0)));
+		assert_eq!(calculator.calculate(&pool, &tx4), Err(TransactionError::Overspend));
+
+		pool.insert_verified(tx1.clone().into(), &calculator).unwrap();
+		let entry = pool.iter(OrderingStrategy::ByTimestamp).next().unwrap();
+		assert_eq!((entry.miner_fee, entry.miner_fee_rate), (100_000, 100_000 / entry.size as i64));
+		assert_eq!(calculator.calculate(&pool, &tx2), Ok(50_000));
+		assert_eq!(pool.insert_verified(tx3.clone().into(), &calculator), Err(TransactionError::Input(0)));
+		assert!(!pool.contains(&tx3.hash()));
+	}
 }
@@ -1,5 +1,7 @@
 extern crate byteorder;
 extern crate heapsize;
+#[macro_use]
+extern crate log;
 
 extern crate bitcrypto as crypto;
 extern crate chain;
@@ -22,6 +24,6 @@ pub use block_assembler::{BlockAssembler, BlockTemplate};
 pub use memory_pool::{DoubleSpendCheckResult, HashedOutPoint,
                       Information as MemoryPoolInformation, MemoryPool, MemoryPoolRef,
                       NonFinalDoubleSpendSet, OrderingStrategy as MemoryPoolOrderingStrategy};
-pub use fee::{transaction_fee, transaction_fee_rate};
+pub use fee::{transaction_fee, transaction_fee_rate, FeeCalculator, MemoryPoolFeeCalculator};
 pub use memory_pool_transaction_provider::MemoryPoolTransactionOutputProvider;
 pub use utxo_and_output_provider::UtxoAndOutputProvider;
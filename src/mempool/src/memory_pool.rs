@@ -20,6 +20,8 @@ use heapsize::HeapSizeOf;
 use std::sync::Arc;
 use parking_lot::RwLock;
 use script::Script;
+use verification::TransactionError;
+use fee::MemoryPoolFeeCalculator;
 
 pub type MemoryPoolRef = Arc<RwLock<MemoryPool>>;
 
@@ -49,6 +51,8 @@ pub struct Information {
 pub struct MemoryPool {
 	/// Transactions storage
 	storage: Storage,
+	/// Number of times transactions have been inserted or removed
+	transactions_updated: u64,
 }
 
 /// Single entry
@@ -66,6 +70,8 @@ pub struct Entry {
 	pub storage_index: u64,
 	/// Transaction fee (stored for efficiency)
 	pub miner_fee: i64,
+	/// Transaction fee per byte (stored for efficiency)
+	pub miner_fee_rate: i64,
 	/// Virtual transaction fee (a way to prioritize/penalize transaction)
 	pub miner_virtual_fee: i64,
 	/// size + Sum(size) for all in-pool descendants
@@ -643,6 +649,7 @@ impl Default for MemoryPool {
 	fn default() -> Self {
 		MemoryPool {
 			storage: Storage::new(),
+			transactions_updated: 0,
 		}
 	}
 }
@@ -653,17 +660,27 @@ impl MemoryPool {
 		MemoryPool::default()
 	}
 
-	/// Insert verified transaction to the `MemoryPool`
-	pub fn insert_verified(&mut self, t: IndexedTransaction) {
-		let entry = self.make_entry(t);
+	/// Insert verified transaction to the `MemoryPool`.
+	/// Transaction is rejected if its fee can't be calculated
+	pub fn insert_verified<FC: MemoryPoolFeeCalculator>(&mut self, t: IndexedTransaction, fc: &FC) -> Result<(), TransactionError> {
+		let miner_fee = fc.calculate(self, &t.raw)?;
+		let entry = self.make_entry(t, miner_fee);
+		self.transactions_updated += 1;
 		let descendants = self.storage.remove_by_parent_hash(&entry.hash);
 		self.storage.insert(entry);
 		if let Some(descendants_iter) = descendants.map(|d| d.into_iter()) {
 			for descendant in descendants_iter {
-				let descendant_entry = self.make_entry(descendant);
-				self.storage.insert(descendant_entry);
+				// descendants were valued before, so their fee is expected to be known now too
+				match fc.calculate(self, &descendant.raw) {
+					Ok(miner_fee) => {
+						let descendant_entry = self.make_entry(descendant, miner_fee);
+						self.storage.insert(descendant_entry);
+					},
+					Err(err) => warn!("Dropping descendant {} of transaction from memory pool: {:?}", descendant.hash.to_reversed_str(), err),
+				}
 			}
 		}
+		Ok(())
 	}
 
 	/// Iterator over memory pool transactions according to specified strategy
@@ -674,7 +691,11 @@ impl MemoryPool {
 	/// Removes single transaction by its hash.
 	/// All descedants remain in the pool.
 	pub fn remove_by_hash(&mut self, h: &H256) -> Option<Transaction> {
-		self.storage.remove_by_hash(h).map(|entry| entry.transaction)
+		let removed = self.storage.remove_by_hash(h).map(|entry| entry.transaction);
+		if removed.is_some() {
+			self.transactions_updated += 1;
+		}
+		removed
 	}
 
 	/// Checks if `transaction` spends some outputs, already spent by inpool transactions.
@@ -684,7 +705,11 @@ impl MemoryPool {
 
 	/// Removes transaction (and all its descendants) which has spent given output
 	pub fn remove_by_prevout(&mut self, prevout: &OutPoint) -> Option<Vec<IndexedTransaction>> {
-		self.storage.remove_by_prevout(prevout)
+		let removed = self.storage.remove_by_prevout(prevout);
+		if removed.is_some() {
+			self.transactions_updated += 1;
+		}
+		removed
 	}
 
 	/// Reads single transaction by its hash.
@@ -708,13 +733,21 @@ impl MemoryPool {
 	/// Removes the 'top' transaction from the `MemoryPool` using selected strategy.
 	/// Ancestors are always removed before descendant transactions.
 	pub fn remove_with_strategy(&mut self, strategy: OrderingStrategy) -> Option<IndexedTransaction> {
-		self.storage.remove_with_strategy(strategy)
+		let removed = self.storage.remove_with_strategy(strategy);
+		if removed.is_some() {
+			self.transactions_updated += 1;
+		}
+		removed
 	}
 
 	/// Removes up to n transactions from the `MemoryPool`, using selected strategy.
 	/// Ancestors are always removed before descendant transactions.
 	pub fn remove_n_with_strategy(&mut self, n: usize, strategy: OrderingStrategy) -> Vec<IndexedTransaction> {
-		self.storage.remove_n_with_strategy(n, strategy)
+		let removed = self.storage.remove_n_with_strategy(n, strategy);
+		if !removed.is_empty() {
+			self.transactions_updated += 1;
+		}
+		removed
 	}
 
 	/// Set miner virtual fee for transaction
@@ -741,6 +774,11 @@ impl MemoryPool {
 		}
 	}
 
+	/// Changes whenever transactions are inserted or removed, so block templates know they are outdated
+	pub fn transactions_updated(&self) -> u64 {
+		self.transactions_updated
+	}
+
 	/// Returns TXIDs of all transactions in `MemoryPool` (as in GetRawMemPool RPC)
 	/// https://bitcoin.org/en/developer-reference#getrawmempool
 	pub fn get_transactions_ids(&self) -> Vec<H256> {
@@ -752,11 +790,11 @@ impl MemoryPool {
 		self.storage.is_output_spent(prevout)
 	}
 
-	fn make_entry(&mut self, t: IndexedTransaction) -> Entry {
+	fn make_entry(&mut self, t: IndexedTransaction, miner_fee: u64) -> Entry {
 		let ancestors = self.get_ancestors(&t.raw);
 		let size = self.get_transaction_size(&t.raw);
 		let storage_index = self.get_storage_index();
-		let miner_fee = self.get_transaction_miner_fee(&t.raw);
+		let miner_fee = miner_fee as i64;
 		Entry {
 			transaction: t.raw,
 			hash: t.hash,
@@ -764,6 +802,7 @@ impl MemoryPool {
 			storage_index: storage_index,
 			size: size,
 			miner_fee: miner_fee,
+			miner_fee_rate: miner_fee / size as i64,
 			miner_virtual_fee: 0,
 			// following fields are also updated when inserted to storage
 			package_size: size,
@@ -789,12 +828,6 @@ impl MemoryPool {
 		t.serialized_size()
 	}
 
-	fn get_transaction_miner_fee(&self, t: &Transaction) -> i64 {
-		let input_value = 0; // TODO: sum all inputs of transaction
-		let output_value = t.outputs.iter().fold(0, |acc, output| acc + output.value);
-		(output_value - input_value) as i64
-	}
-
 	#[cfg(not(test))]
 	fn get_storage_index(&mut self) -> u64 {
 		self.storage.counter += 1;
@@ -905,12 +938,13 @@ mod tests {
 	use chain::{Transaction, OutPoint};
 	use heapsize::HeapSizeOf;
 	use super::{MemoryPool, OrderingStrategy, DoubleSpendCheckResult};
+	use fee::NonZeroFeeCalculator;
 	use self::chain_builder::{ChainBuilder, TransactionBuilder};
 
 	fn to_memory_pool(chain: &mut ChainBuilder) -> MemoryPool {
 		let mut pool = MemoryPool::new();
 		for transaction in chain.transactions.iter().cloned() {
-			pool.insert_verified(transaction.into());
+			pool.insert_verified(transaction.into(), &NonZeroFeeCalculator).unwrap();
 		}
 		pool
 	}
@@ -921,11 +955,11 @@ mod tests {
 
 		let size1 = pool.heap_size_of_children();
 
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		let size2 = pool.heap_size_of_children();
 		assert!(size2 > size1);
 
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		let size3 = pool.heap_size_of_children();
 		assert!(size3 > size2);
 	}
@@ -933,11 +967,11 @@ mod tests {
 	#[test]
 	fn test_memory_pool_insert_same_transaction() {
 		let mut pool = MemoryPool::new();
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		assert_eq!(pool.get_transactions_ids().len(), 1);
 
 		// insert the same transaction again
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		assert_eq!(pool.get_transactions_ids().len(), 1);
 	}
 
@@ -947,7 +981,7 @@ mod tests {
 		assert_eq!(pool.read_with_strategy(OrderingStrategy::ByTimestamp), None);
 		assert_eq!(pool.read_n_with_strategy(100, OrderingStrategy::ByTimestamp), vec![]);
 
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		assert_eq!(pool.read_with_strategy(OrderingStrategy::ByTimestamp), Some(Transaction::default().hash()));
 		assert_eq!(pool.read_n_with_strategy(100, OrderingStrategy::ByTimestamp), vec![Transaction::default().hash()]);
 		assert_eq!(pool.read_with_strategy(OrderingStrategy::ByTimestamp), Some(Transaction::default().hash()));
@@ -960,12 +994,12 @@ mod tests {
 		assert_eq!(pool.remove_with_strategy(OrderingStrategy::ByTimestamp), None);
 		assert_eq!(pool.remove_n_with_strategy(100, OrderingStrategy::ByTimestamp), vec![]);
 
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		let removed = pool.remove_with_strategy(OrderingStrategy::ByTimestamp);
 		assert!(removed.is_some());
 		assert_eq!(removed.unwrap(), Transaction::default().into());
 
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		let removed = pool.remove_n_with_strategy(100, OrderingStrategy::ByTimestamp);
 		assert_eq!(removed.len(), 1);
 		assert_eq!(removed[0], Transaction::default().into());
@@ -978,7 +1012,7 @@ mod tests {
 	fn test_memory_pool_remove_by_hash() {
 		let mut pool = MemoryPool::new();
 
-		pool.insert_verified(Transaction::default().into());
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
 		assert_eq!(pool.get_transactions_ids().len(), 1);
 
 		// remove and check remaining transactions
@@ -992,6 +1026,22 @@ mod tests {
 		assert_eq!(pool.get_transactions_ids().len(), 0);
 	}
 
+	#[test]
+	fn test_memory_pool_transactions_updated() {
+		let mut pool = MemoryPool::new();
+		assert_eq!(pool.transactions_updated(), 0);
+
+		pool.insert_verified(Transaction::default().into(), &NonZeroFeeCalculator).unwrap();
+		assert_eq!(pool.transactions_updated(), 1);
+
+		// removing non-existant transaction doesn't change the pool
+		pool.remove_by_hash(&TransactionBuilder::with_version(1).hash());
+		assert_eq!(pool.transactions_updated(), 1);
+
+		pool.remove_by_hash(&Transaction::default().hash());
+		assert_eq!(pool.transactions_updated(), 2);
+	}
+
 	#[test]
 	fn test_memory_pool_insert_parent_after_child() {
 		let chain = &mut ChainBuilder::new();
@@ -1001,9 +1051,9 @@ mod tests {
 
 		// insert child, then parent
 		let mut pool = MemoryPool::new();
-		pool.insert_verified(chain.at(2).into()); // timestamp 0
-		pool.insert_verified(chain.at(1).into()); // timestamp 1
-		pool.insert_verified(chain.at(0).into()); // timestamp 2
+		pool.insert_verified(chain.at(2).into(), &NonZeroFeeCalculator).unwrap(); // timestamp 0
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap(); // timestamp 1
+		pool.insert_verified(chain.at(0).into(), &NonZeroFeeCalculator).unwrap(); // timestamp 2
 
 		// check that parent transaction was removed before child trnasaction
 		let transactions = pool.remove_n_with_strategy(3, OrderingStrategy::ByTimestamp);
@@ -1046,7 +1096,7 @@ mod tests {
 		assert_eq!(pool.get_transactions_ids().len(), 2);
 
 		// insert child transaction back to the pool & assert transactions are removed in correct order
-		pool.insert_verified(chain.at(1).into());
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
 		let transactions = pool.remove_n_with_strategy(3, OrderingStrategy::ByTransactionScore);
 		assert_eq!(transactions.len(), 3);
 		assert_eq!(transactions[0], chain.at(0).into());
@@ -1065,7 +1115,7 @@ mod tests {
 
 		let mut transactions_size = 0;
 		for transaction_index in 0..4 {
-			pool.insert_verified(chain.at(transaction_index).into());
+			pool.insert_verified(chain.at(transaction_index).into(), &NonZeroFeeCalculator).unwrap();
 			transactions_size += chain.size(transaction_index);
 
 			let info = pool.information();
@@ -1153,8 +1203,8 @@ mod tests {
 		// <
 		// score({ transaction2 }) = 35/60
 		let expected = vec![chain.hash(2), chain.hash(0)];
-		pool.insert_verified(chain.at(0).into());
-		pool.insert_verified(chain.at(2).into());
+		pool.insert_verified(chain.at(0).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(2).into(), &NonZeroFeeCalculator).unwrap();
 		assert_eq!(pool.read_n_with_strategy(2, OrderingStrategy::ByPackageScore), expected);
 
 		// { transaction0, transaction1 } now have bigger score than { transaction2 }:
@@ -1163,7 +1213,7 @@ mod tests {
 		// score({ transaction2 }) = 35/60 ~ 0.583
 		// => chain1 is boosted
 		// => so transaction with lesser individual score (but with bigger package score) is mined first
-		pool.insert_verified(chain.at(1).into());
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(0), chain.hash(1), chain.hash(2)];
 		assert_eq!(pool.read_n_with_strategy(3, OrderingStrategy::ByPackageScore), expected);
 
@@ -1172,7 +1222,7 @@ mod tests {
 		// >
 		// score({ transaction2, transaction3 }) = (35 + 10) / 120 ~ 0.375
 		// => chain2 is not boosted
-		pool.insert_verified(chain.at(3).into());
+		pool.insert_verified(chain.at(3).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(0), chain.hash(1), chain.hash(2), chain.hash(3)];
 		assert_eq!(pool.read_n_with_strategy(4, OrderingStrategy::ByPackageScore), expected);
 
@@ -1181,7 +1231,7 @@ mod tests {
 		// <
 		// score({ transaction2, transaction3, transaction4 }) = (35 + 10 + 100) / 180 ~ 0.806
 		// => chain2 is boosted
-		pool.insert_verified(chain.at(4).into());
+		pool.insert_verified(chain.at(4).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(2), chain.hash(3), chain.hash(4), chain.hash(0), chain.hash(1)];
 		assert_eq!(pool.read_n_with_strategy(5, OrderingStrategy::ByPackageScore), expected);
 
@@ -1205,15 +1255,15 @@ mod tests {
 		// chain1_parent is not linked to the chain1_grandchild
 		// => they are in separate chains now
 		// => chain2 has greater score than both of these chains
-		pool.insert_verified(chain.at(3).into());
-		pool.insert_verified(chain.at(0).into());
-		pool.insert_verified(chain.at(2).into());
+		pool.insert_verified(chain.at(3).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(0).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(2).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(3), chain.hash(0), chain.hash(2)];
 		assert_eq!(pool.read_n_with_strategy(3, OrderingStrategy::ByPackageScore), expected);
 
 		// insert the missing transaction to link together chain1
 		// => it now will have better score than chain2
-		pool.insert_verified(chain.at(1).into());
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(0), chain.hash(1), chain.hash(3), chain.hash(2)];
 		assert_eq!(pool.read_n_with_strategy(4, OrderingStrategy::ByPackageScore), expected);
 	}
@@ -1237,9 +1287,9 @@ mod tests {
 		// insert level1 + level2. There are two chains:
 		// score({ transaction3, transaction5 }) = 40 + 60
 		// score({ transaction4, transaction5 }) = 50 + 60
-		pool.insert_verified(chain.at(5).into());
-		pool.insert_verified(chain.at(3).into());
-		pool.insert_verified(chain.at(4).into());
+		pool.insert_verified(chain.at(5).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(3).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(4).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(4), chain.hash(3), chain.hash(5)];
 		assert_eq!(pool.read_n_with_strategy(3, OrderingStrategy::ByTransactionScore), expected);
 		assert_eq!(pool.read_n_with_strategy(3, OrderingStrategy::ByPackageScore), expected);
@@ -1248,7 +1298,7 @@ mod tests {
 		// score({ transaction3, transaction5 }) = 40 + 60
 		// score({ transaction4, transaction5 }) = 50 + 60
 		// score({ transaction2, transaction5 }) = 30 + 60
-		pool.insert_verified(chain.at(2).into());
+		pool.insert_verified(chain.at(2).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(4), chain.hash(3), chain.hash(2), chain.hash(5)];
 		assert_eq!(pool.read_n_with_strategy(4, OrderingStrategy::ByTransactionScore), expected);
 		assert_eq!(pool.read_n_with_strategy(4, OrderingStrategy::ByPackageScore), expected);
@@ -1258,7 +1308,7 @@ mod tests {
 		// score({ transaction1, transaction4, transaction5 }) = 20 + 50 + 60 / 3 ~ 0.333
 		// score({ transaction2, transaction5 }) = 30 + 60 / 2 = 0.45
 		// but second chain will be removed first anyway because previous #1 ({ transaction4, transaction5}) now depends on level 01
-		pool.insert_verified(chain.at(1).into());
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(3), chain.hash(2), chain.hash(1), chain.hash(4), chain.hash(5)];
 		assert_eq!(pool.read_n_with_strategy(5, OrderingStrategy::ByTransactionScore), expected);
 		assert_eq!(pool.read_n_with_strategy(5, OrderingStrategy::ByPackageScore), expected);
@@ -1268,7 +1318,7 @@ mod tests {
 		// score({ transaction0, transaction4, transaction5 }) = (10 + 50 + 60) / (60 + 60 + 142) ~ 0.458
 		// score({ transaction1, transaction3, transaction5 }) = (20 + 50 + 60) / (60 + 60 + 142) ~ 0.496
 		// score({ transaction2, transaction5 }) = (30 + 60) / (60 + 142) ~ 0.445
-		pool.insert_verified(chain.at(0).into());
+		pool.insert_verified(chain.at(0).into(), &NonZeroFeeCalculator).unwrap();
 		let expected = vec![chain.hash(2), chain.hash(1), chain.hash(0), chain.hash(4), chain.hash(3), chain.hash(5)];
 		assert_eq!(pool.read_n_with_strategy(6, OrderingStrategy::ByTransactionScore), expected);
 		assert_eq!(pool.read_n_with_strategy(6, OrderingStrategy::ByPackageScore), expected);
@@ -1289,17 +1339,17 @@ mod tests {
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(1), index: 0, }));
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(2), index: 0, }));
 
-		pool.insert_verified(chain.at(0).into());
+		pool.insert_verified(chain.at(0).into(), &NonZeroFeeCalculator).unwrap();
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(0), index: 0, }));
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(1), index: 0, }));
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(2), index: 0, }));
 
-		pool.insert_verified(chain.at(1).into());
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(0), index: 0, }));
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(1), index: 0, }));
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(2), index: 0, }));
 
-		pool.insert_verified(chain.at(2).into());
+		pool.insert_verified(chain.at(2).into(), &NonZeroFeeCalculator).unwrap();
 		assert!(pool.is_spent(&OutPoint { hash: chain.hash(0), index: 0, }));
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(1), index: 0, }));
 		assert!(!pool.is_spent(&OutPoint { hash: chain.hash(2), index: 0, }));
@@ -1322,10 +1372,10 @@ mod tests {
 			.reset().add_output(40).store(chain);			// transaction3
 		let mut pool = MemoryPool::new();
 
-		pool.insert_verified(chain.at(0).into());
-		pool.insert_verified(chain.at(1).into());
-		pool.insert_verified(chain.at(2).into());
-		pool.insert_verified(chain.at(3).into());
+		pool.insert_verified(chain.at(0).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(2).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(3).into(), &NonZeroFeeCalculator).unwrap();
 		assert_eq!(pool.information().transactions_count, 4);
 
 		assert_eq!(pool.remove_by_prevout(&OutPoint { hash: chain.hash(0), index: 0 }), Some(vec![chain.at(1).into(), chain.at(2).into()]));
@@ -1345,9 +1395,9 @@ mod tests {
 			.reset().set_input(&chain.at(0), 2).add_output(70).store(chain);			// no double spend: t0[2] -> t6
 
 		let mut pool = MemoryPool::new();
-		pool.insert_verified(chain.at(1).into());
-		pool.insert_verified(chain.at(2).into());
-		pool.insert_verified(chain.at(4).into());
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(2).into(), &NonZeroFeeCalculator).unwrap();
+		pool.insert_verified(chain.at(4).into(), &NonZeroFeeCalculator).unwrap();
 		// when output is spent by nonfinal transaction
 		match pool.check_double_spend(&chain.at(3)) {
 			DoubleSpendCheckResult::NonFinalDoubleSpend(set) => {
@@ -1390,7 +1440,7 @@ mod tests {
 			.reset().set_input(&chain.at(0), 0).add_output(40).store(chain);										// good replacement: t0[0] -> t2
 
 		let mut pool = MemoryPool::new();
-		pool.insert_verified(chain.at(1).into());
+		pool.insert_verified(chain.at(1).into(), &NonZeroFeeCalculator).unwrap();
 
 		// when output is spent by nonfinal transaction
 		match pool.check_double_spend(&chain.at(2)) {
@@ -1423,7 +1473,7 @@ mod tests {
 		let out1 = tx1.inputs[0].previous_output.clone();
 		let out2 = tx2.inputs[0].previous_output.clone();
 		let mut memory_pool = MemoryPool::new();
-		memory_pool.insert_verified(tx1.into());
+		memory_pool.insert_verified(tx1.into(), &NonZeroFeeCalculator).unwrap();
 		assert!(memory_pool.is_spent(&out1));
 		assert!(!memory_pool.is_spent(&out2));
 	}
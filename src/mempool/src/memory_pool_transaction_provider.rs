@@ -100,6 +100,7 @@ mod tests {
 	use chain::OutPoint;
 	use db::{TransactionOutputProvider, BlockChainDatabase};
 	use memory_pool::MemoryPool;
+	use fee::NonZeroFeeCalculator;
 	use super::MemoryPoolTransactionOutputProvider;
 
 	#[test]
@@ -114,9 +115,9 @@ mod tests {
 		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![chain_builder::genesis().into()]));
 		let memory_pool = Arc::new(RwLock::new(MemoryPool::new()));
 		{
-			memory_pool.write().insert_verified(dchain.at(0).into());
-			memory_pool.write().insert_verified(dchain.at(1).into());
-			memory_pool.write().insert_verified(dchain.at(2).into());
+			memory_pool.write().insert_verified(dchain.at(0).into(), &NonZeroFeeCalculator).unwrap();
+			memory_pool.write().insert_verified(dchain.at(1).into(), &NonZeroFeeCalculator).unwrap();
+			memory_pool.write().insert_verified(dchain.at(2).into(), &NonZeroFeeCalculator).unwrap();
 		}
 
 		// when inserting t3:
@@ -70,6 +70,20 @@ impl InventoryVector {
 			hash: hash,
 		}
 	}
+
+	pub fn witness_tx(hash: H256) -> Self {
+		InventoryVector {
+			inv_type: InventoryType::MessageWitnessTx,
+			hash: hash,
+		}
+	}
+
+	pub fn witness_block(hash: H256) -> Self {
+		InventoryVector {
+			inv_type: InventoryType::MessageWitnessBlock,
+			hash: hash,
+		}
+	}
 }
 
 impl Serializable for InventoryVector {
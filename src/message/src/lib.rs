@@ -16,6 +16,6 @@ mod error;
 pub use primitives::{hash, bytes};
 
 pub use common::{Command, Services};
-pub use message::{Message, MessageHeader, Payload, to_raw_message};
+pub use message::{Message, MessageHeader, Payload, to_raw_message, HEADER_SIZE, MAX_PAYLOAD_SIZE};
 pub use serialization::{serialize_payload, deserialize_payload};
 pub use error::{Error, MessageResult};
@@ -5,6 +5,11 @@ use params::Magic;
 use common::Command;
 use Error;
 
+/// Magic, command, payload length and checksum
+pub const HEADER_SIZE: usize = 24;
+/// Largest accepted payload. Fits biggest block with witness
+pub const MAX_PAYLOAD_SIZE: u32 = 4_000_000;
+
 #[derive(Debug, PartialEq)]
 pub struct MessageHeader {
 	pub magic: Magic,
@@ -26,7 +31,7 @@ impl MessageHeader {
 
 impl MessageHeader {
 	pub fn deserialize(data: &[u8], expected: Magic) -> Result<Self, Error> {
-		if data.len() != 24 {
+		if data.len() != HEADER_SIZE {
 			return Err(Error::Deserialize);
 		}
 
@@ -3,5 +3,5 @@ mod message_header;
 pub mod payload;
 
 pub use self::message::{Message, to_raw_message};
-pub use self::message_header::MessageHeader;
+pub use self::message_header::{MessageHeader, HEADER_SIZE, MAX_PAYLOAD_SIZE};
 pub use self::payload::Payload;
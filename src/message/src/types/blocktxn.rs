@@ -0,0 +1,33 @@
+use std::io;
+use ser::{Stream, Reader};
+use common::BlockTransactions;
+use {Payload, MessageResult};
+
+/// Transactions of compact block requested with `getblocktxn`
+#[derive(Debug, PartialEq)]
+pub struct BlockTxn {
+	pub request: BlockTransactions,
+}
+
+impl Payload for BlockTxn {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"blocktxn"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let block = BlockTxn {
+			request: try!(reader.read()),
+		};
+
+		Ok(block)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		stream.append(&self.request);
+		Ok(())
+	}
+}
@@ -0,0 +1,103 @@
+use std::io;
+use chain::BlockHeader;
+use ser::{Stream, Reader, Serializable, Deserializable, CompactInteger, Error as ReaderError};
+use {Payload, MessageResult};
+
+/// Most headers sent in one message
+pub const HEADERS_MAX_HEADERS_LEN: usize = 2000;
+
+#[derive(Debug, PartialEq)]
+pub struct Headers {
+	pub headers: Vec<BlockHeader>,
+}
+
+impl Headers {
+	pub fn with_headers(headers: Vec<BlockHeader>) -> Self {
+		Headers {
+			headers: headers,
+		}
+	}
+}
+
+/// Header is followed by transactions count, which is always 0
+struct HeaderWithTxnCount {
+	header: BlockHeader,
+}
+
+impl Deserializable for HeaderWithTxnCount {
+	fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+		let header = HeaderWithTxnCount {
+			header: try!(reader.read()),
+		};
+
+		let txn_count: CompactInteger = try!(reader.read());
+		if u64::from(txn_count) != 0 {
+			return Err(ReaderError::MalformedData);
+		}
+
+		Ok(header)
+	}
+}
+
+struct HeaderWithTxnCountRef<'a> {
+	header: &'a BlockHeader,
+}
+
+impl<'a> Serializable for HeaderWithTxnCountRef<'a> {
+	fn serialize(&self, stream: &mut Stream) {
+		stream
+			.append(self.header)
+			.append(&CompactInteger::from(0u8));
+	}
+}
+
+impl Payload for Headers {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"headers"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let headers: Vec<HeaderWithTxnCount> = try!(reader.read_list_max(HEADERS_MAX_HEADERS_LEN));
+		let headers = Headers {
+			headers: headers.into_iter().map(|header| header.header).collect(),
+		};
+
+		Ok(headers)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		let headers: Vec<_> = self.headers.iter().map(|header| HeaderWithTxnCountRef { header: header }).collect();
+		stream.append_list::<HeaderWithTxnCountRef, HeaderWithTxnCountRef>(&headers);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chain::BlockHeader;
+	use serialization::{serialize_payload, deserialize_payload};
+	use super::Headers;
+
+	#[test]
+	fn test_headers_serde() {
+		let header = BlockHeader {
+			version: 1,
+			previous_header_hash: [2; 32].into(),
+			merkle_root_hash: [3; 32].into(),
+			witness_merkle_root_hash: [4; 32].into(),
+			time: 4,
+			bits: 5.into(),
+			nonce: 6,
+		};
+		let headers = Headers::with_headers(vec![header.clone(), header]);
+
+		let serialized = serialize_payload(&headers, 0).unwrap();
+		// two headers, each followed by zero transactions count
+		assert_eq!(serialized.len(), 1 + 2 * (112 + 1));
+		assert_eq!(deserialize_payload::<Headers>(&serialized, 0).unwrap(), headers);
+	}
+}
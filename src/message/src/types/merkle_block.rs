@@ -0,0 +1,76 @@
+use std::io;
+use ser::{Stream, Reader};
+use chain::BlockHeader;
+use hash::H256;
+use bytes::Bytes;
+use {Payload, MessageResult};
+
+/// Block header with partial merkle tree proving inclusion of transactions matching peer's filter
+#[derive(Debug, PartialEq)]
+pub struct MerkleBlock {
+	pub block_header: BlockHeader,
+	pub total_transactions: u32,
+	/// Hashes of partial merkle tree nodes in depth-first order
+	pub hashes: Vec<H256>,
+	/// Bits telling which nodes are parents of matched transactions, in depth-first order
+	pub flags: Bytes,
+}
+
+impl Payload for MerkleBlock {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"merkleblock"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let merkle_block = MerkleBlock {
+			block_header: try!(reader.read()),
+			total_transactions: try!(reader.read()),
+			hashes: try!(reader.read_list()),
+			flags: try!(reader.read()),
+		};
+
+		Ok(merkle_block)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		stream
+			.append(&self.block_header)
+			.append(&self.total_transactions)
+			.append_list(&self.hashes)
+			.append(&self.flags);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chain::BlockHeader;
+	use serialization::{serialize_payload, deserialize_payload};
+	use super::MerkleBlock;
+
+	#[test]
+	fn test_merkle_block_serde() {
+		let merkle_block = MerkleBlock {
+			block_header: BlockHeader {
+				version: 1,
+				previous_header_hash: [2; 32].into(),
+				merkle_root_hash: [3; 32].into(),
+				witness_merkle_root_hash: [4; 32].into(),
+				time: 4,
+				bits: 5.into(),
+				nonce: 6,
+			},
+			total_transactions: 3,
+			hashes: vec![[3; 32].into()],
+			flags: vec![0u8].into(),
+		};
+
+		let serialized = serialize_payload(&merkle_block, 0).unwrap();
+		assert_eq!(serialized.len(), 112 + 4 + 1 + 32 + 1 + 1);
+		assert_eq!(deserialize_payload::<MerkleBlock>(&serialized, 0).unwrap(), merkle_block);
+	}
+}
@@ -1,13 +1,40 @@
 mod block;
 mod tx;
 mod getblocks;
+mod getheaders;
+mod headers;
 mod inv;
 mod getdata;
+mod notfound;
+mod merkle_block;
+mod version;
+mod verack;
+mod ping;
+mod pong;
+mod sendcompact;
+mod compactblock;
+mod getblocktxn;
+mod blocktxn;
 
 pub use self::block::Block;
 pub use self::tx::Tx;
 pub use self::getblocks::GetBlocks;
+pub use self::getheaders::GetHeaders;
+pub use self::headers::Headers;
 pub use self::inv::Inv;
 pub use self::getdata::GetData;
+pub use self::notfound::NotFound;
+pub use self::merkle_block::MerkleBlock;
+pub use self::version::Version;
+pub use self::verack::Verack;
+pub use self::ping::Ping;
+pub use self::pong::Pong;
+pub use self::sendcompact::SendCompact;
+pub use self::compactblock::CompactBlock;
+pub use self::getblocktxn::GetBlockTxn;
+pub use self::blocktxn::BlockTxn;
 
 pub use self::getblocks::GETBLOCKS_MAX_RESPONSE_HASHES;
+pub use self::headers::HEADERS_MAX_HEADERS_LEN;
+pub use self::version::{PROTOCOL_VERSION, MIN_PROTOCOL_VERSION};
+pub use self::sendcompact::COMPACT_BLOCKS_VERSION;
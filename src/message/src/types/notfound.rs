@@ -0,0 +1,43 @@
+use std::io;
+use ser::{Stream, Reader};
+use common::InventoryVector;
+use {Payload, MessageResult};
+
+pub const NOTFOUND_MAX_INVENTORY_LEN: usize = 50_000;
+
+/// Reply to `getdata` listing requested items which are unknown
+#[derive(Debug, PartialEq)]
+pub struct NotFound {
+	pub inventory: Vec<InventoryVector>,
+}
+
+impl NotFound {
+	pub fn with_inventory(inventory: Vec<InventoryVector>) -> Self {
+		NotFound {
+			inventory: inventory,
+		}
+	}
+}
+
+impl Payload for NotFound {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"notfound"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let notfound = NotFound {
+			inventory: try!(reader.read_list_max(NOTFOUND_MAX_INVENTORY_LEN)),
+		};
+
+		Ok(notfound)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		stream.append_list(&self.inventory);
+		Ok(())
+	}
+}
@@ -0,0 +1,60 @@
+use std::io;
+use ser::{Stream, Reader};
+use {Payload, MessageResult};
+
+/// Version of compact blocks, which short ids are calculated from witness hashes of transactions
+pub const COMPACT_BLOCKS_VERSION: u64 = 2;
+
+/// Tells peer which version of compact blocks we support and whether new blocks should be announced with them
+#[derive(Debug, PartialEq)]
+pub struct SendCompact {
+	/// Peer should send new blocks as compact blocks instead of announcing them with inv
+	pub announce: bool,
+	pub version: u64,
+}
+
+impl Payload for SendCompact {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"sendcmpct"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let send_compact = SendCompact {
+			announce: try!(reader.read()),
+			version: try!(reader.read()),
+		};
+
+		Ok(send_compact)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		stream
+			.append(&self.announce)
+			.append(&self.version);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use serialization::{serialize_payload, deserialize_payload};
+	use super::{SendCompact, COMPACT_BLOCKS_VERSION};
+
+	#[test]
+	fn test_send_compact_serde() {
+		let send_compact = SendCompact {
+			announce: true,
+			version: COMPACT_BLOCKS_VERSION,
+		};
+
+		let expected: Bytes = "010200000000000000".into();
+		let serialized = serialize_payload(&send_compact, 0).unwrap();
+		assert_eq!(serialized, expected);
+		assert_eq!(deserialize_payload::<SendCompact>(&serialized, 0).unwrap(), send_compact);
+	}
+}
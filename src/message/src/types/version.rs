@@ -0,0 +1,111 @@
+use std::io;
+use ser::{Stream, Reader};
+use common::{NetAddress, Services};
+use {Payload, MessageResult};
+
+/// Protocol version of this node
+pub const PROTOCOL_VERSION: u32 = 70015;
+/// Peers with older protocol are disconnected
+pub const MIN_PROTOCOL_VERSION: u32 = 70001;
+/// Relay flag is sent since this protocol version
+const RELAY_VERSION: u32 = 70001;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Version {
+	pub version: u32,
+	pub services: Services,
+	pub timestamp: i64,
+	pub receiver: NetAddress,
+	pub sender: NetAddress,
+	/// Random number used to detect connections to self
+	pub nonce: u64,
+	pub user_agent: String,
+	pub start_height: i32,
+	/// Whether peer wants transactions to be announced
+	pub relay: bool,
+}
+
+impl Payload for Version {
+	fn version() -> u32 {
+		0
+	}
+
+	fn command() -> &'static str {
+		"version"
+	}
+
+	fn deserialize_payload<T>(reader: &mut Reader<T>, _version: u32) -> MessageResult<Self> where T: io::Read {
+		let version: u32 = try!(reader.read());
+		let mut message = Version {
+			version: version,
+			services: try!(reader.read()),
+			timestamp: try!(reader.read()),
+			receiver: try!(reader.read()),
+			sender: try!(reader.read()),
+			nonce: try!(reader.read()),
+			user_agent: try!(reader.read()),
+			start_height: try!(reader.read()),
+			relay: true,
+		};
+		// relay flag is optional even for newer peers
+		if version >= RELAY_VERSION && !reader.is_finished() {
+			message.relay = try!(reader.read());
+		}
+
+		Ok(message)
+	}
+
+	fn serialize_payload(&self, stream: &mut Stream, _version: u32) -> MessageResult<()> {
+		stream
+			.append(&self.version)
+			.append(&self.services)
+			.append(&self.timestamp)
+			.append(&self.receiver)
+			.append(&self.sender)
+			.append(&self.nonce)
+			.append(&self.user_agent)
+			.append(&self.start_height);
+		if self.version >= RELAY_VERSION {
+			stream.append(&self.relay);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use common::Services;
+	use serialization::{serialize_payload, deserialize_payload};
+	use super::Version;
+
+	#[test]
+	fn test_version_serde() {
+		let version = Version {
+			version: 70015,
+			services: Services::default().with_network(true).with_witness(true),
+			timestamp: 1537000000,
+			receiver: "010000000000000000000000000000000000ffff0a000001208d".into(),
+			sender: Default::default(),
+			nonce: 0x0102030405060708,
+			user_agent: "/rustheus:0.1.0/".into(),
+			start_height: 212672,
+			relay: false,
+		};
+
+		let serialized = serialize_payload(&version, 0).unwrap();
+		assert_eq!(deserialize_payload::<Version>(&serialized, 0).unwrap(), version);
+	}
+
+	#[test]
+	fn test_version_without_relay_flag() {
+		// version 60002 message from the protocol documentation
+		let raw: Bytes = "62ea0000010000000000000011b2d05000000000010000000000000000000000000000000000ffff000000000000010000000000000000000000000000000000ffff0000000000003b2eb35d8ce617650f2f5361746f7368693a302e372e322fc03e0300".into();
+		let version = deserialize_payload::<Version>(&raw, 0).unwrap();
+
+		assert_eq!(version.version, 60002);
+		assert_eq!(version.user_agent, "/Satoshi:0.7.2/");
+		assert_eq!(version.start_height, 212672);
+		assert!(version.relay);
+	}
+}
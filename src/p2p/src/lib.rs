@@ -7,10 +7,20 @@ extern crate serde_derive;
 
 extern crate lru_time_cache;
 extern crate maidsafe_utilities;
+extern crate message;
+extern crate params;
 extern crate primitives;
 extern crate routing;
 
+mod loopback_transport;
 mod network;
+mod routing_transport;
+mod tcp_transport;
+mod transport;
 
-pub use routing::XorName;
-pub use network::{PeerAndBytes, PeerIndex, NetworkNode};
\ No newline at end of file
+pub use loopback_transport::{LoopbackEndpoint, LoopbackTransport};
+pub use network::NetworkNode;
+pub use routing_transport::RoutingTransport;
+pub use tcp_transport::TcpTransport;
+pub use transport::{NetworkEvent, NetworkRequest, PeerAddress, PeerAndBytes, PeerIndex, Transport, TransportEvent,
+                    BROADCAST_PEER};
\ No newline at end of file
@@ -0,0 +1,181 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::mpsc::{self, Receiver, Sender};
+use primitives::bytes::Bytes;
+use transport::{PeerAddress, PeerAndBytes, PeerIndex, Transport, TransportEvent, BROADCAST_PEER};
+
+/// Shared by all endpoints, so peer index never points to two different links
+static NEXT_PEER: AtomicUsize = ATOMIC_USIZE_INIT;
+/// Distinguishes endpoints like ip addresses distinguish tcp peers
+static NEXT_ADDRESS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+struct Link {
+    /// Index of remote endpoint on our side
+    peer: PeerIndex,
+    /// Index of our endpoint on remote side
+    remote_peer: PeerIndex,
+    remote_address: PeerAddress,
+    remote: Sender<TransportEvent>,
+    remote_links: Arc<Mutex<Vec<Link>>>,
+}
+
+/// Side of loopback transport which stays with the test after transport is moved to node thread
+#[derive(Clone)]
+pub struct LoopbackEndpoint {
+    address: PeerAddress,
+    events_sender: Sender<TransportEvent>,
+    links: Arc<Mutex<Vec<Link>>>,
+}
+
+impl LoopbackEndpoint {
+    /// Address under which this endpoint is known to linked endpoints
+    pub fn address(&self) -> &PeerAddress {
+        &self.address
+    }
+
+    /// Connects two endpoints, as if one of nodes has dialed another
+    pub fn link(&self, other: &LoopbackEndpoint) {
+        assert!(!Arc::ptr_eq(&self.links, &other.links), "endpoint can't be linked to itself");
+        // index of broadcast is never assigned
+        let peer = NEXT_PEER.fetch_add(1, Ordering::SeqCst) + BROADCAST_PEER + 1;
+        let remote_peer = NEXT_PEER.fetch_add(1, Ordering::SeqCst) + BROADCAST_PEER + 1;
+        self.add_link(Link {
+            peer,
+            remote_peer,
+            remote_address: other.address.clone(),
+            remote: other.events_sender.clone(),
+            remote_links: other.links.clone(),
+        });
+        other.add_link(Link {
+            peer: remote_peer,
+            remote_peer: peer,
+            remote_address: self.address.clone(),
+            remote: self.events_sender.clone(),
+            remote_links: self.links.clone(),
+        });
+    }
+
+    /// Removes link on both sides, as if connection was closed
+    fn unlink(&self, peer: PeerIndex) {
+        let link = {
+            let mut links = self.links.lock().unwrap();
+            match links.iter().position(|link| link.peer == peer) {
+                Some(position) => links.remove(position),
+                None => return,
+            }
+        };
+        link.remote_links.lock().unwrap().retain(|remote_link| remote_link.peer != link.remote_peer);
+        let _ = self.events_sender.send(TransportEvent::PeerDisconnected(peer));
+        let _ = link.remote.send(TransportEvent::PeerDisconnected(link.remote_peer));
+    }
+
+    fn add_link(&self, link: Link) {
+        let mut links = self.links.lock().unwrap();
+        let _ = self.events_sender.send(TransportEvent::PeerConnected(link.peer, link.remote_address.clone()));
+        // node joins the network with its first link
+        if links.is_empty() {
+            let _ = self.events_sender.send(TransportEvent::Connected);
+        }
+        links.push(link);
+    }
+
+    fn deliver(link: &Link, bytes: Bytes) {
+        let message = PeerAndBytes {
+            peer: link.remote_peer,
+            bytes,
+        };
+        // remote node may already be stopped
+        let _ = link.remote.send(TransportEvent::Message(message));
+    }
+}
+
+/// In-memory transport connecting nodes running in the same process
+pub struct LoopbackTransport {
+    endpoint: LoopbackEndpoint,
+    events: Receiver<TransportEvent>,
+}
+
+impl LoopbackTransport {
+    pub fn new() -> Self {
+        let (events_sender, events) = mpsc::channel();
+        LoopbackTransport {
+            endpoint: LoopbackEndpoint {
+                address: format!("loopback#{}", NEXT_ADDRESS.fetch_add(1, Ordering::SeqCst)),
+                events_sender,
+                links: Arc::new(Mutex::new(Vec::new())),
+            },
+            events,
+        }
+    }
+
+    /// Endpoint used to link this transport with others, also after node has started
+    pub fn endpoint(&self) -> LoopbackEndpoint {
+        self.endpoint.clone()
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, peer: PeerIndex, bytes: Bytes) {
+        let links = self.endpoint.links.lock().unwrap();
+        match links.iter().find(|link| link.peer == peer) {
+            Some(link) => LoopbackEndpoint::deliver(link, bytes),
+            None => warn!("Can't send message to unknown peer#{}", peer),
+        }
+    }
+
+    fn broadcast(&mut self, bytes: Bytes) {
+        for link in self.endpoint.links.lock().unwrap().iter() {
+            LoopbackEndpoint::deliver(link, bytes.clone());
+        }
+    }
+
+    fn disconnect(&mut self, peer: PeerIndex) {
+        self.endpoint.unlink(peer);
+    }
+
+    fn poll_event(&mut self) -> Option<TransportEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use primitives::bytes::Bytes;
+    use transport::{Transport, TransportEvent};
+    use super::LoopbackTransport;
+
+    #[test]
+    fn test_linked_transports_exchange_messages() {
+        let mut first = LoopbackTransport::new();
+        let mut second = LoopbackTransport::new();
+        first.endpoint().link(&second.endpoint());
+
+        let second_on_first = match (first.poll_event(), first.poll_event()) {
+            (Some(TransportEvent::PeerConnected(peer, _)), Some(TransportEvent::Connected)) => peer,
+            _ => panic!("first transport is not connected"),
+        };
+        let first_on_second = match (second.poll_event(), second.poll_event()) {
+            (Some(TransportEvent::PeerConnected(peer, _)), Some(TransportEvent::Connected)) => peer,
+            _ => panic!("second transport is not connected"),
+        };
+
+        first.send(second_on_first, "01".into());
+        second.broadcast("02".into());
+
+        match second.poll_event() {
+            Some(TransportEvent::Message(message)) => {
+                assert_eq!(message.peer, first_on_second);
+                assert_eq!(message.bytes, Bytes::from("01"));
+            }
+            _ => panic!("message is not delivered"),
+        }
+        match first.poll_event() {
+            Some(TransportEvent::Message(message)) => {
+                assert_eq!(message.peer, second_on_first);
+                assert_eq!(message.bytes, Bytes::from("02"));
+            }
+            _ => panic!("broadcast is not delivered"),
+        }
+        assert!(first.poll_event().is_none());
+    }
+}
@@ -0,0 +1,413 @@
+use lru_time_cache::LruCache;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{Authority, ClientError, Config, DevConfig, Event, EventStream, ImmutableData,
+              MessageId, MutableData, Node, Prefix, Request, Response, XorName};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+use std::sync::mpsc::TryRecvError;
+use primitives::bytes::Bytes;
+use transport::{PeerAndBytes, PeerIndex, Transport, TransportEvent, BROADCAST_PEER};
+
+/// Transport based on the Routing library. Peers are known by their `XorName`
+pub struct RoutingTransport {
+    /// The node interface to the Routing library.
+    node: Node,
+    idata_store: HashMap<XorName, ImmutableData>,
+    client_accounts: HashMap<XorName, u64>,
+    request_cache: LruCache<MessageId, (Authority<XorName>, Authority<XorName>)>,
+
+    peer_indexes: HashMap<XorName, PeerIndex>,
+    peer_names: HashMap<PeerIndex, XorName>,
+    /// Routing library keeps connections itself, so messages of disconnected peers are ignored instead
+    disconnected: HashSet<XorName>,
+    events: VecDeque<TransportEvent>,
+}
+
+impl RoutingTransport {
+    /// Creates a new node and attempts to establish a connection to the network.
+    pub fn new(first: bool) -> Self {
+        let dev_config = DevConfig {
+            allow_multiple_lan_nodes: true,
+            ..Default::default()
+        };
+        let config = Config {
+            dev: Some(dev_config),
+        };
+        let node = unwrap!(Node::builder().first(first).config(config).create());
+
+        RoutingTransport {
+            node: node,
+            idata_store: HashMap::new(),
+            client_accounts: HashMap::new(),
+            request_cache: LruCache::with_expiry_duration(Duration::from_secs(60 * 10)),
+
+            peer_indexes: HashMap::new(),
+            peer_names: HashMap::new(),
+            disconnected: HashSet::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Index of peer with given name. New index is assigned when peer is seen for the first time
+    fn peer_index(&mut self, name: XorName) -> PeerIndex {
+        if let Some(peer) = self.peer_indexes.get(&name) {
+            return *peer;
+        }
+        // index of broadcast is never assigned
+        let peer = self.peer_indexes.len() + BROADCAST_PEER + 1;
+        self.peer_indexes.insert(name, peer);
+        self.peer_names.insert(peer, name);
+        peer
+    }
+
+    /// Handles events raised by the Routing library. Returns false once node is terminated
+    fn handle_node_event(&mut self, event: Event) -> bool {
+        match event {
+            Event::Request { request, src, dst } => self.handle_request(request, src, dst),
+            Event::Response { response, src, dst } => self.handle_response(response, src, dst),
+            Event::NodeAdded(name, _routing_table) => {
+                info!(
+                    "{} Received NodeAdded event {:?}",
+                    self.get_debug_name(),
+                    name
+                );
+                let peer = self.peer_index(name);
+                self.disconnected.remove(&name);
+                let address = name.0.iter().map(|byte| format!("{:02x}", byte)).collect();
+                self.events.push_back(TransportEvent::PeerConnected(peer, address));
+                self.handle_node_added(name);
+            }
+            Event::NodeLost(name, _routing_table) => {
+                info!(
+                    "{} Received NodeLost event {:?}",
+                    self.get_debug_name(),
+                    name
+                );
+                let peer = self.peer_index(name);
+                if self.disconnected.insert(name) {
+                    self.events.push_back(TransportEvent::PeerDisconnected(peer));
+                }
+            }
+            Event::Connected => {
+                info!("{} Received connected event", self.get_debug_name());
+                self.events.push_back(TransportEvent::Connected);
+            }
+            Event::Terminate => {
+                info!("{} Received Terminate event", self.get_debug_name());
+                return false;
+            }
+            Event::RestartRequired => {
+                info!("{} Received RestartRequired event", self.get_debug_name());
+                self.node = unwrap!(Node::builder().create());
+            }
+            Event::SectionSplit(prefix) => {
+                info!(
+                    "{} Received SectionSplit event {:?}",
+                    self.get_debug_name(),
+                    prefix
+                );
+                self.handle_split(prefix);
+            }
+            Event::SectionMerge(prefix) => {
+                info!(
+                    "{} Received SectionMerge event {:?}",
+                    self.get_debug_name(),
+                    prefix
+                );
+                let pfx = Prefix::new(prefix.bit_count() + 1, *unwrap!(self.node.id()).name());
+                self.send_refresh(MessageId::from_lost_node(pfx.lower_bound()));
+            }
+            Event::Tick => {
+                info!("Tick");
+            }
+        }
+        true
+    }
+
+    fn handle_request(
+        &mut self,
+        request: Request,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+    ) {
+        match request {
+            Request::Refresh(payload, msg_id) => self.handle_refresh(payload, msg_id),
+            Request::GetIData { name, msg_id } => {
+                self.handle_get_idata_request(src, dst, name, msg_id)
+            }
+            Request::PutIData { data, msg_id } => {
+                self.handle_put_idata_request(src, dst, data, msg_id)
+            }
+            Request::GetMDataShell    { .. } |
+            Request::ListMDataEntries { .. } |
+            Request::GetMDataValue    { .. }
+             => warn!("Received mutable request. No mutable database should be implemented for these nodes"),
+            _ => {
+                warn!(
+                    "{:?} NetworkNode: handle for {:?} unimplemented.",
+                    self.get_debug_name(),
+                    request
+                );
+            }
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        response: Response,
+        _src: Authority<XorName>,
+        dst: Authority<XorName>,
+    ) {
+        match (response, dst) {
+            (Response::PutIData { res, msg_id }, Authority::NodeManager(_))
+            | (Response::PutIData { res, msg_id }, Authority::ManagedNode(_)) => {
+                if let Some((src, dst)) = self.request_cache.remove(&msg_id) {
+                    unwrap!(self.node.send_put_idata_response(src, dst, res, msg_id));
+                }
+            }
+            (Response::PutMData { .. }, Authority::NodeManager(_))
+            | (Response::PutMData { .. }, Authority::ManagedNode(_)) => {
+                warn!("Attempt to use response on mutable data request")
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn handle_get_idata_request(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        name: XorName,
+        msg_id: MessageId,
+    ) {
+        match (src, dst) {
+            (src @ Authority::Client { .. }, dst @ Authority::NaeManager(_)) => {
+                let res = if let Some(data) = self.idata_store.get(&name) {
+                    info!("data received is {:?}", data);
+                    Ok(data.clone())
+                } else {
+                    info!(
+                        "{:?} GetIData request failed for {:?}.",
+                        self.get_debug_name(),
+                        name
+                    );
+                    Err(ClientError::NoSuchData)
+                };
+                unwrap!(self.node.send_get_idata_response(dst, src, res, msg_id))
+            }
+            (src, dst) => unreachable!("Wrong Src and Dest Authority {:?} - {:?}", src, dst),
+        }
+    }
+
+    fn handle_put_idata_request(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        data: ImmutableData,
+        msg_id: MessageId,
+    ) {
+        match src {
+            Authority::NaeManager(src_name)
+            | Authority::NodeManager(src_name)
+            | Authority::ManagedNode(src_name)
+            | Authority::ClientManager(src_name) => {
+                let our_name = *unwrap!(self.node.id()).name();
+                if our_name == src_name {
+                    return; //ignore our own broadcasts
+                }
+            },
+            _ => unreachable!("NetworkNode: Received message with unknown source authority src ({:?})", src), 
+        }
+        match dst {
+            Authority::NaeManager(_) => {
+                    info!(
+                    "{:?} Storing : key {:?} sent from {:?}",
+                    self.get_debug_name(),
+                    data.name(),
+                    src.name()
+                );
+                let _ = self.idata_store.insert(*data.name(), data);
+                let _ = self.node.send_put_idata_response(dst, src, Ok(()), msg_id);
+            }
+            Authority::NodeManager(_) | Authority::ManagedNode(_) | Authority::ClientManager(_) => {
+                info!(
+                    "{:?} Put Request: Updating ClientManager: key {:?}",
+                    self.get_debug_name(),
+                    data.name()
+                );
+                if self.request_cache.insert(msg_id, (dst, src)).is_none() {
+                    self.handle_message(src.name(), data.value());
+                } else {
+                    warn!("Attempt to reuse message ID {:?}.", msg_id);
+                    unwrap!(self.node.send_put_idata_response(
+                        dst,
+                        src,
+                        Err(ClientError::InvalidOperation),
+                        msg_id,
+                    ));
+                }
+            }
+            _ => unreachable!("NetworkNode: Unexpected dst ({:?})", dst),
+        }
+    }
+
+    fn handle_node_added(&mut self, name: XorName) {
+        self.send_refresh(MessageId::from_added_node(name));
+    }
+
+    fn handle_split(&mut self, prefix: Prefix<XorName>) {
+        let deleted_clients: Vec<_> = self.client_accounts
+            .iter()
+            .filter(|&(client_name, _)| !prefix.matches(client_name))
+            .map(|(client_name, _)| *client_name)
+            .collect();
+        for client in &deleted_clients {
+            let _ = self.client_accounts.remove(client);
+        }
+
+        let deleted_data: Vec<_> = self.idata_store
+            .iter()
+            .filter(|&(name, _)| !prefix.matches(name))
+            .map(|(name, _)| *name)
+            .collect();
+        for id in &deleted_data {
+            let _ = self.idata_store.remove(id);
+        }
+    }
+
+    fn send_refresh(&mut self, msg_id: MessageId) {
+        for (client_name, stored) in &self.client_accounts {
+            let content = RefreshContent::Account {
+                client_name: *client_name,
+                data: *stored,
+            };
+            let content = unwrap!(serialise(&content));
+            let auth = Authority::ClientManager(*client_name);
+            unwrap!(self.node.send_refresh_request(auth, auth, content, msg_id));
+        }
+
+        for data in self.idata_store.values() {
+            let refresh_content = RefreshContent::ImmutableData(data.clone());
+            let content = unwrap!(serialise(&refresh_content));
+            let auth = Authority::NaeManager(*data.name());
+            unwrap!(self.node.send_refresh_request(auth, auth, content, msg_id));
+        }
+    }
+
+    /// Receiving a refresh message means that a quorum has been reached: Enough other members in
+    /// the section agree, so we need to update our data accordingly.
+    fn handle_refresh(&mut self, content: Vec<u8>, _id: MessageId) {
+        match unwrap!(deserialise(&content)) {
+            RefreshContent::Account { client_name, data } => {
+                info!(
+                    "{:?} handle_refresh for account. client name: {:?}",
+                    self.get_debug_name(),
+                    client_name
+                );
+                let _ = self.client_accounts.insert(client_name, data);
+            }
+            RefreshContent::ImmutableData(data) => {
+                info!(
+                    "{:?} handle_refresh for immutable data. name: {:?}",
+                    self.get_debug_name(),
+                    data.name()
+                );
+                let _ = self.idata_store.insert(*data.name(), data);
+            }
+            RefreshContent::MutableData(_) => {}
+        }
+    }
+
+    fn get_debug_name(&self) -> String {
+        match self.node.id() {
+            Ok(id) => format!("Node({:?})", id.name()),
+            Err(err) => {
+                error!("Could not get node name - {:?}", err);
+                "Node(unknown)".to_owned()
+            }
+        }
+    }
+
+    fn handle_message(&mut self, name: XorName, data: &Vec<u8>) {
+        if self.disconnected.contains(&name) {
+            return;
+        }
+        let peer_and_bytes = PeerAndBytes {
+            peer: self.peer_index(name),
+            bytes: data.clone().into(),
+        };
+        self.events.push_back(TransportEvent::Message(peer_and_bytes));
+    }
+
+    fn broadcast_message(&mut self, message: &Vec<u8>) {
+        let node_name = *unwrap!(self.node.id()).name();
+        let src = Authority::ManagedNode(node_name);
+        let dst = Authority::NodeManager(node_name);
+
+        unwrap!(self.node.send_put_idata_request(
+            src,
+            dst,
+            ImmutableData::new(message.clone()),
+            MessageId::new()
+        ));
+    }
+
+    fn send_message(&mut self, peer_name: XorName, message: &Vec<u8>) {
+        let our_name = *unwrap!(self.node.id()).name();
+        let src = Authority::ManagedNode(our_name);
+        let dst = Authority::ManagedNode(peer_name);
+
+        unwrap!(self.node.send_put_idata_request(
+            src,
+            dst,
+            ImmutableData::new(message.clone()),
+            MessageId::new()
+        ));
+    }
+}
+
+impl Transport for RoutingTransport {
+    fn send(&mut self, peer: PeerIndex, bytes: Bytes) {
+        match self.peer_names.get(&peer).cloned() {
+            Some(ref name) if self.disconnected.contains(name) => {
+                warn!("{} Can't send message to disconnected peer#{}", self.get_debug_name(), peer)
+            }
+            Some(name) => self.send_message(name, &bytes.take()),
+            None => warn!("{} Can't send message to unknown peer#{}", self.get_debug_name(), peer),
+        }
+    }
+
+    fn broadcast(&mut self, bytes: Bytes) {
+        self.broadcast_message(&bytes.take());
+    }
+
+    fn disconnect(&mut self, peer: PeerIndex) {
+        if let Some(name) = self.peer_names.get(&peer).cloned() {
+            if self.disconnected.insert(name) {
+                self.events.push_back(TransportEvent::PeerDisconnected(peer));
+            }
+        }
+    }
+
+    fn poll_event(&mut self) -> Option<TransportEvent> {
+        if self.events.is_empty() {
+            match self.node.try_next_ev() {
+                Ok(event) => if !self.handle_node_event(event) {
+                    self.events.push_back(TransportEvent::Terminated);
+                },
+                Err(error) => if error == TryRecvError::Disconnected {
+                    self.events.push_back(TransportEvent::Terminated);
+                },
+            }
+        }
+        self.events.pop_front()
+    }
+}
+
+/// Refresh messages.
+#[derive(Serialize, Deserialize)]
+enum RefreshContent {
+    Account { client_name: XorName, data: u64 },
+    ImmutableData(ImmutableData),
+    MutableData(MutableData),
+}
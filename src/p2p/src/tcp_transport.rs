@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use message::{Error as MessageError, MessageHeader, HEADER_SIZE, MAX_PAYLOAD_SIZE};
+use params::Magic;
+use primitives::bytes::Bytes;
+use transport::{PeerAndBytes, PeerIndex, Transport, TransportEvent, BROADCAST_PEER};
+
+#[derive(Debug)]
+enum ReadError {
+    Io(io::Error),
+    InvalidHeader(MessageError),
+    TooBig(u32),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> ReadError {
+        ReadError::Io(err)
+    }
+}
+
+impl From<MessageError> for ReadError {
+    fn from(err: MessageError) -> ReadError {
+        ReadError::InvalidHeader(err)
+    }
+}
+
+/// Messages waiting to be written to one peer. Peer which doesn't keep up with them is disconnected
+const MAX_QUEUED_MESSAGES: usize = 1000;
+/// Peer which doesn't accept message in this time is disconnected
+const WRITE_TIMEOUT_SECS: u64 = 30;
+/// Delay before connecting to node again after connection is lost or fails
+const REDIAL_INTERVAL_SECS: u64 = 10;
+
+struct Connection {
+    /// Kept to close connection, so reader and writer threads finish
+    stream: TcpStream,
+    /// Queue of peer's writer thread
+    writer: SyncSender<Bytes>,
+}
+
+/// Open connections shared with threads accepting and reading from peers
+#[derive(Clone)]
+struct Connections {
+    magic: Magic,
+    peers: Arc<Mutex<HashMap<PeerIndex, Connection>>>,
+    next_peer: Arc<AtomicUsize>,
+    events_sender: Sender<TransportEvent>,
+}
+
+impl Connections {
+    /// Remembers connection, reads messages from it and writes queued messages to it in separate threads
+    /// until peer disconnects. Returns reader thread, which finishes once connection is closed
+    fn add_peer(&self, stream: TcpStream) -> Option<JoinHandle<()>> {
+        // peers are banned by ip, so peer without known address can't be accepted
+        let address = match stream.peer_addr() {
+            Ok(address) => address,
+            Err(err) => {
+                error!("Rejecting connection of unknown peer. Reason: {}", err);
+                let _ = stream.shutdown(Shutdown::Both);
+                return None;
+            }
+        };
+        let streams = stream
+            .set_write_timeout(Some(Duration::from_secs(WRITE_TIMEOUT_SECS)))
+            .and_then(|_| stream.try_clone())
+            .and_then(|reader| stream.try_clone().map(|writer| (reader, writer)));
+        let (mut reader, mut writer) = match streams {
+            Ok(streams) => streams,
+            Err(err) => {
+                error!("Failed to set up connection with {}. Reason: {}", address, err);
+                let _ = stream.shutdown(Shutdown::Both);
+                return None;
+            }
+        };
+        let peer = self.next_peer.fetch_add(1, Ordering::SeqCst);
+        info!("Peer#{} connected from {}", peer, address);
+        let (queue_sender, queue) = mpsc::sync_channel::<Bytes>(MAX_QUEUED_MESSAGES);
+        self.peers.lock().unwrap().insert(peer, Connection {
+            stream,
+            writer: queue_sender,
+        });
+        // port changes with every connection
+        let _ = self.events_sender.send(TransportEvent::PeerConnected(peer, address.ip().to_string()));
+
+        // queue is closed once peer is removed
+        thread::spawn(move || {
+            for bytes in queue.iter() {
+                if let Err(err) = writer.write_all(&bytes) {
+                    error!("Failed to send message to peer#{}. Reason: {}", peer, err);
+                    // reader thread notices closed connection and forgets the peer
+                    let _ = writer.shutdown(Shutdown::Both);
+                    break;
+                }
+            }
+        });
+
+        let connections = self.clone();
+        let reader_thread = thread::spawn(move || {
+            loop {
+                match read_message(&mut reader, connections.magic) {
+                    Ok(bytes) => {
+                        let message = TransportEvent::Message(PeerAndBytes { peer, bytes });
+                        if connections.events_sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(ReadError::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                    Err(err) => {
+                        error!("Disconnecting peer#{}. Reason: {:?}", peer, err);
+                        break;
+                    }
+                }
+            }
+            connections.remove_peer(peer);
+        });
+        Some(reader_thread)
+    }
+
+    /// Queues message for peer's writer thread, never blocking
+    fn send(&self, peer: PeerIndex, connection: &Connection, bytes: Bytes) {
+        match connection.writer.try_send(bytes) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                error!("Disconnecting peer#{}. Reason: {} messages are waiting to be sent", peer, MAX_QUEUED_MESSAGES);
+                let _ = connection.stream.shutdown(Shutdown::Both);
+            }
+            // writer has failed and closed connection already
+            Err(TrySendError::Disconnected(_)) => (),
+        }
+    }
+
+    fn remove_peer(&self, peer: PeerIndex) {
+        if let Some(connection) = self.peers.lock().unwrap().remove(&peer) {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+        }
+        let _ = self.events_sender.send(TransportEvent::PeerDisconnected(peer));
+    }
+
+    /// Connects to node and connects to it again whenever connection fails or is lost
+    fn keep_connected(&self, address: SocketAddr) {
+        loop {
+            match TcpStream::connect(address) {
+                Ok(stream) => {
+                    if let Some(reader_thread) = self.add_peer(stream) {
+                        let _ = reader_thread.join();
+                        info!("Connection to node {} is lost, reconnecting in {} seconds", address, REDIAL_INTERVAL_SECS);
+                    }
+                }
+                Err(err) => error!("Can't connect to node {}. Reason: {}", address, err),
+            }
+            thread::sleep(Duration::from_secs(REDIAL_INTERVAL_SECS));
+        }
+    }
+}
+
+/// Transport sending Bitcoin-style framed messages directly over TCP connections
+pub struct TcpTransport {
+    connections: Connections,
+    events: Receiver<TransportEvent>,
+}
+
+impl TcpTransport {
+    /// Accepts peers on `listen_address` and keeps connections to given `nodes`
+    pub fn new(magic: Magic, listen_address: SocketAddr, nodes: &[SocketAddr]) -> io::Result<Self> {
+        let (events_sender, events) = mpsc::channel();
+        let connections = Connections {
+            magic,
+            peers: Arc::new(Mutex::new(HashMap::new())),
+            // index of broadcast is never assigned
+            next_peer: Arc::new(AtomicUsize::new(BROADCAST_PEER + 1)),
+            events_sender,
+        };
+
+        let listener = TcpListener::bind(listen_address)?;
+        info!("Listening for peers on {}", listener.local_addr()?);
+        let acceptor = connections.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        acceptor.add_peer(stream);
+                    }
+                    Err(err) => error!("Failed to accept peer. Reason: {}", err),
+                }
+            }
+        });
+
+        for address in nodes.iter().cloned() {
+            let dialer = connections.clone();
+            thread::spawn(move || dialer.keep_connected(address));
+        }
+        // there is nothing to wait for, unlike with routing network bootstrap
+        let _ = connections.events_sender.send(TransportEvent::Connected);
+
+        Ok(TcpTransport {
+            connections,
+            events,
+        })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, peer: PeerIndex, bytes: Bytes) {
+        match self.connections.peers.lock().unwrap().get(&peer) {
+            Some(connection) => self.connections.send(peer, connection, bytes),
+            None => warn!("Can't send message to disconnected peer#{}", peer),
+        }
+    }
+
+    fn broadcast(&mut self, bytes: Bytes) {
+        for (peer, connection) in self.connections.peers.lock().unwrap().iter() {
+            self.connections.send(*peer, connection, bytes.clone());
+        }
+    }
+
+    fn disconnect(&mut self, peer: PeerIndex) {
+        // reader thread notices closed connection and forgets the peer
+        if let Some(connection) = self.connections.peers.lock().unwrap().get(&peer) {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn poll_event(&mut self) -> Option<TransportEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+/// Reads one message, header included, checking that it belongs to our network
+fn read_message<R: Read>(reader: &mut R, magic: Magic) -> Result<Bytes, ReadError> {
+    let mut message = vec![0u8; HEADER_SIZE];
+    reader.read_exact(&mut message)?;
+    let header = MessageHeader::deserialize(&message, magic)?;
+    if header.len > MAX_PAYLOAD_SIZE {
+        return Err(ReadError::TooBig(header.len));
+    }
+
+    message.resize(HEADER_SIZE + header.len as usize, 0);
+    reader.read_exact(&mut message[HEADER_SIZE..])?;
+    Ok(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+    use message::{to_raw_message, Error as MessageError};
+    use params::NetworkParams;
+    use transport::{PeerIndex, Transport, TransportEvent};
+    use super::{read_message, ReadError, TcpTransport};
+
+    /// Transport connected to returned listener, together with index assigned to the listener's side
+    fn connected_transport() -> (TcpTransport, TcpListener, PeerIndex) {
+        let node = TcpListener::bind("127.0.0.1:0").unwrap();
+        let node_address = node.local_addr().unwrap();
+        let mut transport = TcpTransport::new(NetworkParams::Mainnet.magic(), "127.0.0.1:0".parse().unwrap(), &[node_address]).unwrap();
+        let peer = wait_for_connection(&mut transport);
+        (transport, node, peer)
+    }
+
+    fn wait_for_connection(transport: &mut TcpTransport) -> PeerIndex {
+        loop {
+            match transport.poll_event() {
+                Some(TransportEvent::PeerConnected(peer, address)) => {
+                    assert_eq!(address, "127.0.0.1");
+                    return peer;
+                }
+                Some(_) => (),
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_message() {
+        let magic = NetworkParams::Mainnet.magic();
+        let first = to_raw_message(magic, "tx".into(), &"0102".into());
+        let second = to_raw_message(magic, "block".into(), &"03".into());
+        let mut stream = first.clone().take();
+        stream.extend_from_slice(&second);
+        let mut reader = Cursor::new(stream);
+
+        assert_eq!(read_message(&mut reader, magic).unwrap(), first);
+        assert_eq!(read_message(&mut reader, magic).unwrap(), second);
+    }
+
+    #[test]
+    fn test_read_message_from_other_network() {
+        let raw = to_raw_message(NetworkParams::Testnet.magic(), "tx".into(), &"0102".into());
+        let mut reader = Cursor::new(raw.take());
+
+        match read_message(&mut reader, NetworkParams::Mainnet.magic()) {
+            Err(ReadError::InvalidHeader(MessageError::InvalidMagic)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn messages_are_written_to_peer() {
+        let magic = NetworkParams::Mainnet.magic();
+        let (mut transport, node, peer) = connected_transport();
+        let (mut stream, _) = node.accept().unwrap();
+        let first = to_raw_message(magic, "tx".into(), &"0102".into());
+        let second = to_raw_message(magic, "block".into(), &"03".into());
+
+        transport.send(peer, first.clone());
+        transport.broadcast(second.clone());
+        assert_eq!(read_message(&mut stream, magic).unwrap(), first);
+        assert_eq!(read_message(&mut stream, magic).unwrap(), second);
+    }
+
+    #[test]
+    fn lost_node_connection_is_restored() {
+        let (mut transport, node, peer) = connected_transport();
+        let (stream, _) = node.accept().unwrap();
+        drop(stream);
+
+        // node is dialed again after it drops connection
+        let (_stream, _) = node.accept().unwrap();
+        assert!(wait_for_connection(&mut transport) > peer);
+    }
+}
@@ -0,0 +1,72 @@
+use primitives::bytes::Bytes;
+
+/// Peer identifier assigned by transport when peer connects
+pub type PeerIndex = usize;
+
+/// Identity of peer which outlives its connection, like IP address. Used to ban peers
+pub type PeerAddress = String;
+
+/// Messages sent to this index are broadcasted to every connected peer
+pub const BROADCAST_PEER: PeerIndex = 0;
+
+pub struct PeerAndBytes {
+    pub peer: PeerIndex,
+    pub bytes: Bytes,
+}
+
+/// Event passed from network to node
+pub enum NetworkEvent {
+    PeerConnected(PeerIndex, PeerAddress),
+    PeerDisconnected(PeerIndex),
+    Message(PeerAndBytes),
+}
+
+/// Request passed from node to network
+pub enum NetworkRequest {
+    /// Sends message to peer or broadcasts it when peer is `BROADCAST_PEER`
+    Send(PeerAndBytes),
+    Disconnect(PeerIndex),
+}
+
+pub enum TransportEvent {
+    /// Node joined the network and may start asking peers for data
+    Connected,
+    PeerConnected(PeerIndex, PeerAddress),
+    PeerDisconnected(PeerIndex),
+    /// Serialized network message together with sender
+    Message(PeerAndBytes),
+    /// Transport can't deliver messages anymore
+    Terminated,
+}
+
+/// Delivers serialized network messages between peers
+pub trait Transport {
+    fn send(&mut self, peer: PeerIndex, bytes: Bytes);
+
+    fn broadcast(&mut self, bytes: Bytes);
+
+    /// Closes connection with peer. `PeerDisconnected` event follows
+    fn disconnect(&mut self, peer: PeerIndex);
+
+    /// Returns next event if there is one. Never blocks
+    fn poll_event(&mut self) -> Option<TransportEvent>;
+}
+
+/// Lets node pick transport at runtime
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&mut self, peer: PeerIndex, bytes: Bytes) {
+        (**self).send(peer, bytes)
+    }
+
+    fn broadcast(&mut self, bytes: Bytes) {
+        (**self).broadcast(bytes)
+    }
+
+    fn disconnect(&mut self, peer: PeerIndex) {
+        (**self).disconnect(peer)
+    }
+
+    fn poll_event(&mut self) -> Option<TransportEvent> {
+        (**self).poll_event()
+    }
+}
@@ -7,6 +7,9 @@ pub struct ConsensusParams {
 	pub network: NetworkParams,
 	/// Selected consensus fork.
 	pub fork: ConsensusFork,
+	/// Blocks have to be mined: proof of work, difficulty retarget, median time past
+	/// and future timestamp rules are checked. Dev network accepts unmined blocks.
+	pub require_proof_of_work: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -21,6 +24,16 @@ impl ConsensusParams {
 		ConsensusParams {
 			network: network,
 			fork: fork,
+			require_proof_of_work: true,
+		}
+	}
+
+	/// Development network, where blocks are accepted without proof of work
+	pub fn dev(network: NetworkParams, fork: ConsensusFork) -> Self {
+		ConsensusParams {
+			network: network,
+			fork: fork,
+			require_proof_of_work: false,
 		}
 	}
 
@@ -4,14 +4,20 @@
 use chain::Block;
 use primitives::hash::H256;
 use primitives::bigint::U256;
+use primitives::compact::Compact;
 
 pub const MAGIC_MAINNET: u32 = 0x06A4D09A;
 const MAGIC_TESTNET: u32 = 0x7E274A4D;
+const MAGIC_REGTEST: u32 = 0x3F5A1C8B;
 
 lazy_static! {
 	static ref MAX_BITS_MAINNET: U256 = "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff".parse()
 		.expect("hardcoded value should parse without errors");
-	static ref MAX_BITS_TESTNET: U256 = "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff".parse()
+	// testnet coins have no value, so its difficulty starts within reach of CPU miners too
+	static ref MAX_BITS_TESTNET: U256 = "0000ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff".parse()
+		.expect("hardcoded value should parse without errors");
+	// lowest difficulty is kept within reach of CPU miners
+	static ref MAX_BITS_REGTEST: U256 = "7fffff0000000000000000000000000000000000000000000000000000000000".parse()
 		.expect("hardcoded value should parse without errors");
 }
 
@@ -25,6 +31,8 @@ pub enum NetworkParams {
 	Mainnet,
 	/// The main bitcoin testnet.
 	Testnet,
+	/// Local network for development, where blocks are mined on CPU.
+	Regtest,
 	/// Any other network. By default behaves like bitcoin mainnet.
 	Other(u32),
 }
@@ -34,6 +42,7 @@ impl NetworkParams {
 		match *self {
 			NetworkParams::Mainnet => MAGIC_MAINNET,
 			NetworkParams::Testnet => MAGIC_TESTNET,
+			NetworkParams::Regtest => MAGIC_REGTEST,
 			NetworkParams::Other(value) => value,
 		}
 	}
@@ -42,6 +51,7 @@ impl NetworkParams {
 		match *self {
 			NetworkParams::Mainnet | NetworkParams::Other(_) => MAX_BITS_MAINNET.clone(),
 			NetworkParams::Testnet => MAX_BITS_TESTNET.clone(),
+			NetworkParams::Regtest => MAX_BITS_REGTEST.clone(),
 		}
 	}
 
@@ -49,6 +59,7 @@ impl NetworkParams {
 		match *self {
 			NetworkParams::Mainnet | NetworkParams::Other(_)  => 6470,
 			NetworkParams::Testnet => 16470,
+			NetworkParams::Regtest => 26470,
 		}
 	}
 
@@ -56,6 +67,7 @@ impl NetworkParams {
 		match *self {
 			NetworkParams::Mainnet | NetworkParams::Other(_) => 8992,
 			NetworkParams::Testnet => 18992,
+			NetworkParams::Regtest => 28992,
 		}
 	}
 
@@ -64,42 +76,10 @@ impl NetworkParams {
 	//address: 1KFoaRnZLw9DYhNVMfft84YHAVbLMRmWv5
 
 	pub fn genesis_block(&self) -> Block {
-		use chain::{Block, BlockHeader, Transaction, TransactionInput, TransactionOutput};
 		match *self {
-			NetworkParams::Mainnet | NetworkParams::Other(_) =>
-			{
-				let destination_locking_witness_program = "0014c83ef7b094d48e873f0e13db7892dfe5120418be".into();
-				let transaction = Transaction {
-					version: 0,
-					inputs: vec![ TransactionInput::coinbase("0100".into()) ], //push 1 byte containing block height
-					outputs: vec![ TransactionOutput {
-						value: 50,
-						script_pubkey: destination_locking_witness_program,
-					}],
-					lock_time: 0,
-				};
-
-				let mut block = Block
-				{
-					block_header: BlockHeader
-					{
-						version: 1,
-						previous_header_hash: 0.into(),
-						merkle_root_hash: 0.into(),
-						witness_merkle_root_hash: 0.into(),
-						time: 1234567,
-						bits: 5.into(),
-						nonce: 6,
-					},
-					transactions: vec![transaction]
-				};
-
-				block.block_header.merkle_root_hash = block.merkle_root();
-				block.block_header.witness_merkle_root_hash = block.witness_merkle_root();
-
-				block
-			}
-			NetworkParams::Testnet => "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff001d1aa4ae180101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000".into(),
+			NetworkParams::Mainnet | NetworkParams::Other(_) => genesis_block(5.into(), 6),
+			NetworkParams::Testnet => genesis_block(0x1f00ffff.into(), 68801),
+			NetworkParams::Regtest => genesis_block(0x207fffff.into(), 1),
 		}
 	}
 
@@ -108,29 +88,80 @@ impl NetworkParams {
 	}
 }
 
+/// Block paying 50 satoshis to genesis address, which differs between networks by difficulty
+fn genesis_block(bits: Compact, nonce: u32) -> Block {
+	use chain::{BlockHeader, Transaction, TransactionInput, TransactionOutput};
+	let destination_locking_witness_program = "0014c83ef7b094d48e873f0e13db7892dfe5120418be".into();
+	let transaction = Transaction {
+		version: 0,
+		inputs: vec![ TransactionInput::coinbase("0100".into()) ], //push 1 byte containing block height
+		outputs: vec![ TransactionOutput {
+			value: 50,
+			script_pubkey: destination_locking_witness_program,
+		}],
+		lock_time: 0,
+	};
+
+	let mut block = Block
+	{
+		block_header: BlockHeader
+		{
+			version: 1,
+			previous_header_hash: 0.into(),
+			merkle_root_hash: 0.into(),
+			witness_merkle_root_hash: 0.into(),
+			time: 1234567,
+			bits: bits,
+			nonce: nonce,
+		},
+		transactions: vec![transaction]
+	};
+
+	block.block_header.merkle_root_hash = block.merkle_root();
+	block.block_header.witness_merkle_root_hash = block.witness_merkle_root();
+
+	block
+}
+
 #[cfg(test)]
 mod tests {
 	use compact::Compact;
 	use super::{
-		NetworkParams, MAGIC_MAINNET, MAGIC_TESTNET,
-		MAX_BITS_MAINNET, MAX_BITS_TESTNET,
+		NetworkParams, MAGIC_MAINNET, MAGIC_TESTNET, MAGIC_REGTEST,
+		MAX_BITS_MAINNET, MAX_BITS_TESTNET, MAX_BITS_REGTEST,
 	};
 
 	#[test]
 	fn test_network_magic_number() {
 		assert_eq!(MAGIC_MAINNET, NetworkParams::Mainnet.magic());
 		assert_eq!(MAGIC_TESTNET, NetworkParams::Testnet.magic());
+		assert_eq!(MAGIC_REGTEST, NetworkParams::Regtest.magic());
 	}
 
 	#[test]
 	fn test_network_max_bits() {
 		assert_eq!(NetworkParams::Mainnet.max_bits(), *MAX_BITS_MAINNET);
 		assert_eq!(NetworkParams::Testnet.max_bits(), *MAX_BITS_TESTNET);
+		assert_eq!(NetworkParams::Regtest.max_bits(), *MAX_BITS_REGTEST);
 	}
 
 	#[test]
 	fn test_network_port() {
 		assert_eq!(NetworkParams::Mainnet.port(), 6470);
 		assert_eq!(NetworkParams::Testnet.port(), 16470);
+		assert_eq!(NetworkParams::Regtest.port(), 26470);
+	}
+
+	#[test]
+	fn test_testnet_genesis_has_lowest_difficulty() {
+		let genesis = NetworkParams::Testnet.genesis_block();
+		assert_eq!(genesis.block_header.bits, Compact::from_u256(NetworkParams::Testnet.max_bits()));
+	}
+
+	#[test]
+	fn test_regtest_genesis_has_lowest_difficulty() {
+		let genesis = NetworkParams::Regtest.genesis_block();
+		assert_eq!(genesis.block_header.bits, Compact::from_u256(NetworkParams::Regtest.max_bits()));
+		assert!(genesis.hash() != NetworkParams::Mainnet.genesis_block().hash());
 	}
 }
@@ -26,6 +26,7 @@ pub mod rpc_server;
 pub use rustc_serialize::hex;
 
 pub use jsonrpc_core::{MetaIoHandler, Compatibility, Error};
+pub use jsonrpc_macros::Trailing;
 pub use jsonrpc_http_server::tokio_core::reactor::{Remote};
 
 pub use jsonrpc_http_server::Server;
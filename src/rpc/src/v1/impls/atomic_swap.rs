@@ -0,0 +1,366 @@
+use jsonrpc_core::Error;
+use v1::traits::AtomicSwap;
+use v1::types::{Bytes, ContractAudit, SwapContract, SwapTransaction, H256};
+use v1::helpers::errors::{execution, invalid_params};
+use chain::constants::SATOSHIS_IN_COIN;
+use keys::Address;
+use primitives::bytes::Bytes as GlobalBytes;
+use primitives::hash::H256 as GlobalH256;
+
+pub struct AtomicSwapClient<T: AtomicSwapClientCoreApi> {
+	core: T,
+}
+
+/// Atomic swapper lives in node binary, so it provides implementation of this trait
+pub trait AtomicSwapClientCoreApi: Send + Sync + 'static {
+	fn initiate(&self, address: Address, amount: u64) -> Result<SwapContract, String>;
+	fn participate(&self, address: Address, amount: u64, secret_hash: GlobalH256) -> Result<SwapContract, String>;
+	fn redeem(&self, contract: GlobalBytes, contract_transaction: GlobalBytes, secret: GlobalBytes) -> Result<SwapTransaction, String>;
+	fn audit_contract(&self, contract: GlobalBytes, contract_transaction: GlobalBytes) -> Result<ContractAudit, String>;
+	fn refund(&self, contract: GlobalBytes, contract_transaction: GlobalBytes) -> Result<SwapTransaction, String>;
+	fn extract_secret(&self, redeem_transaction: GlobalBytes, secret_hash: GlobalH256) -> Result<GlobalBytes, String>;
+}
+
+impl<T> AtomicSwapClient<T> where T: AtomicSwapClientCoreApi {
+	pub fn new(core: T) -> Self {
+		AtomicSwapClient {
+			core: core,
+		}
+	}
+}
+
+fn parse_address_and_amount(address: String, amount: f64) -> Result<(Address, u64), Error> {
+	let address: Address = address.parse().map_err(|e| invalid_params("address", e))?;
+	if !(amount > 0.0) {
+		return Err(invalid_params("amount", "amount must be positive"));
+	}
+	Ok((address, (amount * (SATOSHIS_IN_COIN as f64)).round() as u64))
+}
+
+impl<T> AtomicSwap for AtomicSwapClient<T> where T: AtomicSwapClientCoreApi {
+	fn initiate(&self, address: String, amount: f64) -> Result<SwapContract, Error> {
+		let (address, amount) = parse_address_and_amount(address, amount)?;
+		self.core.initiate(address, amount)
+			.map_err(|e| execution(e))
+	}
+
+	fn participate(&self, address: String, amount: f64, secret_hash: H256) -> Result<SwapContract, Error> {
+		let (address, amount) = parse_address_and_amount(address, amount)?;
+		self.core.participate(address, amount, secret_hash.into())
+			.map_err(|e| execution(e))
+	}
+
+	fn redeem(&self, contract: Bytes, contract_transaction: Bytes, secret: Bytes) -> Result<SwapTransaction, Error> {
+		self.core.redeem(contract.to_vec().into(), contract_transaction.to_vec().into(), secret.to_vec().into())
+			.map_err(|e| execution(e))
+	}
+
+	fn audit_contract(&self, contract: Bytes, contract_transaction: Bytes) -> Result<ContractAudit, Error> {
+		self.core.audit_contract(contract.to_vec().into(), contract_transaction.to_vec().into())
+			.map_err(|e| execution(e))
+	}
+
+	fn refund(&self, contract: Bytes, contract_transaction: Bytes) -> Result<SwapTransaction, Error> {
+		self.core.refund(contract.to_vec().into(), contract_transaction.to_vec().into())
+			.map_err(|e| execution(e))
+	}
+
+	fn extract_secret(&self, redeem_transaction: Bytes, secret_hash: H256) -> Result<Bytes, Error> {
+		self.core.extract_secret(redeem_transaction.to_vec().into(), secret_hash.into())
+			.map(|secret| secret.into())
+			.map_err(|e| execution(e))
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use jsonrpc_core::IoHandler;
+	use keys::Address;
+	use primitives::bytes::Bytes as GlobalBytes;
+	use primitives::hash::H256 as GlobalH256;
+	use v1::traits::AtomicSwap;
+	use v1::types::{Bytes, ContractAudit, SwapContract, SwapTransaction, H256};
+	use super::*;
+
+	#[derive(Default)]
+	struct SuccessAtomicSwapClientCore;
+
+	#[derive(Default)]
+	struct ErrorAtomicSwapClientCore;
+
+	fn sample_contract(secret: Option<Bytes>) -> SwapContract {
+		SwapContract {
+			contract: Bytes::new(vec![0x63]),
+			contract_p2wsh: H256::from(1),
+			contract_tx: Bytes::new(vec![0x01]),
+			contract_txid: H256::from(2),
+			contract_fee: 0.0001,
+			refund_tx: Bytes::new(vec![0x02]),
+			refund_txid: H256::from(3),
+			refund_fee: 0.0002,
+			secret: secret,
+			secret_hash: H256::from(4),
+		}
+	}
+
+	impl AtomicSwapClientCoreApi for SuccessAtomicSwapClientCore {
+		fn initiate(&self, _address: Address, amount: u64) -> Result<SwapContract, String> {
+			assert_eq!(amount, 10_000_000);
+			Ok(sample_contract(Some(Bytes::new(vec![0xaa]))))
+		}
+
+		fn participate(&self, _address: Address, _amount: u64, secret_hash: GlobalH256) -> Result<SwapContract, String> {
+			assert_eq!(secret_hash, GlobalH256::from(4));
+			Ok(sample_contract(None))
+		}
+
+		fn redeem(&self, contract: GlobalBytes, _contract_transaction: GlobalBytes, secret: GlobalBytes) -> Result<SwapTransaction, String> {
+			assert_eq!(contract, GlobalBytes::from(vec![0x63]));
+			assert_eq!(secret, GlobalBytes::from(vec![0xaa]));
+			Ok(SwapTransaction {
+				tx: Bytes::new(vec![0x01, 0x02]),
+				txid: H256::from(5),
+				fee: 0.00015,
+			})
+		}
+
+		fn audit_contract(&self, _contract: GlobalBytes, _contract_transaction: GlobalBytes) -> Result<ContractAudit, String> {
+			Ok(ContractAudit {
+				contract_p2wsh: H256::from(1),
+				contract_value: 0.1,
+				recipient_address: "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into(),
+				refund_address: "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into(),
+				secret_hash: H256::from(4),
+				locktime: 500,
+				locktime_reached_in: None,
+			})
+		}
+
+		fn refund(&self, _contract: GlobalBytes, _contract_transaction: GlobalBytes) -> Result<SwapTransaction, String> {
+			Ok(SwapTransaction {
+				tx: Bytes::new(vec![0x03]),
+				txid: H256::from(6),
+				fee: 0.0001,
+			})
+		}
+
+		fn extract_secret(&self, _redeem_transaction: GlobalBytes, secret_hash: GlobalH256) -> Result<GlobalBytes, String> {
+			assert_eq!(secret_hash, GlobalH256::from(4));
+			Ok(vec![0xaa].into())
+		}
+	}
+
+	impl AtomicSwapClientCoreApi for ErrorAtomicSwapClientCore {
+		fn initiate(&self, _address: Address, _amount: u64) -> Result<SwapContract, String> {
+			Err("not enough funds".into())
+		}
+
+		fn participate(&self, _address: Address, _amount: u64, _secret_hash: GlobalH256) -> Result<SwapContract, String> {
+			Err("not enough funds".into())
+		}
+
+		fn redeem(&self, _contract: GlobalBytes, _contract_transaction: GlobalBytes, _secret: GlobalBytes) -> Result<SwapTransaction, String> {
+			Err("not an atomic swap contract".into())
+		}
+
+		fn audit_contract(&self, _contract: GlobalBytes, _contract_transaction: GlobalBytes) -> Result<ContractAudit, String> {
+			Err("not an atomic swap contract".into())
+		}
+
+		fn refund(&self, _contract: GlobalBytes, _contract_transaction: GlobalBytes) -> Result<SwapTransaction, String> {
+			Err("LocktimeNotReached(3600)".into())
+		}
+
+		fn extract_secret(&self, _redeem_transaction: GlobalBytes, _secret_hash: GlobalH256) -> Result<GlobalBytes, String> {
+			Err("NoSecret".into())
+		}
+	}
+
+	#[test]
+	fn initiate_success() {
+		let client = AtomicSwapClient::new(SuccessAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "initiate",
+				"params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"contract":"63","contract_p2wsh":"0100000000000000000000000000000000000000000000000000000000000000","contract_tx":"01","contract_txid":"0200000000000000000000000000000000000000000000000000000000000000","contract_fee":0.0001,"refund_tx":"02","refund_txid":"0300000000000000000000000000000000000000000000000000000000000000","refund_fee":0.0002,"secret":"aa","secret_hash":"0400000000000000000000000000000000000000000000000000000000000000"},"id":1}"#);
+	}
+
+	#[test]
+	fn initiate_invalid_address() {
+		let client = AtomicSwapClient::new(SuccessAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "initiate",
+				"params": ["invalid", 0.1],
+				"id": 1
+			}"#)).unwrap();
+
+		assert!(sample.contains(r#""code":-32602"#));
+	}
+
+	#[test]
+	fn initiate_error() {
+		let client = AtomicSwapClient::new(ErrorAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "initiate",
+				"params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"not enough funds\""},"id":1}"#);
+	}
+
+	#[test]
+	fn participate_success() {
+		let client = AtomicSwapClient::new(SuccessAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "participate",
+				"params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1, "0400000000000000000000000000000000000000000000000000000000000000"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert!(!sample.contains("secret\""));
+		assert!(sample.contains(r#""contract_txid":"0200000000000000000000000000000000000000000000000000000000000000""#));
+	}
+
+	#[test]
+	fn redeem_success() {
+		let client = AtomicSwapClient::new(SuccessAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "redeem",
+				"params": ["63", "01", "aa"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"tx":"0102","txid":"0500000000000000000000000000000000000000000000000000000000000000","fee":0.00015},"id":1}"#);
+	}
+
+	#[test]
+	fn auditcontract_success() {
+		let client = AtomicSwapClient::new(SuccessAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "auditcontract",
+				"params": ["63", "01"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"contract_p2wsh":"0100000000000000000000000000000000000000000000000000000000000000","contract_value":0.1,"recipient_address":"1H5m1XzvHsjWX3wwU781ubctznEpNACrNC","refund_address":"1H5m1XzvHsjWX3wwU781ubctznEpNACrNC","secret_hash":"0400000000000000000000000000000000000000000000000000000000000000","locktime":500},"id":1}"#);
+	}
+
+	#[test]
+	fn auditcontract_error() {
+		let client = AtomicSwapClient::new(ErrorAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "auditcontract",
+				"params": ["63", "01"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"not an atomic swap contract\""},"id":1}"#);
+	}
+
+	#[test]
+	fn refund_success() {
+		let client = AtomicSwapClient::new(SuccessAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "refund",
+				"params": ["63", "01"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"tx":"03","txid":"0600000000000000000000000000000000000000000000000000000000000000","fee":0.0001},"id":1}"#);
+	}
+
+	#[test]
+	fn refund_error() {
+		let client = AtomicSwapClient::new(ErrorAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "refund",
+				"params": ["63", "01"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"LocktimeNotReached(3600)\""},"id":1}"#);
+	}
+
+	#[test]
+	fn extractsecret_success() {
+		let client = AtomicSwapClient::new(SuccessAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "extractsecret",
+				"params": ["01", "0400000000000000000000000000000000000000000000000000000000000000"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":"aa","id":1}"#);
+	}
+
+	#[test]
+	fn extractsecret_error() {
+		let client = AtomicSwapClient::new(ErrorAtomicSwapClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "extractsecret",
+				"params": ["01", "0400000000000000000000000000000000000000000000000000000000000000"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"NoSecret\""},"id":1}"#);
+	}
+}
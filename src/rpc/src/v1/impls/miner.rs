@@ -1,7 +1,19 @@
+use std::time::{Duration, Instant};
 use v1::traits::Miner;
-use v1::types::{BlockTemplate, BlockTemplateRequest};
+use v1::types::{BlockTemplate, BlockTemplateRequest, BlockTemplateRequestMode, RawBlock};
+use v1::helpers::errors::invalid_params;
 use jsonrpc_core::Error;
-use memory_pool;
+use jsonrpc_core::futures::Future;
+use ser::{deserialize, Reader};
+use chain;
+use db::{Error as DBError, SharedStore};
+use memory_pool::{self, MemoryPoolRef};
+use params::ConsensusParams;
+use verification;
+use sync::{self, AcceptorRef};
+
+/// Mempool changes alone outdate template only after this delay, so miners aren't flooded with templates
+const LONG_POLL_MEMPOOL_DELAY_SECS: u64 = 60;
 
 pub struct MinerClient<T: MinerClientCoreApi> {
 	core: T,
@@ -9,21 +21,84 @@ pub struct MinerClient<T: MinerClientCoreApi> {
 
 pub trait MinerClientCoreApi: Send + Sync + 'static {
 	fn get_block_template(&self) -> memory_pool::BlockTemplate;
+	/// Identifies state template is built from: best block and mempool contents
+	fn long_poll_id(&self) -> String;
+	/// Blocks until template with given id becomes outdated
+	fn wait_for_template_change(&self, long_poll_id: &str);
+	/// Returns BIP22 rejection reason if block isn't accepted
+	fn submit_block(&self, block: chain::Block) -> Result<(), String>;
 }
 
 pub struct MinerClientCore {
+	store: SharedStore,
+	mempool: MemoryPoolRef,
+	acceptor: AcceptorRef,
+	consensus: ConsensusParams,
 }
 
 impl MinerClientCore {
-	pub fn new() -> Self {
+	pub fn new(store: SharedStore, mempool: MemoryPoolRef, acceptor: AcceptorRef, consensus: ConsensusParams) -> Self {
 		MinerClientCore {
+			store: store,
+			mempool: mempool,
+			acceptor: acceptor,
+			consensus: consensus,
 		}
 	}
 }
 
 impl MinerClientCoreApi for MinerClientCore {
 	fn get_block_template(&self) -> memory_pool::BlockTemplate {
-		unimplemented!();
+		sync::block_template(&self.store, &self.mempool, &self.consensus)
+	}
+
+	fn long_poll_id(&self) -> String {
+		let best_block = self.store.best_block();
+		format!("{}{}", best_block.hash.to_reversed_str(), self.mempool.read().transactions_updated())
+	}
+
+	fn wait_for_template_change(&self, long_poll_id: &str) {
+		let mempool_deadline = Instant::now() + Duration::from_secs(LONG_POLL_MEMPOOL_DELAY_SECS);
+		loop {
+			// changes are counted before checking, so ones made meanwhile wake us up
+			let seen_changes = self.acceptor.changes();
+			// new best block outdates template right away
+			if !long_poll_id.starts_with(&self.store.best_block().hash.to_reversed_str()) {
+				return;
+			}
+			let deadline = if long_poll_id != self.long_poll_id() {
+				if Instant::now() >= mempool_deadline {
+					return;
+				}
+				mempool_deadline
+			} else {
+				Instant::now() + Duration::from_secs(LONG_POLL_MEMPOOL_DELAY_SECS)
+			};
+			self.acceptor.wait_for_change(seen_changes, deadline);
+		}
+	}
+
+	fn submit_block(&self, block: chain::Block) -> Result<(), String> {
+		self.acceptor.accept_block(block).wait()
+			.map(|_| ())
+			.map_err(|err| rejection_reason(&err).to_owned())
+	}
+}
+
+/// Reason of block rejection as suggested by BIP22
+fn rejection_reason(err: &verification::Error) -> &'static str {
+	use verification::Error;
+	match *err {
+		Error::Duplicate => "duplicate",
+		Error::Database(DBError::UnknownParent) => "inconclusive",
+		Error::Pow => "high-hash",
+		Error::Difficulty { .. } => "bad-diffbits",
+		Error::Timestamp => "time-too-old",
+		Error::FuturisticTimestamp => "time-too-new",
+		Error::MerkleRoot => "bad-txnmrklroot",
+		Error::Coinbase | Error::CoinbaseScript => "bad-cb-missing",
+		Error::CoinbaseOverspend { .. } => "bad-cb-amount",
+		_ => "rejected",
 	}
 }
 
@@ -36,16 +111,36 @@ impl<T> MinerClient<T> where T: MinerClientCoreApi {
 }
 
 impl<T> Miner for MinerClient<T> where T: MinerClientCoreApi {
-	fn get_block_template(&self, _request: BlockTemplateRequest) -> Result<BlockTemplate, Error> {
-		Ok(self.core.get_block_template().into())
+	fn get_block_template(&self, request: BlockTemplateRequest) -> Result<BlockTemplate, Error> {
+		if request.mode == Some(BlockTemplateRequestMode::Proposal) {
+			return rpc_unimplemented!();
+		}
+		if let Some(ref long_poll_id) = request.longpollid {
+			self.core.wait_for_template_change(long_poll_id);
+		}
+		// id is taken before template, so changes made meanwhile outdate returned template
+		let long_poll_id = self.core.long_poll_id();
+		let mut template: BlockTemplate = self.core.get_block_template().into();
+		template.longpollid = Some(long_poll_id);
+		Ok(template)
+	}
+
+	fn submit_block(&self, raw_block: RawBlock) -> Result<Option<String>, Error> {
+		let raw_block: Vec<u8> = raw_block.into();
+		let block = try!(deserialize(Reader::new(&raw_block)).map_err(|e| invalid_params("block", e)));
+		Ok(self.core.submit_block(block).err())
 	}
 }
 
 #[cfg(test)]
 pub mod tests {
+	extern crate chain_builder;
+
 	use jsonrpc_core::IoHandler;
 	use v1::traits::Miner;
 	use primitives::hash::H256;
+	use hex::ToHex;
+	use ser::serialize;
 	use chain;
 	use memory_pool;
 	use super::*;
@@ -53,6 +148,9 @@ pub mod tests {
 	#[derive(Default)]
 	struct SuccessMinerClientCore;
 
+	#[derive(Default)]
+	struct ErrorMinerClientCore;
+
 	impl MinerClientCoreApi for SuccessMinerClientCore {
 		fn get_block_template(&self) -> memory_pool::BlockTemplate {
 			let tx: chain::Transaction = "00000000013ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a0000000000000000000101000000000000000000000000".into();
@@ -70,6 +168,39 @@ pub mod tests {
 				sigop_limit: 88,
 			}
 		}
+
+		fn long_poll_id(&self) -> String {
+			"id".to_owned()
+		}
+
+		fn wait_for_template_change(&self, _long_poll_id: &str) {
+		}
+
+		fn submit_block(&self, _block: chain::Block) -> Result<(), String> {
+			Ok(())
+		}
+	}
+
+	impl MinerClientCoreApi for ErrorMinerClientCore {
+		fn get_block_template(&self) -> memory_pool::BlockTemplate {
+			SuccessMinerClientCore.get_block_template()
+		}
+
+		fn long_poll_id(&self) -> String {
+			"id".to_owned()
+		}
+
+		fn wait_for_template_change(&self, _long_poll_id: &str) {
+		}
+
+		fn submit_block(&self, _block: chain::Block) -> Result<(), String> {
+			Err("high-hash".to_owned())
+		}
+	}
+
+	fn submit_block_request() -> String {
+		let block = serialize(&chain_builder::genesis());
+		format!(r#"{{"jsonrpc": "2.0", "method": "submitblock", "params": ["{}"], "id": 1}}"#, block.to_hex())
 	}
 
 	#[test]
@@ -88,6 +219,45 @@ pub mod tests {
 
 		// direct hash is 0100000000000000000000000000000000000000000000000000000000000000
 		// but client expects reverse hash
-		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"bits":44,"coinbaseaux":null,"coinbasetxn":null,"coinbasevalue":66,"curtime":33,"height":55,"mintime":null,"mutable":null,"noncerange":null,"previousblockhash":"0000000000000000000000000000000000000000000000000000000000000001","rules":null,"sigoplimit":88,"sizelimit":77,"target":"0000000000000000000000000000000000000000000000000000000000000000","transactions":[{"data":"00000000013ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a0000000000000000000101000000000000000000000000","depends":null,"fee":null,"hash":null,"required":false,"sigops":null,"txid":null,"weight":null}],"vbavailable":null,"vbrequired":null,"version":777,"weightlimit":null},"id":1}"#);
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"bits":44,"coinbaseaux":null,"coinbasetxn":null,"coinbasevalue":66,"curtime":33,"height":55,"longpollid":"id","mintime":null,"mutable":null,"noncerange":null,"previousblockhash":"0000000000000000000000000000000000000000000000000000000000000001","rules":null,"sigoplimit":88,"sizelimit":77,"target":"0000000000000000000000000000000000000000000000000000000000000000","transactions":[{"data":"00000000013ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a0000000000000000000101000000000000000000000000","depends":null,"fee":null,"hash":null,"required":false,"sigops":null,"txid":null,"weight":null}],"vbavailable":null,"vbrequired":null,"version":777,"weightlimit":null},"id":1}"#);
+	}
+
+	#[test]
+	fn getblocktemplate_proposal_unimplemented() {
+		let client = MinerClient::new(SuccessMinerClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getblocktemplate",
+				"params": [{"mode": "proposal"}],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"This request is not implemented yet. Please create an issue on Github repo."},"id":1}"#);
+	}
+
+	#[test]
+	fn submitblock_accepted() {
+		let client = MinerClient::new(SuccessMinerClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&submit_block_request()).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":null,"id":1}"#);
+	}
+
+	#[test]
+	fn submitblock_rejected() {
+		let client = MinerClient::new(ErrorMinerClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&submit_block_request()).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":"high-hash","id":1}"#);
 	}
 }
@@ -2,8 +2,12 @@ mod blockchain;
 mod miner;
 mod raw;
 mod network;
+mod wallet;
+mod atomic_swap;
 
 pub use self::blockchain::{BlockChainClient, BlockChainClientCore};
 pub use self::miner::{MinerClient, MinerClientCore};
 pub use self::raw::{RawClient, RawClientCore};
 pub use self::network::{NetworkClient, NetworkClientCore};
+pub use self::wallet::{WalletClient, WalletClientCoreApi};
+pub use self::atomic_swap::{AtomicSwapClient, AtomicSwapClientCoreApi};
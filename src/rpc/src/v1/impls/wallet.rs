@@ -0,0 +1,338 @@
+use jsonrpc_core::Error;
+use jsonrpc_macros::Trailing;
+use v1::traits::Wallet;
+use v1::types::{GetBalanceResponse, UnspentOutput, WalletTransaction, H256};
+use v1::helpers::errors::{execution, invalid_params};
+use chain::constants::SATOSHIS_IN_COIN;
+use keys::{Address, Private};
+use primitives::hash::H256 as GlobalH256;
+
+pub struct WalletClient<T: WalletClientCoreApi> {
+	core: T,
+}
+
+/// Wallet lives in node binary, so it provides implementation of this trait
+pub trait WalletClientCoreApi: Send + Sync + 'static {
+	fn new_address(&self) -> Result<Address, String>;
+	fn import_private(&self, private: Private) -> Result<(), String>;
+	fn balance(&self, min_confirmations: Trailing<u32>) -> Result<GetBalanceResponse, String>;
+	fn send_to_address(&self, address: Address, amount: u64, fee_rate: Trailing<u64>) -> Result<GlobalH256, String>;
+	fn list_unspent(&self, min_confirmations: Trailing<u32>) -> Result<Vec<UnspentOutput>, String>;
+	fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String>;
+}
+
+impl<T> WalletClient<T> where T: WalletClientCoreApi {
+	pub fn new(core: T) -> Self {
+		WalletClient {
+			core: core,
+		}
+	}
+}
+
+impl<T> Wallet for WalletClient<T> where T: WalletClientCoreApi {
+	fn new_address(&self) -> Result<String, Error> {
+		self.core.new_address()
+			.map(|address| address.to_string())
+			.map_err(|e| execution(e))
+	}
+
+	fn import_private(&self, private: String) -> Result<(), Error> {
+		let private: Private = private.parse().map_err(|e| invalid_params("private", e))?;
+		self.core.import_private(private)
+			.map_err(|e| execution(e))
+	}
+
+	fn balance(&self, min_confirmations: Trailing<u32>) -> Result<GetBalanceResponse, Error> {
+		self.core.balance(min_confirmations)
+			.map_err(|e| execution(e))
+	}
+
+	fn send_to_address(&self, address: String, amount: f64, fee_rate: Trailing<u64>) -> Result<H256, Error> {
+		let address: Address = address.parse().map_err(|e| invalid_params("address", e))?;
+		if !(amount > 0.0) {
+			return Err(invalid_params("amount", "amount must be positive"));
+		}
+		let amount_in_satoshis = (amount * (SATOSHIS_IN_COIN as f64)).round() as u64;
+		self.core.send_to_address(address, amount_in_satoshis, fee_rate)
+			.map(|hash| hash.reversed().into())
+			.map_err(|e| execution(e))
+	}
+
+	fn list_unspent(&self, min_confirmations: Trailing<u32>) -> Result<Vec<UnspentOutput>, Error> {
+		self.core.list_unspent(min_confirmations)
+			.map_err(|e| execution(e))
+	}
+
+	fn list_transactions(&self) -> Result<Vec<WalletTransaction>, Error> {
+		self.core.list_transactions()
+			.map_err(|e| execution(e))
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use jsonrpc_core::IoHandler;
+	use jsonrpc_macros::Trailing;
+	use keys::{Address, Private};
+	use primitives::hash::H256 as GlobalH256;
+	use v1::traits::Wallet;
+	use v1::types::{GetBalanceResponse, UnspentOutput, WalletTransaction, Bytes, H256};
+	use super::*;
+
+	#[derive(Default)]
+	struct SuccessWalletClientCore;
+
+	#[derive(Default)]
+	struct ErrorWalletClientCore;
+
+	impl WalletClientCoreApi for SuccessWalletClientCore {
+		fn new_address(&self) -> Result<Address, String> {
+			Ok("1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into())
+		}
+
+		fn import_private(&self, _private: Private) -> Result<(), String> {
+			Ok(())
+		}
+
+		fn balance(&self, min_confirmations: Trailing<u32>) -> Result<GetBalanceResponse, String> {
+			Ok(GetBalanceResponse {
+				confirmed: min_confirmations.unwrap_or(1) as f64,
+				unconfirmed: 0.5,
+				immature: 50.0,
+			})
+		}
+
+		fn send_to_address(&self, _address: Address, amount: u64, _fee_rate: Trailing<u64>) -> Result<GlobalH256, String> {
+			assert_eq!(amount, 10_000_000);
+			Ok(GlobalH256::from(1))
+		}
+
+		fn list_unspent(&self, _min_confirmations: Trailing<u32>) -> Result<Vec<UnspentOutput>, String> {
+			Ok(vec![UnspentOutput {
+				txid: H256::from(2),
+				vout: 1,
+				address: "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into(),
+				script_pubkey: Bytes::new(vec![0x51]),
+				amount: 0.1,
+				confirmations: 7,
+				spendable: true,
+			}])
+		}
+
+		fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String> {
+			Ok(vec![WalletTransaction {
+				txid: H256::from(3),
+				amount: -0.1,
+				confirmations: 2,
+			}])
+		}
+	}
+
+	impl WalletClientCoreApi for ErrorWalletClientCore {
+		fn new_address(&self) -> Result<Address, String> {
+			Err("wallet is locked".into())
+		}
+
+		fn import_private(&self, _private: Private) -> Result<(), String> {
+			Err("wallet is locked".into())
+		}
+
+		fn balance(&self, _min_confirmations: Trailing<u32>) -> Result<GetBalanceResponse, String> {
+			Err("wallet is locked".into())
+		}
+
+		fn send_to_address(&self, _address: Address, _amount: u64, _fee_rate: Trailing<u64>) -> Result<GlobalH256, String> {
+			Err("wallet is locked".into())
+		}
+
+		fn list_unspent(&self, _min_confirmations: Trailing<u32>) -> Result<Vec<UnspentOutput>, String> {
+			Err("wallet is locked".into())
+		}
+
+		fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String> {
+			Err("wallet is locked".into())
+		}
+	}
+
+	#[test]
+	fn getnewaddress_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getnewaddress",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":"1H5m1XzvHsjWX3wwU781ubctznEpNACrNC","id":1}"#);
+	}
+
+	#[test]
+	fn importprivkey_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "importprivkey",
+				"params": ["5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":null,"id":1}"#);
+	}
+
+	#[test]
+	fn importprivkey_invalid() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "importprivkey",
+				"params": ["not a key"],
+				"id": 1
+			}"#)).unwrap();
+
+		assert!(sample.contains(r#""code":-32602"#));
+	}
+
+	#[test]
+	fn getbalance_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getbalance",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"confirmed":1.0,"unconfirmed":0.5,"immature":50.0},"id":1}"#);
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getbalance",
+				"params": [6],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":{"confirmed":6.0,"unconfirmed":0.5,"immature":50.0},"id":1}"#);
+	}
+
+	#[test]
+	fn getbalance_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "getbalance",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"wallet is locked\""},"id":1}"#);
+	}
+
+	#[test]
+	fn sendtoaddress_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "sendtoaddress",
+				"params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1],
+				"id": 1
+			}"#)).unwrap();
+
+		// direct hash is 0100000000000000000000000000000000000000000000000000000000000000
+		// but client expects reverse hash
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":"0000000000000000000000000000000000000000000000000000000000000001","id":1}"#);
+	}
+
+	#[test]
+	fn sendtoaddress_invalid_amount() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "sendtoaddress",
+				"params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", -1],
+				"id": 1
+			}"#)).unwrap();
+
+		assert!(sample.contains(r#""code":-32602"#));
+	}
+
+	#[test]
+	fn sendtoaddress_error() {
+		let client = WalletClient::new(ErrorWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "sendtoaddress",
+				"params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Execution error.","data":"\"wallet is locked\""},"id":1}"#);
+	}
+
+	#[test]
+	fn listunspent_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "listunspent",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":[{"txid":"0200000000000000000000000000000000000000000000000000000000000000","vout":1,"address":"1H5m1XzvHsjWX3wwU781ubctznEpNACrNC","scriptPubKey":"51","amount":0.1,"confirmations":7,"spendable":true}],"id":1}"#);
+	}
+
+	#[test]
+	fn listtransactions_success() {
+		let client = WalletClient::new(SuccessWalletClientCore::default());
+		let mut handler = IoHandler::new();
+		handler.extend_with(client.to_delegate());
+
+		let sample = handler.handle_request_sync(&(r#"
+			{
+				"jsonrpc": "2.0",
+				"method": "listtransactions",
+				"params": [],
+				"id": 1
+			}"#)).unwrap();
+
+		assert_eq!(&sample, r#"{"jsonrpc":"2.0","result":[{"txid":"0300000000000000000000000000000000000000000000000000000000000000","amount":-0.1,"confirmations":2}],"id":1}"#);
+	}
+}
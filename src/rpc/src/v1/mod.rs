@@ -8,7 +8,11 @@ pub use self::traits::Raw;
 pub use self::traits::Miner;
 pub use self::traits::BlockChain;
 pub use self::traits::Network;
+pub use self::traits::Wallet;
+pub use self::traits::AtomicSwap;
 pub use self::impls::{RawClient, RawClientCore};
 pub use self::impls::{MinerClient, MinerClientCore};
 pub use self::impls::{BlockChainClient, BlockChainClientCore};
 pub use self::impls::{NetworkClient, NetworkClientCore};
+pub use self::impls::{WalletClient, WalletClientCoreApi};
+pub use self::impls::{AtomicSwapClient, AtomicSwapClientCoreApi};
@@ -0,0 +1,33 @@
+use jsonrpc_core::Error;
+
+use v1::types::{Bytes, ContractAudit, SwapContract, SwapTransaction, H256};
+
+build_rpc_trait! {
+	/// Rustheus atomic swap interface.
+	pub trait AtomicSwap {
+		/// Lock amount in BTC to contract redeemable by address with a new secret. Refundable after 48 hours.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "initiate", "params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "initiate")]
+		fn initiate(&self, String, f64) -> Result<SwapContract, Error>;
+		/// Lock amount in BTC to contract redeemable by address with secret of initiator. Refundable after 24 hours.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "participate", "params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1, "29e0b3b4a2a1e1d0b8b09f6c2a4d6e4b1b0e2d3c4b5a69788796a5b4c3d2e1f0"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "participate")]
+		fn participate(&self, String, f64, H256) -> Result<SwapContract, Error>;
+		/// Redeem contract output of raw contract transaction with the secret.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "redeem", "params": ["63...68", "0100...", "aa..."], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "redeem")]
+		fn redeem(&self, Bytes, Bytes, Bytes) -> Result<SwapTransaction, Error>;
+		/// Check contract and its output in raw contract transaction.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "auditcontract", "params": ["63...68", "0100..."], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "auditcontract")]
+		fn audit_contract(&self, Bytes, Bytes) -> Result<ContractAudit, Error>;
+		/// Return contract output of raw contract transaction to wallet after contract locktime.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "refund", "params": ["63...68", "0100..."], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "refund")]
+		fn refund(&self, Bytes, Bytes) -> Result<SwapTransaction, Error>;
+		/// Find secret revealed by raw transaction redeeming contract with given secret hash.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "extractsecret", "params": ["0100...", "29e0b3b4a2a1e1d0b8b09f6c2a4d6e4b1b0e2d3c4b5a69788796a5b4c3d2e1f0"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "extractsecret")]
+		fn extract_secret(&self, Bytes, H256) -> Result<Bytes, Error>;
+	}
+}
@@ -1,6 +1,6 @@
 use jsonrpc_core::Error;
 
-use v1::types::{BlockTemplate, BlockTemplateRequest};
+use v1::types::{BlockTemplate, BlockTemplateRequest, RawBlock};
 
 build_rpc_trait! {
 	/// Parity-bitcoin miner data interface.
@@ -9,5 +9,9 @@ build_rpc_trait! {
 		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "getblocktemplate", "params": [{"capabilities": ["coinbasetxn", "workid", "coinbase/append"]}], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
 		#[rpc(name = "getblocktemplate")]
 		fn get_block_template(&self, BlockTemplateRequest) -> Result<BlockTemplate, Error>;
+		/// Submits mined block. Returns null when block is accepted, otherwise rejection reason.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "submitblock", "params": ["00000020..."], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "submitblock")]
+		fn submit_block(&self, RawBlock) -> Result<Option<String>, Error>;
 	}
 }
@@ -2,8 +2,12 @@ mod blockchain;
 mod miner;
 mod raw;
 mod network;
+mod wallet;
+mod atomic_swap;
 
 pub use self::blockchain::BlockChain;
 pub use self::miner::Miner;
 pub use self::raw::Raw;
 pub use self::network::Network;
+pub use self::wallet::Wallet;
+pub use self::atomic_swap::AtomicSwap;
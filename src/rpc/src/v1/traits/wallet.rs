@@ -0,0 +1,34 @@
+use jsonrpc_macros::Trailing;
+use jsonrpc_core::Error;
+
+use v1::types::{GetBalanceResponse, UnspentOutput, WalletTransaction, H256};
+
+build_rpc_trait! {
+	/// Rustheus wallet interface.
+	pub trait Wallet {
+		/// Derive next receiving address of wallet seed.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "getnewaddress", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "getnewaddress")]
+		fn new_address(&self) -> Result<String, Error>;
+		/// Add private key in WIF format to wallet.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "importprivkey", "params": ["5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ"], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "importprivkey")]
+		fn import_private(&self, String) -> Result<(), Error>;
+		/// Get balance of all wallet keys. Outputs with less than given number of confirmations (1 by default) are unconfirmed.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "getbalance", "params": [6], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "getbalance")]
+		fn balance(&self, Trailing<u32>) -> Result<GetBalanceResponse, Error>;
+		/// Send amount in BTC to address, paying fee at optional rate in satoshis per 1000 virtual bytes. Returns transaction hash.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "sendtoaddress", "params": ["1H5m1XzvHsjWX3wwU781ubctznEpNACrNC", 0.1], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "sendtoaddress")]
+		fn send_to_address(&self, String, f64, Trailing<u64>) -> Result<H256, Error>;
+		/// List unspent outputs of wallet keys with at least given number of confirmations (0 by default).
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "listunspent", "params": [1], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "listunspent")]
+		fn list_unspent(&self, Trailing<u32>) -> Result<Vec<UnspentOutput>, Error>;
+		/// List wallet transactions included in blocks, oldest first.
+		/// @curl-example: curl --data-binary '{"jsonrpc": "2.0", "method": "listtransactions", "params": [], "id":1 }' -H 'content-type: application/json' http://127.0.0.1:8332/
+		#[rpc(name = "listtransactions")]
+		fn list_transactions(&self) -> Result<Vec<WalletTransaction>, Error>;
+	}
+}
@@ -3,6 +3,7 @@ use super::hash::H256;
 use chain;
 use super::transaction::RawTransaction;
 use memory_pool;
+use primitives::bigint::U256;
 
 /// Block template as described in:
 /// https://github.com/bitcoin/bips/blob/master/bip-0022.mediawiki
@@ -53,6 +54,9 @@ pub struct BlockTemplate {
 	pub bits: u32,
 	/// The height of the next block
 	pub height: u32,
+	/// Id to send with long polling request, so it returns once this template is outdated
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub longpollid: Option<String>,
 }
 
 /// Transaction data as included in `BlockTemplate`
@@ -81,9 +85,13 @@ pub struct BlockTemplateTransaction {
 
 impl From<memory_pool::BlockTemplate> for BlockTemplate {
 	fn from(block: memory_pool::BlockTemplate) -> Self {
+		let target: U256 = block.bits.into();
+		let mut target_bytes = [0u8; 32];
+		target.to_big_endian(&mut target_bytes);
 		BlockTemplate {
 			version: block.version,
-			previousblockhash: block.previous_header_hash.into(),
+			previousblockhash: block.previous_header_hash.reversed().into(),
+			target: target_bytes.into(),
 			curtime: block.time,
 			bits: block.bits.into(),
 			height: block.height,
@@ -190,6 +198,7 @@ mod tests {
 			curtime: 100,
 			bits: 200,
 			height: 300,
+			longpollid: None,
 		}).unwrap(), r#"{"version":0,"rules":null,"vbavailable":null,"vbrequired":null,"previousblockhash":"0000000000000000000000000000000000000000000000000000000000000000","transactions":[],"coinbaseaux":null,"coinbasevalue":null,"coinbasetxn":null,"target":"0000000000000000000000000000000000000000000000000000000000000000","mintime":null,"mutable":null,"noncerange":null,"sigoplimit":null,"sizelimit":null,"weightlimit":null,"curtime":100,"bits":200,"height":300}"#);
 		assert_eq!(serde_json::to_string(&BlockTemplate {
 			version: 0,
@@ -229,6 +238,7 @@ mod tests {
 			curtime: 100,
 			bits: 200,
 			height: 300,
+			longpollid: None,
 		}).unwrap(), r#"{"version":0,"rules":["a"],"vbavailable":{"b":5},"vbrequired":10,"previousblockhash":"0a00000000000000000000000000000000000000000000000000000000000000","transactions":[{"data":"00010203","txid":null,"hash":null,"depends":null,"fee":null,"sigops":null,"weight":null,"required":false}],"coinbaseaux":{"c":"d"},"coinbasevalue":30,"coinbasetxn":{"data":"555555","txid":"2c00000000000000000000000000000000000000000000000000000000000000","hash":"3700000000000000000000000000000000000000000000000000000000000000","depends":[1],"fee":300,"sigops":400,"weight":500,"required":true},"target":"6400000000000000000000000000000000000000000000000000000000000000","mintime":7,"mutable":["afg"],"noncerange":"00000000ffffffff","sigoplimit":45,"sizelimit":449,"weightlimit":523,"curtime":100,"bits":200,"height":300}"#);
 	}
 
@@ -256,6 +266,7 @@ mod tests {
 				curtime: 100,
 				bits: 200,
 				height: 300,
+				longpollid: None,
 			});
 		assert_eq!(
 			serde_json::from_str::<BlockTemplate>(r#"{"version":0,"rules":["a"],"vbavailable":{"b":5},"vbrequired":10,"previousblockhash":"0a00000000000000000000000000000000000000000000000000000000000000","transactions":[{"data":"00010203","txid":null,"hash":null,"depends":null,"fee":null,"sigops":null,"weight":null,"required":false}],"coinbaseaux":{"c":"d"},"coinbasevalue":30,"coinbasetxn":{"data":"555555","txid":"2c00000000000000000000000000000000000000000000000000000000000000","hash":"3700000000000000000000000000000000000000000000000000000000000000","depends":[1],"fee":300,"sigops":400,"weight":500,"required":true},"target":"6400000000000000000000000000000000000000000000000000000000000000","mintime":7,"mutable":["afg"],"noncerange":"00000000ffffffff","sigoplimit":45,"sizelimit":449,"weightlimit":523,"curtime":100,"bits":200,"height":300}"#).unwrap(),
@@ -297,6 +308,7 @@ mod tests {
 				curtime: 100,
 				bits: 200,
 				height: 300,
+				longpollid: None,
 			});
 	}
 }
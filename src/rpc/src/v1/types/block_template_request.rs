@@ -26,6 +26,9 @@ pub struct BlockTemplateRequest {
 	pub capabilities: Option<HashSet<String>>,
 	/// Softfork deployments, supported by client
 	pub rules: Option<HashSet<String>>,
+	/// Id of previously received template. Request is held until that template becomes outdated
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub longpollid: Option<String>,
 }
 
 #[cfg(test)]
@@ -52,6 +55,7 @@ mod tests {
 			mode: Some(BlockTemplateRequestMode::Template),
 			capabilities: Some(vec!["a".to_owned()].into_iter().collect()),
 			rules: Some(vec!["b".to_owned()].into_iter().collect()),
+			longpollid: None,
 		}).unwrap(), r#"{"mode":"template","capabilities":["a"],"rules":["b"]}"#);
 	}
 
@@ -63,6 +67,7 @@ mod tests {
 				mode: None,
 				capabilities: None,
 				rules: None,
+				longpollid: None,
 			});
 		assert_eq!(
 			serde_json::from_str::<BlockTemplateRequest>(r#"{"mode":"template","capabilities":["a"],"rules":["b"]}"#).unwrap(),
@@ -70,6 +75,15 @@ mod tests {
 				mode: Some(BlockTemplateRequestMode::Template),
 				capabilities: Some(vec!["a".to_owned()].into_iter().collect()),
 				rules: Some(vec!["b".to_owned()].into_iter().collect()),
+				longpollid: None,
+			});
+		assert_eq!(
+			serde_json::from_str::<BlockTemplateRequest>(r#"{"longpollid":"c"}"#).unwrap(),
+			BlockTemplateRequest {
+				mode: None,
+				capabilities: None,
+				rules: None,
+				longpollid: Some("c".to_owned()),
 			});
 	}
 }
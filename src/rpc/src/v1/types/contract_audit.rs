@@ -0,0 +1,46 @@
+use keys::Address;
+use v1::types;
+use super::hash::H256;
+
+/// Atomic swap contract details checked against its funding transaction
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ContractAudit {
+	/// Witness program of P2WSH output locking funds to the contract
+	pub contract_p2wsh: H256,
+	/// Value locked in contract in BTC
+	pub contract_value: f64,
+	/// Address able to redeem contract with the secret
+	#[serde(with = "types::address")]
+	pub recipient_address: Address,
+	/// Address of contract author able to refund after locktime
+	#[serde(with = "types::address")]
+	pub refund_address: Address,
+	/// Hash of the secret
+	pub secret_hash: H256,
+	/// Unix time or block height after which contract can be refunded
+	pub locktime: u32,
+	/// Seconds left until refund is possible. Negative once expired, absent for block height locktime
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub locktime_reached_in: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::hash::H256;
+	use super::ContractAudit;
+
+	#[test]
+	fn contract_audit_serialize() {
+		let audit = ContractAudit {
+			contract_p2wsh: H256::from(1),
+			contract_value: 0.5,
+			recipient_address: "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into(),
+			refund_address: "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into(),
+			secret_hash: H256::from(2),
+			locktime: 500,
+			locktime_reached_in: None,
+		};
+		assert_eq!(serde_json::to_string(&audit).unwrap(), r#"{"contract_p2wsh":"0100000000000000000000000000000000000000000000000000000000000000","contract_value":0.5,"recipient_address":"1H5m1XzvHsjWX3wwU781ubctznEpNACrNC","refund_address":"1H5m1XzvHsjWX3wwU781ubctznEpNACrNC","secret_hash":"0200000000000000000000000000000000000000000000000000000000000000","locktime":500}"#);
+	}
+}
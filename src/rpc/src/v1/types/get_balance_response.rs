@@ -0,0 +1,26 @@
+/// Wallet balance split by confirmation state
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct GetBalanceResponse {
+	/// Value of outputs with at least requested number of confirmations in BTC
+	pub confirmed: f64,
+	/// Value of mempool outputs and outputs with fewer confirmations in BTC
+	pub unconfirmed: f64,
+	/// Value of coinbase outputs which are not mature yet in BTC
+	pub immature: f64,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::GetBalanceResponse;
+
+	#[test]
+	fn get_balance_response_serialize() {
+		let balance = GetBalanceResponse {
+			confirmed: 1.5,
+			unconfirmed: 0.25,
+			immature: 50.0,
+		};
+		assert_eq!(serde_json::to_string(&balance).unwrap(), r#"{"confirmed":1.5,"unconfirmed":0.25,"immature":50.0}"#);
+	}
+}
@@ -3,27 +3,39 @@ mod block;
 mod block_template;
 mod block_template_request;
 mod bytes;
+mod contract_audit;
+mod get_balance_response;
 mod get_block_response;
 mod get_tx_out_response;
 mod get_tx_out_set_info_response;
 mod hash;
 mod script;
+mod swap_contract;
+mod swap_transaction;
 mod transaction;
 mod uint;
+mod unspent_output;
+mod wallet_transaction;
 mod nodes;
 
 pub use self::block::RawBlock;
 pub use self::block_template::{BlockTemplate, BlockTemplateTransaction};
 pub use self::block_template_request::{BlockTemplateRequest, BlockTemplateRequestMode};
 pub use self::bytes::Bytes;
+pub use self::contract_audit::ContractAudit;
+pub use self::get_balance_response::GetBalanceResponse;
 pub use self::get_block_response::{GetBlockResponse, VerboseBlock};
 pub use self::get_tx_out_response::GetTxOutResponse;
 pub use self::get_tx_out_set_info_response::GetTxOutSetInfoResponse;
 pub use self::hash::{H160, H256};
 pub use self::script::ScriptType;
+pub use self::swap_contract::SwapContract;
+pub use self::swap_transaction::SwapTransaction;
 pub use self::transaction::{RawTransaction, Transaction, TransactionInput, TransactionOutput,
 	TransactionOutputWithAddress, TransactionOutputWithScriptData, TransactionInputScript,
 	TransactionOutputScript, SignedTransactionInput, GetRawTransactionResponse,
 	SignedTransactionOutput, TransactionOutputs};
 pub use self::uint::U256;
+pub use self::unspent_output::UnspentOutput;
+pub use self::wallet_transaction::WalletTransaction;
 pub use self::nodes::{AddNodeOperation, NodeInfo};
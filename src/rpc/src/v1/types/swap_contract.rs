@@ -0,0 +1,56 @@
+use super::bytes::Bytes;
+use super::hash::H256;
+
+/// Atomic swap contract funded by this node
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SwapContract {
+	/// Contract script
+	pub contract: Bytes,
+	/// Witness program of P2WSH output locking funds to the contract
+	pub contract_p2wsh: H256,
+	/// Signed contract transaction
+	pub contract_tx: Bytes,
+	/// Contract transaction hash
+	pub contract_txid: H256,
+	/// Fee paid by contract transaction in BTC
+	pub contract_fee: f64,
+	/// Signed transaction returning funds after locktime
+	pub refund_tx: Bytes,
+	/// Refund transaction hash
+	pub refund_txid: H256,
+	/// Fee paid by refund transaction in BTC
+	pub refund_fee: f64,
+	/// Secret generated by initiator. Participant does not know it yet
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub secret: Option<Bytes>,
+	/// Hash of the secret
+	pub secret_hash: H256,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::bytes::Bytes;
+	use super::super::hash::H256;
+	use super::SwapContract;
+
+	#[test]
+	fn swap_contract_serialize() {
+		let mut contract = SwapContract {
+			contract: Bytes::new(vec![0x63]),
+			contract_p2wsh: H256::from(1),
+			contract_tx: Bytes::new(vec![0x01]),
+			contract_txid: H256::from(2),
+			contract_fee: 0.0001,
+			refund_tx: Bytes::new(vec![0x02]),
+			refund_txid: H256::from(3),
+			refund_fee: 0.0002,
+			secret: None,
+			secret_hash: H256::from(4),
+		};
+		assert_eq!(serde_json::to_string(&contract).unwrap(), r#"{"contract":"63","contract_p2wsh":"0100000000000000000000000000000000000000000000000000000000000000","contract_tx":"01","contract_txid":"0200000000000000000000000000000000000000000000000000000000000000","contract_fee":0.0001,"refund_tx":"02","refund_txid":"0300000000000000000000000000000000000000000000000000000000000000","refund_fee":0.0002,"secret_hash":"0400000000000000000000000000000000000000000000000000000000000000"}"#);
+
+		contract.secret = Some(Bytes::new(vec![0xaa]));
+		assert!(serde_json::to_string(&contract).unwrap().contains(r#""secret":"aa""#));
+	}
+}
@@ -0,0 +1,31 @@
+use super::bytes::Bytes;
+use super::hash::H256;
+
+/// Transaction spending atomic swap contract output
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SwapTransaction {
+	/// Signed transaction
+	pub tx: Bytes,
+	/// Transaction hash
+	pub txid: H256,
+	/// Fee paid by transaction in BTC
+	pub fee: f64,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::bytes::Bytes;
+	use super::super::hash::H256;
+	use super::SwapTransaction;
+
+	#[test]
+	fn swap_transaction_serialize() {
+		let transaction = SwapTransaction {
+			tx: Bytes::new(vec![0x01, 0x02]),
+			txid: H256::from(1),
+			fee: 0.00015,
+		};
+		assert_eq!(serde_json::to_string(&transaction).unwrap(), r#"{"tx":"0102","txid":"0100000000000000000000000000000000000000000000000000000000000000","fee":0.00015}"#);
+	}
+}
@@ -0,0 +1,47 @@
+use keys::Address;
+use v1::types;
+use super::hash::H256;
+use super::bytes::Bytes;
+
+/// Unspent output of wallet key
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UnspentOutput {
+	/// Hash of transaction containing the output
+	pub txid: H256,
+	/// Index of the output in transaction
+	pub vout: u32,
+	/// Wallet address the output pays to
+	#[serde(with = "types::address")]
+	pub address: Address,
+	/// Output script
+	#[serde(rename = "scriptPubKey")]
+	pub script_pubkey: Bytes,
+	/// Output value in BTC
+	pub amount: f64,
+	/// Number of confirmations. Zero for mempool outputs
+	pub confirmations: u32,
+	/// Whether output can be spent now. Immature coinbase outputs can not
+	pub spendable: bool,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::bytes::Bytes;
+	use super::super::hash::H256;
+	use super::UnspentOutput;
+
+	#[test]
+	fn unspent_output_serialize() {
+		let unspent = UnspentOutput {
+			txid: H256::from(1),
+			vout: 2,
+			address: "1H5m1XzvHsjWX3wwU781ubctznEpNACrNC".into(),
+			script_pubkey: Bytes::new(vec![0x00, 0x14, 0xaa]),
+			amount: 0.5,
+			confirmations: 3,
+			spendable: true,
+		};
+		assert_eq!(serde_json::to_string(&unspent).unwrap(), r#"{"txid":"0100000000000000000000000000000000000000000000000000000000000000","vout":2,"address":"1H5m1XzvHsjWX3wwU781ubctznEpNACrNC","scriptPubKey":"0014aa","amount":0.5,"confirmations":3,"spendable":true}"#);
+	}
+}
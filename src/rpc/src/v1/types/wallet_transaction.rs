@@ -0,0 +1,29 @@
+use super::hash::H256;
+
+/// Transaction affecting wallet funds
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct WalletTransaction {
+	/// Transaction hash
+	pub txid: H256,
+	/// Value received by wallet minus value spent from it in BTC
+	pub amount: f64,
+	/// Number of confirmations
+	pub confirmations: u32,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::super::hash::H256;
+	use super::WalletTransaction;
+
+	#[test]
+	fn wallet_transaction_serialize() {
+		let transaction = WalletTransaction {
+			txid: H256::from(1),
+			amount: -0.25,
+			confirmations: 6,
+		};
+		assert_eq!(serde_json::to_string(&transaction).unwrap(), r#"{"txid":"0100000000000000000000000000000000000000000000000000000000000000","amount":-0.25,"confirmations":6}"#);
+	}
+}
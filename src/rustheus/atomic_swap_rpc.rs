@@ -0,0 +1,94 @@
+use chain::bytes::Bytes;
+use chain::Transaction;
+use ethcore_rpc::v1::AtomicSwapClientCoreApi;
+use ethcore_rpc::v1::types::{ContractAudit, SwapContract, SwapTransaction};
+use keys::Address;
+use primitives::hash::H256;
+use ser::{serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS};
+use atomic_swapper::{AtomicSwapperRef, BuiltContract, ContractSpend};
+use wallet_rpc::to_coins;
+
+/// Serves atomic swap json-rpc methods, since swaps are funded by wallet inside node binary
+pub struct AtomicSwapClientCore {
+    atomic_swapper: AtomicSwapperRef,
+}
+
+impl AtomicSwapClientCore {
+    pub fn new(atomic_swapper: AtomicSwapperRef) -> Self {
+        AtomicSwapClientCore {
+            atomic_swapper,
+        }
+    }
+}
+
+fn raw_transaction(transaction: &Transaction) -> Bytes {
+    serialize_with_flags(transaction, SERIALIZE_TRANSACTION_WITNESS)
+}
+
+fn swap_contract(contract: BuiltContract, secret: Option<Bytes>) -> SwapContract {
+    SwapContract {
+        contract_tx: raw_transaction(&contract.contractTx).into(),
+        refund_tx: raw_transaction(&contract.refundTx).into(),
+        refund_txid: contract.refundTx.hash().reversed().into(),
+        contract: contract.contract.into(),
+        contract_p2wsh: contract.contractP2WSH.into(),
+        contract_txid: contract.contractTxHash.reversed().into(),
+        contract_fee: to_coins(contract.contractFee),
+        refund_fee: to_coins(contract.refundFee),
+        secret: secret.map(Into::into),
+        secret_hash: contract.secretHash.into(),
+    }
+}
+
+fn swap_transaction(spend: ContractSpend) -> SwapTransaction {
+    SwapTransaction {
+        tx: raw_transaction(&spend.transaction).into(),
+        txid: spend.transaction.hash().reversed().into(),
+        fee: to_coins(spend.fee),
+    }
+}
+
+impl AtomicSwapClientCoreApi for AtomicSwapClientCore {
+    fn initiate(&self, address: Address, amount: u64) -> Result<SwapContract, String> {
+        self.atomic_swapper.initiate(address, amount)
+            .map(|initiated| swap_contract(initiated.contract, Some(initiated.secret)))
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn participate(&self, address: Address, amount: u64, secret_hash: H256) -> Result<SwapContract, String> {
+        self.atomic_swapper.participate(address, amount, secret_hash)
+            .map(|contract| swap_contract(contract, None))
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn redeem(&self, contract: Bytes, contract_transaction: Bytes, secret: Bytes) -> Result<SwapTransaction, String> {
+        self.atomic_swapper.redeem(contract, contract_transaction, secret)
+            .map(swap_transaction)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn audit_contract(&self, contract: Bytes, contract_transaction: Bytes) -> Result<ContractAudit, String> {
+        self.atomic_swapper.audit_contract(contract, contract_transaction)
+            .map(|audit| ContractAudit {
+                locktime_reached_in: audit.locktime_reached_in(),
+                contract_p2wsh: audit.contract_p2wsh.into(),
+                contract_value: to_coins(audit.value),
+                recipient_address: audit.recipient,
+                refund_address: audit.refund,
+                secret_hash: audit.secret_hash.into(),
+                locktime: audit.locktime,
+            })
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn refund(&self, contract: Bytes, contract_transaction: Bytes) -> Result<SwapTransaction, String> {
+        self.atomic_swapper.refund(contract, contract_transaction)
+            .map(swap_transaction)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn extract_secret(&self, redeem_transaction: Bytes, secret_hash: H256) -> Result<Bytes, String> {
+        self.atomic_swapper.extract_secret(redeem_transaction, secret_hash)
+            .map_err(|err| format!("{:?}", err))
+    }
+}
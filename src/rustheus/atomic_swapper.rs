@@ -5,32 +5,56 @@ use chain_builder::TransactionBuilder;
 use keys::generator::Random;
 use primitives::hash::{H256, H160};
 use keys::{Address, AddressHash};
-use sync::{AcceptorRef, MessageWrapper};
+use sync::AcceptorRef;
 use chain::bytes::Bytes;
-use chain::{Transaction};
+use chain::{OutPoint, Transaction, TransactionInput, TransactionOutput};
 use crypto::{dhash160, sha256};
-use std::time::{SystemTime, UNIX_EPOCH};
-use wallet::WalletRef;
-use message::types::Tx;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use wallet::{Wallet, WalletError, WalletRef};
 use transaction_helper::{TransactionHelperRef, SignError, FundError};
-use std::sync::mpsc::Receiver;
-use ser::{deserialize, serialize, Reader};
+use coin_selection::{estimate_vsize, witness_size, Fallback, InputType, DEFAULT_FEE_RATE, SIGNATURE_SIZE, COMPRESSED_PUBLIC_SIZE};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use ser::{deserialize, serialize, serialize_with_flags, Reader, Error as ReaderError, SERIALIZE_TRANSACTION_WITNESS};
 use script::Error as ScriptError;
 use script::{Script, Opcode, Num};
-use chain::constants::LOCKTIME_THRESHOLD;
+use chain::constants::{LOCKTIME_THRESHOLD, SEQUENCE_FINAL};
 use keys::Network;
 use keys::Type as AddressType;
+use verification::TransactionError;
 use futures::prelude::*;
-use futures_cpupool::CpuPool;
-use std::time::Duration;
+use swap_journal::{SwapJournal, SwapRecord, SwapState};
 
 const SECRET_SIZE: usize = 32;
+/// Delay before refund of expired swap is tried again
+const REFUND_RETRY_INTERVAL_SECS: u64 = 60;
+
+pub type AtomicSwapperRef = Arc<AtomicSwapper>;
 
 #[derive(Debug)]
 pub enum ContractError
 {
+    WalletNotReady,
+    Wallet(WalletError),
+    SecretGeneration,
     FundError(FundError),
     SignError(SignError),
+    InvalidContract(PushExtractionError),
+    InvalidTransaction(ReaderError),
+    NoContractOutput,
+    /// Contract recipient is not one of wallet keys
+    NotRecipient,
+    /// Contract refund key is not one of wallet keys
+    NotAuthor,
+    /// Seconds left until contract can be refunded
+    LocktimeNotReached(i64),
+    /// Transaction does not reveal preimage of secret hash
+    NoSecret,
+    UnexpectedSecretSize(i64),
+    /// Fee and contract value
+    FeeExceedsValue(u64, u64),
+    Rejected(TransactionError),
 }
 
 #[derive(Debug)]
@@ -48,6 +72,12 @@ impl From<ScriptError> for PushExtractionError {
     }
 }
 
+impl From<WalletError> for ContractError {
+    fn from(err: WalletError) -> ContractError {
+        ContractError::Wallet(err)
+    }
+}
+
 impl From<SignError> for ContractError {
     fn from(err: SignError) -> ContractError {
         ContractError::SignError(err)
@@ -60,15 +90,36 @@ impl From<FundError> for ContractError {
     }
 }
 
+impl From<PushExtractionError> for ContractError {
+    fn from(err: PushExtractionError) -> ContractError {
+        ContractError::InvalidContract(err)
+    }
+}
+
+impl From<ReaderError> for ContractError {
+    fn from(err: ReaderError) -> ContractError {
+        ContractError::InvalidTransaction(err)
+    }
+}
+
+impl From<TransactionError> for ContractError {
+    fn from(err: TransactionError) -> ContractError {
+        ContractError::Rejected(err)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Task {
     //atomic swaps
     Initiate(Address, u64),
     Participate(Address, u64, H256),
     Redeem(Bytes, Bytes, Bytes),
-    ExtractSecret(H256, H256),
+    ExtractSecret(Bytes, H256),
     AuditContract(Bytes, Bytes),
-    //TODO refund
+    Refund(Bytes, Bytes),
+    /// Transaction was accepted to mempool or included in block
+    TransactionAccepted(Transaction),
+    ListSwaps,
 }
 
 struct ContractArgs {
@@ -78,14 +129,51 @@ struct ContractArgs {
     secret_hash: H256,
 }
 
-struct BuiltContract {
-    contract:       Bytes,
-    contractP2WSH:  H256,
-    contractTxHash: H256,
-    contractTx:     Transaction,
-    contractFee:    u64,
-    refundTx:       Transaction,
-    refundFee:      u64,
+pub struct BuiltContract {
+    pub contract:       Bytes,
+    pub contractP2WSH:  H256,
+    pub contractTxHash: H256,
+    pub contractTx:     Transaction,
+    pub contractFee:    u64,
+    pub refundTx:       Transaction,
+    pub refundFee:      u64,
+    pub secretHash:     H256,
+}
+
+/// Contract of swap initiator together with the secret only initiator knows so far
+pub struct InitiatedContract {
+    pub secret:     Bytes,
+    pub contract:   BuiltContract,
+}
+
+/// Signed transaction spending contract output
+pub struct ContractSpend {
+    pub transaction: Transaction,
+    pub fee:         u64,
+}
+
+/// Terms of counterparty contract
+pub struct AuditedContract {
+    pub contract_p2wsh: H256,
+    pub value:          u64,
+    pub recipient:      Address,
+    pub refund:         Address,
+    pub secret_hash:    H256,
+    pub locktime:       u32,
+}
+
+impl AuditedContract {
+    /// Seconds left until contract can be refunded, negative once it can. None if locktime is block height
+    pub fn locktime_reached_in(&self) -> Option<i64> {
+        locktime_reached_in(self.locktime)
+    }
+}
+
+/// Audited contract of counterparty not yet matched with a contract funded by us
+struct CounterpartyContract {
+    contract:             Bytes,
+    contract_transaction: Bytes,
+    secret_hash:          H256,
 }
 
 // AtomicSwapDataPushes houses the data pushes found in atomic swap contracts.
@@ -99,231 +187,293 @@ struct AtomicSwapDataPushes {
 
 pub struct AtomicSwapper {
     acceptor: AcceptorRef,
-    cpupool: CpuPool,
-    message_wrapper: MessageWrapper, 
     transaction_helper: TransactionHelperRef,
-    task_receiver: Receiver<Task>,
     wallet: WalletRef,
+    /// Swaps funded by us. Counterparty reveals the secret when redeeming our contract
+    journal: Mutex<SwapJournal>,
+    counterparty_contracts: Mutex<Vec<CounterpartyContract>>,
 }
 
 impl AtomicSwapper {
     pub fn new(
         acceptor: AcceptorRef,
         transaction_helper: TransactionHelperRef,
-        cpupool: CpuPool,    
-        message_wrapper: MessageWrapper,
-        task_receiver: Receiver<Task>,
-        wallet: WalletRef, 
+        wallet: WalletRef,
+        journal: SwapJournal,
     ) -> Self {
         AtomicSwapper {
             acceptor,
             transaction_helper,
-            cpupool,
-            message_wrapper,
-            task_receiver,
             wallet,
+            journal: Mutex::new(journal),
+            counterparty_contracts: Mutex::new(Vec::new()),
         }
     }
 
-    pub fn run(&mut self) {
+    /// Serves shell commands and prints their results. Refunds swaps once their locktime passes
+    pub fn run(&self, task_receiver: Receiver<Task>) {
+        let pending = self.journal.lock().swaps().iter().filter(|swap| swap.state.is_pending()).count();
+        if pending > 0 {
+            info!("Resuming {} pending atomic swaps", pending);
+        }
+
         loop {
-            if let Ok(task) = self.task_receiver.recv() {
-                match task {
-                    Task::Initiate(address, amount) => self.initiate(address, amount),
-                    Task::Participate(address, amount, secret_hash) => self.participate(address, amount, secret_hash),
-                    Task::Redeem(contract, contract_transaction, secret) => self.redeem(contract, contract_transaction, secret),
-                    Task::ExtractSecret(transaction, secret) => self.extract_secret(transaction, secret),
-                    Task::AuditContract(contract, contract_transaction) => self.audit_contract(contract, contract_transaction),
+            let task = match self.next_expiry() {
+                Some(timeout) => match task_receiver.recv_timeout(timeout) {
+                    Ok(task) => task,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.expire_swaps();
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+                None => match task_receiver.recv() {
+                    Ok(task) => task,
+                    Err(_) => break,
+                },
+            };
+
+            match task {
+                Task::Initiate(address, amount) => match self.initiate(address, amount) {
+                    Ok(initiated) => {
+                        println!("Secret:      {:?}", initiated.secret);
+                        println!("Secret hash: {}\n", initiated.contract.secretHash);
+                        print_contract(&initiated.contract);
+                    }
+                    Err(err) => error!("Failed to initiate swap. Reason: {:?}", err),
+                },
+                Task::Participate(address, amount, secret_hash) => match self.participate(address, amount, secret_hash) {
+                    Ok(contract) => print_contract(&contract),
+                    Err(err) => error!("Failed to participate in swap. Reason: {:?}", err),
+                },
+                Task::Redeem(contract, contract_transaction, secret) => match self.redeem(contract, contract_transaction, secret) {
+                    Ok(redeem) => {
+                        println!("Redeem transaction {}:", redeem.transaction.hash());
+                        println!("Redeem fee: {}", redeem.fee);
+                        println!("Size {} bytes", serialize(&redeem.transaction).len());
+                    }
+                    Err(err) => error!("Failed to redeem contract. Reason: {:?}", err),
+                },
+                Task::ExtractSecret(redeem_transaction, secret_hash) => match self.extract_secret(redeem_transaction, secret_hash) {
+                    Ok(secret) => println!("Secret: {:?}", secret),
+                    Err(err) => error!("Failed to extract secret. Reason: {:?}", err),
+                },
+                Task::AuditContract(contract, contract_transaction) => match self.audit_contract(contract, contract_transaction) {
+                    Ok(audit) => print_audit(&audit),
+                    Err(err) => error!("Failed to audit contract. Reason: {:?}", err),
+                },
+                Task::Refund(contract, contract_transaction) => match self.refund(contract, contract_transaction) {
+                    Ok(refund) => {
+                        println!("Refund transaction {}:", refund.transaction.hash());
+                        println!("Refund fee: {}", refund.fee);
+                        println!("Size {} bytes", serialize(&refund.transaction).len());
+                    }
+                    Err(err) => error!("Failed to refund contract. Reason: {:?}", err),
+                },
+                Task::TransactionAccepted(transaction) => self.watch_transaction(&transaction),
+                Task::ListSwaps => print_swaps(&self.swaps(), &self.wallet.read()),
+            }
+        }
+    }
+
+    /// All swaps funded by us, including finished ones
+    pub fn swaps(&self) -> Vec<SwapRecord> {
+        self.journal.lock().swaps().to_vec()
+    }
+
+    /// Time until locktime of the earliest unfinished swap. None if there are no swaps to wait for.
+    /// Swaps which are already past locktime weren't refunded yet, so their refund is retried after a delay
+    fn next_expiry(&self) -> Option<Duration> {
+        let earliest = self.journal.lock().swaps().iter()
+            .filter(|swap| swap.state.is_pending())
+            .filter_map(|swap| locktime_reached_in(swap.locktime))
+            .min();
+        earliest.map(|reached_in| Duration::from_secs(if reached_in > 0 { reached_in as u64 } else { REFUND_RETRY_INTERVAL_SECS }))
+    }
+
+    /// Refunds swaps which counterparty did not redeem before locktime.
+    /// Swap, which can't be refunded yet, is marked expired and its refund is retried on next expiry
+    fn expire_swaps(&self) {
+        let expired: Vec<(usize, SwapRecord)> = self.journal.lock().swaps().iter()
+            .enumerate()
+            .filter(|&(_, swap)| swap.state.is_pending())
+            .filter(|&(_, swap)| locktime_reached_in(swap.locktime).map_or(false, |reached_in| reached_in <= 0))
+            .map(|(index, swap)| (index, swap.clone()))
+            .collect();
+
+        for (index, swap) in expired {
+            match self.refund(swap.contract, swap.contract_transaction) {
+                Ok(refund) => info!("Refunded atomic swap {} with transaction {}", index, refund.transaction.hash()),
+                Err(err) => {
+                    warn!("Failed to refund atomic swap {}. Reason: {:?}. Retrying in {} seconds", index, err, REFUND_RETRY_INTERVAL_SECS);
+                    self.journal.lock().set_expired(index);
                 }
-            } else {
-                break;
             }
         }
     }
 
-    fn initiate(&self, address: Address, amount: u64) {
+    pub fn initiate(&self, address: Address, amount: u64) -> Result<InitiatedContract, ContractError> {
         //TODO check if correct network
-		//let mut secret = [u8; 32];
         let mut secret: [u8; SECRET_SIZE] = [0u8; SECRET_SIZE];
-        if let Err(_) = Random::generate_bytes(&mut secret[..]) {
-            error!("Could not generate bytes for secret");
-            return;
-        }
+        Random::generate_bytes(&mut secret[..]).map_err(|_| ContractError::SecretGeneration)?;
         let secret_hash = sha256(&secret);
-        
-        let current_time = SystemTime::now();
-        let time_since_the_epoch = current_time
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
 
-        let locktime = time_since_the_epoch.as_secs() + (48 * 60 * 60); //48 hours
-
-        println!("Secret:      {:?}", Bytes::from(&secret[..]));
-        println!("Secret hash: {}\n", secret_hash);
+        let locktime = current_time() + (48 * 60 * 60); //48 hours
 
         let contract = self.buildContract(ContractArgs {
             them:       address.hash,
             amount:     amount,
             locktime:   locktime as u32,    //TODO check if u32 is suitable
             secret_hash: secret_hash,
-        });
-
-        let contract = match contract {
-            Ok(built_contract) => built_contract,
-            Err(err) => {
-                error!("Failed to build contract. Reason: {:?}", err);
-                return;
-            }
-        };
-
-        let refundTxHash = contract.refundTx.hash();
-        //TODO fee calculation
-        //let contractFeePerKb = calcFeePerKb(b.contractFee, b.contractTx.SerializeSize())
-        //let refundFeePerKb = calcFeePerKb(b.refundFee, b.refundTx.SerializeSize())
-
-        //println!("Contract fee: %v (%0.8f BTC/kB)\n", b.contractFee, contractFeePerKb);
-        //println!("Refund fee:   %v (%0.8f BTC/kB)\n\n", b.refundFee, refundFeePerKb);
-        println!("Contract ({}):", contract.contractP2WSH);
-        println!("{:?}\n", contract.contract);
-        
-        println!("Contract transaction ({}):", contract.contractTxHash);
-        println!("{:?}\n", serialize(&contract.contractTx));
-
-        println!("Refund transaction ({}):\n", refundTxHash);
-        println!("{:?}\n", contract.refundTx);
-
-        let message_wrapper = self.message_wrapper.to_owned();
-        let task = self.acceptor.async_accept_transaction(contract.contractTx.clone())
-            .map(move |transaction| message_wrapper.broadcast(&Tx::with_transaction(transaction)));
-
-        let _ = self.cpupool.spawn(task);
+        })?;
+
+        // secret is kept encrypted in wallet. Without it counterparty contract can't be redeemed
+        let secret = Bytes::from(&secret[..]);
+        self.wallet.write().add_swap_secret(secret.clone())?;
+        self.publish(contract.contractTx.clone())?;
+        self.record_swap(SwapState::Initiated, &contract, locktime as u32);
+        Ok(InitiatedContract {
+            secret,
+            contract,
+        })
     }
     
-    fn participate(&self, address: Address, amount: u64, secret_hash: H256) {
-        let current_time = SystemTime::now();
-        let time_since_the_epoch = current_time
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
-        let locktime = time_since_the_epoch.as_secs() + (24 * 60 * 60); //24 hours        
+    pub fn participate(&self, address: Address, amount: u64, secret_hash: H256) -> Result<BuiltContract, ContractError> {
+        let locktime = current_time() + (24 * 60 * 60); //24 hours        
 
         let contract = self.buildContract(ContractArgs {
             them:       address.hash,
             amount:     amount,
             locktime:   locktime as u32,
-            secret_hash: secret_hash,
-        });
-
-        let contract = match contract {
-            Ok(built_contract) => built_contract,
-            Err(err) => {
-                error!("Failed to build contract. Reason: {:?}", err);
-                return;
-            }
-        };
-
-        let refundTxHash = contract.refundTx.hash();
-        //contractFeePerKb := calcFeePerKb(b.contractFee, b.contractTx.SerializeSize())
-        //refundFeePerKb := calcFeePerKb(b.refundFee, b.refundTx.SerializeSize())
+            secret_hash: secret_hash.clone(),
+        })?;
 
-        // println!("Contract fee: %v (%0.8f BTC/kB)\n", b.contractFee, contractFeePerKb)
-        // println!("Refund fee:   %v (%0.8f BTC/kB)\n\n", b.refundFee, refundFeePerKb)
-        println!("Contract ({}):", contract.contractP2WSH);
-        println!("{:?}\n", contract.contract);
-        
-        println!("Contract transaction ({}):", contract.contractTxHash);
-        println!("{:?}\n", serialize(&contract.contractTx));
+        self.publish(contract.contractTx.clone())?;
+        // initiator reveals the secret when redeeming our contract
+        self.record_swap(SwapState::Participated, &contract, locktime as u32);
+        Ok(contract)
+    }
 
-        println!("Refund transaction ({}):\n", refundTxHash);
-        println!("{:?}\n", contract.refundTx);
+    /// Adds published contract to journal together with counterparty contract audited before
+    fn record_swap(&self, state: SwapState, contract: &BuiltContract, locktime: u32) {
+        let output_index = find_contract_output(&contract.contractTx, &contract.contractP2WSH)
+            .map(|(index, _)| index)
+            .expect("contract transaction is built with contract output");
+        let counterparty = {
+            let mut counterparty_contracts = self.counterparty_contracts.lock();
+            counterparty_contracts.iter()
+                .position(|counterparty| counterparty.secret_hash == contract.secretHash)
+                .map(|position| counterparty_contracts.remove(position))
+        };
+        let (counterparty_contract, counterparty_contract_transaction) = match counterparty {
+            Some(counterparty) => (counterparty.contract, counterparty.contract_transaction),
+            None => (Bytes::new(), Bytes::new()),
+        };
 
-        let message_wrapper = self.message_wrapper.to_owned();
-        let task = self.acceptor.async_accept_transaction(contract.contractTx.clone())
-            .map(move |transaction| message_wrapper.broadcast(&Tx::with_transaction(transaction)));
-        
-        let _ = self.cpupool.spawn(task);
+        self.journal.lock().add(SwapRecord {
+            state,
+            contract: contract.contract.clone(),
+            contract_transaction: serialize_with_flags(&contract.contractTx, SERIALIZE_TRANSACTION_WITNESS),
+            contract_out_point: OutPoint {
+                hash: contract.contractTxHash.clone(),
+                index: output_index as u32,
+            },
+            secret_hash: contract.secretHash.clone(),
+            locktime,
+            counterparty_contract,
+            counterparty_contract_transaction,
+        });
     }
 
-    fn extract_secret(&self, transaction: H256, secret: H256) {
-        unimplemented!();
+    /// Finds secret in witness of transaction redeeming contract with given secret hash
+    pub fn extract_secret(&self, raw_redeem_transaction: Bytes, secret_hash: H256) -> Result<Bytes, ContractError> {
+        let transaction = deserialize_transaction(raw_redeem_transaction)?;
+        transaction.inputs.iter()
+            .filter_map(|input| find_secret(input, &secret_hash))
+            .next()
+            .ok_or(ContractError::NoSecret)
     }
 
-    fn redeem(&self, contract: Bytes, raw_contract_transaction: Bytes, secret: Bytes) {
-        let contractHash256 = sha256(&contract);        
-        let pushes =  match extractAtomicSwapDataPushes(0, contract.clone()) {
-            Ok(pushes) => pushes,
-            Err(err) => {
-                error!("Cannot parse contract. Reason {:?}", err);
-                return;
+    /// Extracts secret when counterparty redeems our contract and redeems their contract with it
+    fn watch_transaction(&self, transaction: &Transaction) {
+        for input in &transaction.inputs {
+            let found = self.journal.lock().swaps().iter()
+                .position(|swap| swap.state.is_pending() && swap.contract_out_point == input.previous_output);
+            let index = match found {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let swap = self.journal.lock().swaps()[index].clone();
+            match find_secret(input, &swap.secret_hash) {
+                Some(secret) => {
+                    info!("Extracted secret {:?} from transaction {}", secret, transaction.hash());
+                    self.journal.lock().set_state(index, SwapState::Redeemed);
+                    self.redeem_counterparty_contract(swap, secret);
+                }
+                // our refund
+                None => self.journal.lock().set_state(index, SwapState::Refunded),
             }
-        };
-
-        let network = Network::Mainnet; //TODO check for network correctness
+        }
+    }
 
-        Address {
-            hash: pushes.RecipientHash160,
-            network: network,
-            kind: AddressType::P2PKH,
+    fn redeem_counterparty_contract(&self, swap: SwapRecord, secret: Bytes) {
+        let counterparty_contract = if swap.counterparty_contract.is_empty() {
+            let mut counterparty_contracts = self.counterparty_contracts.lock();
+            counterparty_contracts.iter()
+                .position(|counterparty| counterparty.secret_hash == swap.secret_hash)
+                .map(|position| counterparty_contracts.remove(position))
+                .map(|counterparty| (counterparty.contract, counterparty.contract_transaction))
+        } else {
+            Some((swap.counterparty_contract, swap.counterparty_contract_transaction))
         };
 
-       let raw_transaction_data: Vec<u8> = raw_contract_transaction.into();
-		let transaction: Transaction = match deserialize(Reader::new(&raw_transaction_data)) {
-            Ok(transaction) => transaction,
-            Err(err) => {
-                error!("Cannot deserialize transaction: {:?}", err);
-                return;
-            }
-        };
+        match counterparty_contract {
+            Some((contract, contract_transaction)) => match self.redeem(contract, contract_transaction, secret) {
+                Ok(redeem) => info!("Redeemed counterparty contract with transaction {}", redeem.transaction.hash()),
+                Err(err) => error!("Failed to redeem counterparty contract. Reason: {:?}", err),
+            },
+            None => info!("No audited counterparty contract with secret hash {}. Use `redeem` with the secret", swap.secret_hash),
+        }
+    }
 
-        let found_output = transaction.outputs.iter()
-            .enumerate()
-            .find(|(_, output)| {
-                if output.script_pubkey.len() == 34 {       //TODO use script address instead of script hash here
-                    let script_unlocking_hash = &output.script_pubkey[2..];
-                    return script_unlocking_hash == &contractHash256[..];
-                }
-                false
-            });
-
-        let (output_index, output) = match found_output {
-            Some((output_index, output)) => (output_index, output),
-            None => {
-                error!("Transaction does not contain the contract output");
-                return;
-            }
-        };
+    pub fn redeem(&self, contract: Bytes, raw_contract_transaction: Bytes, secret: Bytes) -> Result<ContractSpend, ContractError> {
+        self.ensure_wallet_ready()?;
+        let contractHash256 = sha256(&contract);        
+        let pushes = extractAtomicSwapDataPushes(0, contract.clone())?;
+
+        let transaction = deserialize_transaction(raw_contract_transaction)?;
+        let (output_index, output) = find_contract_output(&transaction, &contractHash256)
+            .ok_or(ContractError::NoContractOutput)?;
 
+        if self.wallet.read().find_keypair_with_public_hash(&pushes.RecipientHash160).is_none() {
+            return Err(ContractError::NotRecipient);
+        }
         let recipientAddr = self.wallet.write().new_keypair();
         let wallet = self.wallet.read();
-        let key = wallet.find_keypair_with_public_hash(&recipientAddr.hash).unwrap(); //HACK
+        let key = wallet.find_keypair_with_public_hash(&pushes.RecipientHash160).expect("checked above");
 
         let outScript = ScriptBuilder::build_p2wpkh(&recipientAddr.hash);
 
-        //feePerKb, minFeePerKb, err := getFeePerKb(c)
-        let (feePerKb, minFeePerKb) = (0,0);
-
         let mut redeemTx: Transaction = TransactionBuilder::with_output_and_pubkey(0, outScript.to_bytes())
             .set_input(&transaction, output_index as u32)
             .set_lock_time(pushes.LockTime as u32)
             .into();
 
-        //redeemSize := estimateRedeemSerializeSize(cmd.contract, redeemTx.TxOut)
-        //fee := txrules.FeeForSerializeSize(feePerKb, redeemSize)
-        let fee = 0;
-        //redeemTx.TxOut[0].Value = cmd.contractTx.TxOut[contractOut].Value - int64(fee)
-        //if txrules.IsDustOutput(redeemTx.TxOut[0], minFeePerKb) {
-        //    return fmt.Errorf("redeem output value of %v is dust", btcutil.Amount(redeemTx.TxOut[0].Value))
-        //}
+        let redeemWitnessSize = witness_size(&[SIGNATURE_SIZE, COMPRESSED_PUBLIC_SIZE, secret.len(), 1, contract.len()]);
+        let redeemSize = estimate_vsize(&[InputType::P2WSH(redeemWitnessSize)], &redeemTx.outputs);
+        let fee = DEFAULT_FEE_RATE.fee(redeemSize);
+        if output.value <= fee {
+            return Err(ContractError::FeeExceedsValue(fee, output.value));
+        }
+        redeemTx.outputs[0].value = output.value - fee;
         let (redeemSig, redeemPubKey) = self.transaction_helper.create_signature_for_input(&redeemTx, 0, output.value, contract.clone().into(), &key);
         let redeemSigScript = redeemP2WSHContract(contract, redeemSig, redeemPubKey, secret);
         
         redeemTx.inputs[0].script_witness = redeemSigScript;
 
-        let redeemTxHash = redeemTx.hash();
         //redeemFeePerKb := calcFeePerKb(fee, redeemTx.SerializeSize()) //TODO
 
-        println!("Redeem transaction {}:", &redeemTxHash);
-        println!("Size {} bytes", serialize(&redeemTx).len());
-
         //TODO if verify flag was specified let script run and check that everything is ok
         // if verify {
         //     e, err := txscript.NewEngine(cmd.contractTx.TxOut[contractOutPoint.Index].PkScript,
@@ -338,53 +488,42 @@ impl AtomicSwapper {
         //     }
         // }
 
-        let message_wrapper = self.message_wrapper.to_owned();
-        let task = self.acceptor.async_accept_transaction(redeemTx.clone())
-            .map(move |transaction| message_wrapper.broadcast(&Tx::with_transaction(transaction)));
-        
-        let _ = self.cpupool.spawn(task);
+        self.publish(redeemTx.clone())?;
+        self.finish_swap(&pushes.SecretHash, SwapState::Redeemed);
+        Ok(ContractSpend {
+            transaction: redeemTx,
+            fee,
+        })
     }
     
-    fn audit_contract(&self, contract: Bytes, raw_contract_transaction: Bytes) {
+    pub fn audit_contract(&self, contract: Bytes, raw_contract_transaction: Bytes) -> Result<AuditedContract, ContractError> {
         let contractHash256 = sha256(&contract);
 
-		let raw_transaction_data: Vec<u8> = raw_contract_transaction.into();
-		let transaction: Transaction = match deserialize(Reader::new(&raw_transaction_data)) {
-            Ok(transaction) => transaction,
-            Err(err) => {
-                error!("Cannot deserialize transaction: {:?}", err);
-                return;
-            }
-        };
+        let transaction = deserialize_transaction(raw_contract_transaction.clone())?;
+        let (_, output) = find_contract_output(&transaction, &contractHash256)
+            .ok_or(ContractError::NoContractOutput)?;
 
-        let output = transaction.outputs.iter()
-            .find(|output| {
-                if output.script_pubkey.len() == 34 {       //TODO use script address instead of script hash here
-                    let script_unlocking_hash = &output.script_pubkey[2..];
-                    return script_unlocking_hash == &contractHash256[..];
-                }
-                false
-            });
-
-        let output = match output {
-            Some(output) => output,
-            None => {
-                error!("Transaction does not contain the contract output");
-                return;
-            }
-        };
-
-        let pushes =  match extractAtomicSwapDataPushes(0, contract) {
-            Ok(pushes) => pushes,
-            Err(err) => {
-                error!("Cannot parse contract. Reason {:?}", err);
-                return;
-            }
-        };
+        let pushes = extractAtomicSwapDataPushes(0, contract.clone())?;
 
         if pushes.SecretSize as usize != SECRET_SIZE {
-            error!("Contract specifies strange secret size {}", pushes.SecretSize);
-            return;
+            return Err(ContractError::UnexpectedSecretSize(pushes.SecretSize));
+        }
+
+        // remember contracts paying to us, so they are redeemed as soon as the secret is revealed
+        if self.wallet.read().find_keypair_with_public_hash(&pushes.RecipientHash160).is_some() {
+            let mut journal = self.journal.lock();
+            let recorded = journal.swaps().iter()
+                .position(|swap| swap.state.is_pending() && swap.secret_hash == pushes.SecretHash && swap.counterparty_contract.is_empty());
+            let mut counterparty_contracts = self.counterparty_contracts.lock();
+            if let Some(index) = recorded {
+                journal.set_counterparty_contract(index, contract, raw_contract_transaction);
+            } else if !counterparty_contracts.iter().any(|counterparty| counterparty.contract == contract) {
+                counterparty_contracts.push(CounterpartyContract {
+                    contract,
+                    contract_transaction: raw_contract_transaction,
+                    secret_hash: pushes.SecretHash.clone(),
+                });
+            }
         }
 
         let network = Network::Mainnet; //TODO check for network correctness
@@ -406,57 +545,39 @@ impl AtomicSwapper {
             kind: AddressType::P2PKH,
         };
 
-        println!("Contract address:        {}", contractHash256);
-        println!("Contract value:          {}", output.value);
-        println!("Recipient address:       {}", recipientAddr);
-        println!("Author's refund address: {}\n", refundAddr);
-
-        println!("Secret hash: {}\n", pushes.SecretHash);
-
-        if pushes.LockTime >= LOCKTIME_THRESHOLD as i64 {
-            let current_time = SystemTime::now();
-            let locktime = Duration::from_secs(pushes.LockTime as u64);
-            let time_since_the_epoch = current_time
-                .duration_since(UNIX_EPOCH)
-                .expect("System time went backwards");
-            println!("Locktime: {}", pushes.LockTime);
-            let reachedAt: i128 = locktime.as_secs() as i128 - time_since_the_epoch.as_secs() as i128;
-            if reachedAt > 0 {
-                println!("Locktime reached in {} seconds", reachedAt);
-            } else {
-                println!("Contract refund time lock has expired");
-            }
-        } else {
-            println!("Locktime: block {}", pushes.LockTime);
-        }
+        Ok(AuditedContract {
+            contract_p2wsh: contractHash256,
+            value: output.value,
+            recipient: recipientAddr,
+            refund: refundAddr,
+            secret_hash: pushes.SecretHash,
+            locktime: pushes.LockTime as u32,
+        })
     }
 
     fn buildContract(&self, args: ContractArgs) -> Result<BuiltContract, ContractError> {
+        self.ensure_wallet_ready()?;
         let refund_address_hash = self.wallet.write().new_keypair().hash;
 
         let contract = atomicSwapContract(refund_address_hash, args.them,
-            args.locktime, args.secret_hash);
+            args.locktime, args.secret_hash.clone());
 
         let contract = contract.to_bytes();
 
         let contractP2WSH = sha256(&contract[..]);
         let contractP2SHPkScript = ScriptBuilder::build_p2wsh(&contractP2WSH);
 
-        //TODO fee calculation
-        let (feePerKb, minFeePerKb) = (0,0);
-
         let transaction: Transaction = TransactionBuilder::with_output_and_pubkey(args.amount, contractP2SHPkScript.to_bytes()).into();
 
-        let funded_transaction = self.transaction_helper.fund_transaction(transaction)?;
+        // largest coins first keep contract transaction small
+        let funded_transaction = self.transaction_helper.fund_transaction(transaction, DEFAULT_FEE_RATE, Fallback::LargestFirst)?;
+        let contractFee = self.transaction_helper.transaction_fee(&funded_transaction).unwrap_or_default();
         let contractTx = self.transaction_helper.sign_transaction(funded_transaction)?;
 
-        let contractFee = 0u64;
-
-        // let task = self.wallet_manager.spawn(fund_and_sign);
-        //TODO build a refund transaction
-        //refundTx, refundFee, err := buildRefund(c, contract, contractTx, feePerKb, minFeePerKb)
-        let refundTx = TransactionBuilder::default().into();
-        let refundFee = 0u64;
+        // refund is signed right away, so funds can be recovered even if counterparty disappears
+        let refund = self.buildRefund(&contract, &contractTx)?;
+        let refundTx = refund.transaction;
+        let refundFee = refund.fee;
 
         let contractTxHash = contractTx.hash();
         Ok(BuiltContract {
@@ -467,8 +588,184 @@ impl AtomicSwapper {
             contractFee,
             refundTx,
             refundFee,
+            secretHash: args.secret_hash,
         })
     }
+
+    /// Returns contract value back to wallet once contract locktime is reached
+    pub fn refund(&self, contract: Bytes, raw_contract_transaction: Bytes) -> Result<ContractSpend, ContractError> {
+        self.ensure_wallet_ready()?;
+        let pushes = extractAtomicSwapDataPushes(0, contract.clone())?;
+        match locktime_reached_in(pushes.LockTime as u32) {
+            Some(reachedAt) if reachedAt > 0 => return Err(ContractError::LocktimeNotReached(reachedAt)),
+            _ => (),
+        }
+
+        let transaction = deserialize_transaction(raw_contract_transaction)?;
+        let refund = self.buildRefund(&contract, &transaction)?;
+        self.publish(refund.transaction.clone())?;
+        self.finish_swap(&pushes.SecretHash, SwapState::Refunded);
+        Ok(refund)
+    }
+
+    /// Marks unfinished swap with given secret hash as finished
+    fn finish_swap(&self, secret_hash: &H256, state: SwapState) {
+        let mut journal = self.journal.lock();
+        let found = journal.swaps().iter()
+            .position(|swap| swap.state.is_pending() && swap.secret_hash == *secret_hash);
+        if let Some(index) = found {
+            journal.set_state(index, state);
+        }
+    }
+
+    fn buildRefund(&self, contract: &Bytes, contractTx: &Transaction) -> Result<ContractSpend, ContractError> {
+        let contractHash256 = sha256(contract);
+        let pushes = extractAtomicSwapDataPushes(0, contract.clone())?;
+        let (output_index, output) = find_contract_output(contractTx, &contractHash256)
+            .ok_or(ContractError::NoContractOutput)?;
+
+        if self.wallet.read().find_keypair_with_public_hash(&pushes.RefundHash160).is_none() {
+            return Err(ContractError::NotAuthor);
+        }
+        let refundAddr = self.wallet.write().new_change_keypair();
+        let wallet = self.wallet.read();
+        let key = wallet.find_keypair_with_public_hash(&pushes.RefundHash160).expect("checked above");
+
+        let outScript = ScriptBuilder::build_p2wpkh(&refundAddr.hash);
+
+        let mut refundTx: Transaction = TransactionBuilder::with_output_and_pubkey(0, outScript.to_bytes())
+            .set_input(contractTx, output_index as u32)
+            .set_lock_time(pushes.LockTime as u32)
+            .into();
+        // lock time is ignored when all inputs are final, and contract checks it
+        refundTx.inputs[0].sequence = SEQUENCE_FINAL - 1;
+
+        let refundWitnessSize = witness_size(&[SIGNATURE_SIZE, COMPRESSED_PUBLIC_SIZE, 0, contract.len()]);
+        let refundSize = estimate_vsize(&[InputType::P2WSH(refundWitnessSize)], &refundTx.outputs);
+        let fee = DEFAULT_FEE_RATE.fee(refundSize);
+        if output.value <= fee {
+            return Err(ContractError::FeeExceedsValue(fee, output.value));
+        }
+        refundTx.outputs[0].value = output.value - fee;
+        let (refundSig, refundPubKey) = self.transaction_helper.create_signature_for_input(&refundTx, 0, output.value, contract.clone().into(), &key);
+        refundTx.inputs[0].script_witness = refundP2WSHContract(contract.clone(), refundSig, refundPubKey);
+
+        Ok(ContractSpend {
+            transaction: refundTx,
+            fee,
+        })
+    }
+
+    fn ensure_wallet_ready(&self) -> Result<(), ContractError> {
+        if self.wallet.read().is_ready() {
+            Ok(())
+        } else {
+            Err(ContractError::WalletNotReady)
+        }
+    }
+
+    /// Adds transaction to mempool and announces it to peers
+    fn publish(&self, transaction: Transaction) -> Result<(), ContractError> {
+        self.acceptor.accept_transaction(transaction).wait()?;
+        Ok(())
+    }
+}
+
+fn current_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+/// Seconds left until time based locktime is reached, negative once it is. None if locktime is block height
+fn locktime_reached_in(locktime: u32) -> Option<i64> {
+    if locktime >= LOCKTIME_THRESHOLD {
+        Some(locktime as i64 - current_time() as i64)
+    } else {
+        None
+    }
+}
+
+/// Secret is the witness item hashing to secret hash
+fn find_secret(input: &TransactionInput, secret_hash: &H256) -> Option<Bytes> {
+    input.script_witness.iter()
+        .find(|item| sha256(item) == *secret_hash)
+        .cloned()
+}
+
+fn deserialize_transaction(raw_transaction: Bytes) -> Result<Transaction, ReaderError> {
+    let raw_transaction_data: Vec<u8> = raw_transaction.into();
+    deserialize(Reader::new(&raw_transaction_data))
+}
+
+/// Finds P2WSH output paying to contract with given hash
+fn find_contract_output<'a>(transaction: &'a Transaction, contract_hash: &H256) -> Option<(usize, &'a TransactionOutput)> {
+    transaction.outputs.iter()
+        .enumerate()
+        .find(|&(_, output)| {
+            if output.script_pubkey.len() == 34 {       //TODO use script address instead of script hash here
+                let script_unlocking_hash = &output.script_pubkey[2..];
+                return script_unlocking_hash == &contract_hash[..];
+            }
+            false
+        })
+}
+
+fn print_contract(contract: &BuiltContract) {
+    println!("Contract fee: {} ({} bytes)", contract.contractFee, serialize(&contract.contractTx).len());
+    println!("Refund fee:   {} ({} bytes)\n", contract.refundFee, serialize(&contract.refundTx).len());
+    println!("Contract ({}):", contract.contractP2WSH);
+    println!("{:?}\n", contract.contract);
+
+    println!("Contract transaction ({}):", contract.contractTxHash);
+    println!("{:?}\n", serialize(&contract.contractTx));
+
+    println!("Refund transaction ({}):", contract.refundTx.hash());
+    println!("{:?}\n", serialize_with_flags(&contract.refundTx, SERIALIZE_TRANSACTION_WITNESS));
+}
+
+fn print_swaps(swaps: &[SwapRecord], wallet: &Wallet) {
+    if swaps.is_empty() {
+        println!("No atomic swaps");
+    }
+    for (index, swap) in swaps.iter().enumerate() {
+        println!("Swap {}: {}", index, swap.state);
+        println!("  Secret hash:       {}", swap.secret_hash);
+        if let Some(secret) = wallet.swap_secret(&swap.secret_hash) {
+            println!("  Secret:            {:?}", secret);
+        }
+        println!("  Contract output:   {}:{}", swap.contract_out_point.hash.reversed(), swap.contract_out_point.index);
+        println!("  Contract:          {:?}", swap.contract);
+        println!("  Locktime:          {}", swap.locktime);
+        if let Some(reached_in) = locktime_reached_in(swap.locktime) {
+            if reached_in > 0 {
+                println!("  Refundable in:     {} seconds", reached_in);
+            }
+        }
+        println!("  Counterparty:      {}", if swap.counterparty_contract.is_empty() { "not audited" } else { "audited" });
+    }
+}
+
+fn print_audit(audit: &AuditedContract) {
+    println!("Contract address:        {}", audit.contract_p2wsh);
+    println!("Contract value:          {}", audit.value);
+    println!("Recipient address:       {}", audit.recipient);
+    println!("Author's refund address: {}\n", audit.refund);
+
+    println!("Secret hash: {}\n", audit.secret_hash);
+
+    match audit.locktime_reached_in() {
+        Some(reachedAt) => {
+            println!("Locktime: {}", audit.locktime);
+            if reachedAt > 0 {
+                println!("Locktime reached in {} seconds", reachedAt);
+            } else {
+                println!("Contract refund time lock has expired");
+            }
+        }
+        None => println!("Locktime: block {}", audit.locktime),
+    }
 }
 
 // atomicSwapContract returns an output script that may be redeemed by one of
@@ -595,6 +892,7 @@ fn redeemP2WSHContract(contract: Bytes, sig: Bytes, pubkey: Bytes, secret: Bytes
 // refundP2SHContract returns the signature script to refund a contract output
 // using the contract author's signature after the locktime has been reached.
 // This function assumes P2WSH and appends the contract as the final data push.
+// Empty push selects refund branch, since witness scripts require minimal OP_IF argument.
 fn refundP2WSHContract(contract: Bytes, sig: Bytes, pubkey: Bytes) -> Vec<Bytes> {
-    vec![sig, pubkey, vec![0].into(), contract]
+    vec![sig, pubkey, Bytes::new(), contract]
 }
@@ -0,0 +1,439 @@
+//! Chooses which wallet outputs fund a transaction, paying fee for its estimated signed size
+
+use chain::{OutPoint, TransactionOutput};
+use keys::generator::Random;
+use script::{Builder, Script};
+use ser::serialize;
+use primitives::hash::H160;
+use transaction_helper::FundError;
+
+/// Fee rate used when user does not specify one
+pub const DEFAULT_FEE_RATE: FeeRate = FeeRate(1000);
+
+/// Change below this value costs more to spend than it is worth, so it is left to miners
+const DUST_THRESHOLD: u64 = 294;
+
+/// Branch and bound gives up after visiting this many nodes of the search tree
+const BNB_MAX_TRIES: usize = 100_000;
+
+const WITNESS_SCALE_FACTOR: usize = 4;
+/// Version and lock time
+const TRANSACTION_OVERHEAD_SIZE: usize = 8;
+/// Segwit marker and flag bytes, they are part of witness
+const SEGWIT_MARKER_WEIGHT: usize = 2;
+/// Previous output hash and index, script length and sequence
+const INPUT_BASE_SIZE: usize = 32 + 4 + 1 + 4;
+/// DER signature with sighash byte is at most 72 bytes
+pub const SIGNATURE_SIZE: usize = 72;
+pub const COMPRESSED_PUBLIC_SIZE: usize = 33;
+
+/// Fee rate in satoshis per 1000 virtual bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeRate(pub u64);
+
+impl FeeRate {
+    /// Fee for given virtual size rounded up
+    pub fn fee(&self, vsize: usize) -> u64 {
+        (self.0 * vsize as u64 + 999) / 1000
+    }
+}
+
+/// Type of spent output. It determines size of the input once signed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputType {
+    P2PKH,
+    P2WPKH,
+    /// Pay to witness script hash with serialized size of the witness stack
+    P2WSH(usize),
+}
+
+impl InputType {
+    /// Input types for scripts which can be estimated without knowing how they are unlocked
+    pub fn from_script(script: &Script) -> Option<InputType> {
+        if script.is_pay_to_public_key_hash() {
+            Some(InputType::P2PKH)
+        } else if script.is_pay_to_witness_key_hash() {
+            Some(InputType::P2WPKH)
+        } else {
+            None
+        }
+    }
+
+    /// Estimated weight of signed input, including its witness
+    pub fn weight(&self) -> usize {
+        match *self {
+            InputType::P2PKH => {
+                let script_sig_size = 1 + SIGNATURE_SIZE + 1 + COMPRESSED_PUBLIC_SIZE;
+                (INPUT_BASE_SIZE + script_sig_size) * WITNESS_SCALE_FACTOR
+            },
+            InputType::P2WPKH => INPUT_BASE_SIZE * WITNESS_SCALE_FACTOR + witness_size(&[SIGNATURE_SIZE, COMPRESSED_PUBLIC_SIZE]),
+            InputType::P2WSH(witness_size) => INPUT_BASE_SIZE * WITNESS_SCALE_FACTOR + witness_size,
+        }
+    }
+
+    pub fn is_witness(&self) -> bool {
+        *self != InputType::P2PKH
+    }
+}
+
+/// Serialized size of witness stack with items of given sizes
+pub fn witness_size(items: &[usize]) -> usize {
+    items.iter().fold(compact_size_len(items.len()), |size, item| size + compact_size_len(*item) + item)
+}
+
+fn compact_size_len(value: usize) -> usize {
+    match value {
+        0...0xfc => 1,
+        0xfd...0xffff => 3,
+        0x10000...0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+fn output_weight(output: &TransactionOutput) -> usize {
+    serialize(output).len() * WITNESS_SCALE_FACTOR
+}
+
+fn weight_to_vsize(weight: usize) -> usize {
+    (weight + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR
+}
+
+/// Estimates virtual size of transaction once all its inputs are signed
+pub fn estimate_vsize(inputs: &[InputType], outputs: &[TransactionOutput]) -> usize {
+    let mut weight = (TRANSACTION_OVERHEAD_SIZE + compact_size_len(inputs.len()) + compact_size_len(outputs.len())) * WITNESS_SCALE_FACTOR;
+    weight += outputs.iter().map(output_weight).sum::<usize>();
+    weight += inputs.iter().map(InputType::weight).sum::<usize>();
+    if inputs.iter().any(InputType::is_witness) {
+        // non witness inputs still need empty witness stack
+        let legacy_inputs = inputs.iter().filter(|input| !input.is_witness()).count();
+        weight += SEGWIT_MARKER_WEIGHT + legacy_inputs;
+    }
+    weight_to_vsize(weight)
+}
+
+/// Unspent output available for funding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coin {
+    pub out_point: OutPoint,
+    pub value: u64,
+    pub input_type: InputType,
+}
+
+/// Strategy used when there is no combination of coins avoiding change output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fallback {
+    /// Spends biggest coins first. Gives smallest transactions
+    LargestFirst,
+    /// Spends coins in random order. Does not reveal how big the wallet coins are
+    Random,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Selection {
+    pub coins: Vec<Coin>,
+    pub fee: u64,
+    /// Value returned to wallet. Zero means transaction has no change output
+    pub change: u64,
+}
+
+pub struct CoinSelector {
+    fee_rate: FeeRate,
+    fallback: Fallback,
+}
+
+impl CoinSelector {
+    pub fn new(fee_rate: FeeRate, fallback: Fallback) -> Self {
+        CoinSelector {
+            fee_rate,
+            fallback,
+        }
+    }
+
+    /// Picks coins paying for `outputs` and fee. Change is assumed to be sent to P2WPKH output
+    pub fn select(&self, coins: Vec<Coin>, outputs: &[TransactionOutput]) -> Result<Selection, FundError> {
+        if coins.is_empty() {
+            return Err(FundError::NoFunds);
+        }
+
+        let outputs_value = outputs.iter().fold(0, |sum, output| sum + output.value);
+        let change_output = TransactionOutput {
+            value: 0,
+            script_pubkey: Builder::build_p2wpkh(&H160::default()).to_bytes(),
+        };
+        let change_output_fee = self.fee_rate.fee(weight_to_vsize(output_weight(&change_output)));
+        // creating change is only worth it if it pays for itself and its future spending
+        let cost_of_change = change_output_fee + self.input_fee(InputType::P2WPKH);
+        let target = outputs_value + self.base_fee(outputs);
+
+        // coins which cost more to spend than they are worth are never selected
+        let mut candidates: Vec<(Coin, u64)> = coins.into_iter()
+            .filter_map(|coin| {
+                let input_fee = self.input_fee(coin.input_type);
+                if coin.value > input_fee {
+                    let effective_value = coin.value - input_fee;
+                    Some((coin, effective_value))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.1.cmp(&a.1));
+
+        let effective_values: Vec<u64> = candidates.iter().map(|&(_, value)| value).collect();
+        if let Some(selected) = branch_and_bound(&effective_values, target, cost_of_change) {
+            let coins = candidates.into_iter()
+                .zip(selected)
+                .filter(|&(_, selected)| selected)
+                .map(|((coin, _), _)| coin)
+                .collect();
+            return self.selection(coins, outputs, None);
+        }
+
+        if self.fallback == Fallback::Random {
+            shuffle(&mut candidates);
+        }
+
+        let mut selected = Vec::new();
+        let mut selected_value = 0;
+        for (coin, effective_value) in candidates {
+            selected.push(coin);
+            selected_value += effective_value;
+            if selected_value >= target + change_output_fee + DUST_THRESHOLD {
+                break;
+            }
+        }
+
+        if selected_value < target {
+            return Err(FundError::NotEnoughFunds);
+        }
+        self.selection(selected, outputs, Some(change_output))
+    }
+
+    /// Fee of transaction without inputs. Wallet spends only witness outputs, so segwit marker is counted in
+    fn base_fee(&self, outputs: &[TransactionOutput]) -> u64 {
+        self.fee_rate.fee(estimate_vsize(&[], outputs) + 1)
+    }
+
+    fn input_fee(&self, input_type: InputType) -> u64 {
+        self.fee_rate.fee(weight_to_vsize(input_type.weight()))
+    }
+
+    /// Fee summed up from the same rounded parts as target and effective values of coins,
+    /// so coins reaching the target always pay it
+    fn fee(&self, coins: &[Coin], outputs: &[TransactionOutput]) -> u64 {
+        coins.iter().fold(self.base_fee(outputs), |fee, coin| fee + self.input_fee(coin.input_type))
+    }
+
+    /// Calculates fee of selected coins and decides if change output is needed
+    fn selection(&self, coins: Vec<Coin>, outputs: &[TransactionOutput], change_output: Option<TransactionOutput>) -> Result<Selection, FundError> {
+        let inputs_value = coins.iter().fold(0, |sum, coin| sum + coin.value);
+        let outputs_value = outputs.iter().fold(0, |sum, output| sum + output.value);
+
+        if let Some(change_output) = change_output {
+            let mut outputs = outputs.to_vec();
+            outputs.push(change_output);
+            let fee = self.fee(&coins, &outputs);
+            match inputs_value.checked_sub(outputs_value + fee) {
+                Some(change) if change >= DUST_THRESHOLD => return Ok(Selection { coins, fee, change }),
+                _ => (),
+            }
+        }
+
+        // everything above outputs goes to miners
+        let fee = self.fee(&coins, outputs);
+        if inputs_value < outputs_value + fee {
+            return Err(FundError::NotEnoughFunds);
+        }
+        Ok(Selection { coins, fee: inputs_value - outputs_value, change: 0 })
+    }
+}
+
+/// Depth first search for coins which sum falls between target and target plus cost of change.
+/// Values must be sorted in descending order. Returns which coins are selected
+fn branch_and_bound(values: &[u64], target: u64, cost_of_change: u64) -> Option<Vec<bool>> {
+    let mut remaining: u64 = values.iter().sum();
+    if remaining < target {
+        return None;
+    }
+
+    let mut selected = vec![false; values.len()];
+    let mut best: Option<(Vec<bool>, u64)> = None;
+    let mut current = 0;
+    let mut depth = 0;
+
+    for _ in 0..BNB_MAX_TRIES {
+        let backtrack = if current + remaining < target || current > target + cost_of_change {
+            true
+        } else if current >= target {
+            let waste = current - target;
+            if best.as_ref().map_or(true, |&(_, best_waste)| waste < best_waste) {
+                best = Some((selected.clone(), waste));
+            }
+            true
+        } else {
+            false
+        };
+
+        if backtrack {
+            // undecide excluded coins until the last included one
+            while depth > 0 && !selected[depth - 1] {
+                depth -= 1;
+                remaining += values[depth];
+            }
+            if depth == 0 {
+                break;
+            }
+            // and try branch without it
+            selected[depth - 1] = false;
+            current -= values[depth - 1];
+        } else {
+            selected[depth] = true;
+            current += values[depth];
+            remaining -= values[depth];
+            depth += 1;
+        }
+    }
+
+    best.map(|(selected, _)| selected)
+}
+
+fn shuffle<T>(items: &mut Vec<T>) {
+    for i in (1..items.len()).rev() {
+        let mut bytes = [0u8; 4];
+        if Random::generate_bytes(&mut bytes).is_err() {
+            return;
+        }
+        let random = (bytes[0] as usize) << 24 | (bytes[1] as usize) << 16 | (bytes[2] as usize) << 8 | bytes[3] as usize;
+        items.swap(i, random % (i + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{OutPoint, TransactionOutput};
+    use primitives::hash::{H160, H256};
+    use script::Builder;
+    use transaction_helper::FundError;
+    use super::{estimate_vsize, Coin, CoinSelector, Fallback, FeeRate, InputType, Selection, DUST_THRESHOLD};
+
+    const RATE: FeeRate = FeeRate(1000);
+    /// Fee of one P2WPKH input at 1 satoshi per vbyte
+    const INPUT_FEE: u64 = 68;
+    /// Fee of transaction with one P2WPKH output and no inputs, segwit marker included
+    const BASE_FEE: u64 = 42;
+    const CHANGE_OUTPUT_FEE: u64 = 31;
+
+    fn coin(index: u8, value: u64) -> Coin {
+        Coin {
+            out_point: OutPoint {
+                hash: H256::from(index),
+                index: 0,
+            },
+            value,
+            input_type: InputType::P2WPKH,
+        }
+    }
+
+    fn output(value: u64) -> TransactionOutput {
+        TransactionOutput {
+            value,
+            script_pubkey: Builder::build_p2wpkh(&H160::default()).to_bytes(),
+        }
+    }
+
+    fn select(coins: Vec<Coin>, value: u64) -> Result<Selection, FundError> {
+        CoinSelector::new(RATE, Fallback::LargestFirst).select(coins, &[output(value)])
+    }
+
+    #[test]
+    fn fee_rate_rounds_up() {
+        assert_eq!(FeeRate(1000).fee(100), 100);
+        assert_eq!(FeeRate(1001).fee(100), 101);
+        assert_eq!(FeeRate(1).fee(1), 1);
+        assert_eq!(FeeRate(0).fee(100), 0);
+    }
+
+    #[test]
+    fn exact_match_needs_no_change() {
+        let exact = coin(1, 10_000 + BASE_FEE + INPUT_FEE);
+        let coins = vec![coin(0, 50_000), exact.clone(), coin(2, 3_000)];
+        assert_eq!(select(coins, 10_000), Ok(Selection {
+            coins: vec![exact],
+            fee: BASE_FEE + INPUT_FEE,
+            change: 0,
+        }));
+    }
+
+    #[test]
+    fn excess_cheaper_than_change_goes_to_fee() {
+        let almost_exact = coin(1, 10_000 + BASE_FEE + INPUT_FEE + 50);
+        let coins = vec![coin(0, 50_000), almost_exact.clone()];
+        assert_eq!(select(coins, 10_000), Ok(Selection {
+            coins: vec![almost_exact],
+            fee: BASE_FEE + INPUT_FEE + 50,
+            change: 0,
+        }));
+    }
+
+    #[test]
+    fn largest_coin_is_spent_with_change_when_nothing_matches() {
+        let largest = coin(0, 50_000);
+        let coins = vec![coin(1, 30_000), largest.clone()];
+        let fee = BASE_FEE + CHANGE_OUTPUT_FEE + INPUT_FEE;
+        assert_eq!(select(coins, 10_000), Ok(Selection {
+            coins: vec![largest],
+            fee,
+            change: 50_000 - 10_000 - fee,
+        }));
+    }
+
+    #[test]
+    fn random_fallback_funds_outputs() {
+        let coins = vec![coin(0, 50_000), coin(1, 30_000), coin(2, 20_000)];
+        let selection = CoinSelector::new(RATE, Fallback::Random).select(coins, &[output(10_000)]).unwrap();
+        let inputs_value: u64 = selection.coins.iter().map(|coin| coin.value).sum();
+        assert_eq!(inputs_value, 10_000 + selection.fee + selection.change);
+        assert!(selection.change >= DUST_THRESHOLD);
+    }
+
+    #[test]
+    fn dust_change_goes_to_fee() {
+        // too much for exact match, too little for change above dust
+        let coin = coin(0, 10_300);
+        assert_eq!(select(vec![coin.clone()], 10_000), Ok(Selection {
+            coins: vec![coin],
+            fee: 300,
+            change: 0,
+        }));
+    }
+
+    #[test]
+    fn insufficient_funds_are_reported() {
+        assert_eq!(select(vec![], 10_000), Err(FundError::NoFunds));
+        assert_eq!(select(vec![coin(0, 5_000), coin(1, 3_000)], 10_000), Err(FundError::NotEnoughFunds));
+        // coin which costs more to spend than it is worth is never used
+        assert_eq!(select(vec![coin(0, INPUT_FEE)], 0), Err(FundError::NotEnoughFunds));
+        // outputs alone fit, but not together with fee
+        assert_eq!(select(vec![coin(0, 10_000 + BASE_FEE)], 10_000), Err(FundError::NotEnoughFunds));
+    }
+
+    #[test]
+    fn fee_is_rounded_like_target() {
+        let outputs = vec![output(10_000)];
+        for &rate in &[1000, 1234, 2500, 3333, 7777] {
+            let fee_rate = FeeRate(rate);
+            let input_vsize = (InputType::P2WPKH.weight() + 3) / 4;
+            let fee = fee_rate.fee(estimate_vsize(&[], &outputs) + 1) + fee_rate.fee(input_vsize);
+            // coin paying target exactly is matched and its fee is what target has counted on
+            let exact = coin(0, 10_000 + fee);
+            let selection = CoinSelector::new(fee_rate, Fallback::LargestFirst).select(vec![exact.clone()], &outputs);
+            assert_eq!(selection, Ok(Selection {
+                coins: vec![exact],
+                fee,
+                change: 0,
+            }));
+            // rounding parts separately never pays less than rounding whole transaction
+            assert!(fee >= fee_rate.fee(estimate_vsize(&[InputType::P2WPKH], &outputs)));
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use clap;
+use std::net::SocketAddr;
 use params::{NetworkParams, ConsensusParams, ConsensusFork};
 use rpc_apis::ApiSet;
 use rpc::HttpConfiguration as RpcHttpConfig;
@@ -11,20 +12,37 @@ pub struct Config {
 	pub number: u16,
 	pub telnet_port: u16,
 	pub rpc_config: RpcHttpConfig,
+	pub transport: TransportConfig,
+	/// Seconds misbehaving peer stays banned
+	pub ban_time: u64,
+}
 
+#[derive(Clone)]
+pub enum TransportConfig {
+	/// MaidSafe routing network
+	Routing,
+	/// Direct connections listening on port and connecting to given nodes
+	Tcp { port: u16, nodes: Vec<SocketAddr> },
 }
 
 pub const DEFAULT_DB_CACHE: usize = 512;
 pub const DEFAULT_TELNET_PORT: u16 = 4070;
+pub const DEFAULT_BAN_TIME: u64 = 24 * 60 * 60;
 
 pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 
-	let network = match matches.is_present("testnet") {
-		true => NetworkParams::Testnet,
-		false => NetworkParams::Mainnet,
+	let network = if matches.is_present("testnet") {
+		NetworkParams::Testnet
+	} else if matches.is_present("regtest") {
+		NetworkParams::Regtest
+	} else {
+		NetworkParams::Mainnet
 	};
 
-	let consensus = ConsensusParams::new(network, ConsensusFork::NoFork);
+	let consensus = match matches.is_present("dev") {
+		true => ConsensusParams::dev(network, ConsensusFork::NoFork),
+		false => ConsensusParams::new(network, ConsensusFork::NoFork),
+	};
 
 	let number = matches
         .value_of("number")
@@ -39,13 +57,22 @@ pub fn parse(matches: &clap::ArgMatches) -> Result<Config, String> {
 	let mut rpc_config = parse_rpc_config(network, matches)?;
 	rpc_config.port += number;
 
+	let transport = parse_transport_config(network, number, matches)?;
+
+	let ban_time = match matches.value_of("bantime") {
+		Some(ban_time) => ban_time.parse().map_err(|_| "Invalid ban time".to_owned())?,
+		None => DEFAULT_BAN_TIME,
+	};
+
 	let config = Config {
 		is_first,
 		number,
 		network,
 		telnet_port,
 		consensus,
-		rpc_config
+		rpc_config,
+		transport,
+		ban_time,
 	};
 
 	Ok(config)
@@ -76,3 +103,23 @@ fn parse_rpc_config(network: NetworkParams, matches: &clap::ArgMatches) -> Resul
 
 	Ok(config)
 }
+
+fn parse_transport_config(network: NetworkParams, number: u16, matches: &clap::ArgMatches) -> Result<TransportConfig, String> {
+	match matches.value_of("transport").unwrap_or("routing") {
+		"routing" => Ok(TransportConfig::Routing),
+		"tcp" => {
+			let port = match matches.value_of("port") {
+				Some(port) => port.parse().map_err(|_| "Invalid port".to_owned())?,
+				None => network.port() + number,
+			};
+			let nodes = match matches.values_of("connect") {
+				Some(nodes) => nodes
+					.map(|node| node.parse().map_err(|_| format!("Invalid node address {}", node)))
+					.collect::<Result<Vec<_>, _>>()?,
+				None => Vec::new(),
+			};
+			Ok(TransportConfig::Tcp { port, nodes })
+		},
+		_ => Err("Invalid transport".to_owned()),
+	}
+}
@@ -2,9 +2,11 @@ use shrust::{Shell, ShellIO};
 use std::net::TcpListener;
 use std::sync::mpsc::Sender;
 use std::str::FromStr;
-use executor::Task as ExecutorTask;
-use keys::{Address, Private};
+use sync::executor::Task as ExecutorTask;
+use keys::{Address, Private, Mnemonic};
 use wallet_manager::Task as WalletTask;
+use transaction_helper::DEFAULT_MIN_CONFIRMATIONS;
+use coin_selection::{FeeRate, DEFAULT_FEE_RATE};
 use primitives::hash::H256;
 use primitives::bytes::Bytes;
 use atomic_swapper::Task as AtomicSwapperTask;
@@ -42,7 +44,7 @@ impl InputListener {
         let mut shell = Shell::new(senders);
         shell.new_command(
             "blocksign",
-            "Sign block with all known transactions",
+            "Mine single block with mempool transactions fitting into it",
             1,
             |_, senders, args| {
                 let ref executor = senders.0;
@@ -55,9 +57,34 @@ impl InputListener {
                 Ok(())
             },
         );
+        shell.new_command(
+            "mine",
+            "Keep mining blocks in background, paying rewards to provided address",
+            1,
+            |_, senders, args| {
+                let ref executor = senders.0;
+                match Address::from_str(args[0]) {
+                    Ok(coinbase_recipient) => {
+                        executor.send(ExecutorTask::StartMining(coinbase_recipient))?
+                    }
+                    Err(err) => error!("Can't parse address: {}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "minestop",
+            "Stop background mining",
+            0,
+            |_, senders, _| {
+                let ref executor = senders.0;
+                executor.send(ExecutorTask::StopMining())?;
+                Ok(())
+            },
+        );
         shell.new_command(
             "walletcreate",
-            "Create address and show private and public keys",
+            "Create address and show private and public keys. Shows recovery phrase when called first time",
             0,
             |_, senders, _| {
                 let ref wallet_manager = senders.1;
@@ -88,26 +115,82 @@ impl InputListener {
             },
         );
         shell.new_command(
-            "balance",
-            "Show balance of currently loaded wallet",
+            "walletrestore",
+            "Restore wallet from recovery phrase <word1> ... <word24>",
+            12,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                match Mnemonic::from_str(&args.join(" ")) {
+                    Ok(mnemonic) => {
+                        info!("Restoring wallet...");
+                        wallet_manager.send(WalletTask::RestoreWallet(mnemonic))?;
+                    }
+                    Err(err) => error!("Can't parse recovery phrase: {}", err),
+                }
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "walletunlock",
+            "Unlock wallet file with <passphrase>. Sets passphrase if there is no wallet file yet",
+            1,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                wallet_manager.send(WalletTask::Unlock(args[0].to_owned()))?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "walletlock",
+            "Remove wallet keys from memory until next unlock",
             0,
             |_, senders, _| {
                 let ref wallet_manager = senders.1;
-                let task = WalletTask::CalculateBalance();
+                wallet_manager.send(WalletTask::Lock())?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "balance",
+            "Show balance of all wallet keys. Usage: balance [min confirmations]",
+            0,
+            |_, senders, args| {
+                let ref wallet_manager = senders.1;
+                let min_confirmations = match args.get(0) {
+                    Some(arg) => match arg.parse::<u32>() {
+                        Ok(min_confirmations) => min_confirmations,
+                        Err(err) => {
+                            error!("Can't parse min confirmations: {}", err);
+                            return Ok(());
+                        }
+                    },
+                    None => DEFAULT_MIN_CONFIRMATIONS,
+                };
+                let task = WalletTask::CalculateBalance(min_confirmations);
                 wallet_manager.send(task)?;
                 Ok(())
             },
         );
         shell.new_command(
             "transfer",
-            "Transfer <address> <amount>",
+            "Transfer <address> <amount> [fee rate in satoshis per 1000 vbytes]",
             2,
             |_, senders, args| {
                 let ref wallet_manager = senders.1;
+                let fee_rate = match args.get(2) {
+                    Some(arg) => match arg.parse::<u64>() {
+                        Ok(fee_rate) => FeeRate(fee_rate),
+                        Err(err) => {
+                            error!("Can't parse fee rate: {}", err);
+                            return Ok(());
+                        }
+                    },
+                    None => DEFAULT_FEE_RATE,
+                };
                 match Address::from_str(args[0]) {
                     Ok(address) => match args[1].parse::<u64>() {
                         Ok(amount) => {
-                            let task = WalletTask::SendCash(address, amount);
+                            let task = WalletTask::SendCash(address, amount, fee_rate);
                             wallet_manager.send(task)?;
                         }
                         Err(err) => error!("Can't parse amount: {}", err),
@@ -221,6 +304,32 @@ impl InputListener {
                 Ok(())
             },
         );
+        shell.new_command(
+            "refund",
+            "Atomic swap refund <contract> <contract_raw_transaction> after contract locktime",
+            2,
+            |_, senders, args| {
+                let ref atomic_swapper = senders.2;
+                let contract: Bytes = Bytes::from_str(args[0])?;
+                let contract_raw_transaction = Bytes::from_str(args[1])?;
+                let task = AtomicSwapperTask::Refund(contract, contract_raw_transaction);
+                atomic_swapper.send(task)?;
+                Ok(())
+            },
+        );
+        shell.new_command(
+            "extractsecret",
+            "Atomic swap extractsecret <redeem_raw_transaction> <secret hash>",
+            2,
+            |_, senders, args| {
+                let ref atomic_swapper = senders.2;
+                let redeem_raw_transaction = Bytes::from_str(args[0])?;
+                let secret_hash = H256::from_str(args[1])?;
+                let task = AtomicSwapperTask::ExtractSecret(redeem_raw_transaction, secret_hash);
+                atomic_swapper.send(task)?;
+                Ok(())
+            },
+        );
         shell.new_command(
             "participate",
             "Atomic swap participate <contract> <contract_raw_transaction> <secret>",
@@ -235,6 +344,16 @@ impl InputListener {
                 Ok(())
             },
         );
+        shell.new_command(
+            "swaps",
+            "List atomic swaps funded by this node and their state",
+            0,
+            |_, senders, _| {
+                let ref atomic_swapper = senders.2;
+                atomic_swapper.send(AtomicSwapperTask::ListSwaps)?;
+                Ok(())
+            },
+        );
 
         shell
     }
@@ -26,12 +26,15 @@ extern crate chain_builder;
 
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serialization_derive;
 
 use clap::*;
 
 use memory_pool::MemoryPool;
-use params::NetworkParams;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 use std::process;
 use std::sync::Arc;
 use std::sync::mpsc;
@@ -39,7 +42,6 @@ use std::thread;
 
 mod config;
 mod db_utils;
-mod executor;
 mod input_listener;
 mod service;
 mod wallet;
@@ -48,17 +50,23 @@ mod rpc;
 mod rpc_apis;
 mod atomic_swapper;
 mod transaction_helper;
+mod coin_selection;
+mod wallet_rpc;
+mod atomic_swap_rpc;
+mod swap_journal;
 
-use executor::Executor;
-use executor::Task as ExecutorTask;
+use sync::executor::Task as ExecutorTask;
 use futures_cpupool::CpuPool;
+use config::TransportConfig;
 use input_listener::InputListener;
-use p2p::NetworkNode;
+use p2p::{NetworkNode, RoutingTransport, TcpTransport, Transport};
 use service::Service;
-use sync::{Acceptor, MessageHandler, MessageWrapper, Responder};
+use sync::{Acceptor, BanList, Executor, MessageHandler, MessageWrapper, Miner, Peers, Responder};
 use wallet::Wallet;
 use wallet_manager::WalletManager;
 use atomic_swapper::AtomicSwapper;
+use atomic_swapper::Task as AtomicSwapperTask;
+use swap_journal::SwapJournal;
 use transaction_helper::TransactionHelper;
 use memory_pool::UtxoAndOutputProvider;
 
@@ -88,6 +96,44 @@ fn main() {
                 .long("testnet")
                 .help("Use testnet rules where tokens have no real world value")
         )
+        .arg(
+            Arg::with_name("regtest")
+                .long("regtest")
+                .help("Use local development network where blocks are mined on CPU")
+                .conflicts_with("testnet")
+        )
+        .arg(
+            Arg::with_name("dev")
+                .long("dev")
+                .help("Accept blocks without proof of work, like on development network")
+        )
+        .arg(
+            Arg::with_name("transport")
+                .long("transport")
+                .help("Peer to peer transport: `routing` network or direct `tcp` connections")
+                .takes_value(true)
+                .possible_values(&["routing", "tcp"])
+        )
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .help("Port accepting tcp transport peers. Node number is added to default port")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("connect")
+                .short("c")
+                .long("connect")
+                .help("Address of node to connect to using tcp transport, e.g. 127.0.0.1:6470. Lost connection is restored")
+                .takes_value(true)
+                .multiple(true)
+        )
+        .arg(
+            Arg::with_name("bantime")
+                .long("bantime")
+                .help("Number of seconds misbehaving peers are banned for. Default is one day")
+                .takes_value(true)
+        )
         .get_matches();
 
     let config = config::parse(&matches).expect("Could not parse command line arguments");
@@ -95,8 +141,22 @@ fn main() {
     //setup database
     let db_path_string = "./db".to_owned() + matches.value_of("number").unwrap_or("") + "/";
     let default_db_cache = 512;
-    let storage = db_utils::open_db(db_path_string, default_db_cache);
-    db_utils::init_db(storage.clone(), NetworkParams::Mainnet).unwrap(); //init db with genesis block
+    let swap_journal_path = PathBuf::from(db_path_string.clone() + "swaps.dat");
+    let ban_list_path = PathBuf::from(db_path_string.clone() + "banlist.dat");
+    let storage = db_utils::open_db(db_path_string.clone(), default_db_cache);
+    //init db with genesis block
+    if let Err(err) = db_utils::init_db(storage.clone(), config.network) {
+        error!("{}. Database at {} belongs to another network or genesis block, remove it to start {:?} chain from scratch", err, db_path_string, config.network);
+        process::exit(1);
+    }
+    //databases created before address index existed are indexed once
+    if !storage.has_address_index() {
+        info!("Building address index of stored blocks");
+        if let Err(err) = storage.reindex_addresses() {
+            error!("Failed to build address index of database at {}: {:?}", db_path_string, err);
+            process::exit(1);
+        }
+    }
 
     //setup mempool
     let mempool_ref = Arc::new(RwLock::new(MemoryPool::new()));
@@ -110,47 +170,77 @@ fn main() {
     let (wallet_manager_sender, wallet_manager_receiver) = mpsc::channel();
     let (atomic_swapper_sender, atomic_swapper_receiver) = mpsc::channel();
 
-    let message_wrapper = MessageWrapper::new(config.network, to_network_sender.clone());
+    //setup table of connected peers
+    let ban_list = BanList::open(ban_list_path).expect("Failed to open ban list");
+    let peers = Arc::new(Peers::new(ban_list, config.ban_time));
+
+    let message_wrapper = MessageWrapper::new(config.network, peers.clone(), to_network_sender.clone());
 
     let cpupool = CpuPool::new_num_cpus();
 
     //setup network requests responder
     let responder = Responder {
         storage: storage.clone(),
+        mempool: mempool_ref.clone(),
         task_receiver: responder_task_receiver,
         message_wrapper: message_wrapper.clone(),
     };
 
-    let acceptor = Arc::new(Acceptor::new(
+    let mut acceptor = Acceptor::new(
         mempool_ref.clone(),
         storage.clone(),
-        config.network,
+        message_wrapper.clone(),
+        config.consensus.clone(),
         cpupool.clone(),
-    ));
+    );
+    // atomic swapper looks for secrets revealed by counterparties. Weak reference lets its thread finish on exit
+    let swap_watcher = Arc::new(Mutex::new(atomic_swapper_sender.clone()));
+    let weak_swap_watcher = Arc::downgrade(&swap_watcher);
+    acceptor.set_on_transaction_handler(move |transaction| {
+        if let Some(swap_watcher) = weak_swap_watcher.upgrade() {
+            let _ = swap_watcher.lock().send(AtomicSwapperTask::TransactionAccepted(transaction.clone()));
+        }
+    });
+    let acceptor = Arc::new(acceptor);
 
     //setup network messages handler
     let mut message_handler = MessageHandler::new(
         storage.clone(),
+        mempool_ref.clone(),
+        peers,
         from_network_receiver,
         responder_task_sender,
         acceptor.clone(),
         message_wrapper.clone(),
-        config.network,
+        config.consensus.clone(),
     );
 
     //setup p2p layer
+    let transport: Box<Transport> = match config.transport {
+        TransportConfig::Routing => Box::new(RoutingTransport::new(config.is_first)),
+        TransportConfig::Tcp { port, ref nodes } => {
+            let listen_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
+            Box::new(TcpTransport::new(config.network.magic(), listen_address, nodes).expect("Can't start tcp transport"))
+        }
+    };
     let mut network = NetworkNode::new(
-        config.is_first,
+        transport,
         from_network_sender,
         to_network_receiver,
         terminate_receiver,
     );
 
-    let wallet = Arc::new(RwLock::new(Wallet::new()));
+    let wallet_path = PathBuf::from("./wallet".to_owned() + matches.value_of("number").unwrap_or("") + ".dat");
+    let wallet = Wallet::new(wallet_path);
+    if wallet.file_exists() {
+        info!("Wallet file found. Use `walletunlock <passphrase>` to access your keys");
+    }
+    let wallet = Arc::new(RwLock::new(wallet));
 
     let utxo_provider = UtxoAndOutputProvider::new(storage.clone(), mempool_ref.clone());
     let transaction_helper = Arc::new(TransactionHelper::new(
         utxo_provider,
+        storage.clone(),
         wallet.clone(),
     ));
 
@@ -159,25 +249,30 @@ fn main() {
         mempool_ref.clone(),
         storage.clone(),
         wallet_manager_receiver,
-        message_wrapper.clone(),
+        acceptor.clone(),
         wallet.clone(),
         transaction_helper.clone(),
     );
-    let mut executor = Executor::new(
+    let miner = Arc::new(Miner::new(
+        storage.clone(),
         mempool_ref.clone(),
+        acceptor.clone(),
+        config.consensus.clone(),
+        cpupool,
+    ));
+    let mut executor = Executor::new(
         storage.clone(),
         executor_receiver,
         message_wrapper.clone(),
+        miner,
     );
 
-    let mut atomic_swapper = AtomicSwapper::new(
+    let atomic_swapper = Arc::new(AtomicSwapper::new(
         acceptor.clone(),
-        transaction_helper,
-        cpupool,
-        message_wrapper,
-        atomic_swapper_receiver,
-        wallet
-    );
+        transaction_helper.clone(),
+        wallet.clone(),
+        SwapJournal::open(swap_journal_path).expect("Failed to open atomic swap journal"),
+    ));
 
     //setup telnet listener
     let input_listener = InputListener::new(
@@ -191,7 +286,12 @@ fn main() {
     let rpc_deps = rpc::Dependencies {
 		network: config.network,
 		storage: storage,
+		mempool: mempool_ref,
+		consensus: config.consensus.clone(),
 		acceptor,
+		wallet,
+		transaction_helper,
+		atomic_swapper: atomic_swapper.clone(),
 	};
 	let _rpc_server = rpc::new_http(config.rpc_config, rpc_deps).expect("Can't launch json-rpc service");
 
@@ -201,7 +301,7 @@ fn main() {
     let executor_thread = thread::spawn(move || executor.run());
     let wallet_manager_thread = thread::spawn(move || wallet_manager.run());
     let message_handler_thread = thread::spawn(move || message_handler.run());
-    let atomic_swapper_thread = thread::spawn(move || atomic_swapper.run());
+    let atomic_swapper_thread = thread::spawn(move || atomic_swapper.run(atomic_swapper_receiver));
 
     //prepare to handle Ctrl-C
     ctrlc::set_handler(move || {
@@ -219,6 +319,7 @@ fn main() {
 
     network.run(); //main thread loop
     drop(network); //remove everything after network loop has finished
+    drop(swap_watcher);
 
     info!("Node is about to finish. If it doesn't it means one of the threads hangs and database won't save");
 
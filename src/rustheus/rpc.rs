@@ -2,15 +2,24 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use rpc_apis::{self, ApiSet};
 use ethcore_rpc::{Server, start_http, MetaIoHandler, Compatibility, Remote};
-use params::NetworkParams;
+use params::{ConsensusParams, NetworkParams};
 use std::io;
 use sync;
 use db::SharedStore;
+use memory_pool::MemoryPoolRef;
+use wallet::WalletRef;
+use transaction_helper::TransactionHelperRef;
+use atomic_swapper::AtomicSwapperRef;
 
 pub struct Dependencies {
 	pub network: NetworkParams,
 	pub acceptor: sync::AcceptorRef,
 	pub storage: SharedStore,
+	pub mempool: MemoryPoolRef,
+	pub consensus: ConsensusParams,
+	pub wallet: WalletRef,
+	pub transaction_helper: TransactionHelperRef,
+	pub atomic_swapper: AtomicSwapperRef,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -2,6 +2,8 @@ use std::str::FromStr;
 use std::collections::HashSet;
 use rpc::Dependencies;
 use ethcore_rpc::MetaIoHandler;
+use wallet_rpc::WalletClientCore;
+use atomic_swap_rpc::AtomicSwapClientCore;
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum Api {
@@ -13,6 +15,10 @@ pub enum Api {
 	BlockChain,
 	/// Network
 	Network,
+	/// Wallet of this node
+	Wallet,
+	/// Atomic swaps funded by wallet of this node
+	AtomicSwap,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -22,7 +28,7 @@ pub enum ApiSet {
 
 impl Default for ApiSet {
 	fn default() -> Self {
-		ApiSet::List(vec![Api::Raw, Api::Miner, Api::BlockChain, Api::Network].into_iter().collect())
+		ApiSet::List(vec![Api::Raw, Api::Miner, Api::BlockChain, Api::Network, Api::Wallet, Api::AtomicSwap].into_iter().collect())
 	}
 }
 
@@ -35,6 +41,8 @@ impl FromStr for Api {
 			"miner" => Ok(Api::Miner),
 			"blockchain" => Ok(Api::BlockChain),
 			"network" => Ok(Api::Network),
+			"wallet" => Ok(Api::Wallet),
+			"atomicswap" => Ok(Api::AtomicSwap),
 			api => Err(format!("Unknown api: {}", api)),
 		}
 	}
@@ -54,9 +62,11 @@ pub fn setup_rpc(mut handler: MetaIoHandler<()>, apis: ApiSet, deps: Dependencie
 	for api in apis.list_apis() {
 		match api {
 			Api::Raw => handler.extend_with(RawClient::new(RawClientCore::new(deps.acceptor.clone())).to_delegate()),
-			Api::Miner => handler.extend_with(MinerClient::new(MinerClientCore::new()).to_delegate()),
+			Api::Miner => handler.extend_with(MinerClient::new(MinerClientCore::new(deps.storage.clone(), deps.mempool.clone(), deps.acceptor.clone(), deps.consensus.clone())).to_delegate()),
 			Api::BlockChain => handler.extend_with(BlockChainClient::new(BlockChainClientCore::new(deps.network, deps.storage.clone())).to_delegate()),
 			Api::Network => handler.extend_with(NetworkClient::new(NetworkClientCore::new()).to_delegate()),
+			Api::Wallet => handler.extend_with(WalletClient::new(WalletClientCore::new(deps.wallet.clone(), deps.transaction_helper.clone(), deps.acceptor.clone())).to_delegate()),
+			Api::AtomicSwap => handler.extend_with(AtomicSwapClient::new(AtomicSwapClientCore::new(deps.atomic_swapper.clone())).to_delegate()),
 
 		}
 	}
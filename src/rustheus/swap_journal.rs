@@ -0,0 +1,274 @@
+//! Record of atomic swaps funded by this node, so they can be finished after restart
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use chain::OutPoint;
+use chain::bytes::Bytes;
+use sync::atomic_file;
+use primitives::hash::H256;
+use ser::{serialize_list, Deserializable, Error as ReaderError, Reader, Serializable, Stream};
+
+#[derive(Debug)]
+pub enum JournalError {
+    Io(io::Error),
+    Corrupted(ReaderError),
+}
+
+impl From<io::Error> for JournalError {
+    fn from(err: io::Error) -> JournalError {
+        JournalError::Io(err)
+    }
+}
+
+impl From<ReaderError> for JournalError {
+    fn from(err: ReaderError) -> JournalError {
+        JournalError::Corrupted(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwapState {
+    /// We locked funds first and know the secret
+    Initiated,
+    /// We locked funds redeemable with initiator's secret
+    Participated,
+    /// Secret was revealed and swap is finished
+    Redeemed,
+    /// Our funds returned after locktime
+    Refunded,
+    /// Locktime passed, but funds could not be refunded automatically
+    Expired,
+}
+
+impl SwapState {
+    /// Our contract may still be spent by counterparty or refunded
+    pub fn is_pending(&self) -> bool {
+        match *self {
+            SwapState::Initiated | SwapState::Participated | SwapState::Expired => true,
+            SwapState::Redeemed | SwapState::Refunded => false,
+        }
+    }
+
+    /// Swap waits for counterparty and is refunded automatically once locktime passes
+    pub fn is_active(&self) -> bool {
+        *self == SwapState::Initiated || *self == SwapState::Participated
+    }
+}
+
+impl fmt::Display for SwapState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            SwapState::Initiated => "initiated",
+            SwapState::Participated => "participated",
+            SwapState::Redeemed => "redeemed",
+            SwapState::Refunded => "refunded",
+            SwapState::Expired => "expired",
+        };
+        name.fmt(f)
+    }
+}
+
+impl Serializable for SwapState {
+    fn serialize(&self, stream: &mut Stream) {
+        stream.append(&(*self as u8));
+    }
+
+    fn serialized_size(&self) -> usize {
+        1
+    }
+}
+
+impl Deserializable for SwapState {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, ReaderError> where T: io::Read {
+        match reader.read::<u8>()? {
+            0 => Ok(SwapState::Initiated),
+            1 => Ok(SwapState::Participated),
+            2 => Ok(SwapState::Redeemed),
+            3 => Ok(SwapState::Refunded),
+            4 => Ok(SwapState::Expired),
+            _ => Err(ReaderError::MalformedData),
+        }
+    }
+}
+
+/// Swap in which this node funded a contract. Initiator's secret is kept in the wallet, not here
+#[derive(Debug, Clone, PartialEq, Serializable, Deserializable)]
+pub struct SwapRecord {
+    pub state: SwapState,
+    pub contract: Bytes,
+    pub contract_transaction: Bytes,
+    pub contract_out_point: OutPoint,
+    pub secret_hash: H256,
+    pub locktime: u32,
+    /// Audited contract paying to us. Empty until audited
+    pub counterparty_contract: Bytes,
+    pub counterparty_contract_transaction: Bytes,
+}
+
+pub struct SwapJournal {
+    path: PathBuf,
+    swaps: Vec<SwapRecord>,
+}
+
+impl SwapJournal {
+    /// Loads journal file, or starts empty journal if there is no file yet
+    pub fn open(path: PathBuf) -> Result<Self, JournalError> {
+        let swaps = match atomic_file::read(&path)? {
+            Some(data) => Reader::new(&data).read_list()?,
+            None => vec![],
+        };
+        Ok(SwapJournal { path, swaps })
+    }
+
+    pub fn swaps(&self) -> &[SwapRecord] {
+        &self.swaps
+    }
+
+    pub fn add(&mut self, swap: SwapRecord) {
+        self.swaps.push(swap);
+        self.persist();
+    }
+
+    pub fn set_state(&mut self, index: usize, state: SwapState) {
+        info!("Atomic swap {} is {}", index, state);
+        self.swaps[index].state = state;
+        self.persist();
+    }
+
+    /// Marks active swap, which could not be refunded after locktime, as expired. It stays pending, so refund is retried
+    pub fn set_expired(&mut self, index: usize) {
+        if self.swaps[index].state.is_active() {
+            self.set_state(index, SwapState::Expired);
+        }
+    }
+
+    pub fn set_counterparty_contract(&mut self, index: usize, contract: Bytes, contract_transaction: Bytes) {
+        self.swaps[index].counterparty_contract = contract;
+        self.swaps[index].counterparty_contract_transaction = contract_transaction;
+        self.persist();
+    }
+
+    fn persist(&self) {
+        let data = serialize_list::<SwapRecord, SwapRecord>(&self.swaps);
+        atomic_file::persist(&self.path, &data, "atomic swap journal");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use self::tempdir::TempDir;
+    use std::fs::File;
+    use std::io::Write;
+    use chain::OutPoint;
+    use primitives::hash::H256;
+    use ser::{deserialize, serialize, Error as ReaderError};
+    use super::{JournalError, SwapJournal, SwapRecord, SwapState};
+
+    fn swap_record() -> SwapRecord {
+        SwapRecord {
+            state: SwapState::Initiated,
+            contract: "6382012088a820".into(),
+            contract_transaction: "0100000001".into(),
+            contract_out_point: OutPoint {
+                hash: H256::from(1),
+                index: 1,
+            },
+            secret_hash: H256::from(2),
+            locktime: 1_500_000_000,
+            counterparty_contract: "".into(),
+            counterparty_contract_transaction: "".into(),
+        }
+    }
+
+    #[test]
+    fn swap_state_round_trip() {
+        for state in &[SwapState::Initiated, SwapState::Participated, SwapState::Redeemed, SwapState::Refunded, SwapState::Expired] {
+            let deserialized: SwapState = deserialize(&serialize(state) as &[u8]).unwrap();
+            assert_eq!(deserialized, *state);
+        }
+    }
+
+    #[test]
+    fn unknown_swap_state_is_rejected() {
+        let result: Result<SwapState, _> = deserialize(&[5u8] as &[u8]);
+        assert_eq!(result.unwrap_err(), ReaderError::MalformedData);
+    }
+
+    #[test]
+    fn swap_record_round_trip() {
+        let mut record = swap_record();
+        record.counterparty_contract = "63a8".into();
+        record.counterparty_contract_transaction = "0200000001".into();
+
+        let deserialized: SwapRecord = deserialize(&serialize(&record) as &[u8]).unwrap();
+        assert_eq!(deserialized, record);
+    }
+
+    #[test]
+    fn journal_is_empty_without_file() {
+        let dir = TempDir::new("swap_journal").unwrap();
+        let journal = SwapJournal::open(dir.path().join("swaps.dat")).unwrap();
+        assert!(journal.swaps().is_empty());
+    }
+
+    #[test]
+    fn journal_changes_survive_reopening() {
+        let dir = TempDir::new("swap_journal").unwrap();
+        let path = dir.path().join("swaps.dat");
+
+        let mut journal = SwapJournal::open(path.clone()).unwrap();
+        journal.add(swap_record());
+        journal.add(swap_record());
+        journal.set_state(0, SwapState::Redeemed);
+        journal.set_counterparty_contract(1, "63a8".into(), "0200000001".into());
+
+        let reopened = SwapJournal::open(path).unwrap();
+        assert_eq!(reopened.swaps(), journal.swaps());
+        assert_eq!(reopened.swaps()[0].state, SwapState::Redeemed);
+        assert_eq!(reopened.swaps()[1].counterparty_contract, "63a8".into());
+    }
+
+    #[test]
+    fn swap_which_failed_to_refund_expires_and_stays_pending() {
+        let dir = TempDir::new("swap_journal").unwrap();
+        let path = dir.path().join("swaps.dat");
+        let mut journal = SwapJournal::open(path.clone()).unwrap();
+        journal.add(swap_record());
+
+        journal.set_expired(0);
+        assert_eq!(journal.swaps()[0].state, SwapState::Expired);
+        assert!(journal.swaps()[0].state.is_pending());
+        assert!(!journal.swaps()[0].state.is_active());
+        assert_eq!(SwapJournal::open(path).unwrap().swaps()[0].state, SwapState::Expired);
+
+        // retried refund finishes expired swap
+        journal.set_state(0, SwapState::Refunded);
+        assert!(!journal.swaps()[0].state.is_pending());
+    }
+
+    #[test]
+    fn finished_swap_does_not_expire() {
+        let dir = TempDir::new("swap_journal").unwrap();
+        let mut journal = SwapJournal::open(dir.path().join("swaps.dat")).unwrap();
+        journal.add(swap_record());
+        journal.set_state(0, SwapState::Redeemed);
+
+        journal.set_expired(0);
+        assert_eq!(journal.swaps()[0].state, SwapState::Redeemed);
+    }
+
+    #[test]
+    fn corrupted_journal_is_reported() {
+        let dir = TempDir::new("swap_journal").unwrap();
+        let path = dir.path().join("swaps.dat");
+        File::create(&path).unwrap().write_all(&[1, 9]).unwrap();
+
+        match SwapJournal::open(path) {
+            Err(JournalError::Corrupted(_)) => (),
+            other => panic!("expected corrupted journal, got {:?}", other.map(|journal| journal.swaps().len())),
+        }
+    }
+}
@@ -1,17 +1,24 @@
 use chain::constants::SEQUENCE_LOCKTIME_DISABLE_FLAG;
 use chain::{OutPoint, Transaction};
-use db::{TransactionUtxoProvider, TransactionOutputProvider};
-use keys::{Private, KeyPair};
+use db::{SharedStore, TransactionProvider, TransactionUtxoProvider, TransactionOutputProvider, TransactionMetaProvider,
+    TransactionHistoryProvider};
+use keys::{Address, Private, KeyPair};
 use script::{Builder, Script, SighashBase, SignatureVersion, TransactionInputSigner};
 use wallet::WalletRef;
 use chain::{TransactionInput, TransactionOutput};
 use std::sync::Arc;
 use memory_pool::UtxoAndOutputProvider;
 use primitives::bytes::Bytes;
+use primitives::hash::{H160, H256};
+use verification::constants::COINBASE_MATURITY;
+use coin_selection::{Coin, CoinSelector, Fallback, FeeRate, InputType};
 
 pub type TransactionHelperRef = Arc<TransactionHelper>;
 
-#[derive(Debug)]
+/// Confirmations needed for output to count as confirmed balance when not specified
+pub const DEFAULT_MIN_CONFIRMATIONS: u32 = 1;
+
+#[derive(Debug, PartialEq)]
 pub enum FundError {
     NoFunds,
     NotEnoughFunds,
@@ -26,6 +33,49 @@ pub enum SignError {
     FundError(FundError),
 }
 
+/// Wallet funds in satoshis, split by how safe they are to spend
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Balance {
+    /// Outputs with at least the requested number of confirmations
+    pub confirmed: u64,
+    /// Outputs in mempool or with fewer confirmations than requested
+    pub unconfirmed: u64,
+    /// Coinbase outputs which are not spendable until `COINBASE_MATURITY` blocks are mined on top
+    pub immature: u64,
+}
+
+impl Balance {
+    pub fn total(&self) -> u64 {
+        self.confirmed + self.unconfirmed + self.immature
+    }
+}
+
+/// Unspent output paying to one of wallet keys
+#[derive(Debug, Clone)]
+pub struct WalletOutput {
+    pub out_point: OutPoint,
+    pub address: Address,
+    pub output: TransactionOutput,
+    /// Zero for mempool outputs
+    pub confirmations: u32,
+    pub is_coinbase: bool,
+}
+
+impl WalletOutput {
+    pub fn is_mature(&self) -> bool {
+        !self.is_coinbase || self.confirmations >= COINBASE_MATURITY
+    }
+}
+
+/// Transaction affecting wallet funds
+#[derive(Debug, Clone)]
+pub struct WalletTransaction {
+    pub hash: H256,
+    /// Satoshis received by wallet minus satoshis spent from it
+    pub amount: i64,
+    pub confirmations: u32,
+}
+
 impl From<FundError> for SignError {
     fn from(err: FundError) -> SignError {
         SignError::FundError(err)
@@ -34,85 +84,208 @@ impl From<FundError> for SignError {
 
 pub struct TransactionHelper {
     utxo_provider: UtxoAndOutputProvider,
+    storage: SharedStore,
     wallet: WalletRef,
 }
 
 impl TransactionHelper {
     pub fn new(
         utxo_provider: UtxoAndOutputProvider,
+        storage: SharedStore,
         wallet: WalletRef,
     ) -> Self {
         TransactionHelper {
             utxo_provider,
+            storage,
             wallet,
         }
     }
 
-    //TODO seek for spent outputs in mempool
-    fn get_unspent_out_points(&self) -> Vec<OutPoint> {
-        self.wallet
-            .read()
-            .keys
-            .iter()
-            .flat_map(|keypair| 
+    /// Addresses of all wallet keys. Imported key may duplicate a derived one, so each address is listed once
+    fn addresses(&self) -> Vec<Address> {
+        let mut addresses: Vec<Address> = Vec::new();
+        for keypair in &self.wallet.read().keys {
+            let address = keypair.address();
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        addresses
+    }
+
+    /// Outputs paying to wallet keys, which are spent neither in store nor in mempool
+    pub fn unspent_outputs(&self) -> Vec<WalletOutput> {
+        let best_height = self.storage.best_block().number;
+        self.addresses()
+            .into_iter()
+            .flat_map(|address| {
                 self.utxo_provider
-                    .transaction_with_output_address(&keypair.address().hash))
+                    .transaction_with_output_address(&address.hash)
+                    .into_iter()
+                    .filter_map(move |out_point| {
+                        let output = self.utxo_provider.transaction_output(&out_point, 0)?;
+                        // not in store, so it comes from mempool
+                        let (confirmations, is_coinbase) = match self.storage.transaction_meta(&out_point.hash) {
+                            Some(meta) => (best_height.saturating_sub(meta.height()) + 1, meta.is_coinbase()),
+                            None => (0, false),
+                        };
+                        Some(WalletOutput {
+                            out_point,
+                            address: address.clone(),
+                            output,
+                            confirmations,
+                            is_coinbase,
+                        })
+                    })
+            })
             .collect()
     }
 
-    //TODO accept fee
-    pub fn fund_transaction(&self, transaction: Transaction) -> Result<Transaction, FundError> {
-        let unspent_out_points = self.get_unspent_out_points();
-        if unspent_out_points.is_empty() {
-            return Err(FundError::NoFunds);
+    /// Sums unspent outputs of every wallet key, including change ones.
+    /// Outputs spent by mempool transactions are not counted
+    pub fn balance(&self, min_confirmations: u32) -> Balance {
+        let mut balance = Balance::default();
+        for unspent in self.unspent_outputs() {
+            let value = unspent.output.value;
+            if !unspent.is_mature() {
+                balance.immature += value;
+            } else if unspent.confirmations >= min_confirmations {
+                balance.confirmed += value;
+            } else {
+                balance.unconfirmed += value;
+            }
         }
+        balance
+    }
 
-        let needed_amount = transaction
-            .outputs
-            .iter()
-            .fold(0, |acc, output| acc + output.value);
+    /// Canon chain transactions paying to or spending from wallet keys, oldest first
+    pub fn transactions(&self) -> Vec<WalletTransaction> {
+        let address_hashes: Vec<H160> = self.addresses().into_iter().map(|address| address.hash).collect();
+        let is_wallet_output = |output: &TransactionOutput| {
+            output_key_hash(&output.script_pubkey).map_or(false, |hash| address_hashes.contains(&hash))
+        };
 
-        let mut inputs: Vec<TransactionInput> = vec![];
+        let mut hashes: Vec<H256> = Vec::new();
+        for address_hash in &address_hashes {
+            for hash in self.storage.transactions_with_address(address_hash) {
+                if !hashes.contains(&hash) {
+                    hashes.push(hash);
+                }
+            }
+        }
+
+        let best_height = self.storage.best_block().number;
+        let mut transactions: Vec<(u32, WalletTransaction)> = hashes
+            .into_iter()
+            .filter_map(|hash| {
+                let meta = self.storage.transaction_meta(&hash)?;
+                let transaction = self.storage.transaction(&hash)?;
+                let received = transaction.outputs
+                    .iter()
+                    .filter(|output| is_wallet_output(output))
+                    .fold(0, |sum, output| sum + output.value);
+                let spent = if meta.is_coinbase() {
+                    0
+                } else {
+                    transaction.inputs
+                        .iter()
+                        .filter_map(|input| self.storage.transaction(&input.previous_output.hash)
+                            .and_then(|previous| previous.outputs.into_iter().nth(input.previous_output.index as usize)))
+                        .filter(|output| is_wallet_output(output))
+                        .fold(0, |sum, output| sum + output.value)
+                };
+                Some((meta.height(), WalletTransaction {
+                    hash,
+                    amount: received as i64 - spent as i64,
+                    confirmations: best_height.saturating_sub(meta.height()) + 1,
+                }))
+            })
+            .collect();
+        transactions.sort_by_key(|&(height, _)| height);
+        transactions.into_iter().map(|(_, transaction)| transaction).collect()
+    }
 
-        let mut inputs_sum = 0;
-        for out_point in unspent_out_points {
-            let output = self.utxo_provider.transaction_output(&out_point, 0).unwrap();
-            let input = TransactionInput {
-                previous_output: out_point,
+    /// Unspent outputs which wallet is able to sign. Immature coinbase outputs are skipped
+    fn spendable_coins(&self) -> Vec<Coin> {
+        self.unspent_outputs()
+            .into_iter()
+            .filter(WalletOutput::is_mature)
+            .filter_map(|unspent| {
+                match InputType::from_script(&unspent.output.script_pubkey.into()) {
+                    // key hash and witness key hash inputs are signed by `sign_input`
+                    Some(input_type @ InputType::P2PKH) | Some(input_type @ InputType::P2WPKH) => Some(Coin {
+                        out_point: unspent.out_point,
+                        value: unspent.output.value,
+                        input_type,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Adds inputs paying for outputs and fee at given rate, and change output if it is worth creating.
+    /// Version and lock time of the transaction are kept
+    pub fn fund_transaction(&self, transaction: Transaction, fee_rate: FeeRate, fallback: Fallback) -> Result<Transaction, FundError> {
+        let selector = CoinSelector::new(fee_rate, fallback);
+        let selection = selector.select(self.spendable_coins(), &transaction.outputs)?;
+
+        let inputs = selection.coins
+            .into_iter()
+            .map(|coin| TransactionInput {
+                previous_output: coin.out_point,
                 script_sig: Default::default(),
                 sequence: SEQUENCE_LOCKTIME_DISABLE_FLAG, //TODO remove this for atomic swap probably
                 script_witness: vec![],
-            };
-            inputs.push(input);
-
-            inputs_sum += output.value;
-            if inputs_sum >= needed_amount {
-                break;
-            }
-        }
-
-        let mut outputs = transaction.outputs.clone();
+            })
+            .collect();
 
+        let mut outputs = transaction.outputs;
         //TODO create option to return leftovers to the same address
-        if inputs_sum > needed_amount {
-            let new_address = self.wallet.write().new_keypair();
-            let leftover = TransactionOutput {
-                value: inputs_sum - needed_amount,
+        if selection.change != 0 {
+            let new_address = self.wallet.write().new_change_keypair();
+            outputs.push(TransactionOutput {
+                value: selection.change,
                 script_pubkey: Builder::build_p2wpkh(&new_address.hash).to_bytes(),
-            };
-            outputs.push(leftover);
-        } else if inputs_sum < needed_amount {
-            return Err(FundError::NotEnoughFunds);
+            });
         }
 
         Ok(Transaction {
-            version: 0,
+            version: transaction.version,
             inputs,
             outputs,
-            lock_time: 0,
+            lock_time: transaction.lock_time,
         })
     }
 
+    /// Funds and signs transaction paying `amount` to witness key hash of `recipient`
+    pub fn create_payment(&self, recipient: &Address, amount: u64, fee_rate: FeeRate, fallback: Fallback) -> Result<Transaction, SignError> {
+        let transaction = Transaction {
+            version: 0,
+            inputs: vec![],
+            outputs: vec![
+                TransactionOutput {
+                    value: amount,
+                    script_pubkey: Builder::build_p2wpkh(&recipient.hash).to_bytes(),
+                },
+            ],
+            lock_time: 0,
+        };
+
+        let funded_transaction = self.fund_transaction(transaction, fee_rate, fallback)?;
+        self.sign_transaction(funded_transaction)
+    }
+
+    /// Fee paid by transaction, if all its previous outputs are known
+    pub fn transaction_fee(&self, transaction: &Transaction) -> Option<u64> {
+        let mut inputs_value = 0;
+        for input in &transaction.inputs {
+            inputs_value += self.utxo_provider.transaction_output(&input.previous_output, 0)?.value;
+        }
+        let outputs_value = transaction.outputs.iter().fold(0, |sum, output| sum + output.value);
+        inputs_value.checked_sub(outputs_value)
+    }
+
     // createSig creates and returns the serialized raw signature and compressed
     // pubkey for a transaction input signature
     pub fn create_signature_for_input(&self, transaction: &Transaction, input_index: usize,
@@ -159,6 +332,22 @@ impl TransactionHelper {
         }
         let prevout = prevout.unwrap();
         let prevout_script = Script::new(prevout.script_pubkey.clone());
+        // key hash outputs, such as coinbase outputs of our miner, are unlocked by script sig
+        if prevout_script.is_pay_to_public_key_hash() {
+            let wallet = self.wallet.read();
+            let keys = wallet
+                .find_keypair_with_public_hash(&H160::from(&prevout.script_pubkey[3..23]))
+                .ok_or(SignError::NoKeysToUnlockPrevout)?;
+            return Ok(signer.signed_input(
+                keys,
+                input_index,
+                prevout.value,
+                &prevout_script,
+                SignatureVersion::Base,
+                SighashBase::All.into(),
+            ));
+        }
+
         let prevout_witness = prevout_script.parse_witness_program();
         if prevout_witness == None {
             return Err(SignError::PrevoutWitnessParseError);
@@ -191,3 +380,15 @@ impl TransactionHelper {
         Ok(signed_input)
     }
 }
+
+/// Key hash of P2PKH and P2WPKH outputs, which are the only ones wallet keys receive
+fn output_key_hash(script_pubkey: &Bytes) -> Option<H160> {
+    let script = Script::new(script_pubkey.clone());
+    if script.is_pay_to_public_key_hash() {
+        Some(H160::from(&script_pubkey[3..23]))
+    } else if script.is_pay_to_witness_key_hash() {
+        Some(H160::from(&script_pubkey[2..22]))
+    } else {
+        None
+    }
+}
@@ -1,39 +1,344 @@
+use crypto::{aes256_cbc_decrypt, aes256_cbc_encrypt, hmac_sha256, pbkdf2_sha256, sha256};
+use sync::atomic_file;
 use keys::generator::{Random, Generator};
 use keys::network::Network;
-use keys::{KeyPair, Private, Error, Address};
-use primitives::hash::H160;
+use keys::{KeyPair, Private, Error, Address, Mnemonic, ExtendedPrivate, DerivationPath};
+use primitives::bytes::Bytes;
+use primitives::hash::{H160, H256};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use parking_lot::RwLock;
 
 pub type WalletRef = Arc<RwLock<Wallet>>;
 
+/// Wallet file layout: version (1) | salt (16) | iv (16) | ciphertext | hmac (32)
+const WALLET_FILE_VERSION: u8 = 1;
+const SALT_SIZE: usize = 16;
+const IV_SIZE: usize = 16;
+const MAC_SIZE: usize = 32;
+const KDF_ITERATIONS: u32 = 20_000;
+
+const MNEMONIC_WORDS: usize = 24;
+/// BIP44 account all wallet addresses are derived from
+const ACCOUNT: u32 = 0;
+
+#[derive(Debug)]
+pub enum WalletError {
+    /// Wallet has no passphrase set, so keys can't be read or written
+    Locked,
+    /// Passphrase doesn't match the one wallet file was encrypted with
+    WrongPassphrase,
+    /// Wallet file is malformed or was created by unsupported version
+    Corrupted,
+    /// Seed phrase already exists and would be overwritten
+    SeedExists,
+    Keys(Error),
+    Io(io::Error),
+}
+
+impl From<io::Error> for WalletError {
+    fn from(err: io::Error) -> Self {
+        WalletError::Io(err)
+    }
+}
+
+impl From<Error> for WalletError {
+    fn from(err: Error) -> Self {
+        WalletError::Keys(err)
+    }
+}
+
+/// Seed and derivation state of HD wallet
+struct HdChain {
+    mnemonic: Mnemonic,
+    master: ExtendedPrivate,
+    /// Number of receive addresses handed out so far
+    receive_count: u32,
+    /// Number of change addresses handed out so far
+    change_count: u32,
+}
+
 pub struct Wallet
 {
-    pub keys: Vec<KeyPair>
+    pub keys: Vec<KeyPair>,
+    hd_chain: Option<HdChain>,
+    /// Keys loaded with `walletload` which can't be derived from seed
+    imported: Vec<Private>,
+    /// Secrets of atomic swaps initiated by us, by their hash
+    swap_secrets: HashMap<H256, Bytes>,
+    path: PathBuf,
+    passphrase: Option<String>,
 }
 
 impl Wallet
 {
-    pub fn new() -> Self
+    pub fn new(path: PathBuf) -> Self
     {
-       Wallet { keys: vec![] }
+       Wallet { keys: vec![], hd_chain: None, imported: vec![], swap_secrets: HashMap::new(), path, passphrase: None }
     }
 
-    pub fn new_keypair(&mut self) -> Address {
+    pub fn file_exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.passphrase.is_some()
+    }
+
+    pub fn has_seed(&self) -> bool {
+        self.hd_chain.is_some()
+    }
+
+    /// Decrypts keys from wallet file. If there is no wallet file yet,
+    /// passphrase is remembered and used to encrypt a newly created one
+    pub fn unlock(&mut self, passphrase: String) -> Result<(), WalletError> {
+        if self.file_exists() {
+            let plain = Self::decrypt(&self.path, &passphrase)?;
+            self.load_contents(&plain)?;
+            info!("Wallet unlocked, {} keys loaded", self.keys.len());
+            self.passphrase = Some(passphrase);
+        } else {
+            self.passphrase = Some(passphrase);
+            self.save()?;
+            info!("Created new wallet file at {}", self.path.display());
+        }
+        Ok(())
+    }
+
+    /// Removes keys and passphrase from memory. Keys stay in wallet file
+    pub fn lock(&mut self) {
+        self.keys.clear();
+        self.imported.clear();
+        self.swap_secrets.clear();
+        self.hd_chain = None;
+        self.passphrase = None;
+        info!("Wallet locked");
+    }
+
+    /// Generates new seed phrase all further addresses are derived from
+    pub fn create_seed(&mut self) -> Result<Mnemonic, WalletError> {
+        if self.has_seed() {
+            return Err(WalletError::SeedExists);
+        }
+        let mnemonic = Mnemonic::generate(MNEMONIC_WORDS)?;
+        self.set_seed(mnemonic.clone(), 0, 0)?;
+        self.persist();
+        Ok(mnemonic)
+    }
+
+    /// Sets seed from phrase and derives given number of receive and change keys.
+    /// Existing seed is never overwritten
+    pub fn restore_seed(&mut self, mnemonic: Mnemonic, receive_count: u32, change_count: u32) -> Result<(), WalletError> {
+        if self.has_seed() {
+            return Err(WalletError::SeedExists);
+        }
+        self.set_seed(mnemonic, receive_count, change_count)?;
+        self.persist();
+        info!("Wallet restored with {} receive and {} change addresses", receive_count, change_count);
+        Ok(())
+    }
+
+    /// Master key of HD chain which is derived from seed phrase
+    pub fn master_key(mnemonic: &Mnemonic) -> Result<ExtendedPrivate, Error> {
         //TODO testnet support
-        let generator = Random::new(Network::Mainnet);
-        let keypair = generator.generate().expect("Could not generate keypair");
+        ExtendedPrivate::from_seed(&*mnemonic.to_seed(""), Network::Mainnet)
+    }
+
+    /// Derives keypair at m/44'/coin'/0'/change/index without adding it to wallet
+    pub fn derive_keypair(master: &ExtendedPrivate, change: bool, index: u32) -> Result<KeyPair, Error> {
+        let path = DerivationPath::bip44(master.network, ACCOUNT, change, index);
+        master.derive_path(&path)?.key_pair()
+    }
+
+    fn set_seed(&mut self, mnemonic: Mnemonic, receive_count: u32, change_count: u32) -> Result<(), WalletError> {
+        let master = Self::master_key(&mnemonic)?;
+        let mut keys = vec![];
+        for index in 0..receive_count {
+            keys.push(Self::derive_keypair(&master, false, index)?);
+        }
+        for index in 0..change_count {
+            keys.push(Self::derive_keypair(&master, true, index)?);
+        }
+        for private in &self.imported {
+            keys.push(KeyPair::from_private(private.clone())?);
+        }
+
+        self.keys = keys;
+        self.hd_chain = Some(HdChain { mnemonic, master, receive_count, change_count });
+        Ok(())
+    }
+
+    /// Encrypts all keys with current passphrase and writes them to wallet file
+    pub fn save(&self) -> Result<(), WalletError> {
+        let passphrase = match self.passphrase {
+            Some(ref passphrase) => passphrase,
+            None => return Err(WalletError::Locked),
+        };
+
+        let mut salt = [0u8; SALT_SIZE];
+        let mut iv = [0u8; IV_SIZE];
+        Random::generate_bytes(&mut salt).expect("Could not generate salt");
+        Random::generate_bytes(&mut iv).expect("Could not generate iv");
+        let (encryption_key, mac_key) = Self::derive_keys(passphrase, &salt);
+
+        let mut data = vec![WALLET_FILE_VERSION];
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&iv);
+        data.extend(aes256_cbc_encrypt(&encryption_key, &iv, self.contents().as_bytes()));
+        let mac = hmac_sha256(&mac_key, &data);
+        data.extend_from_slice(&*mac);
+
+        atomic_file::write(&self.path, &data)?;
+        Ok(())
+    }
+
+    /// Plain text wallet contents, one `name: value` pair per line
+    fn contents(&self) -> String {
+        let mut lines = vec![];
+        if let Some(ref hd_chain) = self.hd_chain {
+            lines.push(format!("mnemonic: {}", hd_chain.mnemonic));
+            lines.push(format!("receive: {}", hd_chain.receive_count));
+            lines.push(format!("change: {}", hd_chain.change_count));
+        }
+        for private in &self.imported {
+            lines.push(format!("imported: {}", private));
+        }
+        for secret in self.swap_secrets.values() {
+            lines.push(format!("swapsecret: {:?}", secret));
+        }
+        lines.join("\n")
+    }
+
+    fn load_contents(&mut self, plain: &str) -> Result<(), WalletError> {
+        let mut mnemonic = None;
+        let mut receive_count = 0;
+        let mut change_count = 0;
+        let mut imported = vec![];
+        let mut swap_secrets = HashMap::new();
+
+        for line in plain.split('\n').filter(|line| !line.is_empty()) {
+            let mut parts = line.splitn(2, ": ");
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => return Err(WalletError::Corrupted),
+            };
+            match name {
+                "mnemonic" => mnemonic = Some(Mnemonic::from_str(value).map_err(|_| WalletError::Corrupted)?),
+                "receive" => receive_count = value.parse().map_err(|_| WalletError::Corrupted)?,
+                "change" => change_count = value.parse().map_err(|_| WalletError::Corrupted)?,
+                "imported" => imported.push(Private::from_str(value).map_err(|_| WalletError::Corrupted)?),
+                "swapsecret" => {
+                    let secret = Bytes::from_str(value).map_err(|_| WalletError::Corrupted)?;
+                    swap_secrets.insert(sha256(&secret), secret);
+                }
+                _ => return Err(WalletError::Corrupted),
+            }
+        }
+
+        self.imported = imported;
+        self.swap_secrets = swap_secrets;
+        match mnemonic {
+            Some(mnemonic) => self.set_seed(mnemonic, receive_count, change_count)?,
+            None => {
+                self.hd_chain = None;
+                self.keys = self.imported
+                    .iter()
+                    .map(|private| KeyPair::from_private(private.clone()))
+                    .collect::<Result<Vec<_>, _>>()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decrypt(path: &PathBuf, passphrase: &str) -> Result<String, WalletError> {
+        let data = match atomic_file::read(path)? {
+            Some(data) => data,
+            None => return Err(WalletError::Io(io::ErrorKind::NotFound.into())),
+        };
+
+        if data.len() < 1 + SALT_SIZE + IV_SIZE + MAC_SIZE || data[0] != WALLET_FILE_VERSION {
+            return Err(WalletError::Corrupted);
+        }
+
+        let (payload, mac) = data.split_at(data.len() - MAC_SIZE);
+        let salt = &payload[1..1 + SALT_SIZE];
+        let iv = &payload[1 + SALT_SIZE..1 + SALT_SIZE + IV_SIZE];
+        let ciphertext = &payload[1 + SALT_SIZE + IV_SIZE..];
+
+        let (encryption_key, mac_key) = Self::derive_keys(passphrase, salt);
+        if &*hmac_sha256(&mac_key, payload) != mac {
+            return Err(WalletError::WrongPassphrase);
+        }
+
+        let plain = aes256_cbc_decrypt(&encryption_key, iv, ciphertext).map_err(|_| WalletError::Corrupted)?;
+        String::from_utf8(plain).map_err(|_| WalletError::Corrupted)
+    }
+
+    fn derive_keys(passphrase: &str, salt: &[u8]) -> ([u8; 32], [u8; 32]) {
+        let mut derived = [0u8; 64];
+        pbkdf2_sha256(passphrase.as_bytes(), salt, KDF_ITERATIONS, &mut derived);
+        let mut encryption_key = [0u8; 32];
+        let mut mac_key = [0u8; 32];
+        encryption_key.copy_from_slice(&derived[..32]);
+        mac_key.copy_from_slice(&derived[32..]);
+        (encryption_key, mac_key)
+    }
+
+    /// Saves wallet after it was changed and reports if it could not be done
+    fn persist(&self) {
+        if let Err(err) = self.save() {
+            error!("Failed to save wallet to {}: {:?}. New keys will be lost on restart", self.path.display(), err);
+        }
+    }
+
+    /// Derives next key on receive or change chain. Falls back to random key if wallet has no seed
+    fn next_keypair(&mut self, change: bool) -> KeyPair {
+        match self.hd_chain {
+            Some(ref mut hd_chain) => {
+                let index = if change { hd_chain.change_count } else { hd_chain.receive_count };
+                let keypair = Self::derive_keypair(&hd_chain.master, change, index).expect("Could not derive keypair");
+                if change { hd_chain.change_count += 1 } else { hd_chain.receive_count += 1 }
+                info!("Derived keypair {}", DerivationPath::bip44(hd_chain.master.network, ACCOUNT, change, index));
+                keypair
+            }
+            None => {
+                //TODO testnet support
+                let generator = Random::new(Network::Mainnet);
+                let keypair = generator.generate().expect("Could not generate keypair");
+                self.imported.push(keypair.private().clone());
+                keypair
+            }
+        }
+    }
+
+    /// Hands out next receive address
+    pub fn new_keypair(&mut self) -> Address {
+        let keypair = self.next_keypair(false);
         let address = keypair.address();
         info!("Generated keypair {}", keypair);
         info!("Public key hash is {}", address.hash);
         info!("Address is {}", address);
         self.keys.push(keypair);
+        self.persist();
+        address
+    }
+
+    /// Hands out next change address
+    pub fn new_change_keypair(&mut self) -> Address {
+        let keypair = self.next_keypair(true);
+        let address = keypair.address();
+        info!("Change address is {}", address);
+        self.keys.push(keypair);
+        self.persist();
         address
     }
 
     pub fn add_keypair_from_private(&mut self, private: Private) -> Result<Address, Error>
     {
-        match KeyPair::from_private(private)
+        match KeyPair::from_private(private.clone())
         {
             Ok(keypair) =>
             {
@@ -42,22 +347,41 @@ impl Wallet
                 info!("Public key hash is {}", address.hash);
                 info!("Address is {}", address);
                 self.keys.push(keypair);
+                self.imported.push(private);
+                self.persist();
                 Ok(address)
-            } 
+            }
             Err(error) => Err(error)
         }
     }
 
+    /// Remembers secret of atomic swap we initiate. It is needed to redeem counterparty contract
+    /// if secret can't be extracted from chain, so it is saved before contract is published
+    pub fn add_swap_secret(&mut self, secret: Bytes) -> Result<(), WalletError> {
+        if !self.is_unlocked() {
+            return Err(WalletError::Locked);
+        }
+        self.swap_secrets.insert(sha256(&secret), secret);
+        self.save()
+    }
+
+    pub fn swap_secret(&self, secret_hash: &H256) -> Option<&Bytes> {
+        self.swap_secrets.get(secret_hash)
+    }
+
     pub fn find_keypair_with_public_hash(&self, pubkey_hash: &H160) -> Option<&KeyPair> {
         self.keys.iter().find(|&keypair| keypair.public().address_hash() == *pubkey_hash)
     }
-    /// Checks if wallet has at least one key and shows error message if not
+    /// Checks if wallet is unlocked and has at least one key and shows error message if not
     pub fn is_ready(&self) -> bool {
-        if self.keys.is_empty() {
-            error!("No wallet was created or loaded. Use `walletcreate` or `walletload` to create one.");
+        if !self.is_unlocked() {
+            error!("Wallet is locked. Use `walletunlock <passphrase>` to unlock it or to set passphrase for a new one.");
+            false
+        } else if self.keys.is_empty() {
+            error!("No wallet was created or loaded. Use `walletcreate`, `walletrestore` or `walletload` to create one.");
             false
         } else {
             true
         }
     }
-}
\ No newline at end of file
+}
@@ -2,29 +2,36 @@ use chain::constants::SEQUENCE_LOCKTIME_DISABLE_FLAG;
 use chain::{OutPoint, Transaction};
 //use chain_builder::TransactionBuilder;
 use db::SharedStore;
-use keys::{Address, Private};
+use keys::{Address, Private, Mnemonic, ExtendedPrivate};
 use memory_pool::MemoryPoolRef;
-use message::types::Tx;
 use script::{Builder, Script, SighashBase, SignatureVersion, TransactionInputSigner};
 use service::Service;
 use std::sync::mpsc::Receiver;
-use sync::MessageWrapper;
+use sync::AcceptorRef;
+use futures::Future;
 use wallet::{Wallet, WalletRef};
 use transaction_helper::TransactionHelperRef;
+use coin_selection::{Fallback, FeeRate};
 use chain::{TransactionInput, TransactionOutput};
 
+/// Number of unused addresses in a row after which wallet restore stops looking for more
+const ADDRESS_GAP_LIMIT: u32 = 20;
+
 #[derive(Debug, PartialEq)]
 pub enum Task {
     CreateWallet(),
-    SendCash(Address, u64),
+    SendCash(Address, u64, FeeRate),
     LoadWallet(Private),
-    CalculateBalance(),
+    RestoreWallet(Mnemonic),
+    CalculateBalance(u32),
+    Unlock(String),
+    Lock(),
 }
 
 pub struct WalletManager {
     receiver: Receiver<Task>,
     mempool: MemoryPoolRef,
-    wrapper: MessageWrapper,
+    acceptor: AcceptorRef,
     wallet: WalletRef,
     storage: SharedStore,
     transaction_helper: TransactionHelperRef,
@@ -35,14 +42,14 @@ impl WalletManager {
         mempool: MemoryPoolRef,
         storage: SharedStore,
         receiver: Receiver<Task>,
-        wrapper: MessageWrapper,
+        acceptor: AcceptorRef,
         wallet: WalletRef,
         transaction_helper: TransactionHelperRef,    
     ) -> Self {
         WalletManager {
             receiver,
             mempool,
-            wrapper,
+            acceptor,
             storage,
             wallet,
             transaction_helper,
@@ -50,56 +57,108 @@ impl WalletManager {
     }
 
     fn create_wallet(&self) {
-        self.wallet.write().new_keypair();
+        let mut wallet = self.wallet.write();
+        if !wallet.is_unlocked() {
+            error!("Wallet is locked. Use `walletunlock <passphrase>` first");
+            return;
+        }
+        if !wallet.has_seed() {
+            match wallet.create_seed() {
+                Ok(mnemonic) => info!("Write down your recovery phrase, it restores every wallet address: {}", mnemonic),
+                Err(err) => {
+                    error!("Failed to create wallet seed: {:?}", err);
+                    return;
+                }
+            }
+        }
+        wallet.new_keypair();
+    }
+
+    fn restore_wallet(&self, mnemonic: Mnemonic) {
+        if !self.wallet.read().is_unlocked() {
+            error!("Wallet is locked. Use `walletunlock <passphrase>` first");
+            return;
+        }
+        if self.wallet.read().has_seed() {
+            error!("Wallet already has a seed. Restoring would overwrite it and its keys");
+            return;
+        }
+        let master = match Wallet::master_key(&mnemonic) {
+            Ok(master) => master,
+            Err(err) => {
+                error!("Failed to restore wallet from seed: {}", err);
+                return;
+            }
+        };
+        let receive_count = self.count_used_addresses(&master, false);
+        let change_count = self.count_used_addresses(&master, true);
+        if let Err(err) = self.wallet.write().restore_seed(mnemonic, receive_count, change_count) {
+            error!("Failed to restore wallet from seed: {:?}", err);
+        }
+    }
+
+    /// Finds how many addresses of a chain were handed out by looking for transactions involving them
+    /// until `ADDRESS_GAP_LIMIT` addresses in a row have none. Addresses whose coins are all spent count as used
+    fn count_used_addresses(&self, master: &ExtendedPrivate, change: bool) -> u32 {
+        let mut count = 0;
+        let mut index = 0;
+        while index < count + ADDRESS_GAP_LIMIT {
+            let keypair = match Wallet::derive_keypair(master, change, index) {
+                Ok(keypair) => keypair,
+                Err(err) => {
+                    error!("Failed to derive key {}: {}", index, err);
+                    break;
+                }
+            };
+            if !self.storage.transactions_with_address(&keypair.address().hash).is_empty() {
+                count = index + 1;
+            }
+            index += 1;
+        }
+        count
     }
 
     fn load_from_key(&self, private: Private) {
+        if !self.wallet.read().is_unlocked() {
+            error!("Wallet is locked. Use `walletunlock <passphrase>` first");
+            return;
+        }
         match self.wallet.write().add_keypair_from_private(private) {
             Ok(_) => {}
             Err(err) => error!("Failed to create wallet from private: {}", err),
         }
     }
 
-    fn calculate_balance(&self) {
-        if !self.wallet.read().is_ready() { return; }
-        let wallet = &self.wallet;
+    fn unlock(&self, passphrase: String) {
+        if let Err(err) = self.wallet.write().unlock(passphrase) {
+            error!("Failed to unlock wallet: {:?}", err);
+        }
+    }
 
-        let user_address_hash = wallet.read().keys[0].address().hash;
-        let out_points = self.storage
-            .transaction_with_output_address(&user_address_hash);
-        let balance = out_points
-            .iter()
-            .map(|out_point| self.storage.transaction_output(out_point, 0).unwrap())
-            .fold(0, |credit, outpoint| credit + outpoint.value);
+    fn lock(&self) {
+        self.wallet.write().lock();
+    }
+
+    fn calculate_balance(&self, min_confirmations: u32) {
+        if !self.wallet.read().is_ready() { return; }
 
-        info!("wallet balance is {}", balance);
+        let balance = self.transaction_helper.balance(min_confirmations);
+        info!("wallet balance is {} (confirmed: {}, unconfirmed: {}, immature: {})",
+            balance.total(), balance.confirmed, balance.unconfirmed, balance.immature);
     }
 
     //TODO needs refactoring so it not just returns in case of error
-    fn send_cash(&self, recipient: Address, amount: u64) {
+    fn send_cash(&self, recipient: Address, amount: u64, fee_rate: FeeRate) {
         if !self.wallet.read().is_ready() { return; }
 
-        let transaction = Transaction {
-            version: 0,
-            inputs: vec![],
-            outputs: vec![
-                TransactionOutput {
-                    value: amount,
-                    script_pubkey: Builder::build_p2wpkh(&recipient.hash).to_bytes(),
-                },
-            ],
-            lock_time: 0,
-        };
-
-        //TODO pattern match returned results
-        let funded_transaction = match self.transaction_helper.fund_transaction(transaction) {
+        // random order does not reveal which coins the wallet holds when change is needed
+        let signed_transaction = match self.transaction_helper.create_payment(&recipient, amount, fee_rate, Fallback::Random) {
             Ok(transaction) => transaction,
             Err(err) => {
-                error!("Error funding transaction: {:?}", err);
+                error!("Error creating transaction: {:?}", err);
                 return;
             }
         };
-        let signed_transaction = self.transaction_helper.sign_transaction(funded_transaction).unwrap();
 
         let hash = signed_transaction.hash();
         if self.mempool.read().contains(&hash) {
@@ -107,15 +166,12 @@ impl WalletManager {
             return;
         }
 
-        let tx = Tx {
-            transaction: signed_transaction.clone(),
-        };
-        self.wrapper.broadcast(&tx);
-
         debug!("transaction to insert: {:?}", signed_transaction);
 
-        let mut mempool = self.mempool.write();
-        mempool.insert_verified(signed_transaction.into());
+        // acceptor announces transaction to peers
+        if let Err(err) = self.acceptor.accept_transaction(signed_transaction).wait() {
+            error!("Error sending transaction: {:?}", err);
+        }
     }
 }
 
@@ -128,8 +184,11 @@ impl Service for WalletManager {
                 match task {
                     Task::CreateWallet() => self.create_wallet(),
                     Task::LoadWallet(private) => self.load_from_key(private),
-                    Task::CalculateBalance() => self.calculate_balance(),
-                    Task::SendCash(to, amount) => self.send_cash(to, amount),
+                    Task::RestoreWallet(mnemonic) => self.restore_wallet(mnemonic),
+                    Task::CalculateBalance(min_confirmations) => self.calculate_balance(min_confirmations),
+                    Task::SendCash(to, amount, fee_rate) => self.send_cash(to, amount, fee_rate),
+                    Task::Unlock(passphrase) => self.unlock(passphrase),
+                    Task::Lock() => self.lock(),
                 }
             } else {
                 debug!("wallet manager thread ended");
@@ -0,0 +1,115 @@
+use chain::constants::SATOSHIS_IN_COIN;
+use ethcore_rpc::Trailing;
+use ethcore_rpc::v1::WalletClientCoreApi;
+use ethcore_rpc::v1::types::{GetBalanceResponse, UnspentOutput, WalletTransaction};
+use futures::Future;
+use keys::{Address, Private};
+use primitives::hash::H256;
+use sync::AcceptorRef;
+use transaction_helper::{TransactionHelperRef, DEFAULT_MIN_CONFIRMATIONS};
+use coin_selection::{Fallback, FeeRate, DEFAULT_FEE_RATE};
+use wallet::WalletRef;
+
+/// Serves wallet json-rpc methods, since wallet is only available inside node binary
+pub struct WalletClientCore {
+    wallet: WalletRef,
+    transaction_helper: TransactionHelperRef,
+    acceptor: AcceptorRef,
+}
+
+impl WalletClientCore {
+    pub fn new(
+        wallet: WalletRef,
+        transaction_helper: TransactionHelperRef,
+        acceptor: AcceptorRef,
+    ) -> Self {
+        WalletClientCore {
+            wallet,
+            transaction_helper,
+            acceptor,
+        }
+    }
+
+    fn ensure_unlocked(&self) -> Result<(), String> {
+        if self.wallet.read().is_unlocked() {
+            Ok(())
+        } else {
+            Err("Wallet is locked. Use `walletunlock <passphrase>` first".into())
+        }
+    }
+}
+
+pub fn to_coins(satoshis: u64) -> f64 {
+    satoshis as f64 / SATOSHIS_IN_COIN as f64
+}
+
+impl WalletClientCoreApi for WalletClientCore {
+    fn new_address(&self) -> Result<Address, String> {
+        self.ensure_unlocked()?;
+        let mut wallet = self.wallet.write();
+        if !wallet.has_seed() {
+            return Err("Wallet has no seed. Use `walletcreate` or `walletrestore` first".into());
+        }
+        Ok(wallet.new_keypair())
+    }
+
+    fn import_private(&self, private: Private) -> Result<(), String> {
+        self.ensure_unlocked()?;
+        self.wallet.write().add_keypair_from_private(private)
+            .map(|address| info!("Imported key for address {}", address))
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn balance(&self, min_confirmations: Trailing<u32>) -> Result<GetBalanceResponse, String> {
+        self.ensure_unlocked()?;
+        let balance = self.transaction_helper.balance(min_confirmations.unwrap_or(DEFAULT_MIN_CONFIRMATIONS));
+        Ok(GetBalanceResponse {
+            confirmed: to_coins(balance.confirmed),
+            unconfirmed: to_coins(balance.unconfirmed),
+            immature: to_coins(balance.immature),
+        })
+    }
+
+    fn send_to_address(&self, address: Address, amount: u64, fee_rate: Trailing<u64>) -> Result<H256, String> {
+        self.ensure_unlocked()?;
+        let fee_rate = FeeRate(fee_rate.unwrap_or(DEFAULT_FEE_RATE.0));
+        // random order does not reveal which coins the wallet holds when change is needed
+        let transaction = self.transaction_helper.create_payment(&address, amount, fee_rate, Fallback::Random)
+            .map_err(|err| format!("{:?}", err))?;
+
+        // acceptor announces transaction to peers
+        let transaction = self.acceptor.accept_transaction(transaction).wait()
+            .map_err(|err| format!("{:?}", err))?;
+        Ok(transaction.hash())
+    }
+
+    fn list_unspent(&self, min_confirmations: Trailing<u32>) -> Result<Vec<UnspentOutput>, String> {
+        self.ensure_unlocked()?;
+        let min_confirmations = min_confirmations.unwrap_or(0);
+        let unspent = self.transaction_helper.unspent_outputs().into_iter()
+            .filter(|output| output.confirmations >= min_confirmations)
+            .map(|output| UnspentOutput {
+                spendable: output.is_mature(),
+                txid: output.out_point.hash.reversed().into(),
+                vout: output.out_point.index,
+                address: output.address,
+                script_pubkey: output.output.script_pubkey.into(),
+                amount: to_coins(output.output.value),
+                confirmations: output.confirmations,
+            })
+            .collect();
+        Ok(unspent)
+    }
+
+    fn list_transactions(&self) -> Result<Vec<WalletTransaction>, String> {
+        self.ensure_unlocked()?;
+        let transactions = self.transaction_helper.transactions().into_iter()
+            .map(|transaction| WalletTransaction {
+                txid: transaction.hash.reversed().into(),
+                amount: transaction.amount as f64 / SATOSHIS_IN_COIN as f64,
+                confirmations: transaction.confirmations,
+            })
+            .collect();
+        Ok(transactions)
+    }
+}
@@ -150,7 +150,8 @@ impl TransactionInputSigner {
 	) -> TransactionInput {
 		match sigversion {
 			SignatureVersion::WitnessV0 => self.signed_input_witness(keypair, input_index, input_amount, script_pubkey, sigversion, sighash),
-			_ => panic!("Non witness signature should not be used"),
+			SignatureVersion::Base => self.signed_input_base(keypair, input_index, script_pubkey, sighash),
+			SignatureVersion::ForkId => panic!("Fork id signature should not be used"),
 		}
 	}
 
@@ -172,6 +173,25 @@ impl TransactionInputSigner {
 		(signature.into(), pubkey)
 	}
 
+	/// Unlocks pay to public key hash output with signature and public key pushed in script sig
+	fn signed_input_base(
+		&self,
+		keypair: &KeyPair,
+		input_index: usize,
+		script_pubkey: &Script,
+		sighash: u32,
+	) -> TransactionInput {
+		let (signature, pubkey) = self.compute_signature_for_input(keypair, input_index, 0, script_pubkey, SignatureVersion::Base, sighash);
+
+		let unsigned_input = &self.inputs[input_index];
+		TransactionInput {
+			previous_output: unsigned_input.previous_output.clone(),
+			sequence: unsigned_input.sequence,
+			script_sig: Builder::default().push_data(&signature).push_data(&pubkey).into_bytes(),
+			script_witness: vec![],
+		}
+	}
+
 	//TODO switch to using compute_signature_for_input
 	fn signed_input_witness(
 		&self,
@@ -347,7 +367,8 @@ mod tests {
 	use hash::H256;
 	use keys::{KeyPair, Private, Address};
 	use chain::{OutPoint, TransactionOutput, Transaction};
-	use script::Script;
+	use script::{Script, ScriptWitness};
+	use {Builder, TransactionSignatureChecker, VerificationFlags, verify_script};
 	use super::{Sighash, UnsignedTransactionInput, TransactionInputSigner, SighashBase, SignatureVersion};
 
 	// http://www.righto.com/2014/02/bitcoins-hard-way-using-raw-bitcoin.html
@@ -394,6 +415,42 @@ mod tests {
 		assert_eq!(hash, expected_signature_hash);
 	}
 
+	#[test]
+	fn test_signed_input_unlocks_p2pkh_output() {
+		let private: Private = "5HusYj2b2x4nroApgfvaSfKYZhRbKFH41bVyPooymbC6KfgSXdD".into();
+		let kp = KeyPair::from_private(private).unwrap();
+		let previous_output = Builder::build_p2pkh(&kp.address().hash);
+
+		let input_signer = TransactionInputSigner {
+			version: 1,
+			lock_time: 0,
+			inputs: vec![UnsignedTransactionInput {
+				sequence: 0xffff_ffff,
+				previous_output: OutPoint {
+					index: 0,
+					hash: H256::from(1),
+				},
+			}],
+			outputs: vec![TransactionOutput {
+				value: 91234,
+				script_pubkey: "76a914c8e90996c7c6080ee06284600c684ed904d14c5c88ac".into(),
+			}],
+		};
+
+		let input = input_signer.signed_input(&kp, 0, 0, &previous_output, SignatureVersion::Base, SighashBase::All.into());
+		assert!(input.script_witness.is_empty());
+
+		let checker = TransactionSignatureChecker {
+			signer: input_signer,
+			input_index: 0,
+			input_amount: 0,
+		};
+		let flags = VerificationFlags::default()
+			.verify_p2sh(true);
+		let script_sig: Script = input.script_sig.into();
+		assert_eq!(verify_script(&script_sig, &previous_output, &ScriptWitness::default(), &flags, &checker, SignatureVersion::Base), Ok(()));
+	}
+
 	fn run_test_sighash(
 		tx: &'static str,
 		script: &'static str,
@@ -1,49 +1,147 @@
-use chain::IndexedBlock;
+use chain::{IndexedBlock, IndexedTransaction};
 use chain::{Block, Transaction};
 use db::Error as DBError;
-use db::SharedStore;
+use db::{BlockOrigin, SharedStore};
 use memory_pool::MemoryPoolRef;
-use memory_pool::MemoryPoolTransactionOutputProvider;
-use params::{ConsensusFork, ConsensusParams, NetworkParams};
+use memory_pool::{FeeCalculator, MemoryPoolFeeCalculator, MemoryPoolTransactionOutputProvider};
+use params::ConsensusParams;
 use primitives::hash::H256;
 use verification::BackwardsCompatibleChainVerifier as ChainVerifier;
 use verification::{Error, TransactionError};
 use verification::{VerificationLevel, Verify};
+use message::common::InventoryVector;
+use message_wrapper::MessageWrapper;
+use orphan_blocks::OrphanBlocks;
+use orphan_transactions::OrphanTransactions;
 
 use futures::done;
 use futures::prelude::*;
 use futures_cpupool::CpuPool;
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Instant;
 
 pub type AcceptorRef = Arc<Acceptor>;
 
+/// Called for every transaction accepted to mempool or included in canonized block
+pub type TransactionHandler = Box<Fn(&Transaction) + Send + Sync>;
+
+/// Blocks which have joined and left canon chain when block was stored
+#[derive(Default)]
+pub struct CanonChange {
+    /// Newly canonized blocks, from oldest to newest
+    pub canonized: Vec<IndexedBlock>,
+    /// Blocks of previous canon branch, from oldest to newest
+    pub decanonized: Vec<IndexedBlock>,
+}
+
+/// Stores block and canonizes it, if it extends canon chain or makes its side chain the longest one.
+/// In the latter case canon chain is switched to side chain at once, so readers never see it half way
+pub fn store_block(store: &SharedStore, block: IndexedBlock) -> Result<CanonChange, DBError> {
+    let hash = block.hash().clone();
+    let origin = store.block_origin(&block.header)?;
+    store.insert(block.clone())?;
+    match origin {
+        BlockOrigin::KnownBlock | BlockOrigin::SideChain(_) => Ok(CanonChange::default()),
+        BlockOrigin::CanonChain { .. } => {
+            store.canonize(&hash)?;
+            Ok(CanonChange {
+                canonized: vec![block],
+                decanonized: Vec::new(),
+            })
+        }
+        BlockOrigin::SideChainBecomingCanonChain(origin) => {
+            let decanonized = stored_blocks(store, &origin.decanonized_route)?;
+            let mut canonized = stored_blocks(store, &origin.canonized_route)?;
+            {
+                let fork = store.fork(origin)?;
+                fork.store().canonize(&hash)?;
+                store.switch_to_fork(fork)?;
+            }
+            canonized.push(block);
+            Ok(CanonChange { canonized, decanonized })
+        }
+    }
+}
+
+fn stored_blocks(store: &SharedStore, hashes: &[H256]) -> Result<Vec<IndexedBlock>, DBError> {
+    hashes
+        .iter()
+        .map(|hash| store.indexed_block(hash.clone().into()).ok_or(DBError::CannotCanonize))
+        .collect()
+}
+
 pub struct Acceptor {
-    //message_wrapper: MessageWrapper,
+    /// Announces accepted transactions and blocks. Sender of network channel can't be shared between threads without lock
+    message_wrapper: Mutex<MessageWrapper>,
     mempool: MemoryPoolRef,
     store: SharedStore,
     cpupool: CpuPool,
 
     verifier: ChainVerifier,
+    on_transaction: Option<TransactionHandler>,
+    orphans: Mutex<OrphanBlocks>,
+    orphan_transactions: Mutex<OrphanTransactions>,
+    /// Counts stored blocks and mempool changes, so block template users can wait for them
+    changes: Mutex<u64>,
+    changed: Condvar,
 }
 
 impl Acceptor {
     pub fn new(
         mempool: MemoryPoolRef,
         store: SharedStore,
-        //message_wrapper: MessageWrapper,
-        params: NetworkParams,
+        message_wrapper: MessageWrapper,
+        consensus: ConsensusParams,
         cpupool: CpuPool,
     ) -> Self {
-        let verifier = ChainVerifier::new(
-            store.clone(),
-            ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork),
-        );
+        let orphans = OrphanBlocks::new(consensus.clone());
+        let verifier = ChainVerifier::new(store.clone(), consensus);
         Acceptor {
-            //message_wrapper,
+            message_wrapper: Mutex::new(message_wrapper),
             mempool,
             store,
             verifier,
             cpupool,
+            on_transaction: None,
+            orphans: Mutex::new(orphans),
+            orphan_transactions: Mutex::new(OrphanTransactions::new()),
+            changes: Mutex::new(0),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Lets other services watch transactions. Has to be set before acceptor is shared
+    pub fn set_on_transaction_handler<CB: 'static + Fn(&Transaction) + Send + Sync>(&mut self, c: CB) {
+        self.on_transaction = Some(Box::new(c));
+    }
+
+    /// Number of changes so far, to be passed to `wait_for_change`
+    pub fn changes(&self) -> u64 {
+        *self.changes.lock()
+    }
+
+    /// Blocks until block is stored or mempool changes after given number of changes, or until deadline.
+    /// Returns false if deadline has passed
+    pub fn wait_for_change(&self, seen_changes: u64, deadline: Instant) -> bool {
+        let mut changes = self.changes.lock();
+        while *changes == seen_changes {
+            if self.changed.wait_until(&mut changes, deadline).timed_out() {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn notify_change(&self) {
+        *self.changes.lock() += 1;
+        self.changed.notify_all();
+    }
+
+    fn notify_transaction(&self, transaction: &Transaction) {
+        if let Some(ref on_transaction) = self.on_transaction {
+            on_transaction(transaction);
         }
     }
 
@@ -71,10 +169,63 @@ impl Acceptor {
         done(self.try_accept_block(block))
     }
 
+    /// Orphan is a block which has been received before its parent
+    pub fn is_orphan(&self, hash: &H256) -> bool {
+        self.orphans.lock().contains(hash)
+    }
+
+    /// Block, which has to be received to accept given orphan
+    pub fn missing_ancestor(&self, hash: &H256) -> Option<H256> {
+        self.orphans.lock().missing_ancestor(hash)
+    }
+
+    /// Orphan transaction spends outputs of transactions which haven't been received yet
+    pub fn is_orphan_transaction(&self, hash: &H256) -> bool {
+        self.orphan_transactions.lock().contains(hash)
+    }
+
+    /// Transaction is in mempool, in stored block or waits for its parents
+    pub fn knows_transaction(&self, hash: &H256) -> bool {
+        self.mempool.read().contains(hash) || self.store.transaction(hash).is_some() || self.is_orphan_transaction(hash)
+    }
+
+    /// Transactions, which have to be received to accept given orphan
+    pub fn missing_parents(&self, hash: &H256) -> Vec<H256> {
+        self.orphan_transactions.lock().missing_parents(hash)
+    }
+
     fn try_accept_block(&self, block: Block) -> Result<H256, Error> {
+        let hash = self.verify_and_add_block(block)?;
+        self.accept_orphans(hash.clone());
+        Ok(hash)
+    }
+
+    /// Accepts orphans which were waiting for given block, and then their own children
+    fn accept_orphans(&self, parent: H256) {
+        let mut parents = vec![parent];
+        while let Some(parent) = parents.pop() {
+            let children = self.orphans.lock().remove_children(&parent);
+            for child in children {
+                if let Ok(hash) = self.verify_and_add_block(child) {
+                    parents.push(hash);
+                }
+            }
+        }
+    }
+
+    fn verify_and_add_block(&self, block: Block) -> Result<H256, Error> {
         let block: IndexedBlock = block.into();
         match self.verifier.verify(VerificationLevel::Full, &block) {
             Ok(_) => self.add_verified_block(block).map_err(Error::Database),
+            Err(Error::Database(DBError::UnknownParent)) => {
+                let hash = block.hash().clone();
+                if let Err(err) = self.orphans.lock().insert(block.to_raw_block()) {
+                    error!("Invalid orphan block {} received: {:?}", hash, err);
+                    return Err(err);
+                }
+                debug!("Block {} is received before its parent, keeping it as orphan", hash);
+                Err(Error::Database(DBError::UnknownParent))
+            }
             Err(err) => {
                 error!("Invalid block received: {:?}", err);
                 return Err(err);
@@ -82,33 +233,122 @@ impl Acceptor {
         }
     }
 
-    fn add_and_canonize_block(&self, block: IndexedBlock) -> Result<(), DBError> {
-        let hash = block.hash().clone();
-        try!(self.store.insert(block));
-        try!(self.store.canonize(&hash));
-        Ok(())
-    }
-
     fn add_verified_block(&self, block: IndexedBlock) -> Result<H256, DBError> {
         let hash = block.hash().clone();
-        let transactions = block.transactions.clone();
-        match self.add_and_canonize_block(block) {
-            Ok(_) => {
-                info!("Block inserted and canonized with hash {}", hash);
-                let mut mempool = self.mempool.write();
-                for transaction in transactions {
-                    mempool.remove_by_hash(&transaction.hash);
-                }
-                return Ok(hash);
-            }
+        let change = match store_block(&self.store, block) {
+            Ok(change) => change,
             Err(err) => {
                 error!("Cannot canonize received block due to {:?}", err);
                 return Err(err);
             }
+        };
+        let tip = match change.canonized.last() {
+            Some(tip) => tip.to_raw_block(),
+            None => {
+                info!("Block {} stored in side chain", hash);
+                return Ok(hash);
+            }
+        };
+        if change.decanonized.is_empty() {
+            info!("Block inserted and canonized with hash {}", hash);
+        } else {
+            info!("Canon chain switched to block {}, {} blocks decanonized", hash, change.decanonized.len());
+        }
+
+        let transactions: Vec<&IndexedTransaction> =
+            change.canonized.iter().flat_map(|block| block.transactions.iter()).collect();
+        let mut mempool = self.mempool.write();
+        for transaction in &transactions {
+            mempool.remove_by_hash(&transaction.hash);
+        }
+        drop(mempool);
+        self.notify_change();
+        for transaction in &transactions {
+            self.notify_transaction(&transaction.raw);
+        }
+        self.message_wrapper.lock().relay_compact_block(&tip);
+        let included: HashSet<H256> = transactions.iter().map(|transaction| transaction.hash.clone()).collect();
+        self.return_to_mempool(change.decanonized, &included);
+        for transaction in &transactions {
+            self.accept_orphan_transactions(transaction.hash.clone());
+        }
+        Ok(hash)
+    }
+
+    /// Transactions of blocks, which have left canon chain, wait in mempool for the next block again,
+    /// unless new canon chain includes them already
+    fn return_to_mempool(&self, decanonized: Vec<IndexedBlock>, included: &HashSet<H256>) {
+        for block in decanonized {
+            // coinbase is valid only in its own block
+            for transaction in block.transactions.into_iter().skip(1) {
+                if included.contains(&transaction.hash) {
+                    continue;
+                }
+                if let Err(err) = self.try_accept_transaction(transaction.raw) {
+                    debug!("Transaction {} of decanonized block is dropped: {:?}", transaction.hash, err);
+                }
+            }
         }
     }
 
     fn try_accept_transaction(&self, transaction: Transaction) -> Result<Transaction, TransactionError> {
+        match self.verify_and_insert_transaction(transaction.clone()) {
+            Ok(transaction) => {
+                self.accept_orphan_transactions(transaction.hash());
+                Ok(transaction)
+            }
+            Err(err) => {
+                self.keep_if_orphan(transaction, &err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Accepts orphans which were waiting for given transaction, and then their own children
+    fn accept_orphan_transactions(&self, parent: H256) {
+        let mut parents = vec![parent];
+        while let Some(parent) = parents.pop() {
+            let children = self.orphan_transactions.lock().remove_children(&parent);
+            for child in children {
+                match self.verify_and_insert_transaction(child.clone()) {
+                    Ok(transaction) => parents.push(transaction.hash()),
+                    // orphan may still wait for its other parents
+                    Err(err) => self.keep_if_orphan(child, &err),
+                }
+            }
+        }
+    }
+
+    /// Transaction, which spends outputs of unknown transactions, waits for them in orphan pool
+    fn keep_if_orphan(&self, transaction: Transaction, err: &TransactionError) {
+        match *err {
+            TransactionError::Input(_) | TransactionError::UnknownReference(_) => (),
+            _ => return,
+        }
+        let missing_parents = self.unknown_parents(&transaction);
+        if missing_parents.is_empty() {
+            return;
+        }
+        let hash = transaction.hash();
+        let parents_count = missing_parents.len();
+        if self.orphan_transactions.lock().insert(transaction, missing_parents) {
+            debug!("Transaction {} spends outputs of {} unknown transactions, keeping it as orphan", hash, parents_count);
+        } else {
+            debug!("Transaction {} spends outputs of unknown transactions, but is too big to be kept as orphan", hash);
+        }
+    }
+
+    fn unknown_parents(&self, transaction: &Transaction) -> HashSet<H256> {
+        let mempool = self.mempool.read();
+        transaction.inputs
+            .iter()
+            .map(|input| &input.previous_output.hash)
+            .filter(|hash| !mempool.contains(hash) && self.store.transaction(hash).is_none())
+            .cloned()
+            .collect()
+    }
+
+    fn verify_and_insert_transaction(&self, transaction: Transaction) -> Result<Transaction, TransactionError> {
         let hash = transaction.hash();
         if self.mempool.read().contains(&hash) {
             trace!(target: "handler", "Received transaction which already exists in mempool. Ignoring");
@@ -146,16 +386,26 @@ impl Acceptor {
             &transaction,
         ) {
             Ok(_) => {
+                let fee_calculator = FeeCalculator(&tx_output_provider);
+                let mut memory_pool = self.mempool.write();
+                // fee is checked before anything is evicted, so rejected transaction doesn't replace anything
+                if let Err(e) = fee_calculator.calculate(&memory_pool, &transaction) {
+                    error!("Can't calculate fee of transaction {}: {:?}", transaction.hash(), e);
+                    return Err(e);
+                }
                 // we have verified transaction, but possibly this transaction replaces
                 // existing transaction from memory pool
                 // => remove previous transactions before
-                let mut memory_pool = self.mempool.write();
                 for input in &transaction.inputs {
                     memory_pool.remove_by_prevout(&input.previous_output);
                 }
                 let transaction_clone = transaction.clone();
-                // now insert transaction itself
-                memory_pool.insert_verified(transaction.into());
+                // now insert transaction itself. Evicted transactions didn't fund it, so its fee is still known
+                memory_pool.insert_verified(transaction.into(), &fee_calculator)?;
+                drop(memory_pool);
+                self.notify_change();
+                self.notify_transaction(&transaction_clone);
+                self.message_wrapper.lock().relay(InventoryVector::tx(transaction_clone.hash()));
                 return Ok(transaction_clone);
             }
             Err(e) => {
@@ -0,0 +1,61 @@
+//! Small files which are always replaced as a whole, like wallet or ban list
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Reads whole file. Returns `None` if there is no file yet
+pub fn read(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut data = vec![];
+    File::open(path)?.read_to_end(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Replaces file contents. Data is written to temporary file first, so crash won't leave file half written
+pub fn write(path: &Path, data: &[u8]) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp_path, path)
+}
+
+/// Writes file after its contents were changed and reports if it could not be done.
+/// `description` names what is stored in the file
+pub fn persist(path: &Path, data: &[u8], description: &str) {
+    if let Err(err) = write(path, data) {
+        error!("Failed to save {} to {}: {:?}", description, path.display(), err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use self::tempdir::TempDir;
+    use super::{read, write};
+
+    #[test]
+    fn read_missing_file() {
+        let dir = TempDir::new("atomic_file").unwrap();
+        assert_eq!(read(&dir.path().join("missing.dat")).unwrap(), None);
+    }
+
+    #[test]
+    fn write_replaces_contents() {
+        let dir = TempDir::new("atomic_file").unwrap();
+        let path = dir.path().join("file.dat");
+
+        write(&path, &[1, 2, 3]).unwrap();
+        assert_eq!(read(&path).unwrap(), Some(vec![1, 2, 3]));
+
+        write(&path, &[4]).unwrap();
+        assert_eq!(read(&path).unwrap(), Some(vec![4]));
+        assert!(!path.with_extension("tmp").exists());
+    }
+}
@@ -0,0 +1,147 @@
+//! Peers banned for misbehavior, kept across restarts
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use atomic_file;
+use p2p::PeerAddress;
+use ser::{serialize_list, Error as ReaderError, Reader};
+
+#[derive(Debug)]
+pub enum BanListError {
+    Io(io::Error),
+    Corrupted(ReaderError),
+}
+
+impl From<io::Error> for BanListError {
+    fn from(err: io::Error) -> BanListError {
+        BanListError::Io(err)
+    }
+}
+
+impl From<ReaderError> for BanListError {
+    fn from(err: ReaderError) -> BanListError {
+        BanListError::Corrupted(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serializable, Deserializable)]
+struct BanEntry {
+    address: PeerAddress,
+    /// Unix time when ban expires
+    until: u64,
+}
+
+pub struct BanList {
+    /// Ban list is not saved when there is no path
+    path: Option<PathBuf>,
+    bans: HashMap<PeerAddress, u64>,
+}
+
+impl BanList {
+    /// Loads ban list file, or starts empty list if there is no file yet
+    pub fn open(path: PathBuf) -> Result<Self, BanListError> {
+        let entries: Vec<BanEntry> = match atomic_file::read(&path)? {
+            Some(data) => Reader::new(&data).read_list()?,
+            None => vec![],
+        };
+        Ok(BanList {
+            path: Some(path),
+            bans: entries.into_iter().map(|entry| (entry.address, entry.until)).collect(),
+        })
+    }
+
+    /// Ban list which is forgotten on exit
+    pub fn in_memory() -> Self {
+        BanList {
+            path: None,
+            bans: HashMap::new(),
+        }
+    }
+
+    pub fn ban(&mut self, address: PeerAddress, until: u64) {
+        self.bans.insert(address, until);
+        self.persist();
+    }
+
+    /// Checks if address is banned at given unix time. Expired bans are forgotten
+    pub fn is_banned(&mut self, address: &PeerAddress, now: u64) -> bool {
+        match self.bans.get(address).cloned() {
+            Some(until) if until > now => true,
+            Some(_) => {
+                self.bans.remove(address);
+                self.persist();
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn persist(&self) {
+        if let Some(ref path) = self.path {
+            let entries: Vec<BanEntry> = self.bans
+                .iter()
+                .map(|(address, until)| BanEntry {
+                    address: address.clone(),
+                    until: *until,
+                })
+                .collect();
+            atomic_file::persist(path, &serialize_list::<BanEntry, BanEntry>(&entries), "ban list");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use self::tempdir::TempDir;
+    use std::fs::File;
+    use std::io::Write;
+    use super::{BanList, BanListError};
+
+    #[test]
+    fn ban_lasts_until_expiry() {
+        let mut ban_list = BanList::in_memory();
+        let address = "127.0.0.1:8333".to_owned();
+        ban_list.ban(address.clone(), 1000);
+
+        assert!(ban_list.is_banned(&address, 999));
+        assert!(!ban_list.is_banned(&"127.0.0.2:8333".to_owned(), 999));
+        assert!(!ban_list.is_banned(&address, 1000));
+        assert!(ban_list.bans.is_empty());
+    }
+
+    #[test]
+    fn bans_survive_reopening() {
+        let dir = TempDir::new("ban_list").unwrap();
+        let path = dir.path().join("banlist.dat");
+        {
+            let mut ban_list = BanList::open(path.clone()).unwrap();
+            ban_list.ban("127.0.0.1:8333".to_owned(), 1000);
+            ban_list.ban("127.0.0.2:8333".to_owned(), 2000);
+        }
+
+        let mut reopened = BanList::open(path.clone()).unwrap();
+        assert!(reopened.is_banned(&"127.0.0.1:8333".to_owned(), 500));
+        assert!(reopened.is_banned(&"127.0.0.2:8333".to_owned(), 1500));
+        // expired ban is removed from file as well
+        assert!(!reopened.is_banned(&"127.0.0.1:8333".to_owned(), 1500));
+
+        let mut reopened = BanList::open(path).unwrap();
+        assert_eq!(reopened.bans.len(), 1);
+        assert!(reopened.is_banned(&"127.0.0.2:8333".to_owned(), 1500));
+    }
+
+    #[test]
+    fn corrupted_ban_list_is_reported() {
+        let dir = TempDir::new("ban_list").unwrap();
+        let path = dir.path().join("banlist.dat");
+        File::create(&path).unwrap().write_all(&[3, 1]).unwrap();
+
+        match BanList::open(path) {
+            Err(BanListError::Corrupted(_)) => (),
+            Err(err) => panic!("expected corrupted ban list, got {:?}", err),
+            Ok(_) => panic!("expected corrupted ban list"),
+        }
+    }
+}
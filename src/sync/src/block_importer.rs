@@ -0,0 +1,88 @@
+//! Blocks are verified and stored in their own thread, so message handling isn't blocked meanwhile
+
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use chain::Block;
+use db::Error as DBError;
+use futures::Future;
+use message::common::InventoryVector;
+use message::types::GetData;
+use p2p::PeerIndex;
+use parking_lot::Mutex;
+use verification::Error as BlockError;
+use acceptor::AcceptorRef;
+use message_wrapper::MessageWrapper;
+use peers::INVALID_BLOCK_SCORE;
+use synchronizer::Synchronizer;
+
+#[derive(Debug, PartialEq)]
+pub enum ImportTask {
+    /// Block of synchronizer's header chain, which parent is stored already
+    Downloaded(PeerIndex, Block),
+    /// Block which peer has announced or relayed on its own
+    Received(PeerIndex, Block),
+}
+
+pub struct BlockImporter {
+    task_receiver: Receiver<ImportTask>,
+    acceptor: AcceptorRef,
+    message_wrapper: MessageWrapper,
+    synchronizer: Arc<Mutex<Synchronizer>>,
+}
+
+impl BlockImporter {
+    pub fn new(
+        task_receiver: Receiver<ImportTask>,
+        acceptor: AcceptorRef,
+        message_wrapper: MessageWrapper,
+        synchronizer: Arc<Mutex<Synchronizer>>,
+    ) -> Self {
+        BlockImporter {
+            task_receiver,
+            acceptor,
+            message_wrapper,
+            synchronizer,
+        }
+    }
+
+    /// Imports blocks in order they are received, until all task senders are gone
+    pub fn run(&self) {
+        for task in self.task_receiver.iter() {
+            match task {
+                ImportTask::Downloaded(peer, block) => {
+                    let hash = block.hash();
+                    let result = self.acceptor.accept_block(block).wait();
+                    self.synchronizer.lock().on_block_imported(peer, &hash, result);
+                }
+                ImportTask::Received(peer, block) => self.import_received(peer, block),
+            }
+        }
+        debug!("block importer thread finished");
+    }
+
+    fn import_received(&self, peer: PeerIndex, block: Block) {
+        let hash = block.hash();
+        match self.acceptor.accept_block(block).wait() {
+            // block is kept as orphan until peer sends its missing ancestor
+            Err(BlockError::Database(DBError::UnknownParent)) => {
+                if let Some(ancestor) = self.acceptor.missing_ancestor(&hash) {
+                    let message = GetData::with_inventory(vec![InventoryVector::witness_block(ancestor)]);
+                    self.message_wrapper.send(peer, &message);
+                }
+            }
+            Err(ref err) if is_invalid_block(err) => {
+                let reason = format!("Invalid block: {:?}", err);
+                self.message_wrapper.misbehaving(peer, INVALID_BLOCK_SCORE, &reason);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Errors which can't happen to honest peer, unlike missing parents or races with other blocks
+fn is_invalid_block(err: &BlockError) -> bool {
+    match *err {
+        BlockError::Database(_) | BlockError::Duplicate | BlockError::FuturisticTimestamp => false,
+        _ => true,
+    }
+}
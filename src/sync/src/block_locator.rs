@@ -0,0 +1,36 @@
+use db::SharedStore;
+use primitives::hash::H256;
+
+type BlockHeight = u32;
+
+/// Hashes of best chain used in getblocks, dense near the tip and sparse towards genesis
+pub fn block_locator_hashes(store: &SharedStore) -> Vec<H256> {
+    let mut index: BlockHeight = store.best_block().number;
+    let mut step: BlockHeight = 1;
+    let mut hashes = vec![];
+
+    loop {
+        let block_hash = store
+            .block_hash(index)
+            .expect("index is never above best block number; qed");
+        hashes.push(block_hash);
+
+        if hashes.len() >= 10 {
+            step <<= 1;
+        }
+        if index < step {
+            // always include genesis hash
+            if index != 0 {
+                let genesis_block_hash = store
+                    .block_hash(0)
+                    .expect("No genesis block found at height 0");
+                hashes.push(genesis_block_hash);
+            }
+
+            break;
+        }
+        index -= step;
+    }
+
+    hashes
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use chain::{Block, BlockHeader, ShortTransactionID, Transaction};
+use crypto::{sha256, siphash24};
+use memory_pool::MemoryPool;
+use message::common::{BlockHeaderAndIDs, BlockTransactionsRequest, PrefilledTransaction};
+use primitives::hash::H256;
+use ser::Stream;
+
+/// Size of transaction without inputs and outputs, so block can't hold more transactions than its max size over it
+const MIN_TRANSACTION_SIZE: usize = 10;
+
+#[derive(Debug, PartialEq)]
+pub enum CompactBlockError {
+    /// Block can't hold this many transactions
+    TooManyTransactions(usize),
+    /// Index of prefilled or requested transaction is outside of block
+    InvalidIndex,
+    /// Peer has sent other number of transactions than requested
+    UnexpectedTransactions,
+    /// Some transactions are still missing
+    Incomplete,
+    /// Reconstructed block doesn't match its header, which happens on short id collision
+    MerkleRootMismatch,
+}
+
+/// Compact block with coinbase prefilled, as receiver can't have it in mempool
+pub fn build_compact_block(block: &Block, nonce: u64) -> BlockHeaderAndIDs {
+    let keys = short_id_keys(&block.block_header, nonce);
+    let mut transactions = block.transactions.iter();
+    let prefilled_transactions = transactions
+        .next()
+        .map(|coinbase| PrefilledTransaction {
+            index: 0,
+            transaction: coinbase.clone(),
+        })
+        .into_iter()
+        .collect();
+    let short_ids = transactions
+        .map(|transaction| short_transaction_id(keys, &transaction.witness_hash()))
+        .collect();
+
+    BlockHeaderAndIDs {
+        header: block.block_header.clone(),
+        nonce,
+        short_ids,
+        prefilled_transactions,
+    }
+}
+
+/// Transactions of stored block requested with differentially encoded indexes
+pub fn requested_transactions(block: Block, request: &BlockTransactionsRequest) -> Result<Vec<Transaction>, CompactBlockError> {
+    let indexes = decode_indexes(&request.indexes, block.transactions.len())?;
+    let mut transactions: Vec<Option<Transaction>> = block.transactions.into_iter().map(Some).collect();
+    Ok(indexes
+        .into_iter()
+        .filter_map(|index| transactions[index].take())
+        .collect())
+}
+
+/// Compact block which transactions are being collected
+pub struct PartialBlock {
+    header: BlockHeader,
+    transactions: Vec<Option<Transaction>>,
+}
+
+impl PartialBlock {
+    /// Takes prefilled transactions and looks for the rest in mempool
+    pub fn new(compact: BlockHeaderAndIDs, mempool: &MemoryPool, max_block_size: usize) -> Result<Self, CompactBlockError> {
+        let total = compact.short_ids.len() + compact.prefilled_transactions.len();
+        // slots are allocated for every transaction, so their number is checked first
+        if total > max_block_size / MIN_TRANSACTION_SIZE {
+            return Err(CompactBlockError::TooManyTransactions(total));
+        }
+        let mut transactions: Vec<Option<Transaction>> = vec![None; total];
+        let prefilled_indexes: Vec<usize> = compact.prefilled_transactions.iter().map(|prefilled| prefilled.index).collect();
+        let prefilled_indexes = decode_indexes(&prefilled_indexes, total)?;
+        for (index, prefilled) in prefilled_indexes.into_iter().zip(compact.prefilled_transactions) {
+            transactions[index] = Some(prefilled.transaction);
+        }
+
+        let keys = short_id_keys(&compact.header, compact.nonce);
+        // transactions with colliding short ids can't be told apart and are requested from peer
+        let mut by_short_id: HashMap<ShortTransactionID, Option<H256>> = HashMap::new();
+        for hash in mempool.get_transactions_ids() {
+            if let Some(transaction) = mempool.get(&hash) {
+                let short_id = short_transaction_id(keys, &transaction.witness_hash());
+                let is_collision = by_short_id.contains_key(&short_id);
+                by_short_id.insert(short_id, if is_collision { None } else { Some(hash) });
+            }
+        }
+
+        // short ids are given for transactions which are not prefilled, in block order
+        let empty_slots = transactions.iter_mut().filter(|transaction| transaction.is_none());
+        for (slot, short_id) in empty_slots.zip(compact.short_ids.iter()) {
+            *slot = by_short_id
+                .get(short_id)
+                .and_then(Option::as_ref)
+                .and_then(|hash| mempool.get(hash))
+                .cloned();
+        }
+
+        Ok(PartialBlock {
+            header: compact.header,
+            transactions,
+        })
+    }
+
+    pub fn hash(&self) -> H256 {
+        self.header.hash()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.transactions.iter().all(Option::is_some)
+    }
+
+    /// Request for transactions which haven't been found in mempool
+    pub fn missing_request(&self) -> BlockTransactionsRequest {
+        let missing: Vec<usize> = self.missing_indexes();
+        BlockTransactionsRequest {
+            blockhash: self.hash(),
+            indexes: encode_indexes(&missing),
+        }
+    }
+
+    /// Fills missing transactions in order they have been requested
+    pub fn fill(&mut self, transactions: Vec<Transaction>) -> Result<(), CompactBlockError> {
+        let missing = self.missing_indexes();
+        if missing.len() != transactions.len() {
+            return Err(CompactBlockError::UnexpectedTransactions);
+        }
+        for (index, transaction) in missing.into_iter().zip(transactions) {
+            self.transactions[index] = Some(transaction);
+        }
+        Ok(())
+    }
+
+    pub fn into_block(self) -> Result<Block, CompactBlockError> {
+        let transactions: Option<Vec<Transaction>> = self.transactions.into_iter().collect();
+        let transactions = transactions.ok_or(CompactBlockError::Incomplete)?;
+        let block = Block::new(self.header, transactions);
+        if block.merkle_root() != block.block_header.merkle_root_hash
+            || block.witness_merkle_root() != block.block_header.witness_merkle_root_hash
+        {
+            return Err(CompactBlockError::MerkleRootMismatch);
+        }
+        Ok(block)
+    }
+
+    fn missing_indexes(&self) -> Vec<usize> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .filter(|&(_, transaction)| transaction.is_none())
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Siphash keys derived from block header and nonce chosen by sender
+fn short_id_keys(header: &BlockHeader, nonce: u64) -> (u64, u64) {
+    let mut stream = Stream::new();
+    stream.append(header).append(&nonce);
+    let hash = sha256(&stream.out());
+    (read_u64_le(&hash[0..8]), read_u64_le(&hash[8..16]))
+}
+
+/// Lower 6 bytes of siphash of transaction's witness hash
+fn short_transaction_id(keys: (u64, u64), witness_hash: &H256) -> ShortTransactionID {
+    let siphash = siphash24(keys.0, keys.1, &**witness_hash);
+    let bytes: Vec<u8> = (0..6).map(|byte| (siphash >> (8 * byte)) as u8).collect();
+    ShortTransactionID::from(&bytes[..])
+}
+
+fn read_u64_le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+/// Each index is encoded as difference from previous index plus one
+fn encode_indexes(indexes: &[usize]) -> Vec<usize> {
+    let mut next = 0;
+    indexes
+        .iter()
+        .map(|index| {
+            let encoded = index - next;
+            next = index + 1;
+            encoded
+        })
+        .collect()
+}
+
+fn decode_indexes(encoded: &[usize], total: usize) -> Result<Vec<usize>, CompactBlockError> {
+    let mut next = 0usize;
+    let mut indexes = Vec::with_capacity(encoded.len());
+    for difference in encoded {
+        let index = next.checked_add(*difference).ok_or(CompactBlockError::InvalidIndex)?;
+        if index >= total {
+            return Err(CompactBlockError::InvalidIndex);
+        }
+        indexes.push(index);
+        next = index + 1;
+    }
+    Ok(indexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{Block, BlockHeader, OutPoint, ShortTransactionID, Transaction, TransactionInput, TransactionOutput};
+    use memory_pool::{MemoryPool, MemoryPoolFeeCalculator};
+    use message::common::{BlockHeaderAndIDs, BlockTransactionsRequest, PrefilledTransaction};
+    use primitives::hash::H256;
+    use verification::TransactionError;
+    use super::{build_compact_block, decode_indexes, encode_indexes, requested_transactions, short_transaction_id,
+        CompactBlockError, PartialBlock};
+
+    const MAX_BLOCK_SIZE: usize = 4_000_000;
+
+    struct ZeroFeeCalculator;
+
+    impl MemoryPoolFeeCalculator for ZeroFeeCalculator {
+        fn calculate(&self, _: &MemoryPool, _: &Transaction) -> Result<u64, TransactionError> {
+            Ok(0)
+        }
+    }
+
+    fn transaction(spent: u8) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    hash: H256::from(spent),
+                    index: 0,
+                },
+                script_sig: "51".into(),
+                sequence: 0xffffffff,
+                script_witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: u64::from(spent),
+                script_pubkey: "51".into(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    /// Block with coinbase and given transactions, which header commits to them
+    fn block(transactions: Vec<Transaction>) -> Block {
+        let coinbase = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput::coinbase("0101".into())],
+            outputs: vec![TransactionOutput {
+                value: 50,
+                script_pubkey: "51".into(),
+            }],
+            lock_time: 0,
+        };
+        let header = BlockHeader {
+            version: 1,
+            previous_header_hash: H256::from(1),
+            merkle_root_hash: H256::default(),
+            witness_merkle_root_hash: H256::default(),
+            time: 1234567,
+            bits: 0x1f00ffff.into(),
+            nonce: 0,
+        };
+        let mut block = Block::new(header, vec![coinbase].into_iter().chain(transactions).collect());
+        block.block_header.merkle_root_hash = block.merkle_root();
+        block.block_header.witness_merkle_root_hash = block.witness_merkle_root();
+        block
+    }
+
+    fn mempool(transactions: &[Transaction]) -> MemoryPool {
+        let mut mempool = MemoryPool::new();
+        for transaction in transactions {
+            mempool.insert_verified(transaction.clone().into(), &ZeroFeeCalculator).unwrap();
+        }
+        mempool
+    }
+
+    #[test]
+    fn short_id_is_lower_bytes_of_siphash() {
+        // SipHash-2-4 of 32 bytes 00..1f with keys from reference implementation is 0x7127512f72f27cce
+        let mut witness_hash = [0u8; 32];
+        for (index, byte) in witness_hash.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        let keys = (0x0706050403020100, 0x0F0E0D0C0B0A0908);
+        let expected = ShortTransactionID::from(&[0xce, 0x7c, 0xf2, 0x72, 0x2f, 0x51][..]);
+        assert_eq!(short_transaction_id(keys, &H256::from(witness_hash)), expected);
+    }
+
+    #[test]
+    fn indexes_are_encoded_differentially() {
+        let indexes = vec![0, 1, 5, 6, 100];
+        let encoded = encode_indexes(&indexes);
+        assert_eq!(encoded, vec![0, 0, 3, 0, 93]);
+        assert_eq!(decode_indexes(&encoded, 101), Ok(indexes));
+    }
+
+    #[test]
+    fn decoded_indexes_stay_in_block() {
+        assert_eq!(decode_indexes(&[0, 0, 3], 5), Err(CompactBlockError::InvalidIndex));
+        assert_eq!(decode_indexes(&[1, usize::max_value()], 5), Err(CompactBlockError::InvalidIndex));
+    }
+
+    #[test]
+    fn compact_block_prefills_coinbase() {
+        let block = block(vec![transaction(1), transaction(2)]);
+        let compact = build_compact_block(&block, 42);
+        assert_eq!(compact.header, block.block_header);
+        assert_eq!(compact.prefilled_transactions.len(), 1);
+        assert_eq!(compact.prefilled_transactions[0].index, 0);
+        assert_eq!(compact.prefilled_transactions[0].transaction, block.transactions[0]);
+        assert_eq!(compact.short_ids.len(), 2);
+    }
+
+    #[test]
+    fn block_is_reconstructed_from_mempool() {
+        let transactions = vec![transaction(1), transaction(2)];
+        let block = block(transactions.clone());
+        let partial_block = PartialBlock::new(build_compact_block(&block, 42), &mempool(&transactions), MAX_BLOCK_SIZE).unwrap();
+        assert!(partial_block.is_complete());
+        assert_eq!(partial_block.into_block(), Ok(block));
+    }
+
+    #[test]
+    fn missing_transactions_are_requested_and_filled() {
+        let transactions = vec![transaction(1), transaction(2), transaction(3), transaction(4)];
+        let block = block(transactions.clone());
+        let mempool = mempool(&[transactions[0].clone(), transactions[2].clone()]);
+        let mut partial_block = PartialBlock::new(build_compact_block(&block, 42), &mempool, MAX_BLOCK_SIZE).unwrap();
+        assert!(!partial_block.is_complete());
+
+        // transactions at indexes 2 and 4 are missing
+        let request = partial_block.missing_request();
+        assert_eq!(request.blockhash, block.hash());
+        assert_eq!(request.indexes, vec![2, 1]);
+
+        // peer answers getblocktxn with transactions of stored block
+        let missing = requested_transactions(block.clone(), &request).unwrap();
+        assert_eq!(missing, vec![transactions[1].clone(), transactions[3].clone()]);
+        assert_eq!(partial_block.fill(missing[..1].to_vec()), Err(CompactBlockError::UnexpectedTransactions));
+        partial_block.fill(missing).unwrap();
+        assert_eq!(partial_block.into_block(), Ok(block));
+    }
+
+    #[test]
+    fn wrong_transactions_fail_reconstruction() {
+        let block = block(vec![transaction(1)]);
+        let mut partial_block = PartialBlock::new(build_compact_block(&block, 42), &MemoryPool::new(), MAX_BLOCK_SIZE).unwrap();
+        assert_eq!(partial_block.missing_request().indexes, vec![1]);
+        partial_block.fill(vec![transaction(2)]).unwrap();
+        assert_eq!(partial_block.into_block(), Err(CompactBlockError::MerkleRootMismatch));
+    }
+
+    #[test]
+    fn request_outside_of_block_is_rejected() {
+        let block = block(vec![transaction(1)]);
+        let request = BlockTransactionsRequest {
+            blockhash: block.hash(),
+            indexes: vec![2],
+        };
+        assert_eq!(requested_transactions(block, &request), Err(CompactBlockError::InvalidIndex));
+    }
+
+    #[test]
+    fn compact_block_with_too_many_transactions_is_rejected() {
+        let block = block(vec![]);
+        let compact = BlockHeaderAndIDs {
+            header: block.block_header.clone(),
+            nonce: 42,
+            short_ids: vec![ShortTransactionID::default(); 10],
+            prefilled_transactions: vec![PrefilledTransaction {
+                index: 0,
+                transaction: block.transactions[0].clone(),
+            }],
+        };
+        match PartialBlock::new(compact, &MemoryPool::new(), 100) {
+            Err(err) => assert_eq!(err, CompactBlockError::TooManyTransactions(11)),
+            Ok(_) => panic!("compact block with 11 transactions fits into 100 bytes"),
+        }
+    }
+}
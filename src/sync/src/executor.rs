@@ -0,0 +1,121 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::thread;
+use message::types::GetHeaders;
+use message_wrapper::MessageWrapper;
+use block_locator::block_locator_hashes;
+use db::SharedStore;
+use keys::Address;
+use primitives::hash::H256;
+use miner::MinerRef;
+
+#[derive(Debug, PartialEq)]
+pub enum Task {
+    SignBlock(Address),
+    StartMining(Address),
+    StopMining(),
+    RequestLatestBlocks(),
+
+    //debug and explore
+    GetTransaction(H256),
+    GetTransactionMeta(H256),
+    GetBlockHash(u32),
+}
+
+pub struct Executor {
+    task_receiver: Receiver<Task>,
+    message_wrapper: MessageWrapper,
+    store: SharedStore,
+    miner: MinerRef,
+    /// Stops background mining thread, if there is one
+    mining: Option<Arc<AtomicBool>>,
+}
+
+impl Executor {
+    pub fn new(
+        store: SharedStore,
+        task_receiver: Receiver<Task>,
+        message_wrapper: MessageWrapper,
+        miner: MinerRef,
+    ) -> Self {
+        Executor {
+            task_receiver,
+            message_wrapper,
+            store,
+            miner,
+            mining: None,
+        }
+    }
+
+    pub fn run(&mut self) {
+        loop {
+            if let Ok(task) = self.task_receiver.recv() {
+                info!("task received, it is {:?}", task);
+                match task {
+                    Task::SignBlock(coinbase_recipient) => self.sign_block(coinbase_recipient),
+                    Task::StartMining(coinbase_recipient) => self.start_mining(coinbase_recipient),
+                    Task::StopMining() => self.stop_mining(),
+                    Task::GetTransactionMeta(hash) => self.get_transaction_meta(hash),
+                    Task::GetTransaction(hash) => self.get_transaction(hash),
+                    Task::GetBlockHash(height) => self.get_block_hash(height),
+                    Task::RequestLatestBlocks() => self.request_latest_blocks(),
+                }
+            } else {
+                break;
+            }
+        }
+        self.stop_mining();
+    }
+
+    /// Mines single block on top of best block
+    fn sign_block(&mut self, coinbase_recipient: Address) {
+        let stop = AtomicBool::new(false);
+        if self.miner.mine_block(&coinbase_recipient, &stop).is_none() {
+            error!("Failed to mine block");
+        }
+    }
+
+    /// Keeps mining new blocks in background thread until stopped
+    fn start_mining(&mut self, coinbase_recipient: Address) {
+        self.stop_mining();
+        let stop = Arc::new(AtomicBool::new(false));
+        self.mining = Some(stop.clone());
+        let miner = self.miner.clone();
+        thread::spawn(move || miner.run(&coinbase_recipient, &stop));
+    }
+
+    fn stop_mining(&mut self) {
+        if let Some(stop) = self.mining.take() {
+            stop.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn get_transaction_meta(&self, hash: H256) {
+        match self.store.transaction_meta(&hash) {
+            Some(meta) => debug!("Meta is {:?}", meta),
+            None => error!("No transaction with such hash"),
+        }
+    }
+
+    fn get_transaction(&self, hash: H256) {
+       match self.store.transaction(&hash) {
+            Some(tx) => debug!("Transaction info {:?}", tx),
+            None => error!("No transaction with such hash"),
+        } 
+    }
+
+    fn get_block_hash(&self, height: u32) {
+        match self.store.block_hash(height) {
+            Some(hash) => debug!("Block hash is {:?}", hash),
+            None => error!("No block at this height"),
+        }
+    }
+
+    fn request_latest_blocks(&self) {
+        info!("Requesting latest blocks from network");
+        let block_locator_hashes = block_locator_hashes(&self.store);
+        let get_headers_msg = GetHeaders::with_block_locator_hashes(block_locator_hashes);
+        self.message_wrapper.broadcast(&get_headers_msg);
+    }
+}
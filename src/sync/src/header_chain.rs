@@ -0,0 +1,543 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use chain::{BlockHeader, IndexedBlockHeader};
+use db::{BlockHeaderProvider, BlockRef, SharedStore};
+use params::ConsensusParams;
+use primitives::bigint::U256;
+use primitives::bytes::Bytes;
+use primitives::hash::H256;
+use ser::serialize;
+use verification::{block_work, CanonHeader, Error, HeaderAcceptor, HeaderVerifier};
+use block_locator::block_locator_hashes;
+use peers::unix_time;
+
+#[derive(Debug, PartialEq)]
+pub enum HeadersError {
+    /// Header's parent is neither stored block nor known header
+    Unconnected(H256),
+    Invalid(H256, Error),
+}
+
+/// Place of block in block tree
+#[derive(Debug, Clone)]
+struct Position {
+    height: u32,
+    /// Work of block and its ancestors which are not in canon chain
+    work: U256,
+    /// Height of canon block, which branch starts from
+    fork_height: u32,
+}
+
+/// Verified header which block is not stored yet
+struct HeaderEntry {
+    header: IndexedBlockHeader,
+    position: Position,
+}
+
+/// Verified headers of blocks which are not stored yet. Headers may form several branches
+/// starting from stored blocks, and blocks of branch with most work are downloaded
+pub struct HeaderChain {
+    store: SharedStore,
+    consensus: ConsensusParams,
+    /// Every header is preceded by its parent, if parent is not stored
+    headers: Vec<HeaderEntry>,
+    indexes: HashMap<H256, usize>,
+    /// Branch with more work than canon chain, in chain order. Its blocks are downloaded
+    best: Vec<H256>,
+}
+
+impl HeaderChain {
+    pub fn new(store: SharedStore, consensus: ConsensusParams) -> Self {
+        HeaderChain {
+            store,
+            consensus,
+            headers: Vec::new(),
+            indexes: HashMap::new(),
+            best: Vec::new(),
+        }
+    }
+
+    /// Height of last header of best branch
+    pub fn best_height(&self) -> u32 {
+        match self.best.last() {
+            Some(hash) => self.entry(hash).expect("best branch consists of known headers; qed").position.height,
+            None => self.store.best_block().number,
+        }
+    }
+
+    /// Height of header which is waiting for its block
+    pub fn height(&self, hash: &H256) -> Option<u32> {
+        self.entry(hash).map(|entry| entry.position.height)
+    }
+
+    /// Checks if header is in best branch, so its block has to be downloaded
+    pub fn contains(&self, hash: &H256) -> bool {
+        let first_height = match self.best.first().and_then(|first| self.height(first)) {
+            Some(height) => height,
+            None => return false,
+        };
+        match self.height(hash) {
+            Some(height) if height >= first_height => self.best.get((height - first_height) as usize) == Some(hash),
+            _ => false,
+        }
+    }
+
+    /// Hash of header, which block is the next one to be stored
+    pub fn first(&self) -> Option<&H256> {
+        self.best.first()
+    }
+
+    /// Headers of best branch, which blocks are not stored yet
+    pub fn best_branch(&self) -> &[H256] {
+        &self.best
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.best.is_empty()
+    }
+
+    /// Locator for getheaders, which starts from last header of best branch
+    pub fn locator_hashes(&self) -> Vec<H256> {
+        let mut hashes: Vec<H256> = self.best.iter().rev().take(10).cloned().collect();
+        hashes.extend(block_locator_hashes(&self.store));
+        hashes
+    }
+
+    /// Verifies headers and adds them to the tree. Returns hashes of new headers
+    pub fn insert(&mut self, headers: Vec<BlockHeader>) -> Result<Vec<H256>, HeadersError> {
+        self.prune();
+        let mut inserted = Vec::new();
+        let mut result = Ok(());
+        for header in headers {
+            let header: IndexedBlockHeader = header.into();
+            if self.indexes.contains_key(&header.hash) || self.is_stored(&header.hash) {
+                continue;
+            }
+            let parent = match self.position(&header.raw.previous_header_hash) {
+                Some(parent) => parent,
+                None => {
+                    result = Err(HeadersError::Unconnected(header.hash));
+                    break;
+                }
+            };
+            if let Err(err) = self.verify(&header, parent.height + 1) {
+                result = Err(HeadersError::Invalid(header.hash.clone(), err));
+                break;
+            }
+
+            inserted.push(header.hash.clone());
+            self.push(header, &parent);
+        }
+        // headers verified before failure are kept
+        self.select_best();
+        result.map(|_| inserted)
+    }
+
+    /// Forgets headers, which blocks have been stored, and chooses best branch again
+    pub fn prune(&mut self) {
+        let headers = mem::replace(&mut self.headers, Vec::new());
+        self.indexes.clear();
+        for entry in headers {
+            let parent_hash = entry.header.raw.previous_header_hash.clone();
+            // blocks are stored after their parents, so only headers with stored parents are checked
+            if !self.indexes.contains_key(&parent_hash) && self.is_stored(&entry.header.hash) {
+                continue;
+            }
+            // positions are computed again, since canon chain may have changed
+            if let Some(parent) = self.position(&parent_hash) {
+                self.push(entry.header, &parent);
+            }
+        }
+        self.select_best();
+    }
+
+    /// Forgets invalid header together with its descendants
+    pub fn invalidate(&mut self, hash: &H256) {
+        let headers = mem::replace(&mut self.headers, Vec::new());
+        self.indexes.clear();
+        let mut invalid = HashSet::new();
+        invalid.insert(hash.clone());
+        for entry in headers {
+            if invalid.contains(&entry.header.hash) || invalid.contains(&entry.header.raw.previous_header_hash) {
+                invalid.insert(entry.header.hash.clone());
+                continue;
+            }
+            self.indexes.insert(entry.header.hash.clone(), self.headers.len());
+            self.headers.push(entry);
+        }
+        self.select_best();
+    }
+
+    pub fn clear(&mut self) {
+        self.headers.clear();
+        self.indexes.clear();
+        self.best.clear();
+    }
+
+    fn entry(&self, hash: &H256) -> Option<&HeaderEntry> {
+        self.indexes.get(hash).map(|index| &self.headers[*index])
+    }
+
+    fn is_stored(&self, hash: &H256) -> bool {
+        self.store.block_header(hash.clone().into()).is_some()
+    }
+
+    fn push(&mut self, header: IndexedBlockHeader, parent: &Position) {
+        let position = Position {
+            height: parent.height + 1,
+            work: parent.work + block_work(header.raw.bits),
+            fork_height: parent.fork_height,
+        };
+        self.indexes.insert(header.hash.clone(), self.headers.len());
+        self.headers.push(HeaderEntry { header, position });
+    }
+
+    /// Position of known header or stored block. Stored side chain is followed down to canon chain
+    fn position(&self, hash: &H256) -> Option<Position> {
+        if let Some(entry) = self.entry(hash) {
+            return Some(entry.position.clone());
+        }
+
+        let mut hash = hash.clone();
+        let mut side_chain_length = 0;
+        let mut work = U256::from(0);
+        loop {
+            if let Some(number) = self.store.block_number(&hash) {
+                return Some(Position {
+                    height: number + side_chain_length,
+                    work,
+                    fork_height: number,
+                });
+            }
+            let header = self.store.block_header(hash.into())?;
+            side_chain_length += 1;
+            work = work + block_work(header.bits);
+            hash = header.previous_header_hash;
+        }
+    }
+
+    /// Work of canon blocks above given height
+    fn canon_work_above(&self, height: u32) -> U256 {
+        let best_number = self.store.best_block().number;
+        (height + 1..best_number + 1)
+            .filter_map(|number| self.store.block_header(BlockRef::Number(number)))
+            .fold(U256::from(0), |work, header| work + block_work(header.bits))
+    }
+
+    /// Chooses branch with most work. Branch is ignored unless it has more work than canon chain
+    fn select_best(&mut self) {
+        let mut canon_work = HashMap::new();
+        // index of branch end, its work and work of canon chain above the same fork point
+        let mut best: Option<(usize, U256, U256)> = None;
+        for (index, entry) in self.headers.iter().enumerate() {
+            let fork_height = entry.position.fork_height;
+            let canon = *canon_work.entry(fork_height).or_insert_with(|| self.canon_work_above(fork_height));
+            if entry.position.work <= canon {
+                continue;
+            }
+            // compared branches may start at different heights, so canon work above each of them is taken into account.
+            // When work is the same, branch received first is kept
+            let is_better = match best {
+                Some((_, work, best_canon)) => entry.position.work + best_canon > work + canon,
+                None => true,
+            };
+            if is_better {
+                best = Some((index, entry.position.work, canon));
+            }
+        }
+
+        let branch = {
+            let mut branch = Vec::new();
+            let mut next = best.map(|(index, _, _)| &self.headers[index]);
+            while let Some(entry) = next {
+                branch.push(entry.header.hash.clone());
+                next = self.entry(&entry.header.raw.previous_header_hash);
+            }
+            branch.reverse();
+            branch
+        };
+        self.best = branch;
+    }
+
+    fn verify(&self, header: &IndexedBlockHeader, height: u32) -> Result<(), Error> {
+        HeaderVerifier::new(header, &self.consensus, unix_time() as u32).check()?;
+        let provider = HeaderChainProvider {
+            chain: self,
+            tip: header.raw.previous_header_hash.clone(),
+            tip_height: height - 1,
+        };
+        HeaderAcceptor::new(&provider, &self.consensus, CanonHeader::new(header), height).check()
+    }
+}
+
+/// Looks for ancestors of verified header in header chain and then in store
+struct HeaderChainProvider<'a> {
+    chain: &'a HeaderChain,
+    /// Parent of verified header
+    tip: H256,
+    tip_height: u32,
+}
+
+impl<'a> BlockHeaderProvider for HeaderChainProvider<'a> {
+    fn block_header_bytes(&self, block_ref: BlockRef) -> Option<Bytes> {
+        self.block_header(block_ref).map(|header| serialize(&header))
+    }
+
+    fn block_header(&self, block_ref: BlockRef) -> Option<BlockHeader> {
+        let number = match block_ref {
+            BlockRef::Hash(hash) => {
+                return match self.chain.entry(&hash) {
+                    Some(entry) => Some(entry.header.raw.clone()),
+                    None => self.chain.store.block_header(hash.into()),
+                };
+            }
+            BlockRef::Number(number) if number <= self.tip_height => number,
+            BlockRef::Number(_) => return None,
+        };
+
+        // ancestor at given height may be in side branch, so branch is followed down to canon chain
+        let mut hash = self.tip.clone();
+        let mut height = self.tip_height;
+        loop {
+            let header = match self.chain.entry(&hash) {
+                Some(entry) => entry.header.raw.clone(),
+                None if self.chain.store.block_number(&hash).is_some() => {
+                    return self.chain.store.block_header(BlockRef::Number(number));
+                }
+                None => self.chain.store.block_header(hash.into())?,
+            };
+            if height == number {
+                return Some(header);
+            }
+            hash = header.previous_header_hash;
+            height -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use chain::{Block, BlockHeader, IndexedBlock};
+    use db::kv::MemoryDatabase;
+    use db::{BlockChainDatabase, SharedStore};
+    use params::{ConsensusFork, ConsensusParams, NetworkParams};
+    use primitives::hash::H256;
+    use verification::Error;
+    use acceptor::store_block;
+    use super::{HeaderChain, HeadersError};
+
+    /// Lowest difficulty, used by genesis block
+    const EASY_BITS: u32 = 0x1f00ffff;
+    /// 256 times more work than easy bits
+    const HARD_BITS: u32 = 0x1e00ffff;
+
+    type TestStore = Arc<BlockChainDatabase<MemoryDatabase>>;
+
+    fn block(parent: &H256, nonce: u32, bits: u32) -> IndexedBlock {
+        let header = BlockHeader {
+            version: 1,
+            previous_header_hash: parent.clone(),
+            merkle_root_hash: H256::default(),
+            witness_merkle_root_hash: H256::default(),
+            time: 1234567 + nonce,
+            bits: bits.into(),
+            nonce,
+        };
+        Block::new(header, vec![]).into()
+    }
+
+    fn header(parent: &H256, nonce: u32, bits: u32) -> BlockHeader {
+        block(parent, nonce, bits).header.raw
+    }
+
+    /// Headers of blocks following each other, starting from given parent
+    fn branch(parent: &H256, first_nonce: u32, length: u32, bits: u32) -> Vec<BlockHeader> {
+        let mut parent = parent.clone();
+        (first_nonce..first_nonce + length)
+            .map(|nonce| {
+                let header = header(&parent, nonce, bits);
+                parent = header.hash();
+                header
+            })
+            .collect()
+    }
+
+    fn hashes(headers: &[BlockHeader]) -> Vec<H256> {
+        headers.iter().map(BlockHeader::hash).collect()
+    }
+
+    /// Store with genesis and canon blocks following it
+    fn store(canon_length: u32) -> (TestStore, H256) {
+        let genesis: IndexedBlock = NetworkParams::Mainnet.genesis_block().into();
+        let genesis_hash = genesis.hash().clone();
+        let store = Arc::new(BlockChainDatabase::init_test_chain(vec![genesis]));
+        let mut parent = genesis_hash.clone();
+        for nonce in 0..canon_length {
+            let block = block(&parent, nonce, EASY_BITS);
+            parent = block.hash().clone();
+            store.insert(block).unwrap();
+            store.canonize(&parent).unwrap();
+        }
+        (store, genesis_hash)
+    }
+
+    fn header_chain(store: &TestStore) -> HeaderChain {
+        let shared: SharedStore = store.clone();
+        HeaderChain::new(shared, ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork))
+    }
+
+    #[test]
+    fn headers_extending_best_block_are_best_branch() {
+        let (store, genesis) = store(0);
+        let mut header_chain = header_chain(&store);
+        let headers = branch(&genesis, 100, 2, EASY_BITS);
+
+        assert_eq!(header_chain.insert(headers.clone()), Ok(hashes(&headers)));
+        assert_eq!(header_chain.best_branch(), &hashes(&headers)[..]);
+        assert_eq!(header_chain.first(), Some(&headers[0].hash()));
+        assert_eq!(header_chain.best_height(), 2);
+        assert_eq!(header_chain.height(&headers[1].hash()), Some(2));
+        assert!(header_chain.contains(&headers[1].hash()));
+        // known headers are skipped
+        assert_eq!(header_chain.insert(headers), Ok(vec![]));
+    }
+
+    #[test]
+    fn unconnected_header_is_rejected() {
+        let (store, genesis) = store(0);
+        let mut header_chain = header_chain(&store);
+        let connected = header(&genesis, 100, EASY_BITS);
+        let unconnected = header(&H256::from(1), 101, EASY_BITS);
+
+        let result = header_chain.insert(vec![connected.clone(), unconnected.clone()]);
+        assert_eq!(result, Err(HeadersError::Unconnected(unconnected.hash())));
+        // headers preceding unconnected one are kept
+        assert_eq!(header_chain.best_branch(), &[connected.hash()]);
+        assert!(!header_chain.contains(&unconnected.hash()));
+    }
+
+    #[test]
+    fn invalid_header_is_rejected() {
+        let (store, genesis) = store(0);
+        let shared: SharedStore = store.clone();
+        let mut header_chain = HeaderChain::new(shared, ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+        // target of these bits is above network maximum
+        let header = header(&genesis, 100, 0x2100ffff);
+
+        assert_eq!(header_chain.insert(vec![header.clone()]), Err(HeadersError::Invalid(header.hash(), Error::Pow)));
+        assert!(header_chain.is_empty());
+    }
+
+    #[test]
+    fn fork_becomes_best_branch_once_it_has_more_work() {
+        let (store, genesis) = store(2);
+        let mut header_chain = header_chain(&store);
+        let fork = branch(&genesis, 100, 3, EASY_BITS);
+
+        header_chain.insert(fork[..2].to_vec()).unwrap();
+        // fork has as much work as canon chain
+        assert!(header_chain.is_empty());
+        assert_eq!(header_chain.best_height(), 2);
+        assert_eq!(header_chain.height(&fork[1].hash()), Some(2));
+
+        header_chain.insert(fork[2..].to_vec()).unwrap();
+        assert_eq!(header_chain.best_branch(), &hashes(&fork)[..]);
+        assert_eq!(header_chain.best_height(), 3);
+    }
+
+    #[test]
+    fn branch_with_most_work_wins_over_longer_one() {
+        let (store, genesis) = store(0);
+        let mut header_chain = header_chain(&store);
+        let long = branch(&genesis, 100, 3, EASY_BITS);
+        let heavy = branch(&genesis, 200, 1, HARD_BITS);
+
+        header_chain.insert(long.clone()).unwrap();
+        assert_eq!(header_chain.best_branch(), &hashes(&long)[..]);
+
+        header_chain.insert(heavy.clone()).unwrap();
+        assert_eq!(header_chain.best_branch(), &hashes(&heavy)[..]);
+        assert_eq!(header_chain.best_height(), 1);
+        assert!(!header_chain.contains(&long[0].hash()));
+    }
+
+    #[test]
+    fn branch_received_first_wins_when_work_is_equal() {
+        let (store, genesis) = store(0);
+        let mut header_chain = header_chain(&store);
+        let first = branch(&genesis, 100, 2, EASY_BITS);
+        let second = branch(&genesis, 200, 2, EASY_BITS);
+
+        header_chain.insert(first.clone()).unwrap();
+        header_chain.insert(second).unwrap();
+        assert_eq!(header_chain.best_branch(), &hashes(&first)[..]);
+    }
+
+    #[test]
+    fn branch_may_start_from_stored_side_chain() {
+        let (store, genesis) = store(2);
+        let side_block = block(&genesis, 100, EASY_BITS);
+        let side_hash = side_block.hash().clone();
+        store.insert(side_block).unwrap();
+
+        let mut header_chain = header_chain(&store);
+        let fork = branch(&side_hash, 101, 2, EASY_BITS);
+        header_chain.insert(fork.clone()).unwrap();
+        assert_eq!(header_chain.best_branch(), &hashes(&fork)[..]);
+        assert_eq!(header_chain.height(&fork[1].hash()), Some(3));
+    }
+
+    #[test]
+    fn stored_blocks_are_pruned_after_reorganization() {
+        let (store, genesis) = store(1);
+        let mut header_chain = header_chain(&store);
+        let fork: Vec<IndexedBlock> = {
+            let first = block(&genesis, 100, EASY_BITS);
+            let second = block(first.hash(), 101, EASY_BITS);
+            vec![first, second]
+        };
+        let fork_hashes: Vec<H256> = fork.iter().map(|block| block.hash().clone()).collect();
+        header_chain.insert(fork.iter().map(|block| block.header.raw.clone()).collect()).unwrap();
+        assert_eq!(header_chain.best_branch(), &fork_hashes[..]);
+
+        // blocks are stored the way acceptor stores them. First block of fork is stored in side chain
+        let shared: SharedStore = store.clone();
+        store_block(&shared, fork[0].clone()).unwrap();
+        header_chain.prune();
+        assert_eq!(header_chain.best_branch(), &fork_hashes[1..]);
+        assert_eq!(header_chain.best_height(), 2);
+
+        // second block makes fork canon
+        store_block(&shared, fork[1].clone()).unwrap();
+        assert_eq!(store.best_block().hash, fork_hashes[1]);
+        header_chain.prune();
+        assert!(header_chain.is_empty());
+        assert_eq!(header_chain.best_height(), 2);
+    }
+
+    #[test]
+    fn invalid_block_removes_its_descendants() {
+        let (store, genesis) = store(0);
+        let mut header_chain = header_chain(&store);
+        let headers = branch(&genesis, 100, 3, EASY_BITS);
+        header_chain.insert(headers.clone()).unwrap();
+
+        header_chain.invalidate(&headers[1].hash());
+        assert_eq!(header_chain.best_branch(), &[headers[0].hash()]);
+        assert_eq!(header_chain.height(&headers[2].hash()), None);
+    }
+
+    #[test]
+    fn locator_starts_from_best_branch() {
+        let (store, genesis) = store(0);
+        let mut header_chain = header_chain(&store);
+        let headers = branch(&genesis, 100, 2, EASY_BITS);
+        header_chain.insert(headers.clone()).unwrap();
+
+        let locator = header_chain.locator_hashes();
+        assert_eq!(locator[0], headers[1].hash());
+        assert_eq!(locator[1], headers[0].hash());
+        assert_eq!(locator.last(), Some(&genesis));
+    }
+}
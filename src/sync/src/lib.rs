@@ -1,5 +1,7 @@
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serialization_derive;
 
 extern crate memory_pool;
 extern crate db;
@@ -11,16 +13,35 @@ extern crate p2p;
 extern crate bitcrypto as crypto;
 extern crate serialization as ser;
 extern crate primitives;
+extern crate keys;
+extern crate script;
 extern crate futures;
+extern crate parking_lot;
 extern crate futures_cpupool;
 extern crate tokio_core;
 
 pub mod acceptor;
+pub mod atomic_file;
+mod ban_list;
+mod block_importer;
+mod block_locator;
+mod compact_block;
+mod header_chain;
+pub mod executor;
 mod message_handler;
 mod message_wrapper;
+pub mod miner;
+mod orphan_blocks;
+mod orphan_transactions;
+pub mod peers;
 mod responder;
+mod synchronizer;
 
+pub use executor::Executor;
 pub use message_handler::MessageHandler;
 pub use message_wrapper::MessageWrapper;
+pub use peers::{PeerInfo, Peers, PeersRef};
+pub use ban_list::{BanList, BanListError};
 pub use responder::Responder;
-pub use acceptor::{Acceptor, AcceptorRef};
\ No newline at end of file
+pub use acceptor::{Acceptor, AcceptorRef};
+pub use miner::{block_template, Miner, MinerRef};
\ No newline at end of file
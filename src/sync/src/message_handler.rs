@@ -1,62 +1,328 @@
-use std::sync::mpsc::{Receiver, Sender};
-use message::MessageHeader;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use futures::Future;
+use message::{MessageHeader, HEADER_SIZE, MAX_PAYLOAD_SIZE};
 use message::{deserialize_payload, types, Error, Payload};
-use message::common::InventoryType;
+use message::common::{InventoryType, InventoryVector, Services};
 
+use chain::IndexedBlockHeader;
 use crypto::checksum;
 use db::SharedStore;
+use memory_pool::MemoryPoolRef;
+use primitives::hash::H256;
 use responder::ResponderTask;
-use p2p::{PeerAndBytes, PeerIndex};
+use p2p::{NetworkEvent, PeerAddress, PeerAndBytes, PeerIndex};
+use verification::{HeaderVerifier, TransactionError};
 use message_wrapper::MessageWrapper;
 use acceptor::AcceptorRef;
-use params::NetworkParams;
+use block_importer::{BlockImporter, ImportTask};
+use params::{ConsensusParams, NetworkParams};
+use peers::{random_nonce, unix_time, PeersRef};
+use peers::{DUPLICATE_VERSION_SCORE, INVALID_BLOCK_SCORE, INVALID_TRANSACTION_SCORE, MALFORMED_MESSAGE_SCORE,
+            OVERSIZED_MESSAGE_SCORE, UNSOLICITED_DATA_SCORE};
+use synchronizer::Synchronizer;
+use compact_block::PartialBlock;
+use parking_lot::Mutex;
+
+const USER_AGENT: &str = concat!("/rustheus:", env!("CARGO_PKG_VERSION"), "/");
+/// How often handshake and ping timeouts are checked
+const MAINTENANCE_INTERVAL_SECS: u64 = 5;
+/// Compact blocks of one peer waiting for their transactions. Further ones are requested in full
+const MAX_PARTIAL_BLOCKS_PER_PEER: usize = 2;
 
 pub struct MessageHandler {
-    network_data_receiver: Receiver<PeerAndBytes>,
+    network_data_receiver: Receiver<NetworkEvent>,
     store: SharedStore,
+    peers: PeersRef,
     network_responder: Sender<ResponderTask>,
     acceptor: AcceptorRef,
     message_wrapper: MessageWrapper,
     params: NetworkParams,
+    consensus: ConsensusParams,
+    /// Sent in our version messages to detect connections to self
+    local_nonce: u64,
+    synchronizer: Arc<Mutex<Synchronizer>>,
+    /// Verifies and stores blocks, so handler isn't blocked meanwhile
+    importer: Sender<ImportTask>,
+    /// Started in its own thread when handler starts running
+    block_importer: Option<BlockImporter>,
+    mempool: MemoryPoolRef,
+    /// Compact blocks waiting for transactions requested from peers which sent them
+    partial_blocks: Mutex<HashMap<H256, (PeerIndex, PartialBlock)>>,
+    /// Limits number of transactions in compact blocks
+    max_block_size: usize,
 }
 
 impl MessageHandler {
     pub fn new(
         store: SharedStore,
-        network_data_receiver: Receiver<PeerAndBytes>,
+        mempool: MemoryPoolRef,
+        peers: PeersRef,
+        network_data_receiver: Receiver<NetworkEvent>,
         network_responder: Sender<ResponderTask>,
         acceptor: AcceptorRef,
         message_wrapper: MessageWrapper,
-        params: NetworkParams,
+        consensus: ConsensusParams,
     ) -> Self {
-
+        let params = consensus.network;
+        let max_block_size = consensus.fork.max_block_size();
+        let (importer, import_task_receiver) = mpsc::channel();
+        let synchronizer = Arc::new(Mutex::new(Synchronizer::new(
+            store.clone(),
+            importer.clone(),
+            message_wrapper.clone(),
+            peers.clone(),
+            consensus.clone(),
+        )));
+        let block_importer = BlockImporter::new(
+            import_task_receiver,
+            acceptor.clone(),
+            message_wrapper.clone(),
+            synchronizer.clone(),
+        );
         MessageHandler {
             store,
+            peers,
             network_data_receiver,
             network_responder,
             acceptor,
             message_wrapper,
             params,
+            consensus,
+            local_nonce: random_nonce(),
+            synchronizer,
+            importer,
+            block_importer: Some(block_importer),
+            mempool,
+            partial_blocks: Mutex::new(HashMap::new()),
+            max_block_size,
         }
     }
 
-    fn on_transaction(&self, message: types::Tx) {
-        let _ = self.acceptor.accept_transaction(message.transaction);
+    fn on_peer_connected(&self, peer: PeerIndex, address: PeerAddress) {
+        if self.peers.is_banned(&address) {
+            info!("Disconnecting banned peer#{} {}", peer, address);
+            self.message_wrapper.disconnect(peer);
+            return;
+        }
+        self.peers.insert(peer, address);
+        let version = types::Version {
+            version: types::PROTOCOL_VERSION,
+            services: Services::default().with_network(true).with_witness(true),
+            timestamp: unix_time() as i64,
+            receiver: Default::default(),
+            sender: Default::default(),
+            nonce: self.local_nonce,
+            user_agent: USER_AGENT.into(),
+            start_height: self.store.best_block().number as i32,
+            relay: true,
+        };
+        self.message_wrapper.send(peer, &version);
+    }
+
+    fn on_peer_disconnected(&self, peer: PeerIndex) {
+        if let Some(info) = self.peers.remove(peer) {
+            debug!("Peer#{} {} is gone", peer, info.user_agent);
+        }
+        self.synchronizer.lock().on_peer_disconnected(peer);
+        self.partial_blocks.lock().retain(|_, &mut (requested_from, _)| requested_from != peer);
     }
 
-    fn on_block(&self, message: types::Block) {
-        let _ = self.acceptor.accept_block(message.block);
+    fn disconnect(&self, peer: PeerIndex, reason: &str) {
+        info!("Disconnecting peer#{}. Reason: {}", peer, reason);
+        self.peers.remove(peer);
+        self.message_wrapper.disconnect(peer);
+    }
+
+    fn on_version(&self, peer: PeerIndex, message: types::Version) {
+        if message.nonce == self.local_nonce {
+            return self.disconnect(peer, "connected to self");
+        }
+        if message.version < types::MIN_PROTOCOL_VERSION {
+            return self.disconnect(peer, "protocol version is too old");
+        }
+        if !self.peers.on_version(peer, &message) {
+            self.message_wrapper.misbehaving(peer, DUPLICATE_VERSION_SCORE, "Duplicate version message");
+            return;
+        }
+        info!("Peer#{} {} version {} at height {}", peer, message.user_agent, message.version, message.start_height);
+        self.message_wrapper.send(peer, &types::Verack);
+    }
+
+    fn on_verack(&self, peer: PeerIndex) {
+        if !self.peers.on_verack(peer) {
+            return;
+        }
+        debug!("Handshake with peer#{} completed", peer);
+        // only few peers are asked to push compact blocks right away, the rest announce new blocks with inv
+        let send_compact = types::SendCompact {
+            announce: self.peers.select_high_bandwidth(peer),
+            version: types::COMPACT_BLOCKS_VERSION,
+        };
+        self.message_wrapper.send(peer, &send_compact);
+        let peer_height = self.peers.info(peer).map_or(0, |info| info.start_height.max(0) as u32);
+        let mut synchronizer = self.synchronizer.lock();
+        synchronizer.on_peer_height(peer, peer_height);
+        if peer_height > synchronizer.best_height() {
+            synchronizer.request_headers(peer);
+        }
+    }
+
+    fn on_ping(&self, peer: PeerIndex, message: types::Ping) {
+        self.message_wrapper.send(peer, &types::Pong::new(message.nonce));
+    }
+
+    fn on_pong(&self, peer: PeerIndex, message: types::Pong) {
+        self.peers.on_pong(peer, message.nonce);
+    }
+
+    /// Drops peers which never completed handshake or went silent, pings idle ones
+    fn maintain_peers(&self) {
+        let now = unix_time();
+        for peer in self.peers.unresponsive(now) {
+            self.disconnect(peer, "peer is unresponsive");
+        }
+        for peer in self.peers.ping_candidates(now) {
+            let nonce = random_nonce();
+            self.peers.on_ping_sent(peer, nonce);
+            self.message_wrapper.send(peer, &types::Ping::new(nonce));
+        }
+        self.synchronizer.lock().maintain();
+    }
+
+    fn on_transaction(&self, peer: PeerIndex, message: types::Tx) {
+        let hash = message.transaction.hash();
+        // peer is not told about transaction it has sent
+        self.peers.on_inventory_known(peer, hash.clone());
+        match self.acceptor.accept_transaction(message.transaction).wait() {
+            // transaction is kept as orphan until peer sends its missing parents
+            Err(TransactionError::Input(_)) | Err(TransactionError::UnknownReference(_)) => {
+                let missing_parents = self.acceptor.missing_parents(&hash);
+                if !missing_parents.is_empty() {
+                    let inventory = missing_parents.into_iter().map(InventoryVector::witness_tx).collect();
+                    self.message_wrapper.send(peer, &types::GetData::with_inventory(inventory));
+                }
+            }
+            Err(ref err) if is_invalid_transaction(err) => {
+                let reason = format!("Invalid transaction: {:?}", err);
+                self.message_wrapper.misbehaving(peer, INVALID_TRANSACTION_SCORE, &reason);
+            }
+            _ => (),
+        }
+    }
+
+    fn on_block(&self, peer: PeerIndex, message: types::Block) {
+        let hash = message.block.hash();
+        self.peers.on_inventory_known(peer, hash.clone());
+        let mut synchronizer = self.synchronizer.lock();
+        if synchronizer.is_expected(&hash) {
+            return synchronizer.on_block(peer, message.block);
+        }
+        self.importer
+            .send(ImportTask::Received(peer, message.block))
+            .expect("block importer runs as long as message handler");
+    }
+
+    fn on_send_compact(&self, peer: PeerIndex, message: types::SendCompact) {
+        self.peers.on_send_compact(peer, &message);
+    }
+
+    fn on_compact_block(&self, peer: PeerIndex, message: types::CompactBlock) {
+        let hash = message.header.header.hash();
+        self.peers.on_inventory_known(peer, hash.clone());
+        if self.store.block_number(&hash).is_some() || self.acceptor.is_orphan(&hash) {
+            return;
+        }
+        // header is checked before anything is kept for it, so unmined blocks can't fill memory
+        let header: IndexedBlockHeader = message.header.header.clone().into();
+        if let Err(err) = HeaderVerifier::new(&header, &self.consensus, unix_time() as u32).check() {
+            let reason = format!("Invalid compact block header: {:?}", err);
+            self.message_wrapper.misbehaving(peer, INVALID_BLOCK_SCORE, &reason);
+            return;
+        }
+        let partial_block = match PartialBlock::new(message.header, &self.mempool.read(), self.max_block_size) {
+            Ok(partial_block) => partial_block,
+            Err(err) => {
+                let reason = format!("Invalid compact block: {:?}", err);
+                self.message_wrapper.misbehaving(peer, INVALID_BLOCK_SCORE, &reason);
+                return;
+            }
+        };
+        if partial_block.is_complete() {
+            return self.on_reconstructed_block(peer, partial_block);
+        }
+
+        let mut partial_blocks = self.partial_blocks.lock();
+        let pending = partial_blocks.values().filter(|&&(requested_from, _)| requested_from == peer).count();
+        if pending >= MAX_PARTIAL_BLOCKS_PER_PEER {
+            drop(partial_blocks);
+            debug!("Peer#{} has {} compact blocks waiting for transactions, requesting block {} in full",
+                peer, pending, hash.to_reversed_str());
+            let inventory = vec![InventoryVector::witness_block(hash)];
+            self.message_wrapper.send(peer, &types::GetData::with_inventory(inventory));
+            return;
+        }
+
+        let request = partial_block.missing_request();
+        trace!(target: "sync", "Requesting {} transactions of compact block {} from peer#{}",
+            request.indexes.len(), hash.to_reversed_str(), peer);
+        self.message_wrapper.send(peer, &types::GetBlockTxn { request });
+        partial_blocks.insert(hash, (peer, partial_block));
+    }
+
+    fn on_block_txn(&self, peer: PeerIndex, message: types::BlockTxn) {
+        let request = message.request;
+        let mut partial_blocks = self.partial_blocks.lock();
+        let is_requested = partial_blocks
+            .get(&request.blockhash)
+            .map_or(false, |&(requested_from, _)| requested_from == peer);
+        if !is_requested {
+            debug!("Peer#{} has sent transactions of block {} which weren't requested", peer, request.blockhash.to_reversed_str());
+            return;
+        }
+        let (_, mut partial_block) = partial_blocks.remove(&request.blockhash).expect("presence is checked above; qed");
+        drop(partial_blocks);
+
+        match partial_block.fill(request.transactions) {
+            Ok(()) => self.on_reconstructed_block(peer, partial_block),
+            Err(err) => {
+                let reason = format!("Invalid compact block transactions: {:?}", err);
+                self.message_wrapper.misbehaving(peer, INVALID_BLOCK_SCORE, &reason);
+            }
+        }
+    }
+
+    /// Block which doesn't match its header due to short id collision is requested in full
+    fn on_reconstructed_block(&self, peer: PeerIndex, partial_block: PartialBlock) {
+        let hash = partial_block.hash();
+        match partial_block.into_block() {
+            Ok(block) => self.on_block(peer, types::Block::with_block(block)),
+            Err(err) => {
+                debug!("Can't reconstruct compact block {}: {:?}", hash.to_reversed_str(), err);
+                let inventory = vec![InventoryVector::witness_block(hash)];
+                self.message_wrapper.send(peer, &types::GetData::with_inventory(inventory));
+            }
+        }
     }
 
     fn on_inv(&self, peer_index: PeerIndex, message: types::Inv) {
+        for item in &message.inventory {
+            self.peers.on_inventory_known(peer_index, item.hash.clone());
+        }
         let unknown_inventory: Vec<_> = message
             .inventory
             .into_iter()
             .filter(|item| {
                 match item.inv_type {
                     // check that transaction is unknown to us
-                    InventoryType::MessageTx => self.store.transaction(&item.hash).is_none(),
-                    InventoryType::MessageBlock => self.store.block_number(&item.hash).is_none(), //check is block is known
+                    InventoryType::MessageTx => !self.acceptor.knows_transaction(&item.hash),
+                    InventoryType::MessageBlock => {
+                        // check that block is neither stored nor waiting for its parent
+                        self.store.block_number(&item.hash).is_none() && !self.acceptor.is_orphan(&item.hash)
+                    }
                     // we never ask for merkle blocks && we never ask for compact blocks
                     InventoryType::MessageCompactBlock
                     | InventoryType::MessageFilteredBlock
@@ -80,18 +346,42 @@ impl MessageHandler {
 
         trace!(target: "handler", "unknown items are {:?}", unknown_inventory);
 
-        // ask for unknown items
-        let message = types::GetData::with_inventory(unknown_inventory);
+        // ask for unknown items together with their witness data
+        let inventory = unknown_inventory
+            .into_iter()
+            .map(|item| match item.inv_type {
+                InventoryType::MessageTx => InventoryVector::witness_tx(item.hash),
+                InventoryType::MessageBlock => InventoryVector::witness_block(item.hash),
+                _ => item,
+            })
+            .collect();
+        let message = types::GetData::with_inventory(inventory);
         self.message_wrapper.send(peer_index, &message);
     }
 
+    fn on_headers(&self, peer: PeerIndex, message: types::Headers) {
+        self.synchronizer.lock().on_headers(peer, message.headers);
+    }
+
     //TODO maybe move following methods to separate handler
+    fn on_get_headers(&self, peer: PeerIndex, message: types::GetHeaders) {
+        self.network_responder
+            .send(ResponderTask::GetHeaders(peer, message))
+            .unwrap();
+    }
+
     fn on_get_blocks(&self, peer: PeerIndex, message: types::GetBlocks) {
         self.network_responder
             .send(ResponderTask::GetBlocks(peer, message))
             .unwrap();
     }
 
+    fn on_get_block_txn(&self, peer: PeerIndex, message: types::GetBlockTxn) {
+        self.network_responder
+            .send(ResponderTask::GetBlockTxn(peer, message))
+            .unwrap();
+    }
+
     fn on_get_data(&self, peer: PeerIndex, message: types::GetData) {
         self.network_responder
             .send(ResponderTask::GetData(peer, message))
@@ -108,26 +398,86 @@ impl MessageHandler {
             return Err(Error::InvalidChecksum);
         }
 
-        if header.command == types::Tx::command() {
-            let message: types::Tx = try!(deserialize_payload(payload, 0));
+        self.peers.touch(peer);
+        let version = self.peers.version(peer);
+
+        if header.command == types::Version::command() {
+            let message: types::Version = try!(deserialize_payload(payload, version));
+            trace!(target: "handler", "received version {:?}", message);
+            self.on_version(peer, message);
+            return Ok(());
+        } else if header.command == types::Verack::command() {
+            self.on_verack(peer);
+            return Ok(());
+        }
+
+        if !self.peers.is_handshaked(peer) {
+            let is_data = header.command == types::Tx::command()
+                || header.command == types::Block::command()
+                || header.command == types::CompactBlock::command();
+            if is_data {
+                let reason = format!("Got '{}' message before handshake", header.command);
+                self.message_wrapper.misbehaving(peer, UNSOLICITED_DATA_SCORE, &reason);
+            } else {
+                debug!("Ignoring {} from peer#{} before handshake", header.command, peer);
+            }
+            return Ok(());
+        }
+
+        if header.command == types::Ping::command() {
+            let message: types::Ping = try!(deserialize_payload(payload, version));
+            self.on_ping(peer, message);
+        } else if header.command == types::Pong::command() {
+            let message: types::Pong = try!(deserialize_payload(payload, version));
+            self.on_pong(peer, message);
+        } else if header.command == types::Tx::command() {
+            let message: types::Tx = try!(deserialize_payload(payload, version));
             trace!(target: "handler", "received tx {:?}", message);
-            self.on_transaction(message);
+            self.on_transaction(peer, message);
         } else if header.command == types::Block::command() {
-            let message: types::Block = try!(deserialize_payload(payload, 0));
+            let message: types::Block = try!(deserialize_payload(payload, version));
             trace!(target: "handler", "received block {:?}", message);
-            self.on_block(message);
+            self.on_block(peer, message);
         } else if header.command == types::GetBlocks::command() {
-            let message: types::GetBlocks = try!(deserialize_payload(payload, 0));
+            let message: types::GetBlocks = try!(deserialize_payload(payload, version));
             trace!(target: "handler", "received getblocks {:?}", message);
             self.on_get_blocks(peer, message);
+        } else if header.command == types::GetHeaders::command() {
+            let message: types::GetHeaders = try!(deserialize_payload(payload, version));
+            trace!(target: "handler", "received getheaders {:?}", message);
+            self.on_get_headers(peer, message);
+        } else if header.command == types::Headers::command() {
+            let message: types::Headers = try!(deserialize_payload(payload, version));
+            trace!(target: "handler", "received {} headers", message.headers.len());
+            self.on_headers(peer, message);
         } else if header.command == types::Inv::command() {
-            let message: types::Inv = try!(deserialize_payload(payload, 0));
+            let message: types::Inv = try!(deserialize_payload(payload, version));
             trace!(target: "handler", "received inv {:?}", message);
             self.on_inv(peer, message);
         } else if header.command == types::GetData::command() {
-            let message: types::GetData = try!(deserialize_payload(payload, 0));
+            let message: types::GetData = try!(deserialize_payload(payload, version));
             trace!(target: "handler", "received getdata {:?}", message);
             self.on_get_data(peer, message);
+        } else if header.command == types::NotFound::command() {
+            // blocks which are not delivered are requested again from other peers after timeout
+            let message: types::NotFound = try!(deserialize_payload(payload, version));
+            debug!("Peer#{} doesn't have {} of requested items", peer, message.inventory.len());
+        } else if header.command == types::SendCompact::command() {
+            let message: types::SendCompact = try!(deserialize_payload(payload, version));
+            trace!(target: "handler", "received sendcmpct {:?}", message);
+            self.on_send_compact(peer, message);
+        } else if header.command == types::CompactBlock::command() {
+            let message: types::CompactBlock = try!(deserialize_payload(payload, version));
+            trace!(target: "handler", "received cmpctblock {:?}", message);
+            self.on_compact_block(peer, message);
+        } else if header.command == types::GetBlockTxn::command() {
+            let message: types::GetBlockTxn = try!(deserialize_payload(payload, version));
+            trace!(target: "handler", "received getblocktxn {:?}", message);
+            self.on_get_block_txn(peer, message);
+        } else if header.command == types::BlockTxn::command() {
+            let message: types::BlockTxn = try!(deserialize_payload(payload, version));
+            trace!(target: "handler", "received blocktxn for block {}", message.request.blockhash.to_reversed_str());
+            self.on_block_txn(peer, message);
         }
         Ok(())
     }
@@ -135,32 +485,65 @@ impl MessageHandler {
 
 impl MessageHandler {
     pub fn run(&mut self) {
+        if let Some(block_importer) = self.block_importer.take() {
+            thread::spawn(move || block_importer.run());
+        }
+        let maintenance_interval = Duration::from_secs(MAINTENANCE_INTERVAL_SECS);
+        let mut last_maintenance = Instant::now();
         loop {
-            if let Ok(peer_and_bytes) = self.network_data_receiver.recv() {
-                let bytes = peer_and_bytes.bytes;
-                let peer = peer_and_bytes.peer;
-                //TODO check boundaries
-                let data_start = 24;
-                match MessageHeader::deserialize(&bytes[0..data_start], self.params.magic()) {
-                    Ok(header) => {
-                        let data_end = data_start + header.len as usize;
-                        let data = &bytes[data_start..data_end];
-                        if let Err(err) = self.on_message(peer, header, data) {
-                            error!(
-                                "Unable to deserialize received message body. Reason: {:?}",
-                                err
-                            )
-                        }
-                    }
-                    Err(err) => error!(
-                        "Unable to deserialize received message header. Reason: {:?}",
-                        err
-                    ),
+            match self.network_data_receiver.recv_timeout(maintenance_interval) {
+                Ok(NetworkEvent::PeerConnected(peer, address)) => self.on_peer_connected(peer, address),
+                Ok(NetworkEvent::PeerDisconnected(peer)) => self.on_peer_disconnected(peer),
+                Ok(NetworkEvent::Message(peer_and_bytes)) => self.on_raw_message(peer_and_bytes),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    debug!("message handler thread finished");
+                    break;
                 }
-            } else {
-                debug!("message handler thread finished");
-                break;
             }
+            if last_maintenance.elapsed() >= maintenance_interval {
+                self.maintain_peers();
+                last_maintenance = Instant::now();
+            }
+        }
+    }
+
+    fn on_raw_message(&self, peer_and_bytes: PeerAndBytes) {
+        let bytes = peer_and_bytes.bytes;
+        let peer = peer_and_bytes.peer;
+        if bytes.len() < HEADER_SIZE {
+            return self.message_wrapper.misbehaving(peer, MALFORMED_MESSAGE_SCORE, "Message is shorter than header");
+        }
+        let header = match MessageHeader::deserialize(&bytes[0..HEADER_SIZE], self.params.magic()) {
+            Ok(header) => header,
+            Err(err) => {
+                let reason = format!("Unable to deserialize received message header. Reason: {:?}", err);
+                return self.message_wrapper.misbehaving(peer, MALFORMED_MESSAGE_SCORE, &reason);
+            }
+        };
+        if header.len > MAX_PAYLOAD_SIZE {
+            let reason = format!("Message payload of {} bytes is too big", header.len);
+            return self.message_wrapper.misbehaving(peer, OVERSIZED_MESSAGE_SCORE, &reason);
         }
+        let data_end = HEADER_SIZE + header.len as usize;
+        if data_end != bytes.len() {
+            return self.message_wrapper.misbehaving(peer, MALFORMED_MESSAGE_SCORE, "Message length doesn't match header");
+        }
+        if let Err(err) = self.on_message(peer, header, &bytes[HEADER_SIZE..data_end]) {
+            let reason = format!("Unable to deserialize received message body. Reason: {:?}", err);
+            self.message_wrapper.misbehaving(peer, MALFORMED_MESSAGE_SCORE, &reason);
+        }
+    }
+}
+
+fn is_invalid_transaction(err: &TransactionError) -> bool {
+    match *err {
+        TransactionError::Input(_)
+        | TransactionError::UnknownReference(_)
+        | TransactionError::UsingSpentOutput(_, _)
+        | TransactionError::Maturity
+        | TransactionError::PrematureWitness => false,
+        _ => true,
     }
 }
+
@@ -3,18 +3,25 @@ use std::sync::mpsc::Sender;
 use params::NetworkParams;
 use ser::SERIALIZE_TRANSACTION_WITNESS;
 use message::Message;
-use p2p::{PeerAndBytes, PeerIndex, XorName};
+use message::common::{InventoryType, InventoryVector};
+use message::types::{CompactBlock, Inv};
+use chain::Block;
+use compact_block::build_compact_block;
+use p2p::{NetworkRequest, PeerAndBytes, PeerIndex, BROADCAST_PEER};
+use peers::{random_nonce, PeersRef};
 
 #[derive(Clone)]
 pub struct MessageWrapper {
     network_params: NetworkParams,
-    network_channel: Sender<PeerAndBytes>,
+    peers: PeersRef,
+    network_channel: Sender<NetworkRequest>,
 }
 
 impl MessageWrapper {
-    pub fn new(network_params: NetworkParams, network_channel: Sender<PeerAndBytes>, ) -> Self {
+    pub fn new(network_params: NetworkParams, peers: PeersRef, network_channel: Sender<NetworkRequest>) -> Self {
         MessageWrapper {
             network_params,
+            peers,
             network_channel,
         }
     }
@@ -23,35 +30,162 @@ impl MessageWrapper {
     where
         T: Payload, //TODO use moving here instead of borrowing
     {
-        let version = 0;
-        let message = Message::with_flags(
-            self.network_params.magic(),
-            version,
-            payload,
-            SERIALIZE_TRANSACTION_WITNESS,
-        ).expect("failed to create outgoing message");
-        let peer_and_bytes = PeerAndBytes {
-            peer: XorName::default(),
-            bytes: message.as_ref().into(),
-        };
-        self.network_channel.send(peer_and_bytes).unwrap();
+        let version = self.peers.broadcast_version();
+        self.send_with_version(BROADCAST_PEER, version, payload, SERIALIZE_TRANSACTION_WITNESS);
     }
 
     pub fn send<T>(&self, peer: PeerIndex, payload: &T)
     where
         T: Payload, //TODO use moving here instead of borrowing
     {
-        let version = 0;
+        let version = self.peers.version(peer);
+        self.send_with_version(peer, version, payload, SERIALIZE_TRANSACTION_WITNESS);
+    }
+
+    /// Sends transactions stripped of witness data, which peer hasn't asked for
+    pub fn send_without_witness<T: Payload>(&self, peer: PeerIndex, payload: &T) {
+        let version = self.peers.version(peer);
+        self.send_with_version(peer, version, payload, 0);
+    }
+
+    /// Announces transaction or block to peers, which don't know it yet
+    pub fn relay(&self, item: InventoryVector) {
+        let is_transaction = item.inv_type == InventoryType::MessageTx;
+        let targets = self.peers.announce_targets(&item.hash, is_transaction);
+        if !targets.is_empty() {
+            trace!(target: "sync", "Announcing {:?} {} to {} peers", item.inv_type, item.hash.to_reversed_str(), targets.len());
+        }
+        let message = Inv::with_inventory(vec![item]);
+        for peer in targets {
+            self.send(peer, &message);
+        }
+    }
+
+    /// Announces accepted block. Peers preferring compact blocks receive it right away, others get inv
+    pub fn relay_compact_block(&self, block: &Block) {
+        let hash = block.hash();
+        let targets = self.peers.announce_targets(&hash, false);
+        if targets.is_empty() {
+            return;
+        }
+        let compact_block = CompactBlock {
+            header: build_compact_block(block, random_nonce()),
+        };
+        let inv = Inv::with_inventory(vec![InventoryVector::block(hash)]);
+        for peer in targets {
+            if self.peers.prefers_compact_blocks(peer) {
+                self.send(peer, &compact_block);
+            } else {
+                self.send(peer, &inv);
+            }
+        }
+    }
+
+    pub fn disconnect(&self, peer: PeerIndex) {
+        self.network_channel.send(NetworkRequest::Disconnect(peer)).unwrap();
+    }
+
+    /// Scores peer's offence, disconnecting peer once it gets banned
+    pub fn misbehaving(&self, peer: PeerIndex, score: u32, reason: &str) {
+        if self.peers.misbehaving(peer, score, reason) {
+            self.disconnect(peer);
+        }
+    }
+
+    fn send_with_version<T: Payload>(&self, peer: PeerIndex, version: u32, payload: &T, flags: u32) {
         let message = Message::with_flags(
             self.network_params.magic(),
             version,
             payload,
-            SERIALIZE_TRANSACTION_WITNESS,
+            flags,
         ).expect("failed to create outgoing message");
         let peer_and_bytes = PeerAndBytes {
             peer,
             bytes: message.as_ref().into(),
         };
-        self.network_channel.send(peer_and_bytes).unwrap();
+        self.network_channel.send(NetworkRequest::Send(peer_and_bytes)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::mpsc::{self, Receiver};
+    use message::common::{InventoryVector, Services};
+    use message::types::{Inv, Version, PROTOCOL_VERSION};
+    use message::{deserialize_payload, MessageHeader, HEADER_SIZE};
+    use p2p::{NetworkRequest, PeerAndBytes, PeerIndex};
+    use params::NetworkParams;
+    use primitives::hash::H256;
+    use ban_list::BanList;
+    use peers::{Peers, PeersRef};
+    use super::MessageWrapper;
+
+    fn version(relay: bool) -> Version {
+        Version {
+            version: PROTOCOL_VERSION,
+            services: Services::default().with_network(true),
+            timestamp: 0,
+            receiver: Default::default(),
+            sender: Default::default(),
+            nonce: 1,
+            user_agent: "/test/".into(),
+            start_height: 10,
+            relay,
+        }
+    }
+
+    /// Wrapper talking to handshaked peers, which relay transactions as given
+    fn wrapper(relay: &[bool]) -> (MessageWrapper, PeersRef, Receiver<NetworkRequest>) {
+        let peers = Arc::new(Peers::new(BanList::in_memory(), 60));
+        for (peer, relay) in relay.iter().enumerate() {
+            peers.insert(peer, format!("127.0.0.{}:8333", peer + 1));
+            peers.on_version(peer, &version(*relay));
+            peers.on_verack(peer);
+        }
+        let (sender, receiver) = mpsc::channel();
+        (MessageWrapper::new(NetworkParams::Mainnet, peers.clone(), sender), peers, receiver)
+    }
+
+    /// Inventory announced to each peer since last call, in order of peers
+    fn announced(network: &Receiver<NetworkRequest>) -> Vec<(PeerIndex, Vec<InventoryVector>)> {
+        let mut announced: Vec<_> = network
+            .try_iter()
+            .map(|request| match request {
+                NetworkRequest::Send(PeerAndBytes { peer, bytes }) => {
+                    let header = MessageHeader::deserialize(&bytes[..HEADER_SIZE], NetworkParams::Mainnet.magic()).unwrap();
+                    assert_eq!(header.command, "inv");
+                    let inv: Inv = deserialize_payload(&bytes[HEADER_SIZE..], PROTOCOL_VERSION).unwrap();
+                    (peer, inv.inventory)
+                }
+                NetworkRequest::Disconnect(peer) => panic!("peer#{} is unexpectedly disconnected", peer),
+            })
+            .collect();
+        announced.sort_by_key(|&(peer, _)| peer);
+        announced
+    }
+
+    #[test]
+    fn transaction_is_announced_to_relaying_peers() {
+        let (wrapper, _, network) = wrapper(&[true, false, true]);
+        let item = InventoryVector::tx(H256::from(1));
+
+        wrapper.relay(item.clone());
+        assert_eq!(announced(&network), vec![(0, vec![item.clone()]), (2, vec![item])]);
+    }
+
+    #[test]
+    fn known_inventory_is_not_announced_back() {
+        let (wrapper, peers, network) = wrapper(&[true, true]);
+        let item = InventoryVector::tx(H256::from(1));
+        // peer#1 has sent us the transaction
+        peers.on_inventory_known(1, item.hash.clone());
+
+        wrapper.relay(item.clone());
+        assert_eq!(announced(&network), vec![(0, vec![item.clone()])]);
+
+        // peer#0 knows it now too
+        wrapper.relay(item);
+        assert!(announced(&network).is_empty());
     }
 }
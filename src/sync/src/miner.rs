@@ -0,0 +1,242 @@
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use chain::{Block, BlockHeader, Transaction, TransactionInput, TransactionOutput};
+use crypto::dhash256;
+use db::SharedStore;
+use futures::{future, Future};
+use futures_cpupool::CpuPool;
+use keys::Address;
+use memory_pool::{BlockAssembler, BlockTemplate, MemoryPoolRef};
+use params::ConsensusParams;
+use primitives::bytes::Bytes;
+use primitives::hash::H256;
+use script::Builder;
+use verification::{is_valid_proof_of_work_hash, median_timestamp_inclusive};
+use acceptor::AcceptorRef;
+
+/// Prefix of coinbase output committing to witness merkle root, as in BIP141
+const WITNESS_COMMITMENT_HEADER: [u8; 4] = [0xaa, 0x21, 0xa9, 0xed];
+/// Nonce ranges searched at once, spread over pool threads
+const MINING_JOBS: u64 = 8;
+/// Nonces tried by single job. Miner checks for competing blocks after every round of jobs
+const NONCES_PER_JOB: u64 = 1 << 14;
+
+pub type MinerRef = Arc<Miner>;
+
+/// CPU miner searching for blocks on top of our best block
+pub struct Miner {
+    store: SharedStore,
+    mempool: MemoryPoolRef,
+    acceptor: AcceptorRef,
+    consensus: ConsensusParams,
+    cpupool: CpuPool,
+}
+
+impl Miner {
+    pub fn new(
+        store: SharedStore,
+        mempool: MemoryPoolRef,
+        acceptor: AcceptorRef,
+        consensus: ConsensusParams,
+        cpupool: CpuPool,
+    ) -> Self {
+        Miner {
+            store,
+            mempool,
+            acceptor,
+            consensus,
+            cpupool,
+        }
+    }
+
+    /// Mines blocks one after another until stopped or until mined block is rejected
+    pub fn run(&self, coinbase_recipient: &Address, stop: &AtomicBool) {
+        info!("Mining started");
+        while self.mine_block(coinbase_recipient, stop).is_some() {}
+        info!("Mining stopped");
+    }
+
+    /// Mines single block and submits it to acceptor. Work is started over when competing block arrives
+    pub fn mine_block(&self, coinbase_recipient: &Address, stop: &AtomicBool) -> Option<H256> {
+        while !stop.load(Ordering::SeqCst) {
+            let template = block_template(&self.store, &self.mempool, &self.consensus);
+            let block = match self.search(&template, coinbase_recipient, stop) {
+                Some(block) => block,
+                None => {
+                    debug!("Abandoned mining on top of block {}", template.previous_header_hash);
+                    continue;
+                }
+            };
+            // mined transactions stay in mempool until acceptor stores the block
+            return match self.acceptor.accept_block(block).wait() {
+                Ok(hash) => {
+                    info!("Mined block {} at height {}", hash, template.height);
+                    Some(hash)
+                }
+                Err(err) => {
+                    error!("Mined block has been rejected: {:?}", err);
+                    None
+                }
+            };
+        }
+        None
+    }
+
+    /// Iterates nonce and then extranonce until block hash meets the target.
+    /// Gives up when best block changes or mining is stopped
+    fn search(&self, template: &BlockTemplate, coinbase_recipient: &Address, stop: &AtomicBool) -> Option<Block> {
+        let header = BlockHeader {
+            version: template.version,
+            previous_header_hash: template.previous_header_hash.clone(),
+            merkle_root_hash: Default::default(),
+            witness_merkle_root_hash: Default::default(),
+            time: template.time,
+            bits: template.bits,
+            nonce: 0,
+        };
+        let mut transactions = vec![Transaction::default()];
+        transactions.extend(template.transactions.iter().map(|transaction| transaction.raw.clone()));
+        let mut block = Block::new(header, transactions);
+        // witness root leaves coinbase out, so it is the same for every extranonce
+        let witness_merkle_root = block.witness_merkle_root();
+        block.block_header.witness_merkle_root_hash = witness_merkle_root.clone();
+
+        for extranonce in 0..u32::max_value() {
+            block.transactions[0] = create_coinbase(template, extranonce, &witness_merkle_root, coinbase_recipient);
+            block.block_header.merkle_root_hash = block.merkle_root();
+
+            let mut first_nonce = 0;
+            while first_nonce <= u64::from(u32::max_value()) {
+                if stop.load(Ordering::SeqCst) || self.store.best_block().hash != template.previous_header_hash {
+                    return None;
+                }
+                if let Some(nonce) = self.search_nonces(&block.block_header, first_nonce) {
+                    block.block_header.nonce = nonce;
+                    return Some(block);
+                }
+                first_nonce += MINING_JOBS * NONCES_PER_JOB;
+            }
+        }
+        None
+    }
+
+    /// Splits nonces following `first_nonce` between jobs running on the pool
+    fn search_nonces(&self, header: &BlockHeader, first_nonce: u64) -> Option<u32> {
+        let jobs: Vec<_> = (0..MINING_JOBS)
+            .map(|job| {
+                let header = header.clone();
+                let start = (first_nonce + job * NONCES_PER_JOB) as u32;
+                self.cpupool.spawn_fn(move || Ok::<_, ()>(find_nonce(header, start)))
+            })
+            .collect();
+        match future::join_all(jobs).wait() {
+            Ok(nonces) => nonces.into_iter().flatten().next(),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Block on top of our best block, filled with mempool transactions within consensus limits
+pub fn block_template(store: &SharedStore, mempool: &MemoryPoolRef, consensus: &ConsensusParams) -> BlockTemplate {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as u32;
+    // block has to be later than median time of its ancestors
+    let best_block = store.best_block();
+    let time = cmp::max(now, median_timestamp_inclusive(best_block.hash, store.as_block_header_provider()) + 1);
+
+    let max_block_size = consensus.fork.max_block_size();
+    let block_assembler = BlockAssembler {
+        max_block_size: max_block_size as u32,
+        max_block_sigops: consensus.fork.max_block_sigops(best_block.number + 1, max_block_size) as u32,
+    };
+    let mempool = mempool.read();
+    block_assembler.create_new_block(store, &mempool, time, consensus)
+}
+
+/// Coinbase collecting block reward and fees, which commits to witnesses of block transactions
+fn create_coinbase(template: &BlockTemplate, extranonce: u32, witness_merkle_root: &H256, recipient: &Address) -> Transaction {
+    //add block height as coinbase prefix, extranonce follows it
+    let script_sig = Builder::default()
+        .push_num(template.height.into())
+        .push_num(extranonce.into())
+        .into_script();
+
+    let mut input = TransactionInput::coinbase(script_sig.into());
+    let witness_reserved_value = H256::default();
+    input.script_witness = vec![Bytes::from(&witness_reserved_value[..])];
+
+    let mut commitment = WITNESS_COMMITMENT_HEADER.to_vec();
+    commitment.extend_from_slice(&dhash256(&[&witness_merkle_root[..], &witness_reserved_value[..]].concat())[..]);
+
+    Transaction {
+        version: 0,
+        inputs: vec![input],
+        outputs: vec![
+            TransactionOutput {
+                value: template.coinbase_value,
+                script_pubkey: Builder::build_p2pkh(&recipient.hash).to_bytes(),
+            },
+            TransactionOutput {
+                value: 0,
+                script_pubkey: Builder::default().return_bytes(&commitment).into_script().to_bytes(),
+            },
+        ],
+        lock_time: 0,
+    }
+}
+
+fn find_nonce(mut header: BlockHeader, first_nonce: u32) -> Option<u32> {
+    for offset in 0..NONCES_PER_JOB as u32 {
+        header.nonce = first_nonce + offset;
+        if is_valid_proof_of_work_hash(header.bits, &header.hash()) {
+            return Some(header.nonce);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crypto::dhash256;
+    use keys::Address;
+    use memory_pool::BlockTemplate;
+    use primitives::hash::H256;
+    use script::is_witness_commitment_script;
+    use super::create_coinbase;
+
+    #[test]
+    fn coinbase_pays_template_value_and_commits_to_witnesses() {
+        let template = BlockTemplate {
+            version: 1,
+            previous_header_hash: H256::from(1),
+            time: 1234567,
+            bits: 0x207fffff.into(),
+            height: 1,
+            transactions: vec![],
+            coinbase_value: 5_000,
+            size_limit: 1_000_000,
+            sigop_limit: 80_000,
+        };
+        let witness_merkle_root = H256::from(2);
+        let recipient = Address::from_str("1KFoaRnZLw9DYhNVMfft84YHAVbLMRmWv5").unwrap();
+        let coinbase = create_coinbase(&template, 0, &witness_merkle_root, &recipient);
+
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.outputs[0].value, 5_000);
+        let commitment = &coinbase.outputs[1];
+        assert_eq!(commitment.value, 0);
+        assert!(is_witness_commitment_script(&commitment.script_pubkey));
+
+        // witness reserved value is the only witness item of coinbase input
+        let witness = &coinbase.inputs[0].script_witness;
+        assert_eq!(witness.len(), 1);
+        assert_eq!(witness[0].len(), 32);
+        let expected = dhash256(&[&witness_merkle_root[..], &witness[0][..]].concat());
+        assert_eq!(&commitment.script_pubkey[6..38], &expected[..]);
+    }
+}
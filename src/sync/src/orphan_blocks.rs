@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use chain::{Block, IndexedBlockHeader};
+use params::ConsensusParams;
+use primitives::hash::H256;
+use verification::{Error, HeaderVerifier};
+use peers::unix_time;
+
+/// Most orphan blocks kept at once
+const MAX_ORPHAN_BLOCKS: usize = 64;
+/// Orphan which parent hasn't arrived in this time is forgotten
+const MAX_ORPHAN_BLOCK_AGE_SECS: u64 = 20 * 60;
+
+struct OrphanBlock {
+    block: Block,
+    received_at: u64,
+}
+
+/// Blocks which arrived before their parents, keyed by missing parent hash
+pub struct OrphanBlocks {
+    consensus: ConsensusParams,
+    blocks: HashMap<H256, OrphanBlock>,
+    by_parent: HashMap<H256, HashSet<H256>>,
+}
+
+impl OrphanBlocks {
+    pub fn new(consensus: ConsensusParams) -> Self {
+        OrphanBlocks {
+            consensus,
+            blocks: HashMap::new(),
+            by_parent: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, hash: &H256) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Keeps block once its header has valid proof of work, so orphans are expensive to make up
+    pub fn insert(&mut self, block: Block) -> Result<(), Error> {
+        let header: IndexedBlockHeader = block.block_header.clone().into();
+        HeaderVerifier::new(&header, &self.consensus, unix_time() as u32).check()?;
+        let hash = header.hash;
+        if self.contains(&hash) {
+            return Ok(());
+        }
+        self.remove_expired();
+        if self.blocks.len() >= MAX_ORPHAN_BLOCKS {
+            self.remove_oldest();
+        }
+
+        self.by_parent
+            .entry(block.block_header.previous_header_hash.clone())
+            .or_insert_with(HashSet::new)
+            .insert(hash.clone());
+        self.blocks.insert(hash, OrphanBlock {
+            block,
+            received_at: unix_time(),
+        });
+        Ok(())
+    }
+
+    /// Takes orphans which were waiting for given parent
+    pub fn remove_children(&mut self, parent: &H256) -> Vec<Block> {
+        let children = match self.by_parent.remove(parent) {
+            Some(children) => children,
+            None => return Vec::new(),
+        };
+        children
+            .into_iter()
+            .filter_map(|hash| self.blocks.remove(&hash))
+            .map(|orphan| orphan.block)
+            .collect()
+    }
+
+    /// Hash of the first missing block in the chain of orphans leading to given block
+    pub fn missing_ancestor(&self, hash: &H256) -> Option<H256> {
+        let mut parent = self.blocks.get(hash)?.block.block_header.previous_header_hash.clone();
+        while let Some(orphan) = self.blocks.get(&parent) {
+            parent = orphan.block.block_header.previous_header_hash.clone();
+        }
+        Some(parent)
+    }
+
+    fn remove_expired(&mut self) {
+        let now = unix_time();
+        let expired: Vec<H256> = self.blocks
+            .iter()
+            .filter(|&(_, orphan)| now.saturating_sub(orphan.received_at) >= MAX_ORPHAN_BLOCK_AGE_SECS)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in expired {
+            self.remove(&hash);
+        }
+    }
+
+    fn remove_oldest(&mut self) {
+        let oldest = self.blocks
+            .iter()
+            .min_by_key(|&(_, orphan)| orphan.received_at)
+            .map(|(hash, _)| hash.clone());
+        if let Some(hash) = oldest {
+            self.remove(&hash);
+        }
+    }
+
+    fn remove(&mut self, hash: &H256) {
+        if let Some(orphan) = self.blocks.remove(hash) {
+            let parent = &orphan.block.block_header.previous_header_hash;
+            let is_empty = match self.by_parent.get_mut(parent) {
+                Some(children) => {
+                    children.remove(hash);
+                    children.is_empty()
+                }
+                None => false,
+            };
+            if is_empty {
+                self.by_parent.remove(parent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{Block, BlockHeader};
+    use params::{ConsensusFork, ConsensusParams, NetworkParams};
+    use primitives::hash::H256;
+    use verification::{is_valid_proof_of_work_hash, Error};
+    use super::{OrphanBlocks, MAX_ORPHAN_BLOCKS, MAX_ORPHAN_BLOCK_AGE_SECS};
+
+    fn orphan_blocks() -> OrphanBlocks {
+        OrphanBlocks::new(ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork))
+    }
+
+    fn block(parent: &H256, nonce: u32) -> Block {
+        let header = BlockHeader {
+            version: 1,
+            previous_header_hash: parent.clone(),
+            merkle_root_hash: H256::default(),
+            witness_merkle_root_hash: H256::default(),
+            time: 1234567,
+            bits: 0x1d00ffff.into(),
+            nonce,
+        };
+        Block::new(header, vec![])
+    }
+
+    #[test]
+    fn block_without_proof_of_work_is_rejected() {
+        let mut orphans = OrphanBlocks::new(ConsensusParams::new(NetworkParams::Regtest, ConsensusFork::NoFork));
+        let unmined = block(&H256::from(1), 0);
+        assert_eq!(orphans.insert(unmined.clone()), Err(Error::Pow));
+        assert!(!orphans.contains(&unmined.hash()));
+
+        // about every second hash meets regtest target
+        let mut mined = block(&H256::from(1), 0);
+        mined.block_header.bits = 0x207fffff.into();
+        while !is_valid_proof_of_work_hash(mined.block_header.bits, &mined.hash()) {
+            mined.block_header.nonce += 1;
+        }
+        assert_eq!(orphans.insert(mined.clone()), Ok(()));
+        assert!(orphans.contains(&mined.hash()));
+    }
+
+    #[test]
+    fn chain_of_orphans_leads_to_missing_ancestor() {
+        let mut orphans = orphan_blocks();
+        let missing = H256::from(1);
+        let first = block(&missing, 0);
+        let second = block(&first.hash(), 0);
+        let sibling = block(&first.hash(), 1);
+        for block in vec![second.clone(), first.clone(), sibling.clone()] {
+            orphans.insert(block).unwrap();
+        }
+
+        assert_eq!(orphans.missing_ancestor(&second.hash()), Some(missing.clone()));
+        assert_eq!(orphans.missing_ancestor(&first.hash()), Some(missing.clone()));
+        assert_eq!(orphans.missing_ancestor(&missing), None);
+
+        assert_eq!(orphans.remove_children(&missing), vec![first.clone()]);
+        let mut children = orphans.remove_children(&first.hash());
+        children.sort_by_key(|child| child.block_header.nonce);
+        assert_eq!(children, vec![second, sibling]);
+        assert_eq!(orphans.len(), 0);
+    }
+
+    #[test]
+    fn oldest_orphan_is_evicted_when_pool_is_full() {
+        let mut orphans = orphan_blocks();
+        let oldest = block(&H256::from(1), 0);
+        orphans.insert(oldest.clone()).unwrap();
+        orphans.blocks.get_mut(&oldest.hash()).unwrap().received_at -= 1;
+        for nonce in 1..MAX_ORPHAN_BLOCKS as u32 {
+            orphans.insert(block(&H256::from(1), nonce)).unwrap();
+        }
+        assert_eq!(orphans.len(), MAX_ORPHAN_BLOCKS);
+
+        let newest = block(&H256::from(2), 0);
+        orphans.insert(newest.clone()).unwrap();
+        assert_eq!(orphans.len(), MAX_ORPHAN_BLOCKS);
+        assert!(!orphans.contains(&oldest.hash()));
+        assert!(orphans.contains(&newest.hash()));
+        assert_eq!(orphans.remove_children(&H256::from(1)).len(), MAX_ORPHAN_BLOCKS - 1);
+    }
+
+    #[test]
+    fn expired_orphans_are_forgotten() {
+        let mut orphans = orphan_blocks();
+        let expired = block(&H256::from(1), 0);
+        orphans.insert(expired.clone()).unwrap();
+        orphans.blocks.get_mut(&expired.hash()).unwrap().received_at -= MAX_ORPHAN_BLOCK_AGE_SECS;
+
+        let fresh = block(&H256::from(2), 0);
+        orphans.insert(fresh.clone()).unwrap();
+        assert!(!orphans.contains(&expired.hash()));
+        assert!(orphans.contains(&fresh.hash()));
+        assert_eq!(orphans.remove_children(&H256::from(1)), vec![]);
+    }
+}
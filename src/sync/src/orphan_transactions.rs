@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet};
+use chain::Transaction;
+use primitives::hash::H256;
+use ser::{Serializable, SERIALIZE_TRANSACTION_WITNESS};
+use peers::unix_time;
+
+/// Most orphan transactions kept at once
+const MAX_ORPHAN_TRANSACTIONS: usize = 100;
+/// Orphan which parents haven't arrived in this time is forgotten
+const MAX_ORPHAN_TRANSACTION_AGE_SECS: u64 = 20 * 60;
+/// Bigger orphans are not kept, so pool of unverified transactions stays small
+const MAX_ORPHAN_TRANSACTION_WEIGHT: usize = 100_000;
+const WITNESS_SCALE_FACTOR: usize = 4;
+
+struct OrphanTransaction {
+    transaction: Transaction,
+    missing_parents: HashSet<H256>,
+    received_at: u64,
+}
+
+/// Transactions spending outputs of transactions we haven't seen yet, keyed by missing parent hashes
+#[derive(Default)]
+pub struct OrphanTransactions {
+    transactions: HashMap<H256, OrphanTransaction>,
+    by_parent: HashMap<H256, HashSet<H256>>,
+}
+
+impl OrphanTransactions {
+    pub fn new() -> Self {
+        OrphanTransactions::default()
+    }
+
+    pub fn contains(&self, hash: &H256) -> bool {
+        self.transactions.contains_key(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Returns false if transaction is too big to be kept
+    pub fn insert(&mut self, transaction: Transaction, missing_parents: HashSet<H256>) -> bool {
+        if transaction_weight(&transaction) > MAX_ORPHAN_TRANSACTION_WEIGHT {
+            return false;
+        }
+        let hash = transaction.hash();
+        self.remove(&hash);
+        self.remove_expired();
+        if self.transactions.len() >= MAX_ORPHAN_TRANSACTIONS {
+            self.remove_oldest();
+        }
+
+        for parent in &missing_parents {
+            self.by_parent
+                .entry(parent.clone())
+                .or_insert_with(HashSet::new)
+                .insert(hash.clone());
+        }
+        self.transactions.insert(hash, OrphanTransaction {
+            transaction,
+            missing_parents,
+            received_at: unix_time(),
+        });
+        true
+    }
+
+    /// Takes orphans which were waiting for given parent, so they can be verified again
+    pub fn remove_children(&mut self, parent: &H256) -> Vec<Transaction> {
+        let children = match self.by_parent.get(parent) {
+            Some(children) => children.clone(),
+            None => return Vec::new(),
+        };
+        children
+            .into_iter()
+            .filter_map(|hash| self.remove(&hash))
+            .collect()
+    }
+
+    pub fn missing_parents(&self, hash: &H256) -> Vec<H256> {
+        self.transactions
+            .get(hash)
+            .map_or_else(Vec::new, |orphan| orphan.missing_parents.iter().cloned().collect())
+    }
+
+    fn remove_expired(&mut self) {
+        let now = unix_time();
+        let expired: Vec<H256> = self.transactions
+            .iter()
+            .filter(|&(_, orphan)| now.saturating_sub(orphan.received_at) >= MAX_ORPHAN_TRANSACTION_AGE_SECS)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in expired {
+            self.remove(&hash);
+        }
+    }
+
+    fn remove_oldest(&mut self) {
+        let oldest = self.transactions
+            .iter()
+            .min_by_key(|&(_, orphan)| orphan.received_at)
+            .map(|(hash, _)| hash.clone());
+        if let Some(hash) = oldest {
+            self.remove(&hash);
+        }
+    }
+
+    fn remove(&mut self, hash: &H256) -> Option<Transaction> {
+        let orphan = self.transactions.remove(hash)?;
+        for parent in &orphan.missing_parents {
+            let is_empty = match self.by_parent.get_mut(parent) {
+                Some(children) => {
+                    children.remove(hash);
+                    children.is_empty()
+                }
+                None => false,
+            };
+            if is_empty {
+                self.by_parent.remove(parent);
+            }
+        }
+        Some(orphan.transaction)
+    }
+}
+
+/// Size with witness data counting one quarter of other data, as defined by BIP141
+fn transaction_weight(transaction: &Transaction) -> usize {
+    let base_size = transaction.serialized_size();
+    let total_size = transaction.serialized_size_with_flags(SERIALIZE_TRANSACTION_WITNESS);
+    base_size * (WITNESS_SCALE_FACTOR - 1) + total_size
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use chain::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+    use primitives::hash::H256;
+    use super::{OrphanTransactions, MAX_ORPHAN_TRANSACTIONS, MAX_ORPHAN_TRANSACTION_AGE_SECS};
+
+    fn parent(index: u8) -> H256 {
+        H256::from(index)
+    }
+
+    /// Transaction spending first outputs of given parents. Lock time tells transactions apart
+    fn transaction(lock_time: u32, parents: &[H256], script_size: usize) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: parents
+                .iter()
+                .map(|parent| TransactionInput {
+                    previous_output: OutPoint {
+                        hash: parent.clone(),
+                        index: 0,
+                    },
+                    script_sig: "51".into(),
+                    sequence: 0xffffffff,
+                    script_witness: vec![],
+                })
+                .collect(),
+            outputs: vec![TransactionOutput {
+                value: 1000,
+                script_pubkey: vec![0x51u8; script_size].into(),
+            }],
+            lock_time,
+        }
+    }
+
+    fn orphan(lock_time: u32, parents: &[H256]) -> (Transaction, HashSet<H256>) {
+        (transaction(lock_time, parents, 1), parents.iter().cloned().collect())
+    }
+
+    #[test]
+    fn orphan_is_returned_once_parent_arrives() {
+        let mut orphans = OrphanTransactions::new();
+        let (first, first_parents) = orphan(0, &[parent(1)]);
+        let (second, second_parents) = orphan(1, &[parent(1), parent(2)]);
+        assert!(orphans.insert(first.clone(), first_parents));
+        assert!(orphans.insert(second.clone(), second_parents));
+        assert_eq!(orphans.missing_parents(&first.hash()), vec![parent(1)]);
+
+        assert_eq!(orphans.remove_children(&parent(3)), vec![]);
+        let mut children = orphans.remove_children(&parent(1));
+        children.sort_by_key(|child| child.lock_time);
+        // child still missing other parent is verified again and kept by acceptor
+        assert_eq!(children, vec![first.clone(), second.clone()]);
+        assert_eq!(orphans.len(), 0);
+        assert!(!orphans.contains(&second.hash()));
+        assert_eq!(orphans.remove_children(&parent(2)), vec![]);
+    }
+
+    #[test]
+    fn oldest_orphan_is_evicted_when_pool_is_full() {
+        let mut orphans = OrphanTransactions::new();
+        let (oldest, parents) = orphan(0, &[parent(1)]);
+        orphans.insert(oldest.clone(), parents);
+        orphans.transactions.get_mut(&oldest.hash()).unwrap().received_at -= 1;
+        for lock_time in 1..MAX_ORPHAN_TRANSACTIONS as u32 {
+            let (transaction, parents) = orphan(lock_time, &[parent(1)]);
+            orphans.insert(transaction, parents);
+        }
+        assert_eq!(orphans.len(), MAX_ORPHAN_TRANSACTIONS);
+
+        let (newest, parents) = orphan(MAX_ORPHAN_TRANSACTIONS as u32, &[parent(2)]);
+        orphans.insert(newest.clone(), parents);
+        assert_eq!(orphans.len(), MAX_ORPHAN_TRANSACTIONS);
+        assert!(!orphans.contains(&oldest.hash()));
+        assert!(orphans.contains(&newest.hash()));
+        assert_eq!(orphans.remove_children(&parent(1)).len(), MAX_ORPHAN_TRANSACTIONS - 1);
+    }
+
+    #[test]
+    fn expired_orphans_are_forgotten() {
+        let mut orphans = OrphanTransactions::new();
+        let (expired, parents) = orphan(0, &[parent(1)]);
+        orphans.insert(expired.clone(), parents);
+        orphans.transactions.get_mut(&expired.hash()).unwrap().received_at -= MAX_ORPHAN_TRANSACTION_AGE_SECS;
+
+        let (fresh, parents) = orphan(1, &[parent(2)]);
+        orphans.insert(fresh.clone(), parents);
+        assert!(!orphans.contains(&expired.hash()));
+        assert!(orphans.contains(&fresh.hash()));
+        assert!(orphans.by_parent.get(&parent(1)).is_none());
+    }
+
+    #[test]
+    fn big_orphan_is_rejected() {
+        let mut orphans = OrphanTransactions::new();
+        // output script alone weighs 4 * 25_000 units
+        let big = transaction(0, &[parent(1)], 25_000);
+        assert!(!orphans.insert(big.clone(), vec![parent(1)].into_iter().collect()));
+        assert!(!orphans.contains(&big.hash()));
+
+        let small = transaction(1, &[parent(1)], 20_000);
+        assert!(orphans.insert(small.clone(), vec![parent(1)].into_iter().collect()));
+    }
+}
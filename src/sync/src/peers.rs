@@ -0,0 +1,560 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use parking_lot::{Mutex, RwLock};
+use message::common::Services;
+use message::types::{SendCompact, Version, COMPACT_BLOCKS_VERSION, PROTOCOL_VERSION};
+use p2p::{PeerAddress, PeerIndex};
+use primitives::hash::H256;
+use keys::generator::Random;
+use ban_list::BanList;
+
+/// Peer which hasn't completed handshake in this time is dropped
+const HANDSHAKE_TIMEOUT_SECS: u64 = 60;
+/// Silent peer is pinged after this time
+const PING_INTERVAL_SECS: u64 = 2 * 60;
+/// Peer silent for this long is dropped, even if it has been pinged
+const INACTIVITY_TIMEOUT_SECS: u64 = 20 * 60;
+/// Peer is banned once its misbehavior score reaches this value
+const BAN_SCORE: u32 = 100;
+
+/// Misbehavior scores of peer offences, which add up until `BAN_SCORE` is reached
+pub const INVALID_BLOCK_SCORE: u32 = 100;
+pub const INVALID_TRANSACTION_SCORE: u32 = 10;
+/// Headers which don't fit into the chain are part of bogus chain
+pub const INVALID_HEADERS_SCORE: u32 = 100;
+/// Headers which don't connect to known ones, after peer was asked for them again
+pub const UNCONNECTED_HEADERS_SCORE: u32 = 20;
+pub const OVERSIZED_MESSAGE_SCORE: u32 = 20;
+/// Bad checksum, header or payload
+pub const MALFORMED_MESSAGE_SCORE: u32 = 10;
+/// Block or transaction sent before handshake
+pub const UNSOLICITED_DATA_SCORE: u32 = 10;
+pub const DUPLICATE_VERSION_SCORE: u32 = 1;
+/// Every honest peer shares genesis block with us
+pub const UNKNOWN_LOCATOR_SCORE: u32 = 10;
+/// Transactions outside of block can only be requested by broken or malicious peer
+pub const INVALID_BLOCK_TXN_REQUEST_SCORE: u32 = 100;
+/// Most inventory hashes remembered per peer, older ones are forgotten first
+const MAX_KNOWN_INVENTORY: usize = 10_000;
+/// Most peers asked to send new blocks as compact blocks right away, as suggested by BIP152
+const MAX_HIGH_BANDWIDTH_PEERS: usize = 3;
+
+pub type PeersRef = Arc<Peers>;
+
+/// What we know about connected peer
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    pub address: PeerAddress,
+    /// Protocol version negotiated with peer
+    pub version: u32,
+    pub services: Services,
+    pub start_height: i32,
+    pub user_agent: String,
+    pub relay: bool,
+    /// Unix time when peer has connected
+    pub connected_at: u64,
+    /// Unix time of last message received from peer
+    pub last_seen: u64,
+    pub version_received: bool,
+    pub verack_received: bool,
+    /// Nonce of ping which is not answered yet
+    pub ping_nonce: Option<u64>,
+    /// Sum of scores of peer's offences
+    pub misbehavior: u32,
+    /// Peer wants new blocks to be sent as compact blocks instead of announcing them with inv
+    pub compact_blocks: bool,
+    /// We have asked peer to send new blocks as compact blocks instead of announcing them with inv
+    pub high_bandwidth: bool,
+}
+
+impl PeerInfo {
+    fn new(address: PeerAddress, now: u64) -> Self {
+        PeerInfo {
+            address,
+            version: 0,
+            services: Services::default(),
+            start_height: 0,
+            user_agent: String::new(),
+            relay: true,
+            connected_at: now,
+            last_seen: now,
+            version_received: false,
+            verack_received: false,
+            ping_nonce: None,
+            misbehavior: 0,
+            compact_blocks: false,
+            high_bandwidth: false,
+        }
+    }
+
+    pub fn is_handshaked(&self) -> bool {
+        self.version_received && self.verack_received
+    }
+}
+
+/// Hashes of transactions and blocks which peer has or which have been announced to it
+#[derive(Default)]
+struct KnownInventory {
+    hashes: HashSet<H256>,
+    order: VecDeque<H256>,
+}
+
+impl KnownInventory {
+    /// Returns false if item is known already
+    fn insert(&mut self, hash: H256) -> bool {
+        if !self.hashes.insert(hash.clone()) {
+            return false;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > MAX_KNOWN_INVENTORY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// Table of connected peers shared by sync services
+pub struct Peers {
+    peers: RwLock<HashMap<PeerIndex, PeerInfo>>,
+    known_inventory: Mutex<HashMap<PeerIndex, KnownInventory>>,
+    ban_list: Mutex<BanList>,
+    /// How long misbehaving peer stays banned, in seconds
+    ban_time: u64,
+}
+
+impl Peers {
+    pub fn new(ban_list: BanList, ban_time: u64) -> Self {
+        Peers {
+            peers: RwLock::new(HashMap::new()),
+            known_inventory: Mutex::new(HashMap::new()),
+            ban_list: Mutex::new(ban_list),
+            ban_time,
+        }
+    }
+
+    /// Adds connected peer. Messages from peer may arrive before its connection event, so known peer is kept
+    pub fn insert(&self, peer: PeerIndex, address: PeerAddress) {
+        self.peers
+            .write()
+            .entry(peer)
+            .or_insert_with(|| PeerInfo::new(String::new(), unix_time()))
+            .address = address;
+    }
+
+    pub fn remove(&self, peer: PeerIndex) -> Option<PeerInfo> {
+        self.known_inventory.lock().remove(&peer);
+        self.peers.write().remove(&peer)
+    }
+
+    pub fn info(&self, peer: PeerIndex) -> Option<PeerInfo> {
+        self.peers.read().get(&peer).cloned()
+    }
+
+    pub fn is_handshaked(&self, peer: PeerIndex) -> bool {
+        self.peers.read().get(&peer).map_or(false, PeerInfo::is_handshaked)
+    }
+
+    /// Peers which have completed handshake
+    pub fn handshaked(&self) -> Vec<PeerInfo> {
+        self.peers.read().values().filter(|info| info.is_handshaked()).cloned().collect()
+    }
+
+    pub fn handshaked_peers(&self) -> Vec<PeerIndex> {
+        self.peers
+            .read()
+            .iter()
+            .filter(|&(_, info)| info.is_handshaked())
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+
+    /// Remembers peer's version message. Returns false if version has been received already
+    pub fn on_version(&self, peer: PeerIndex, version: &Version) -> bool {
+        let mut peers = self.peers.write();
+        let info = peers.entry(peer).or_insert_with(|| PeerInfo::new(String::new(), unix_time()));
+        if info.version_received {
+            return false;
+        }
+        info.version = version.version.min(PROTOCOL_VERSION);
+        info.services = version.services;
+        info.start_height = version.start_height;
+        info.user_agent = version.user_agent.clone();
+        info.relay = version.relay;
+        info.version_received = true;
+        true
+    }
+
+    /// Returns true if this verack has completed the handshake
+    pub fn on_verack(&self, peer: PeerIndex) -> bool {
+        let mut peers = self.peers.write();
+        match peers.get_mut(&peer) {
+            Some(ref info) if info.verack_received => false,
+            Some(info) => {
+                info.verack_received = true;
+                info.is_handshaked()
+            }
+            None => false,
+        }
+    }
+
+    /// Compact blocks are only sent to peer which supports our version of them
+    pub fn on_send_compact(&self, peer: PeerIndex, message: &SendCompact) {
+        if let Some(info) = self.peers.write().get_mut(&peer) {
+            if message.version == COMPACT_BLOCKS_VERSION {
+                info.compact_blocks = message.announce;
+            }
+        }
+    }
+
+    /// Picks peer to send us compact blocks right away, unless enough peers are picked already
+    pub fn select_high_bandwidth(&self, peer: PeerIndex) -> bool {
+        let mut peers = self.peers.write();
+        let selected = peers.values().filter(|info| info.high_bandwidth).count();
+        let info = match peers.get_mut(&peer) {
+            Some(info) => info,
+            None => return false,
+        };
+        if !info.high_bandwidth && selected >= MAX_HIGH_BANDWIDTH_PEERS {
+            return false;
+        }
+        info.high_bandwidth = true;
+        true
+    }
+
+    pub fn prefers_compact_blocks(&self, peer: PeerIndex) -> bool {
+        self.peers.read().get(&peer).map_or(false, |info| info.compact_blocks)
+    }
+
+    /// Updates last seen time of peer
+    pub fn touch(&self, peer: PeerIndex) {
+        if let Some(info) = self.peers.write().get_mut(&peer) {
+            info.last_seen = unix_time();
+        }
+    }
+
+    pub fn on_ping_sent(&self, peer: PeerIndex, nonce: u64) {
+        if let Some(info) = self.peers.write().get_mut(&peer) {
+            info.ping_nonce = Some(nonce);
+        }
+    }
+
+    /// Clears pending ping if nonce matches
+    pub fn on_pong(&self, peer: PeerIndex, nonce: u64) {
+        if let Some(info) = self.peers.write().get_mut(&peer) {
+            if info.ping_nonce == Some(nonce) {
+                info.ping_nonce = None;
+            }
+        }
+    }
+
+    /// Remembers that peer has given item, so it is never announced to peer
+    pub fn on_inventory_known(&self, peer: PeerIndex, hash: H256) {
+        self.known_inventory
+            .lock()
+            .entry(peer)
+            .or_insert_with(KnownInventory::default)
+            .insert(hash);
+    }
+
+    /// Handshaked peers which don't know given item yet. Item is remembered as known to them.
+    /// Transactions are not announced to peers which asked not to relay them
+    pub fn announce_targets(&self, hash: &H256, is_transaction: bool) -> Vec<PeerIndex> {
+        let peers = self.peers.read();
+        let mut known_inventory = self.known_inventory.lock();
+        peers
+            .iter()
+            .filter(|&(_, info)| info.is_handshaked() && (info.relay || !is_transaction))
+            .map(|(peer, _)| *peer)
+            .filter(|peer| {
+                known_inventory
+                    .entry(*peer)
+                    .or_insert_with(KnownInventory::default)
+                    .insert(hash.clone())
+            })
+            .collect()
+    }
+
+    pub fn is_banned(&self, address: &PeerAddress) -> bool {
+        self.ban_list.lock().is_banned(address, unix_time())
+    }
+
+    /// Adds score to peer's misbehavior. Returns true if peer has been banned and should be disconnected
+    pub fn misbehaving(&self, peer: PeerIndex, score: u32, reason: &str) -> bool {
+        let address = {
+            let mut peers = self.peers.write();
+            let info = match peers.get_mut(&peer) {
+                Some(info) => info,
+                None => return false,
+            };
+            info.misbehavior = info.misbehavior.saturating_add(score);
+            warn!("Peer#{} misbehaving ({}/{}): {}", peer, info.misbehavior, BAN_SCORE, reason);
+            if info.misbehavior < BAN_SCORE {
+                return false;
+            }
+            info.address.clone()
+        };
+        // peer without known address can only be disconnected
+        if !address.is_empty() {
+            info!("Banning peer#{} {} for {} seconds", peer, address, self.ban_time);
+            self.ban_list.lock().ban(address, unix_time() + self.ban_time);
+        }
+        true
+    }
+
+    /// Version used in messages to and from peer
+    pub fn version(&self, peer: PeerIndex) -> u32 {
+        match self.peers.read().get(&peer) {
+            Some(info) if info.version_received => info.version,
+            _ => PROTOCOL_VERSION,
+        }
+    }
+
+    /// Version understood by every handshaked peer
+    pub fn broadcast_version(&self) -> u32 {
+        self.peers
+            .read()
+            .values()
+            .filter(|info| info.is_handshaked())
+            .map(|info| info.version)
+            .min()
+            .unwrap_or(PROTOCOL_VERSION)
+    }
+
+    /// Peers which haven't completed handshake in time or have been silent for too long
+    pub fn unresponsive(&self, now: u64) -> Vec<PeerIndex> {
+        self.peers
+            .read()
+            .iter()
+            .filter(|&(_, info)| {
+                let handshake_expired = !info.is_handshaked()
+                    && now.saturating_sub(info.connected_at) >= HANDSHAKE_TIMEOUT_SECS;
+                let inactive = now.saturating_sub(info.last_seen) >= INACTIVITY_TIMEOUT_SECS;
+                handshake_expired || inactive
+            })
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+
+    /// Handshaked peers silent long enough to be pinged which aren't pinged yet
+    pub fn ping_candidates(&self, now: u64) -> Vec<PeerIndex> {
+        self.peers
+            .read()
+            .iter()
+            .filter(|&(_, info)| {
+                info.is_handshaked() && info.ping_nonce.is_none()
+                    && now.saturating_sub(info.last_seen) >= PING_INTERVAL_SECS
+            })
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+}
+
+pub fn random_nonce() -> u64 {
+    let mut bytes = [0u8; 8];
+    Random::generate_bytes(&mut bytes).expect("os random generator is unavailable");
+    bytes.iter().fold(0u64, |nonce, byte| (nonce << 8) | *byte as u64)
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use message::common::Services;
+    use message::types::{SendCompact, Version, COMPACT_BLOCKS_VERSION, PROTOCOL_VERSION};
+    use primitives::hash::H256;
+    use ban_list::BanList;
+    use super::{unix_time, KnownInventory, Peers, BAN_SCORE, HANDSHAKE_TIMEOUT_SECS, INACTIVITY_TIMEOUT_SECS,
+        MAX_HIGH_BANDWIDTH_PEERS, MAX_KNOWN_INVENTORY, PING_INTERVAL_SECS};
+
+    fn version(relay: bool) -> Version {
+        Version {
+            version: PROTOCOL_VERSION,
+            services: Services::default().with_network(true),
+            timestamp: 0,
+            receiver: Default::default(),
+            sender: Default::default(),
+            nonce: 1,
+            user_agent: "/test/".into(),
+            start_height: 10,
+            relay,
+        }
+    }
+
+    fn handshaked_peers(count: usize) -> Peers {
+        let peers = Peers::new(BanList::in_memory(), 60);
+        for peer in 0..count {
+            peers.insert(peer, format!("127.0.0.{}:8333", peer + 1));
+            peers.on_version(peer, &version(true));
+            peers.on_verack(peer);
+        }
+        peers
+    }
+
+    #[test]
+    fn handshake_needs_version_and_verack() {
+        let peers = Peers::new(BanList::in_memory(), 60);
+        peers.insert(0, "127.0.0.1:8333".into());
+        assert!(!peers.is_handshaked(0));
+
+        assert!(peers.on_version(0, &version(true)));
+        assert!(!peers.on_version(0, &version(true)));
+        assert!(!peers.is_handshaked(0));
+
+        assert!(peers.on_verack(0));
+        assert!(!peers.on_verack(0));
+        assert!(peers.is_handshaked(0));
+        assert_eq!(peers.handshaked_peers(), vec![0]);
+        assert_eq!(peers.info(0).unwrap().start_height, 10);
+    }
+
+    #[test]
+    fn verack_before_version_completes_handshake_later() {
+        let peers = Peers::new(BanList::in_memory(), 60);
+        peers.insert(0, "127.0.0.1:8333".into());
+        assert!(!peers.on_verack(0));
+        assert!(peers.on_version(0, &version(true)));
+        assert!(peers.is_handshaked(0));
+    }
+
+    #[test]
+    fn message_before_connection_event_keeps_peer() {
+        let peers = Peers::new(BanList::in_memory(), 60);
+        peers.on_version(0, &version(true));
+        peers.insert(0, "127.0.0.1:8333".into());
+        let info = peers.info(0).unwrap();
+        assert!(info.version_received);
+        assert_eq!(info.address, "127.0.0.1:8333");
+    }
+
+    #[test]
+    fn version_is_capped_by_ours() {
+        let peers = Peers::new(BanList::in_memory(), 60);
+        let mut newer = version(true);
+        newer.version = PROTOCOL_VERSION + 1;
+        peers.on_version(0, &newer);
+        assert_eq!(peers.version(0), PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn compact_blocks_need_supported_version() {
+        let peers = handshaked_peers(2);
+        peers.on_send_compact(0, &SendCompact { announce: true, version: COMPACT_BLOCKS_VERSION });
+        peers.on_send_compact(1, &SendCompact { announce: true, version: COMPACT_BLOCKS_VERSION + 1 });
+        assert!(peers.prefers_compact_blocks(0));
+        assert!(!peers.prefers_compact_blocks(1));
+    }
+
+    #[test]
+    fn few_peers_are_selected_for_high_bandwidth_mode() {
+        let peers = handshaked_peers(MAX_HIGH_BANDWIDTH_PEERS + 1);
+        for peer in 0..MAX_HIGH_BANDWIDTH_PEERS {
+            assert!(peers.select_high_bandwidth(peer));
+        }
+        assert!(!peers.select_high_bandwidth(MAX_HIGH_BANDWIDTH_PEERS));
+        // selected peer stays selected
+        assert!(peers.select_high_bandwidth(0));
+
+        // slot of disconnected peer is free again
+        peers.remove(0);
+        assert!(peers.select_high_bandwidth(MAX_HIGH_BANDWIDTH_PEERS));
+    }
+
+    #[test]
+    fn peer_is_banned_once_misbehavior_reaches_threshold() {
+        let peers = handshaked_peers(1);
+        assert!(!peers.misbehaving(0, BAN_SCORE - 1, "test"));
+        assert!(!peers.is_banned(&"127.0.0.1:8333".to_owned()));
+
+        assert!(peers.misbehaving(0, 1, "test"));
+        assert!(peers.is_banned(&"127.0.0.1:8333".to_owned()));
+        assert!(!peers.is_banned(&"127.0.0.2:8333".to_owned()));
+    }
+
+    #[test]
+    fn misbehavior_of_unknown_peer_is_ignored() {
+        let peers = Peers::new(BanList::in_memory(), 60);
+        assert!(!peers.misbehaving(0, BAN_SCORE, "test"));
+    }
+
+    #[test]
+    fn known_inventory_forgets_oldest_hashes() {
+        let mut known = KnownInventory::default();
+        assert!(known.insert(H256::from(0)));
+        assert!(!known.insert(H256::from(0)));
+        for index in 1..MAX_KNOWN_INVENTORY + 1 {
+            let mut hash = [0u8; 32];
+            hash[0] = index as u8;
+            hash[1] = (index >> 8) as u8;
+            assert!(known.insert(H256::from(hash)));
+        }
+        assert_eq!(known.order.len(), MAX_KNOWN_INVENTORY);
+        // oldest hash was evicted, so it is unknown again
+        assert!(known.insert(H256::from(0)));
+    }
+
+    #[test]
+    fn announce_targets_skip_peers_knowing_item() {
+        let peers = handshaked_peers(3);
+        let hash = H256::from(1);
+        peers.on_inventory_known(1, hash.clone());
+
+        let mut targets = peers.announce_targets(&hash, false);
+        targets.sort();
+        assert_eq!(targets, vec![0, 2]);
+        // item is known to everyone now
+        assert!(peers.announce_targets(&hash, false).is_empty());
+    }
+
+    #[test]
+    fn transactions_are_not_announced_to_non_relaying_peers() {
+        let peers = handshaked_peers(1);
+        peers.insert(1, "127.0.0.2:8333".into());
+        peers.on_version(1, &version(false));
+        peers.on_verack(1);
+
+        assert_eq!(peers.announce_targets(&H256::from(1), true), vec![0]);
+        let mut targets = peers.announce_targets(&H256::from(2), false);
+        targets.sort();
+        assert_eq!(targets, vec![0, 1]);
+    }
+
+    #[test]
+    fn silent_peer_is_pinged_until_answered() {
+        let peers = handshaked_peers(1);
+        let now = unix_time();
+        assert!(peers.ping_candidates(now).is_empty());
+
+        let later = now + PING_INTERVAL_SECS;
+        assert_eq!(peers.ping_candidates(later), vec![0]);
+        peers.on_ping_sent(0, 7);
+        assert!(peers.ping_candidates(later).is_empty());
+
+        peers.on_pong(0, 8);
+        assert_eq!(peers.info(0).unwrap().ping_nonce, Some(7));
+        peers.on_pong(0, 7);
+        assert_eq!(peers.ping_candidates(later), vec![0]);
+    }
+
+    #[test]
+    fn unresponsive_peers_are_found() {
+        let peers = handshaked_peers(1);
+        peers.insert(1, "127.0.0.2:8333".into());
+        let now = unix_time();
+        assert!(peers.unresponsive(now).is_empty());
+
+        // peer 1 never completed handshake
+        assert_eq!(peers.unresponsive(now + HANDSHAKE_TIMEOUT_SECS), vec![1]);
+
+        let mut silent = peers.unresponsive(now + INACTIVITY_TIMEOUT_SECS);
+        silent.sort();
+        assert_eq!(silent, vec![0, 1]);
+    }
+}
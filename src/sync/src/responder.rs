@@ -1,22 +1,33 @@
 use p2p::PeerIndex;
 use db::SharedStore;
+use memory_pool::MemoryPoolRef;
 use message::{common, types};
 use std::sync::mpsc::Receiver;
 use primitives::hash::H256;
 use message_wrapper::MessageWrapper;
+use compact_block::{build_compact_block, requested_transactions};
+use peers::{random_nonce, INVALID_BLOCK_TXN_REQUEST_SCORE, UNKNOWN_LOCATOR_SCORE};
 
 type BlockHeight = u32;
 
+/// Older blocks are sent in full, since peer is unlikely to have their transactions in mempool
+const MAX_COMPACT_BLOCK_DEPTH: u32 = 5;
+/// Transactions of older blocks are sent as full block, as suggested by BIP152
+const MAX_BLOCK_TXN_DEPTH: u32 = 10;
+
 #[derive(Debug, PartialEq)]
 pub enum ResponderTask {
     GetBlocks(PeerIndex, types::GetBlocks),
+    GetHeaders(PeerIndex, types::GetHeaders),
     GetData(PeerIndex, types::GetData),
+    GetBlockTxn(PeerIndex, types::GetBlockTxn),
 }
 
 pub struct Responder {
     pub task_receiver: Receiver<ResponderTask>,
     pub message_wrapper: MessageWrapper,
     pub storage: SharedStore,
+    pub mempool: MemoryPoolRef,
 }
 
 impl Responder {
@@ -31,9 +42,15 @@ impl Responder {
                     ResponderTask::GetBlocks(peer_index, message) => {
                         self.respond_get_blocks(peer_index, message)
                     }
+                    ResponderTask::GetHeaders(peer_index, message) => {
+                        self.respond_get_headers(peer_index, message)
+                    }
                     ResponderTask::GetData(peer_index, message) => {
                         self.respond_get_data(peer_index, message)
                     }
+                    ResponderTask::GetBlockTxn(peer_index, message) => {
+                        self.respond_get_block_txn(peer_index, message)
+                    }
                 },
             }
         }
@@ -60,32 +77,155 @@ impl Responder {
                 trace!(target: "sync", "'getblocks' request from peer#{} is ignored as there are no new blocks for peer", peer_index);
             }
         } else {
-            //self.peers.misbehaving(peer_index, "Got 'getblocks' message without known blocks");
-            return;
+            self.message_wrapper.misbehaving(peer_index, UNKNOWN_LOCATOR_SCORE, "Got 'getblocks' message without known blocks");
         }
     }
 
+    fn respond_get_headers(&self, peer_index: PeerIndex, message: types::GetHeaders) {
+        let block_height = match self.locate_best_common_block(&message.hash_stop, &message.block_locator_hashes) {
+            Some(block_height) => block_height,
+            None => {
+                self.message_wrapper.misbehaving(peer_index, UNKNOWN_LOCATOR_SCORE, "Got 'getheaders' message without known blocks");
+                return;
+            }
+        };
+        let headers: Vec<_> = (block_height + 1..block_height + 1 + (types::HEADERS_MAX_HEADERS_LEN as BlockHeight))
+            .map(|block_height| self.storage.block_header(block_height.into()))
+            .take_while(Option::is_some)
+            .map(Option::unwrap)
+            .take_while(|header| header.previous_header_hash != message.hash_stop)
+            .collect();
+        // empty headers messages are valid and tell peer that it is synchronized
+        trace!(target: "sync", "'getheaders' response to peer#{} is ready with {} headers", peer_index, headers.len());
+        self.message_wrapper.send(peer_index, &types::Headers::with_headers(headers));
+    }
+
     fn respond_get_data(&self, peer_index: PeerIndex, message: types::GetData) {
+        let mut not_found = Vec::new();
         for next_item in message.inventory.iter().rev() {
-            match next_item.inv_type {
-                common::InventoryType::MessageBlock => {
-                    if let Some(block) = self.storage.block(next_item.hash.clone().into()) {
-                        trace!(target: "sync", "'getblocks' response to peer#{} is ready with block {}", peer_index, next_item.hash.to_reversed_str());
-                        let block = types::Block::with_block(block);
-                        self.message_wrapper.send(peer_index, &block);
-                    } else {
-                        info!(
-                            "peer {} is asking for non existant block {}",
-                            peer_index, next_item.hash
-                        );
-                    }
+            let found = match next_item.inv_type {
+                common::InventoryType::MessageBlock | common::InventoryType::MessageWitnessBlock => {
+                    self.respond_block(peer_index, next_item)
+                }
+                common::InventoryType::MessageTx | common::InventoryType::MessageWitnessTx => {
+                    self.respond_transaction(peer_index, next_item)
                 }
-                _ => error!(
-                    "getdata message contains unhandled inventory type {:?}",
-                    next_item.inv_type
-                ),
+                // bloom filters aren't supported, so there is nothing to filter blocks with
+                common::InventoryType::MessageFilteredBlock => false,
+                common::InventoryType::MessageCompactBlock => self.respond_compact_block(peer_index, next_item),
+                _ => {
+                    error!(
+                        "getdata message contains unhandled inventory type {:?}",
+                        next_item.inv_type
+                    );
+                    false
+                }
+            };
+            if !found {
+                not_found.push(next_item.clone());
+            }
+        }
+
+        if !not_found.is_empty() {
+            trace!(target: "sync", "'getdata' request from peer#{} contains {} unknown items", peer_index, not_found.len());
+            self.message_wrapper.send(peer_index, &types::NotFound::with_inventory(not_found));
+        }
+    }
+
+    fn respond_block(&self, peer_index: PeerIndex, item: &common::InventoryVector) -> bool {
+        let block = match self.storage.block(item.hash.clone().into()) {
+            Some(block) => block,
+            None => {
+                info!("peer {} is asking for non existant block {}", peer_index, item.hash);
+                return false;
+            }
+        };
+        trace!(target: "sync", "'getdata' response to peer#{} is ready with block {}", peer_index, item.hash.to_reversed_str());
+        let block = types::Block::with_block(block);
+        if item.inv_type == common::InventoryType::MessageWitnessBlock {
+            self.message_wrapper.send(peer_index, &block);
+        } else {
+            self.message_wrapper.send_without_witness(peer_index, &block);
+        }
+        true
+    }
+
+    /// Transactions waiting in mempool are served as well as already mined ones
+    fn respond_transaction(&self, peer_index: PeerIndex, item: &common::InventoryVector) -> bool {
+        let transaction = self.mempool.read().read_by_hash(&item.hash).cloned();
+        let transaction = match transaction.or_else(|| self.storage.transaction(&item.hash)) {
+            Some(transaction) => transaction,
+            None => {
+                info!("peer {} is asking for non existant transaction {}", peer_index, item.hash);
+                return false;
             }
+        };
+        trace!(target: "sync", "'getdata' response to peer#{} is ready with transaction {}", peer_index, item.hash.to_reversed_str());
+        let transaction = types::Tx::with_transaction(transaction);
+        if item.inv_type == common::InventoryType::MessageWitnessTx {
+            self.message_wrapper.send(peer_index, &transaction);
+        } else {
+            self.message_wrapper.send_without_witness(peer_index, &transaction);
         }
+        true
+    }
+
+    fn respond_compact_block(&self, peer_index: PeerIndex, item: &common::InventoryVector) -> bool {
+        if !self.is_recent_block(&item.hash, MAX_COMPACT_BLOCK_DEPTH) {
+            return self.respond_block(peer_index, &common::InventoryVector::witness_block(item.hash.clone()));
+        }
+        let block = match self.storage.block(item.hash.clone().into()) {
+            Some(block) => block,
+            None => {
+                info!("peer {} is asking for non existant compact block {}", peer_index, item.hash);
+                return false;
+            }
+        };
+        trace!(target: "sync", "'getdata' response to peer#{} is ready with compact block {}", peer_index, item.hash.to_reversed_str());
+        let compact_block = types::CompactBlock {
+            header: build_compact_block(&block, random_nonce()),
+        };
+        self.message_wrapper.send(peer_index, &compact_block);
+        true
+    }
+
+    fn respond_get_block_txn(&self, peer_index: PeerIndex, message: types::GetBlockTxn) {
+        let request = message.request;
+        if !self.is_recent_block(&request.blockhash, MAX_BLOCK_TXN_DEPTH) {
+            self.respond_block(peer_index, &common::InventoryVector::witness_block(request.blockhash));
+            return;
+        }
+        let block = match self.storage.block(request.blockhash.clone().into()) {
+            Some(block) => block,
+            None => {
+                info!("peer {} is asking for transactions of non existant block {}", peer_index, request.blockhash);
+                return;
+            }
+        };
+        match requested_transactions(block, &request) {
+            Ok(transactions) => {
+                trace!(target: "sync", "'getblocktxn' response to peer#{} is ready with {} transactions", peer_index, transactions.len());
+                let block_txn = types::BlockTxn {
+                    request: common::BlockTransactions {
+                        blockhash: request.blockhash,
+                        transactions,
+                    },
+                };
+                self.message_wrapper.send(peer_index, &block_txn);
+            }
+            Err(err) => {
+                let reason = format!("Invalid 'getblocktxn' request: {:?}", err);
+                self.message_wrapper.misbehaving(peer_index, INVALID_BLOCK_TXN_REQUEST_SCORE, &reason);
+            }
+        }
+    }
+
+    /// Checks if block is in canon chain no deeper than given depth
+    fn is_recent_block(&self, hash: &H256, max_depth: u32) -> bool {
+        let best_number = self.storage.best_block().number;
+        self.storage
+            .block_number(hash)
+            .map_or(false, |number| best_number - number < max_depth)
     }
 
     fn locate_best_common_block(&self, hash_stop: &H256, locator: &[H256]) -> Option<BlockHeight> {
@@ -117,3 +257,160 @@ impl Responder {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::mpsc::{self, Receiver};
+    use chain::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+    use db::BlockChainDatabase;
+    use memory_pool::{MemoryPool, MemoryPoolFeeCalculator};
+    use message::common::{Command, InventoryType, InventoryVector, Services};
+    use message::types::{GetData, NotFound, Tx, Version, PROTOCOL_VERSION};
+    use message::{deserialize_payload, MessageHeader, HEADER_SIZE};
+    use p2p::{NetworkRequest, PeerAndBytes, PeerIndex};
+    use params::NetworkParams;
+    use parking_lot::RwLock;
+    use primitives::hash::H256;
+    use verification::TransactionError;
+    use ban_list::BanList;
+    use message_wrapper::MessageWrapper;
+    use peers::Peers;
+    use super::Responder;
+
+    const PEER: PeerIndex = 1;
+
+    struct ZeroFeeCalculator;
+
+    impl MemoryPoolFeeCalculator for ZeroFeeCalculator {
+        fn calculate(&self, _: &MemoryPool, _: &Transaction) -> Result<u64, TransactionError> {
+            Ok(0)
+        }
+    }
+
+    /// Responder to handshaked peer, which store has genesis block and mempool has given transactions
+    fn responder(mempool_transactions: Vec<Transaction>) -> (Responder, Receiver<NetworkRequest>) {
+        let genesis = NetworkParams::Mainnet.genesis_block();
+        let store = Arc::new(BlockChainDatabase::init_test_chain(vec![genesis.into()]));
+        let mut mempool = MemoryPool::new();
+        for transaction in mempool_transactions {
+            mempool.insert_verified(transaction.into(), &ZeroFeeCalculator).unwrap();
+        }
+
+        let peers = Arc::new(Peers::new(BanList::in_memory(), 60));
+        peers.insert(PEER, "127.0.0.1:8333".into());
+        peers.on_version(PEER, &Version {
+            version: PROTOCOL_VERSION,
+            services: Services::default().with_network(true),
+            timestamp: 0,
+            receiver: Default::default(),
+            sender: Default::default(),
+            nonce: 1,
+            user_agent: "/test/".into(),
+            start_height: 0,
+            relay: true,
+        });
+        peers.on_verack(PEER);
+
+        let (network_sender, network) = mpsc::channel();
+        let (_, task_receiver) = mpsc::channel();
+        let responder = Responder {
+            task_receiver,
+            message_wrapper: MessageWrapper::new(NetworkParams::Mainnet, peers, network_sender),
+            storage: store,
+            mempool: Arc::new(RwLock::new(mempool)),
+        };
+        (responder, network)
+    }
+
+    /// Messages sent to peer as commands with payloads
+    fn sent(network: &Receiver<NetworkRequest>) -> Vec<(Command, Vec<u8>)> {
+        network
+            .try_iter()
+            .map(|request| match request {
+                NetworkRequest::Send(PeerAndBytes { peer, bytes }) => {
+                    assert_eq!(peer, PEER);
+                    let header = MessageHeader::deserialize(&bytes[..HEADER_SIZE], NetworkParams::Mainnet.magic()).unwrap();
+                    (header.command, bytes[HEADER_SIZE..].to_vec())
+                }
+                NetworkRequest::Disconnect(peer) => panic!("peer#{} is unexpectedly disconnected", peer),
+            })
+            .collect()
+    }
+
+    fn transaction(spent: u8) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    hash: H256::from(spent),
+                    index: 0,
+                },
+                script_sig: "51".into(),
+                sequence: 0xffffffff,
+                script_witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: u64::from(spent),
+                script_pubkey: "51".into(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    fn received_transaction(network: &Receiver<NetworkRequest>) -> Transaction {
+        let mut sent = sent(network);
+        assert_eq!(sent.len(), 1);
+        let (command, payload) = sent.remove(0);
+        assert_eq!(command, "tx");
+        let message: Tx = deserialize_payload(&payload, PROTOCOL_VERSION).unwrap();
+        message.transaction
+    }
+
+    #[test]
+    fn transaction_is_served_from_mempool() {
+        let transaction = transaction(1);
+        let (responder, network) = responder(vec![transaction.clone()]);
+        let request = GetData::with_inventory(vec![InventoryVector::tx(transaction.hash())]);
+
+        responder.respond_get_data(PEER, request);
+        assert_eq!(received_transaction(&network), transaction);
+    }
+
+    #[test]
+    fn mined_transaction_is_served_from_store() {
+        let coinbase = NetworkParams::Mainnet.genesis_block().transactions[0].clone();
+        let (responder, network) = responder(vec![]);
+        let request = GetData::with_inventory(vec![InventoryVector::witness_tx(coinbase.hash())]);
+
+        responder.respond_get_data(PEER, request);
+        assert_eq!(received_transaction(&network), coinbase);
+    }
+
+    #[test]
+    fn unknown_items_are_reported_as_not_found() {
+        let genesis_hash = NetworkParams::Mainnet.genesis_block().hash();
+        let (responder, network) = responder(vec![]);
+        let unknown_transaction = InventoryVector::tx(H256::from(1));
+        let unknown_block = InventoryVector::block(H256::from(2));
+        // filtered blocks aren't served even if block is known
+        let filtered_block = InventoryVector {
+            inv_type: InventoryType::MessageFilteredBlock,
+            hash: genesis_hash.clone(),
+        };
+        let request = GetData::with_inventory(vec![
+            unknown_transaction.clone(),
+            InventoryVector::block(genesis_hash),
+            unknown_block.clone(),
+            filtered_block.clone(),
+        ]);
+
+        responder.respond_get_data(PEER, request);
+        let sent = sent(&network);
+        let commands: Vec<_> = sent.iter().map(|&(ref command, _)| command.to_string()).collect();
+        assert_eq!(commands, vec!["block", "notfound"]);
+        let not_found: NotFound = deserialize_payload(&sent[1].1, PROTOCOL_VERSION).unwrap();
+        // items are handled from the last one
+        assert_eq!(not_found.inventory, vec![filtered_block, unknown_block, unknown_transaction]);
+    }
+}
@@ -0,0 +1,568 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::Sender;
+use chain::{Block, BlockHeader};
+use db::SharedStore;
+use message::common::InventoryVector;
+use message::types::{GetData, GetHeaders, HEADERS_MAX_HEADERS_LEN};
+use p2p::PeerIndex;
+use params::ConsensusParams;
+use primitives::hash::H256;
+use verification::Error as BlockError;
+use block_importer::ImportTask;
+use header_chain::{HeaderChain, HeadersError};
+use message_wrapper::MessageWrapper;
+use peers::{unix_time, PeersRef, INVALID_BLOCK_SCORE, INVALID_HEADERS_SCORE, UNCONNECTED_HEADERS_SCORE};
+
+/// Most blocks requested from one peer at once
+const MAX_BLOCKS_IN_FLIGHT_PER_PEER: usize = 16;
+/// Block not delivered in this time is requested from another peer
+const BLOCK_DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+/// Peer is asked for headers again when they don't connect, but only this many times in a row
+const MAX_UNCONNECTED_HEADERS: u32 = 10;
+
+struct BlockRequest {
+    peer: PeerIndex,
+    requested_at: u64,
+}
+
+/// Headers-first synchronization. Headers are verified before their blocks are downloaded in parallel from several peers
+pub struct Synchronizer {
+    header_chain: HeaderChain,
+    importer: Sender<ImportTask>,
+    /// Block handed to importer, which result hasn't arrived yet
+    importing: Option<H256>,
+    message_wrapper: MessageWrapper,
+    peers: PeersRef,
+    /// Blocks of known headers which are not requested yet, in chain order
+    queue: VecDeque<H256>,
+    in_flight: HashMap<H256, BlockRequest>,
+    /// Blocks waiting for their parents, with peers which sent them
+    downloaded: HashMap<H256, (PeerIndex, Block)>,
+    /// Best height each peer is known to have
+    peer_heights: HashMap<PeerIndex, u32>,
+    /// Number of headers messages in a row, which didn't connect to known headers
+    unconnected_headers: HashMap<PeerIndex, u32>,
+}
+
+impl Synchronizer {
+    pub fn new(
+        store: SharedStore,
+        importer: Sender<ImportTask>,
+        message_wrapper: MessageWrapper,
+        peers: PeersRef,
+        consensus: ConsensusParams,
+    ) -> Self {
+        Synchronizer {
+            header_chain: HeaderChain::new(store, consensus),
+            importer,
+            importing: None,
+            message_wrapper,
+            peers,
+            queue: VecDeque::new(),
+            in_flight: HashMap::new(),
+            downloaded: HashMap::new(),
+            peer_heights: HashMap::new(),
+            unconnected_headers: HashMap::new(),
+        }
+    }
+
+    /// Height of best known header, which may be ahead of stored blocks
+    pub fn best_height(&self) -> u32 {
+        self.header_chain.best_height()
+    }
+
+    pub fn request_headers(&self, peer: PeerIndex) {
+        let message = GetHeaders::with_block_locator_hashes(self.header_chain.locator_hashes());
+        self.message_wrapper.send(peer, &message);
+    }
+
+    pub fn on_peer_height(&mut self, peer: PeerIndex, height: u32) {
+        let known_height = self.peer_heights.entry(peer).or_insert(0);
+        if *known_height < height {
+            *known_height = height;
+        }
+    }
+
+    pub fn on_headers(&mut self, peer: PeerIndex, headers: Vec<BlockHeader>) {
+        let is_full = headers.len() == HEADERS_MAX_HEADERS_LEN;
+        let last_hash = headers.last().map(BlockHeader::hash);
+        let result = self.header_chain.insert(headers);
+        // headers preceding the failed one may have changed best branch
+        self.queue_best_branch();
+        match result {
+            Ok(inserted) => {
+                if !inserted.is_empty() {
+                    info!("Received {} new headers from peer#{}, best header is at height {}",
+                        inserted.len(), peer, self.header_chain.best_height());
+                }
+                self.unconnected_headers.remove(&peer);
+            }
+            Err(HeadersError::Unconnected(hash)) => {
+                // peer answers to outdated locator or announces block of branch which start is unknown to us
+                let count = {
+                    let count = self.unconnected_headers.entry(peer).or_insert(0);
+                    *count += 1;
+                    *count
+                };
+                if count < MAX_UNCONNECTED_HEADERS {
+                    debug!("Headers from peer#{} don't connect to known headers at {}, requesting them again",
+                        peer, hash.to_reversed_str());
+                    self.request_headers(peer);
+                } else {
+                    self.unconnected_headers.remove(&peer);
+                    let reason = format!("{} headers messages in a row don't connect to known headers", count);
+                    self.message_wrapper.misbehaving(peer, UNCONNECTED_HEADERS_SCORE, &reason);
+                }
+                self.schedule_downloads();
+                return;
+            }
+            Err(HeadersError::Invalid(hash, err)) => {
+                let reason = format!("Invalid header {}: {:?}", hash.to_reversed_str(), err);
+                self.message_wrapper.misbehaving(peer, INVALID_HEADERS_SCORE, &reason);
+                self.schedule_downloads();
+                return;
+            }
+        }
+
+        if let Some(height) = last_hash.and_then(|hash| self.header_chain.height(&hash)) {
+            self.on_peer_height(peer, height);
+        }
+        // peer has more headers to send
+        if is_full {
+            self.request_headers(peer);
+        }
+        self.schedule_downloads();
+    }
+
+    /// Block downloaded by synchronizer has to be accepted in chain order, after its parent
+    pub fn is_expected(&self, hash: &H256) -> bool {
+        self.header_chain.contains(hash)
+    }
+
+    pub fn on_block(&mut self, peer: PeerIndex, block: Block) {
+        let hash = block.hash();
+        self.in_flight.remove(&hash);
+        self.queue.retain(|queued| *queued != hash);
+        self.downloaded.insert(hash, (peer, block));
+        self.import_next();
+        self.schedule_downloads();
+    }
+
+    /// Handles result of importing block handed to importer and hands the next one
+    pub fn on_block_imported(&mut self, peer: PeerIndex, hash: &H256, result: Result<H256, BlockError>) {
+        if self.importing.as_ref() == Some(hash) {
+            self.importing = None;
+        }
+        match result {
+            Ok(_) | Err(BlockError::Duplicate) => {
+                self.header_chain.prune();
+                if self.header_chain.is_empty() {
+                    info!("Synchronized to height {}", self.header_chain.best_height());
+                }
+            }
+            Err(BlockError::Database(err)) => {
+                error!("Cannot store downloaded block due to {:?}", err);
+                self.header_chain.clear();
+            }
+            Err(err) => {
+                let reason = format!("Invalid block: {:?}", err);
+                self.message_wrapper.misbehaving(peer, INVALID_BLOCK_SCORE, &reason);
+                // descendants of invalid block can't be valid either
+                self.header_chain.invalidate(hash);
+            }
+        }
+        self.forget_unknown();
+        self.queue_best_branch();
+        self.import_next();
+        self.schedule_downloads();
+    }
+
+    pub fn on_peer_disconnected(&mut self, peer: PeerIndex) {
+        self.peer_heights.remove(&peer);
+        self.unconnected_headers.remove(&peer);
+        let abandoned: Vec<H256> = self.in_flight
+            .iter()
+            .filter(|&(_, request)| request.peer == peer)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        self.requeue(abandoned);
+        self.schedule_downloads();
+    }
+
+    /// Requests blocks, which other peers failed to deliver in time
+    pub fn maintain(&mut self) {
+        // blocks may be stored by miner or another peer in the meantime
+        self.header_chain.prune();
+        self.forget_unknown();
+        self.queue_best_branch();
+        let now = unix_time();
+        let expired: Vec<H256> = self.in_flight
+            .iter()
+            .filter(|&(_, request)| now.saturating_sub(request.requested_at) >= BLOCK_DOWNLOAD_TIMEOUT_SECS)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        if !expired.is_empty() {
+            debug!("{} blocks were not delivered in time, requesting them again", expired.len());
+        }
+        self.requeue(expired);
+        self.schedule_downloads();
+    }
+
+    /// Puts blocks back to download queue keeping chain order
+    fn requeue(&mut self, hashes: Vec<H256>) {
+        for hash in hashes {
+            self.in_flight.remove(&hash);
+        }
+        self.queue_best_branch();
+    }
+
+    /// Queues blocks of best branch, which are neither requested nor downloaded, in chain order
+    fn queue_best_branch(&mut self) {
+        let queue: VecDeque<H256> = self.header_chain
+            .best_branch()
+            .iter()
+            .filter(|&hash| {
+                !self.in_flight.contains_key(hash) && !self.downloaded.contains_key(hash)
+                    && self.importing.as_ref() != Some(hash)
+            })
+            .cloned()
+            .collect();
+        self.queue = queue;
+    }
+
+    /// Hands next downloaded block to importer once its parent is stored. Blocks are imported one by one,
+    /// so block is never imported before its parent
+    fn import_next(&mut self) {
+        if self.importing.is_some() {
+            return;
+        }
+        self.header_chain.prune();
+        let next = self.header_chain.first().cloned();
+        if let Some((peer, block)) = next.and_then(|hash| self.downloaded.remove(&hash)) {
+            self.importing = Some(block.hash());
+            self.importer
+                .send(ImportTask::Downloaded(peer, block))
+                .expect("block importer runs as long as synchronizer");
+        }
+    }
+
+    /// Forgets blocks which headers are not in best branch anymore
+    fn forget_unknown(&mut self) {
+        let header_chain = &self.header_chain;
+        self.queue.retain(|hash| header_chain.contains(hash));
+        self.in_flight.retain(|hash, _| header_chain.contains(hash));
+        self.downloaded.retain(|hash, _| header_chain.contains(hash));
+    }
+
+    /// Spreads queued blocks among peers which have them
+    fn schedule_downloads(&mut self) {
+        for peer in self.peers.handshaked_peers() {
+            let in_flight = self.in_flight.values().filter(|request| request.peer == peer).count();
+            let capacity = MAX_BLOCKS_IN_FLIGHT_PER_PEER.saturating_sub(in_flight);
+            let peer_height = self.peer_height(peer);
+
+            let mut inventory = Vec::new();
+            while inventory.len() < capacity {
+                let fits = self.queue
+                    .front()
+                    .and_then(|hash| self.header_chain.height(hash))
+                    .map_or(false, |height| height <= peer_height);
+                if !fits {
+                    break;
+                }
+                let hash = self.queue.pop_front().expect("front of queue is checked above; qed");
+                self.in_flight.insert(hash.clone(), BlockRequest {
+                    peer,
+                    requested_at: unix_time(),
+                });
+                inventory.push(InventoryVector::witness_block(hash));
+            }
+
+            if !inventory.is_empty() {
+                trace!(target: "sync", "Requesting {} blocks from peer#{}", inventory.len(), peer);
+                self.message_wrapper.send(peer, &GetData::with_inventory(inventory));
+            }
+        }
+    }
+
+    fn peer_height(&self, peer: PeerIndex) -> u32 {
+        let start_height = self.peers.info(peer).map_or(0, |info| info.start_height.max(0) as u32);
+        self.peer_heights.get(&peer).cloned().unwrap_or(0).max(start_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::mpsc::{self, Receiver};
+    use chain::{Block, BlockHeader};
+    use db::kv::MemoryDatabase;
+    use db::{BlockChainDatabase, SharedStore};
+    use message::common::{Command, Services};
+    use message::types::{GetData, Version, PROTOCOL_VERSION};
+    use message::{deserialize_payload, MessageHeader, HEADER_SIZE};
+    use p2p::{NetworkRequest, PeerAndBytes, PeerIndex};
+    use params::{ConsensusFork, ConsensusParams, NetworkParams};
+    use primitives::hash::H256;
+    use verification::Error as BlockError;
+    use ban_list::BanList;
+    use block_importer::ImportTask;
+    use message_wrapper::MessageWrapper;
+    use peers::{Peers, PeersRef, INVALID_BLOCK_SCORE, UNCONNECTED_HEADERS_SCORE};
+    use super::{Synchronizer, MAX_UNCONNECTED_HEADERS};
+
+    const PEER: PeerIndex = 1;
+    const EASY_BITS: u32 = 0x1f00ffff;
+    const HARD_BITS: u32 = 0x1e00ffff;
+
+    type TestStore = Arc<BlockChainDatabase<MemoryDatabase>>;
+
+    struct TestSync {
+        store: TestStore,
+        genesis: H256,
+        peers: PeersRef,
+        synchronizer: Synchronizer,
+        network: Receiver<NetworkRequest>,
+        importer: Receiver<ImportTask>,
+    }
+
+    impl TestSync {
+        /// Synchronizer with one handshaked peer and store with given number of canon blocks above genesis
+        fn new(canon_length: u32) -> Self {
+            let genesis = NetworkParams::Mainnet.genesis_block();
+            let genesis_hash = genesis.hash();
+            let store = Arc::new(BlockChainDatabase::init_test_chain(vec![genesis.into()]));
+            let mut parent = genesis_hash.clone();
+            for nonce in 0..canon_length {
+                let block = block(&parent, nonce, EASY_BITS);
+                parent = block.hash();
+                store.insert(block.into()).unwrap();
+                store.canonize(&parent).unwrap();
+            }
+
+            let peers = Arc::new(Peers::new(BanList::in_memory(), 60));
+            peers.insert(PEER, "127.0.0.1:8333".into());
+            peers.on_version(PEER, &version());
+            peers.on_verack(PEER);
+
+            let (network_sender, network) = mpsc::channel();
+            let (importer_sender, importer) = mpsc::channel();
+            let message_wrapper = MessageWrapper::new(NetworkParams::Mainnet, peers.clone(), network_sender);
+            let shared: SharedStore = store.clone();
+            let consensus = ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork);
+            TestSync {
+                store,
+                genesis: genesis_hash,
+                peers: peers.clone(),
+                synchronizer: Synchronizer::new(shared, importer_sender, message_wrapper, peers, consensus),
+                network,
+                importer,
+            }
+        }
+
+        /// Commands of messages sent to peers since last call, with their payloads
+        fn sent(&self) -> Vec<(PeerIndex, Command, Vec<u8>)> {
+            self.network
+                .try_iter()
+                .filter_map(|request| match request {
+                    NetworkRequest::Send(PeerAndBytes { peer, bytes }) => {
+                        let header = MessageHeader::deserialize(&bytes[..HEADER_SIZE], NetworkParams::Mainnet.magic()).unwrap();
+                        Some((peer, header.command, bytes[HEADER_SIZE..].to_vec()))
+                    }
+                    NetworkRequest::Disconnect(_) => None,
+                })
+                .collect()
+        }
+
+        /// Blocks requested with getdata since last call
+        fn requested_blocks(&self) -> Vec<H256> {
+            self.sent()
+                .into_iter()
+                .filter(|&(_, ref command, _)| *command == "getdata")
+                .flat_map(|(_, _, payload)| {
+                    let message: GetData = deserialize_payload(&payload, PROTOCOL_VERSION).unwrap();
+                    message.inventory.into_iter().map(|item| item.hash)
+                })
+                .collect()
+        }
+
+        fn misbehavior(&self) -> u32 {
+            self.peers.info(PEER).unwrap().misbehavior
+        }
+    }
+
+    fn version() -> Version {
+        Version {
+            version: PROTOCOL_VERSION,
+            services: Services::default().with_network(true),
+            timestamp: 0,
+            receiver: Default::default(),
+            sender: Default::default(),
+            nonce: 1,
+            user_agent: "/test/".into(),
+            start_height: 10,
+            relay: true,
+        }
+    }
+
+    fn block(parent: &H256, nonce: u32, bits: u32) -> Block {
+        let header = BlockHeader {
+            version: 1,
+            previous_header_hash: parent.clone(),
+            merkle_root_hash: H256::default(),
+            witness_merkle_root_hash: H256::default(),
+            time: 1234567 + nonce,
+            bits: bits.into(),
+            nonce,
+        };
+        Block::new(header, vec![])
+    }
+
+    /// Blocks following each other, starting from given parent
+    fn branch(parent: &H256, first_nonce: u32, length: u32, bits: u32) -> Vec<Block> {
+        let mut parent = parent.clone();
+        (first_nonce..first_nonce + length)
+            .map(|nonce| {
+                let block = block(&parent, nonce, bits);
+                parent = block.hash();
+                block
+            })
+            .collect()
+    }
+
+    fn headers(blocks: &[Block]) -> Vec<BlockHeader> {
+        blocks.iter().map(|block| block.header().clone()).collect()
+    }
+
+    fn hashes(blocks: &[Block]) -> Vec<H256> {
+        blocks.iter().map(Block::hash).collect()
+    }
+
+    #[test]
+    fn blocks_of_fork_with_more_work_are_requested() {
+        let mut sync = TestSync::new(1);
+        let fork = branch(&sync.genesis, 100, 2, EASY_BITS);
+
+        sync.synchronizer.on_headers(PEER, headers(&fork));
+        assert_eq!(sync.requested_blocks(), hashes(&fork));
+        assert_eq!(sync.synchronizer.best_height(), 2);
+    }
+
+    #[test]
+    fn blocks_of_fork_with_less_work_are_not_requested() {
+        let mut sync = TestSync::new(2);
+        let fork = branch(&sync.genesis, 100, 1, EASY_BITS);
+
+        sync.synchronizer.on_headers(PEER, headers(&fork));
+        assert_eq!(sync.requested_blocks(), vec![]);
+        assert!(!sync.synchronizer.is_expected(&fork[0].hash()));
+        assert_eq!(sync.synchronizer.best_height(), 2);
+    }
+
+    #[test]
+    fn unconnected_headers_are_requested_again() {
+        let mut sync = TestSync::new(0);
+        let unconnected = branch(&H256::from(1), 100, 1, EASY_BITS);
+
+        for _ in 1..MAX_UNCONNECTED_HEADERS {
+            sync.synchronizer.on_headers(PEER, headers(&unconnected));
+            let commands: Vec<_> = sync.sent().into_iter().map(|(peer, command, _)| (peer, command)).collect();
+            assert_eq!(commands, vec![(PEER, "getheaders".into())]);
+            assert_eq!(sync.misbehavior(), 0);
+        }
+
+        sync.synchronizer.on_headers(PEER, headers(&unconnected));
+        assert!(sync.sent().is_empty());
+        assert_eq!(sync.misbehavior(), UNCONNECTED_HEADERS_SCORE);
+    }
+
+    #[test]
+    fn connected_headers_reset_unconnected_count() {
+        let mut sync = TestSync::new(0);
+        let unconnected = branch(&H256::from(1), 100, 1, EASY_BITS);
+        let connected = branch(&sync.genesis, 200, 1, EASY_BITS);
+
+        for _ in 1..MAX_UNCONNECTED_HEADERS {
+            sync.synchronizer.on_headers(PEER, headers(&unconnected));
+        }
+        sync.synchronizer.on_headers(PEER, headers(&connected));
+        sync.synchronizer.on_headers(PEER, headers(&unconnected));
+        assert_eq!(sync.misbehavior(), 0);
+    }
+
+    #[test]
+    fn downloaded_blocks_are_imported_one_by_one_in_chain_order() {
+        let mut sync = TestSync::new(0);
+        let blocks = branch(&sync.genesis, 100, 2, EASY_BITS);
+        sync.synchronizer.on_headers(PEER, headers(&blocks));
+        assert_eq!(sync.requested_blocks(), hashes(&blocks));
+
+        // block can't be imported before its parent
+        sync.synchronizer.on_block(PEER, blocks[1].clone());
+        assert!(sync.importer.try_recv().is_err());
+
+        sync.synchronizer.on_block(PEER, blocks[0].clone());
+        assert_eq!(sync.importer.try_recv(), Ok(ImportTask::Downloaded(PEER, blocks[0].clone())));
+        // next block waits until importer is done
+        assert!(sync.importer.try_recv().is_err());
+
+        sync.store.insert(blocks[0].clone().into()).unwrap();
+        sync.store.canonize(&blocks[0].hash()).unwrap();
+        sync.synchronizer.on_block_imported(PEER, &blocks[0].hash(), Ok(blocks[0].hash()));
+        assert_eq!(sync.importer.try_recv(), Ok(ImportTask::Downloaded(PEER, blocks[1].clone())));
+        assert!(!sync.synchronizer.is_expected(&blocks[0].hash()));
+        assert!(sync.synchronizer.is_expected(&blocks[1].hash()));
+    }
+
+    #[test]
+    fn invalid_block_drops_its_branch() {
+        let mut sync = TestSync::new(0);
+        let blocks = branch(&sync.genesis, 100, 2, EASY_BITS);
+        sync.synchronizer.on_headers(PEER, headers(&blocks));
+        sync.synchronizer.on_block(PEER, blocks[0].clone());
+        sync.synchronizer.on_block(PEER, blocks[1].clone());
+        assert_eq!(sync.importer.try_recv(), Ok(ImportTask::Downloaded(PEER, blocks[0].clone())));
+
+        sync.synchronizer.on_block_imported(PEER, &blocks[0].hash(), Err(BlockError::MerkleRoot));
+        assert_eq!(sync.misbehavior(), INVALID_BLOCK_SCORE);
+        assert!(!sync.synchronizer.is_expected(&blocks[0].hash()));
+        assert!(!sync.synchronizer.is_expected(&blocks[1].hash()));
+        // descendant of invalid block is never imported
+        assert!(sync.importer.try_recv().is_err());
+        assert_eq!(sync.synchronizer.best_height(), 0);
+    }
+
+    #[test]
+    fn heavier_branch_replaces_requested_one() {
+        let mut sync = TestSync::new(0);
+        let long = branch(&sync.genesis, 100, 2, EASY_BITS);
+        let heavy = branch(&sync.genesis, 200, 1, HARD_BITS);
+
+        sync.synchronizer.on_headers(PEER, headers(&long));
+        assert_eq!(sync.requested_blocks(), hashes(&long));
+
+        sync.synchronizer.on_headers(PEER, headers(&heavy));
+        assert_eq!(sync.requested_blocks(), hashes(&heavy));
+        assert!(sync.synchronizer.is_expected(&heavy[0].hash()));
+        assert!(!sync.synchronizer.is_expected(&long[0].hash()));
+        assert_eq!(sync.synchronizer.best_height(), 1);
+    }
+
+    #[test]
+    fn blocks_are_requested_again_after_peer_disconnects() {
+        let mut sync = TestSync::new(0);
+        let blocks = branch(&sync.genesis, 100, 1, EASY_BITS);
+        sync.synchronizer.on_headers(PEER, headers(&blocks));
+        assert_eq!(sync.requested_blocks(), hashes(&blocks));
+
+        let other_peer = PEER + 1;
+        sync.peers.insert(other_peer, "127.0.0.2:8333".into());
+        sync.peers.on_version(other_peer, &version());
+        sync.peers.on_verack(other_peer);
+        sync.peers.remove(PEER);
+        sync.synchronizer.on_peer_disconnected(PEER);
+
+        let requests: Vec<_> = sync.sent().into_iter().map(|(peer, command, _)| (peer, command)).collect();
+        assert_eq!(requests, vec![(other_peer, "getdata".into())]);
+    }
+}
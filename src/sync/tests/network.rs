@@ -0,0 +1,452 @@
+//! Nodes running in one process and talking over loopback transport
+
+extern crate chain;
+extern crate db;
+extern crate futures;
+extern crate futures_cpupool;
+extern crate keys;
+extern crate memory_pool;
+extern crate message;
+extern crate p2p;
+extern crate params;
+extern crate parking_lot;
+extern crate sync;
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use chain::hash::H256;
+use chain::{BlockHeader, ShortTransactionID};
+use db::{BlockChainDatabase, BlockRef, SharedStore};
+use futures::Future;
+use futures_cpupool::CpuPool;
+use keys::Address;
+use memory_pool::MemoryPool;
+use message::common::{BlockHeaderAndIDs, Services};
+use message::types::{self, PROTOCOL_VERSION};
+use message::{deserialize_payload, to_raw_message, Message, MessageHeader, Payload, HEADER_SIZE};
+use p2p::{LoopbackEndpoint, LoopbackTransport, NetworkNode, PeerAndBytes, Transport, TransportEvent};
+use params::{ConsensusFork, ConsensusParams, NetworkParams};
+use parking_lot::RwLock;
+use sync::executor::Task as ExecutorTask;
+use sync::{Acceptor, BanList, Executor, MessageHandler, MessageWrapper, Miner, Peers, PeersRef, Responder};
+
+const CONVERGENCE_TIMEOUT_SECS: u64 = 20;
+const BAN_TIME_SECS: u64 = 60 * 60;
+const COINBASE_RECIPIENT: &str = "1KFoaRnZLw9DYhNVMfft84YHAVbLMRmWv5";
+
+/// Node services wired together like in `rustheus`, each running in its own thread
+struct TestNode {
+    store: SharedStore,
+    peers: PeersRef,
+    endpoint: LoopbackEndpoint,
+    executor: Sender<ExecutorTask>,
+    terminate: Sender<bool>,
+}
+
+impl TestNode {
+    fn start(params: NetworkParams) -> Self {
+        let store: SharedStore = Arc::new(BlockChainDatabase::init_test_chain(vec![params.genesis_block().into()]));
+        let mempool = Arc::new(RwLock::new(MemoryPool::new()));
+
+        let (from_network_sender, from_network_receiver) = mpsc::channel();
+        let (to_network_sender, to_network_receiver) = mpsc::channel();
+        let (responder_sender, responder_receiver) = mpsc::channel();
+        let (executor_sender, executor_receiver) = mpsc::channel();
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+
+        let peers = Arc::new(Peers::new(BanList::in_memory(), BAN_TIME_SECS));
+        let message_wrapper = MessageWrapper::new(params, peers.clone(), to_network_sender);
+        let responder = Responder {
+            storage: store.clone(),
+            mempool: mempool.clone(),
+            task_receiver: responder_receiver,
+            message_wrapper: message_wrapper.clone(),
+        };
+        let consensus = ConsensusParams::new(params, ConsensusFork::NoFork);
+        let cpupool = CpuPool::new(1);
+        let acceptor = Arc::new(Acceptor::new(
+            mempool.clone(),
+            store.clone(),
+            message_wrapper.clone(),
+            consensus.clone(),
+            cpupool.clone(),
+        ));
+        let miner = Arc::new(Miner::new(store.clone(), mempool.clone(), acceptor.clone(), consensus.clone(), cpupool));
+        let mut message_handler = MessageHandler::new(
+            store.clone(),
+            mempool.clone(),
+            peers.clone(),
+            from_network_receiver,
+            responder_sender,
+            acceptor,
+            message_wrapper.clone(),
+            consensus,
+        );
+        let mut executor = Executor::new(store.clone(), executor_receiver, message_wrapper, miner);
+
+        let transport = LoopbackTransport::new();
+        let endpoint = transport.endpoint();
+        let connected_executor = executor_sender.clone();
+
+        thread::spawn(move || responder.run());
+        thread::spawn(move || message_handler.run());
+        thread::spawn(move || executor.run());
+        thread::spawn(move || {
+            let mut network = NetworkNode::new(transport, from_network_sender, to_network_receiver, terminate_receiver);
+            network.set_on_connect_handler(move || {
+                let _ = connected_executor.send(ExecutorTask::RequestLatestBlocks());
+            });
+            network.run();
+        });
+
+        TestNode {
+            store,
+            peers,
+            endpoint,
+            executor: executor_sender,
+            terminate: terminate_sender,
+        }
+    }
+}
+
+impl Drop for TestNode {
+    fn drop(&mut self) {
+        let _ = self.terminate.send(true);
+    }
+}
+
+struct TestNetwork {
+    nodes: Vec<TestNode>,
+}
+
+impl TestNetwork {
+    /// Starts `size` regtest nodes, links pairs of them given by node indexes and waits for handshakes
+    fn with_topology(size: usize, links: &[(usize, usize)]) -> Self {
+        // regtest blocks are mined with real proof of work in no time
+        TestNetwork::on_network(NetworkParams::Regtest, size, links)
+    }
+
+    fn on_network(params: NetworkParams, size: usize, links: &[(usize, usize)]) -> Self {
+        let network = TestNetwork {
+            nodes: (0..size).map(|_| TestNode::start(params)).collect(),
+        };
+        for &(first, second) in links {
+            network.link(first, second);
+        }
+        let handshaked = wait_for(|| {
+            (0..size).all(|node| {
+                let degree = links.iter().filter(|&&(first, second)| first == node || second == node).count();
+                network.handshaked_peers(node) == degree
+            })
+        });
+        assert!(handshaked, "nodes haven't completed handshakes");
+        network
+    }
+
+    fn link(&self, first: usize, second: usize) {
+        self.nodes[first].endpoint.link(&self.nodes[second].endpoint);
+    }
+
+    fn mine_block(&self, node: usize) {
+        let recipient = Address::from_str(COINBASE_RECIPIENT).unwrap();
+        self.nodes[node].executor.send(ExecutorTask::SignBlock(recipient)).unwrap();
+    }
+
+    fn handshaked_peers(&self, node: usize) -> usize {
+        self.nodes[node].peers.handshaked().len()
+    }
+
+    fn height(&self, node: usize) -> u32 {
+        self.nodes[node].store.best_block().number
+    }
+
+    /// Waits until every node has the same best block at given height
+    fn converged_at(&self, height: u32) -> bool {
+        wait_for(|| {
+            let best_block = self.nodes[0].store.best_block();
+            best_block.number == height && self.nodes.iter().all(|node| node.store.best_block() == best_block)
+        })
+    }
+}
+
+/// Transport linked to regtest node, which has sent version and verack to it
+fn handshaked_observer(node: &TestNode) -> LoopbackTransport {
+    let mut observer = LoopbackTransport::new();
+    observer.endpoint().link(&node.endpoint);
+
+    let magic = NetworkParams::Regtest.magic();
+    let version = types::Version {
+        version: PROTOCOL_VERSION,
+        services: Services::default().with_network(true).with_witness(true),
+        timestamp: 0,
+        receiver: Default::default(),
+        sender: Default::default(),
+        nonce: 1,
+        user_agent: "/observer/".into(),
+        start_height: 0,
+        relay: true,
+    };
+    observer.broadcast(Message::new(magic, PROTOCOL_VERSION, &version).unwrap().into());
+    observer.broadcast(Message::new(magic, PROTOCOL_VERSION, &types::Verack).unwrap().into());
+    observer
+}
+
+/// Waits until transport reports that peer has disconnected
+fn wait_for_disconnect<T: Transport>(transport: &mut T) -> bool {
+    wait_for(|| loop {
+        match transport.poll_event() {
+            Some(TransportEvent::PeerDisconnected(_)) => return true,
+            Some(_) => (),
+            None => return false,
+        }
+    })
+}
+
+fn wait_for<F: FnMut() -> bool>(mut condition: F) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(CONVERGENCE_TIMEOUT_SECS) {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+#[test]
+fn mined_block_reaches_linked_nodes() {
+    let network = TestNetwork::with_topology(4, &[(0, 1), (0, 2), (0, 3)]);
+    network.mine_block(0);
+    assert!(network.converged_at(1));
+}
+
+#[test]
+fn testnet_nodes_mine_and_accept_blocks_with_proof_of_work() {
+    let network = TestNetwork::on_network(NetworkParams::Testnet, 2, &[(0, 1)]);
+    network.mine_block(0);
+    assert!(network.converged_at(1));
+}
+
+#[test]
+fn mined_block_is_relayed_through_intermediate_nodes() {
+    let network = TestNetwork::with_topology(4, &[(0, 1), (1, 2), (2, 3)]);
+    network.mine_block(0);
+    assert!(network.converged_at(1));
+}
+
+#[test]
+fn mined_block_is_announced_as_compact_block() {
+    let network = TestNetwork::with_topology(1, &[]);
+    let node = &network.nodes[0];
+    let mut observer = handshaked_observer(node);
+    let magic = NetworkParams::Regtest.magic();
+    let send_compact = types::SendCompact {
+        announce: true,
+        version: types::COMPACT_BLOCKS_VERSION,
+    };
+    observer.broadcast(Message::new(magic, PROTOCOL_VERSION, &send_compact).unwrap().into());
+    assert!(wait_for(|| node.peers.handshaked().iter().any(|info| info.compact_blocks)));
+
+    network.mine_block(0);
+    let mut compact_block = None;
+    assert!(wait_for(|| {
+        while let Some(event) = observer.poll_event() {
+            if let TransportEvent::Message(PeerAndBytes { bytes, .. }) = event {
+                let header = MessageHeader::deserialize(&bytes[..HEADER_SIZE], magic).unwrap();
+                if header.command == types::CompactBlock::command() {
+                    let message: types::CompactBlock = deserialize_payload(&bytes[HEADER_SIZE..], PROTOCOL_VERSION).unwrap();
+                    compact_block = Some(message);
+                }
+            }
+        }
+        compact_block.is_some()
+    }));
+
+    // coinbase is prefilled and the rest of transactions are given by short ids
+    let compact_block = compact_block.unwrap().header;
+    assert_eq!(compact_block.header.hash(), node.store.best_block().hash);
+    assert_eq!(compact_block.prefilled_transactions.len(), 1);
+}
+
+#[test]
+fn compact_block_without_proof_of_work_is_rejected() {
+    let network = TestNetwork::with_topology(1, &[]);
+    let node = &network.nodes[0];
+    let mut observer = handshaked_observer(node);
+    assert!(wait_for(|| network.handshaked_peers(0) == 1));
+
+    // target of these bits is above network maximum, and transaction is missing, so block would wait for it
+    let header = BlockHeader {
+        version: 1,
+        previous_header_hash: node.store.best_block().hash,
+        merkle_root_hash: H256::default(),
+        witness_merkle_root_hash: H256::default(),
+        time: 1234567,
+        bits: 0x2100ffff.into(),
+        nonce: 0,
+    };
+    let compact_block = types::CompactBlock {
+        header: BlockHeaderAndIDs {
+            header,
+            nonce: 0,
+            short_ids: vec![ShortTransactionID::default()],
+            prefilled_transactions: vec![],
+        },
+    };
+    observer.broadcast(Message::new(NetworkParams::Regtest.magic(), PROTOCOL_VERSION, &compact_block).unwrap().into());
+    assert!(wait_for_disconnect(&mut observer));
+    assert!(node.peers.is_banned(observer.endpoint().address()));
+}
+
+#[test]
+fn background_miner_extends_chain_until_stopped() {
+    let network = TestNetwork::with_topology(2, &[(0, 1)]);
+    let recipient = Address::from_str(COINBASE_RECIPIENT).unwrap();
+    network.nodes[0].executor.send(ExecutorTask::StartMining(recipient)).unwrap();
+    assert!(wait_for(|| network.height(1) >= 2));
+
+    network.nodes[0].executor.send(ExecutorTask::StopMining()).unwrap();
+    // miner notices it has been stopped after current round of nonces
+    thread::sleep(Duration::from_secs(2));
+    let height = network.height(0);
+    thread::sleep(Duration::from_secs(2));
+    assert_eq!(network.height(0), height);
+}
+
+#[test]
+fn node_linked_later_catches_up() {
+    let network = TestNetwork::with_topology(2, &[]);
+    network.mine_block(0);
+    assert!(wait_for(|| network.height(0) == 1));
+    assert_eq!(network.height(1), 0);
+
+    network.link(0, 1);
+    assert!(network.converged_at(1));
+}
+
+#[test]
+fn node_switches_to_heavier_fork_served_by_peer() {
+    let network = TestNetwork::with_topology(2, &[]);
+    network.mine_block(1);
+    assert!(wait_for(|| network.height(1) == 1));
+    // blocks mined at different time differ even though they pay the same recipient
+    thread::sleep(Duration::from_millis(1100));
+    network.mine_block(0);
+    assert!(wait_for(|| network.height(0) == 1));
+    let stale = network.nodes[0].store.best_block().hash;
+    thread::sleep(Duration::from_millis(1100));
+    network.mine_block(1);
+    assert!(wait_for(|| network.height(1) == 2));
+    let fork_tip = network.nodes[1].store.best_block().hash;
+
+    network.link(0, 1);
+    assert!(network.converged_at(2));
+    let store = &network.nodes[0].store;
+    assert_eq!(store.best_block().hash, fork_tip);
+    // block of abandoned branch is kept, but isn't canon anymore
+    assert!(store.block(stale.clone().into()).is_some());
+    assert!(store.block_number(&stale).is_none());
+}
+
+#[test]
+fn handshake_shares_best_height() {
+    let network = TestNetwork::with_topology(2, &[]);
+    network.mine_block(0);
+    assert!(wait_for(|| network.height(0) == 1));
+
+    network.link(0, 1);
+    assert!(wait_for(|| network.handshaked_peers(0) == 1 && network.handshaked_peers(1) == 1));
+    let peers = network.nodes[1].peers.handshaked();
+    let node0_on_node1 = &peers[0];
+    assert_eq!(node0_on_node1.start_height, 1);
+    assert_eq!(node0_on_node1.version, PROTOCOL_VERSION);
+    assert!(node0_on_node1.services.witness());
+}
+
+#[test]
+fn blocks_mined_on_different_nodes_converge() {
+    let network = TestNetwork::with_topology(3, &[(0, 1), (1, 2), (2, 0)]);
+    network.mine_block(0);
+    assert!(network.converged_at(1));
+
+    // next block has to be later than median time of previous blocks
+    thread::sleep(Duration::from_millis(1100));
+    network.mine_block(2);
+    assert!(network.converged_at(2));
+}
+
+#[test]
+fn peer_sending_corrupted_messages_is_banned() {
+    let network = TestNetwork::with_topology(1, &[]);
+    let node = &network.nodes[0];
+    let mut attacker = LoopbackTransport::new();
+    attacker.endpoint().link(&node.endpoint);
+
+    let mut corrupted = to_raw_message(NetworkParams::Regtest.magic(), "tx".into(), &"0102".into()).take();
+    *corrupted.last_mut().unwrap() ^= 0xff;
+    // every corrupted message scores 10, ban happens at 100
+    for _ in 0..10 {
+        attacker.broadcast(corrupted.clone().into());
+    }
+    assert!(wait_for_disconnect(&mut attacker));
+    assert!(node.peers.is_banned(attacker.endpoint().address()));
+
+    // banned peer is dropped as soon as it connects again
+    attacker.endpoint().link(&node.endpoint);
+    assert!(wait_for_disconnect(&mut attacker));
+}
+
+#[test]
+fn new_node_downloads_chain_from_linked_peers() {
+    let network = TestNetwork::with_topology(3, &[(0, 1)]);
+    for height in 1..4 {
+        if height > 1 {
+            // next block has to be later than median time of previous blocks
+            thread::sleep(Duration::from_millis(1100));
+        }
+        network.mine_block(0);
+        assert!(wait_for(|| network.height(1) == height));
+    }
+
+    // headers are fetched first, then blocks are downloaded from both peers
+    network.link(2, 0);
+    network.link(2, 1);
+    assert!(network.converged_at(3));
+}
+
+#[test]
+fn orphan_block_is_accepted_after_its_parent() {
+    let network = TestNetwork::with_topology(1, &[]);
+    network.mine_block(0);
+    assert!(wait_for(|| network.height(0) == 1));
+    // next block has to be later than median time of previous blocks
+    thread::sleep(Duration::from_millis(1100));
+    network.mine_block(0);
+    assert!(wait_for(|| network.height(0) == 2));
+
+    let source = &network.nodes[0].store;
+    let first = source.block(BlockRef::Number(1)).unwrap();
+    let second = source.block(BlockRef::Number(2)).unwrap();
+    let second_hash = second.hash();
+
+    let params = NetworkParams::Regtest;
+    let store: SharedStore = Arc::new(BlockChainDatabase::init_test_chain(vec![params.genesis_block().into()]));
+    let mempool = Arc::new(RwLock::new(MemoryPool::new()));
+    let (to_network_sender, _to_network_receiver) = mpsc::channel();
+    let peers = Arc::new(Peers::new(BanList::in_memory(), BAN_TIME_SECS));
+    let message_wrapper = MessageWrapper::new(params, peers, to_network_sender);
+    let consensus = ConsensusParams::new(params, ConsensusFork::NoFork);
+    let acceptor = Acceptor::new(mempool, store.clone(), message_wrapper, consensus, CpuPool::new(1));
+
+    assert!(acceptor.accept_block(second).wait().is_err());
+    assert!(acceptor.is_orphan(&second_hash));
+    assert_eq!(acceptor.missing_ancestor(&second_hash), Some(first.hash()));
+
+    acceptor.accept_block(first).wait().unwrap();
+    assert!(!acceptor.is_orphan(&second_hash));
+    assert_eq!(store.best_block().number, 2);
+    assert_eq!(store.best_block().hash, second_hash);
+}
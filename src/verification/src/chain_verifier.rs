@@ -34,7 +34,7 @@ impl BackwardsCompatibleChainVerifier {
 
 		let current_time = ::time::get_time().sec as u32;
 		// first run pre-verification
-		let chain_verifier = ChainVerifier::new(block, self.consensus.network, current_time);
+		let chain_verifier = ChainVerifier::new(block, &self.consensus, current_time);
 		chain_verifier.check()?;
 
 		assert_eq!(Some(self.store.best_block().hash), self.store.block_hash(self.store.best_block().number));
@@ -79,7 +79,7 @@ impl BackwardsCompatibleChainVerifier {
 		// TODO: full verification
 		let current_time = ::time::get_time().sec as u32;
 		let header = IndexedBlockHeader::new(hash.clone(), header.clone());
-		let header_verifier = HeaderVerifier::new(&header, self.consensus.network, current_time);
+		let header_verifier = HeaderVerifier::new(&header, &self.consensus, current_time);
 		header_verifier.check()
 	}
 
@@ -140,7 +140,7 @@ mod tests {
 	fn verify_orphan() {
 		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![chain_builder::genesis().into()]));
 		let b2 = chain_builder::block_h2().into();
-		let verifier = ChainVerifier::new(storage, ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(storage, ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 		assert_eq!(Err(Error::Database(DBError::UnknownParent)), verifier.verify(VerificationLevel::Full, &b2));
 	}
 
@@ -148,7 +148,7 @@ mod tests {
 	fn verify_smoky() {
 		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![chain_builder::genesis().into()]));
 		let b1 = chain_builder::block_h1();
-		let verifier = ChainVerifier::new(storage, ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(storage, ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 		assert!(verifier.verify(VerificationLevel::Full, &b1.into()).is_ok());
 	}
 
@@ -161,7 +161,7 @@ mod tests {
 				chain_builder::block_h1().into(),
 			]);
 		let b1 = chain_builder::block_h2();
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 		assert!(verifier.verify(VerificationLevel::Full, &b1.into()).is_ok());
 	}
 
@@ -190,7 +190,7 @@ mod tests {
 			.merkled_header().parent(genesis.hash()).build()
 			.build();
 
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 
 		let expected = Err(Error::Transaction(
 			1,
@@ -228,7 +228,7 @@ mod tests {
 			.merkled_header().parent(genesis.hash()).build()
 			.build();
 
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 		assert!(verifier.verify(VerificationLevel::Full, &block.into()).is_ok());
 	}
 
@@ -264,7 +264,7 @@ mod tests {
 			.merkled_header().parent(genesis.hash()).build()
 			.build();
 
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 		assert!(verifier.verify(VerificationLevel::Full, &block.into()).is_ok());
 	}
 
@@ -303,7 +303,7 @@ mod tests {
 			.merkled_header().parent(genesis.hash()).build()
 			.build();
 
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 
 		let expected = Err(Error::Transaction(2, TransactionError::Overspend));
 		assert_eq!(expected, verifier.verify(VerificationLevel::Full, &block.into()));
@@ -345,7 +345,7 @@ mod tests {
 			.merkled_header().parent(best_hash).build()
 			.build();
 
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 		assert!(verifier.verify(VerificationLevel::Full, &block.into()).is_ok());
 	}
 
@@ -392,7 +392,7 @@ mod tests {
 			.build()
 			.into();
 
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 		let expected = Err(Error::MaximumSigops);
 		assert_eq!(expected, verifier.verify(VerificationLevel::Full, &block.into()));
 	}
@@ -414,7 +414,7 @@ mod tests {
 			.build()
 			.into();
 
-		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		let verifier = ChainVerifier::new(Arc::new(storage), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
 
 		let expected = Err(Error::CoinbaseOverspend {
 			expected_max: 5000000000,
@@ -423,4 +423,18 @@ mod tests {
 
 		assert_eq!(expected, verifier.verify(VerificationLevel::Full, &block.into()));
 	}
+
+	#[test]
+	fn unmined_header_is_accepted_on_dev_network_only() {
+		let storage = Arc::new(BlockChainDatabase::init_test_chain(vec![chain_builder::genesis().into()]));
+		let block = chain_builder::block_builder()
+			.header().parent(chain_builder::genesis().hash()).build()
+			.build();
+
+		let verifier = ChainVerifier::new(storage.clone(), ConsensusParams::new(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		assert_eq!(Err(Error::Pow), verifier.verify_block_header(&*storage, &block.hash(), &block.block_header));
+
+		let verifier = ChainVerifier::new(storage.clone(), ConsensusParams::dev(NetworkParams::Mainnet, ConsensusFork::NoFork));
+		assert!(verifier.verify_block_header(&*storage, &block.hash(), &block.block_header).is_ok());
+	}
 }
@@ -104,8 +104,8 @@ pub use verify_transaction::{TransactionVerifier, MemoryPoolTransactionVerifier}
 pub use chain_verifier::BackwardsCompatibleChainVerifier;
 pub use error::{Error, TransactionError};
 pub use sigops::transaction_sigops;
-pub use timestamp::median_timestamp;
-pub use work::{work_required, is_valid_proof_of_work, is_valid_proof_of_work_hash, block_reward_satoshi};
+pub use timestamp::{median_timestamp, median_timestamp_inclusive};
+pub use work::{work_required, is_valid_proof_of_work, is_valid_proof_of_work_hash, block_reward_satoshi, block_work};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 /// Blocks verification level.
@@ -1,6 +1,6 @@
 use primitives::compact::Compact;
 use chain::IndexedBlockHeader;
-use params::NetworkParams;
+use params::{ConsensusParams, NetworkParams};
 use work::is_valid_proof_of_work;
 use error::Error;
 use constants::BLOCK_MAX_FUTURE;
@@ -8,30 +8,34 @@ use constants::BLOCK_MAX_FUTURE;
 pub struct HeaderVerifier<'a> {
 	pub proof_of_work: HeaderProofOfWork<'a>,
 	pub timestamp: HeaderTimestamp<'a>,
+	require_proof_of_work: bool,
 }
 
 impl<'a> HeaderVerifier<'a> {
-	pub fn new(header: &'a IndexedBlockHeader, network: NetworkParams, current_time: u32) -> Self {
+	pub fn new(header: &'a IndexedBlockHeader, consensus: &ConsensusParams, current_time: u32) -> Self {
 		HeaderVerifier {
-			proof_of_work: HeaderProofOfWork::new(header, network),
+			proof_of_work: HeaderProofOfWork::new(header, consensus.network),
 			timestamp: HeaderTimestamp::new(header, current_time, BLOCK_MAX_FUTURE as u32),
+			require_proof_of_work: consensus.require_proof_of_work,
 		}
 	}
 
 	pub fn check(&self) -> Result<(), Error> {
-		//TODO do not verify proof of work and timestamp for now
-		//try!(self.proof_of_work.check());
-		//try!(self.timestamp.check());
+		// unmined blocks are accepted on dev network
+		if !self.require_proof_of_work {
+			return Ok(());
+		}
+		try!(self.proof_of_work.check());
+		try!(self.timestamp.check());
 		Ok(())
 	}
 }
-#[allow(dead_code)] //TODO
+
 pub struct HeaderProofOfWork<'a> {
 	header: &'a IndexedBlockHeader,
 	max_work_bits: Compact,
 }
 
-#[allow(dead_code)] //TODO
 impl<'a> HeaderProofOfWork<'a> {
 	fn new(header: &'a IndexedBlockHeader, network: NetworkParams) -> Self {
 		HeaderProofOfWork {
@@ -49,14 +53,12 @@ impl<'a> HeaderProofOfWork<'a> {
 	}
 }
 
-#[allow(dead_code)]
 pub struct HeaderTimestamp<'a> {
 	header: &'a IndexedBlockHeader,
 	current_time: u32,
 	max_future: u32,
 }
 
-#[allow(dead_code)]
 impl<'a> HeaderTimestamp<'a> {
 	fn new(header: &'a IndexedBlockHeader, current_time: u32, max_future: u32) -> Self {
 		HeaderTimestamp {
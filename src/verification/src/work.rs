@@ -47,6 +47,17 @@ pub fn is_valid_proof_of_work(max_work_bits: Compact, bits: Compact, hash: &H256
 	target <= maximum && value <= target
 }
 
+/// Expected number of hashes needed to find block with given bits. Chain with the highest sum of it is the best one
+pub fn block_work(bits: Compact) -> U256 {
+	let target = match bits.to_u256() {
+		Ok(target) if target != U256::from(0) => target,
+		_ => return U256::from(0),
+	};
+
+	// 2^256 / (target + 1) doesn't fit into U256, but equals to (2^256 - target - 1) / (target + 1) + 1
+	!target / (target + U256::from(1)) + U256::from(1)
+}
+
 /// Returns constrained number of seconds since last retarget
 pub fn retarget_timespan(retarget_timestamp: u32, last_timestamp: u32) -> u32 {
 	// subtract unsigned 32 bit numbers in signed 64 bit space in
@@ -64,6 +75,11 @@ pub fn work_required(parent_hash: H256, time: u32, height: u32, store: &BlockHea
 
 	let parent_header = store.block_header(parent_hash.clone().into()).expect("self.height != 0; qed");
 
+	// regtest difficulty is never retargeted, so blocks stay cheap to mine
+	if consensus.network == NetworkParams::Regtest {
+		return parent_header.bits;
+	}
+
 	if is_retarget_height(height) {
 		return work_required_retarget(parent_header, height, store, max_bits);
 	}
@@ -141,9 +157,10 @@ pub fn block_reward_satoshi(block_height: u32) -> u64 {
 #[cfg(test)]
 mod tests {
 	use primitives::hash::H256;
+	use primitives::bigint::U256;
 	use primitives::compact::Compact;
 	use params::NetworkParams;
-	use super::{is_valid_proof_of_work_hash, is_valid_proof_of_work, block_reward_satoshi};
+	use super::{is_valid_proof_of_work_hash, is_valid_proof_of_work, block_reward_satoshi, block_work};
 
 	fn is_valid_pow(max: Compact, bits: u32, hash: &'static str) -> bool {
 		is_valid_proof_of_work_hash(bits.into(), &H256::from_reversed_str(hash)) &&
@@ -158,6 +175,16 @@ mod tests {
 		assert!(is_valid_pow(NetworkParams::Mainnet.max_bits().into(), 403093919u32, "000000000000000004ec466ce4732fe6f1ed1cddc2ed4b328fff5224276e3f6f"));
 	}
 
+	#[test]
+	fn test_block_work() {
+		// work of bitcoin genesis block is 0x100010001
+		assert_eq!(block_work(0x1d00ffffu32.into()), U256::from(4295032833u64));
+		// lower target needs more work
+		assert!(block_work(403093919u32.into()) > block_work(486604799u32.into()));
+		// negative target
+		assert_eq!(block_work(Compact::new(0x04923456)), U256::from(0));
+	}
+
 	#[test]
 	fn reward() {
 		assert_eq!(block_reward_satoshi(0), 5000000000);